@@ -0,0 +1,20 @@
+use std::{env, process::Command};
+
+fn main() {
+    // Builds without a .git directory (nix, docker) can pass the hash in directly.
+    let git_hash = env::var("DOKITO_GIT_HASH")
+        .ok()
+        .or_else(|| {
+            Command::new("git")
+                .args(["rev-parse", "--short", "HEAD"])
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .and_then(|output| String::from_utf8(output.stdout).ok())
+                .map(|hash| hash.trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=DOKITO_GIT_HASH={git_hash}");
+    println!("cargo:rerun-if-env-changed=DOKITO_GIT_HASH");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+}
@@ -1,5 +1,9 @@
+use crate::health_gate::WORKER_HEALTH_GATE;
 use crate::jurisdiction_schema_mapping::FixedJurisdiction;
-use crate::processing::{attachments::OpenscrapersExtraData, process_case};
+use crate::processing::{
+    attachments::{AttachmentFetchOrder, OpenscrapersExtraData},
+    process_case,
+};
 use crate::s3_stuff::make_s3_client;
 use async_trait::async_trait;
 use dokito_types::raw::RawDocketWithJurisdiction;
@@ -11,6 +15,7 @@ pub struct ProcessCaseWithoutDownload(pub RawDocketWithJurisdiction);
 #[async_trait]
 impl ExecuteUserTask for ProcessCaseWithoutDownload {
     async fn execute_task(self: Box<Self>) -> Result<serde_json::Value, serde_json::Value> {
+        WORKER_HEALTH_GATE.wait_until_ready().await;
         let s3_client = make_s3_client().await;
         let RawDocketWithJurisdiction {
             docket,
@@ -23,6 +28,7 @@ impl ExecuteUserTask for ProcessCaseWithoutDownload {
             s3_client,
             jurisdiction_info: jurisdiction,
             fixed_jurisdiction,
+            attachment_fetch_order: AttachmentFetchOrder::default(),
         };
         let res = process_case(docket, extra_data).await;
         match res {
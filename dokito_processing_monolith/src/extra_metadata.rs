@@ -0,0 +1,121 @@
+//! The `extra_metadata` keys the processor reads or writes, and typed accessors for them on the
+//! processed dockets, fillings and attachments.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::types::processed::{
+    ProcessedGenericAttachment, ProcessedGenericDocket, ProcessedGenericFiling,
+};
+
+/// The NY PUC matter type, more specific than the scraped case type.
+pub const MATTER_TYPE: &str = "matter_type";
+/// The NY PUC matter subtype, paired with `MATTER_TYPE`.
+pub const MATTER_SUBTYPE: &str = "matter_subtype";
+/// The scraped filing type, kept when processing normalizes it to something else.
+pub const ORIGINAL_FILING_TYPE: &str = "original_filing_type";
+/// Scraped names of the orgs intervening in a docket, in the same blob format as the petitioner.
+pub const INTERVENORS: &str = "intervenors";
+/// Scraped names of the orgs responding to a docket, in the same blob format as the petitioner.
+pub const RESPONDENTS: &str = "respondents";
+/// Size of an attachment's file in bytes, when the scraper reported it.
+pub const FILE_SIZE_BYTES: &str = "file_size_bytes";
+/// Hash of the scraped author fields a filling's authors were processed from.
+pub const RAW_AUTHORS_FINGERPRINT: &str = "raw_authors_fingerprint";
+/// Why each filling left out of a docket failed to process, keyed by the filling's govid.
+pub const DROPPED_FILINGS: &str = "dropped_filings";
+
+pub trait ExtraMetadataExt {
+    fn extra_metadata(&self) -> &BTreeMap<String, Value>;
+
+    /// The value under `key`, if there is one and it is a string.
+    fn extra_metadata_str(&self, key: &str) -> Option<&str> {
+        self.extra_metadata().get(key).and_then(Value::as_str)
+    }
+
+    fn matter_type(&self) -> Option<&str> {
+        self.extra_metadata_str(MATTER_TYPE)
+    }
+
+    fn matter_subtype(&self) -> Option<&str> {
+        self.extra_metadata_str(MATTER_SUBTYPE)
+    }
+
+    fn original_filing_type(&self) -> Option<&str> {
+        self.extra_metadata_str(ORIGINAL_FILING_TYPE)
+    }
+
+    fn raw_authors_fingerprint(&self) -> Option<&str> {
+        self.extra_metadata_str(RAW_AUTHORS_FINGERPRINT)
+    }
+
+    fn dropped_filings(&self) -> Option<&serde_json::Map<String, Value>> {
+        self.extra_metadata()
+            .get(DROPPED_FILINGS)
+            .and_then(Value::as_object)
+    }
+
+    fn file_size_bytes(&self) -> Option<u64> {
+        self.extra_metadata()
+            .get(FILE_SIZE_BYTES)
+            .and_then(Value::as_u64)
+    }
+}
+
+impl ExtraMetadataExt for ProcessedGenericDocket {
+    fn extra_metadata(&self) -> &BTreeMap<String, Value> {
+        &self.extra_metadata
+    }
+}
+
+impl ExtraMetadataExt for ProcessedGenericFiling {
+    fn extra_metadata(&self) -> &BTreeMap<String, Value> {
+        &self.extra_metadata
+    }
+}
+
+impl ExtraMetadataExt for ProcessedGenericAttachment {
+    fn extra_metadata(&self) -> &BTreeMap<String, Value> {
+        &self.extra_metadata
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{
+        make_processed_attachment, make_processed_docket, make_processed_filing,
+    };
+    use chrono::NaiveDate;
+    use serde_json::json;
+
+    #[test]
+    fn test_accessors_only_return_present_string_values() {
+        let opened_date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let mut docket = make_processed_docket("TEST-METADATA-1", opened_date, vec![]);
+        assert_eq!(docket.matter_type(), None);
+
+        docket
+            .extra_metadata
+            .insert(MATTER_TYPE.to_string(), json!("Tariff"));
+        docket
+            .extra_metadata
+            .insert(MATTER_SUBTYPE.to_string(), json!(42));
+        assert_eq!(docket.matter_type(), Some("Tariff"));
+        assert_eq!(docket.matter_subtype(), None);
+
+        let mut filing = make_processed_filing("1", opened_date, vec![]);
+        filing
+            .extra_metadata
+            .insert(ORIGINAL_FILING_TYPE.to_string(), json!("Cmnts"));
+        assert_eq!(filing.original_filing_type(), Some("Cmnts"));
+
+        let mut attachment = make_processed_attachment("Comments", "");
+        attachment
+            .extra_metadata
+            .insert("page_count".to_string(), json!(["not", "a", "string"]));
+        assert_eq!(attachment.extra_metadata_str("page_count"), None);
+        assert_eq!(attachment.extra_metadata_str("missing"), None);
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    env,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use tracing::{info, warn};
+
+use crate::{
+    s3_stuff::{limit_s3_request, make_s3_client},
+    sql_ingester_tasks::dokito_sql_connection::get_dokito_pool,
+    types::env_vars::OPENSCRAPERS_S3_OBJECT_BUCKET,
+};
+
+/// Backoff before the first recheck of a failed readiness check, doubled on every failure after.
+pub static HEALTH_GATE_INITIAL_BACKOFF_MS: LazyLock<u64> = LazyLock::new(|| {
+    env::var("HEALTH_GATE_INITIAL_BACKOFF_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&v| v > 0)
+        .unwrap_or(500)
+});
+
+/// Longest the gate waits between readiness checks during an outage.
+pub static HEALTH_GATE_MAX_BACKOFF_SECS: LazyLock<u64> = LazyLock::new(|| {
+    env::var("HEALTH_GATE_MAX_BACKOFF_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&v| v > 0)
+        .unwrap_or(60)
+});
+
+/// A passing check is trusted for this long, so busy workers don't check before every task.
+const READY_RECHECK_AFTER: Duration = Duration::from_secs(5);
+
+/// The worker loop is spawned by `mycorrhiza_common`, so every task the worker runs waits on this
+/// gate as its first step. During an outage a task is held there instead of failing, and the
+/// worker doesn't pull the next task until the held one finishes.
+pub static WORKER_HEALTH_GATE: LazyLock<HealthGate<PostgresAndS3Readiness>> = LazyLock::new(|| {
+    HealthGate::new(
+        PostgresAndS3Readiness,
+        Duration::from_millis(*HEALTH_GATE_INITIAL_BACKOFF_MS),
+        Duration::from_secs(*HEALTH_GATE_MAX_BACKOFF_SECS),
+        READY_RECHECK_AFTER,
+    )
+});
+
+/// Something the worker's tasks can't do anything without.
+#[async_trait]
+pub trait ReadinessCheck: Sync {
+    async fn check_ready(&self) -> anyhow::Result<()>;
+}
+
+/// Postgres answers a query and the openscrapers bucket can be reached.
+pub struct PostgresAndS3Readiness;
+
+#[async_trait]
+impl ReadinessCheck for PostgresAndS3Readiness {
+    async fn check_ready(&self) -> anyhow::Result<()> {
+        let pool = get_dokito_pool().await?;
+        sqlx::query("SELECT 1")
+            .execute(pool)
+            .await
+            .context("postgres is not answering")?;
+        let s3_client = make_s3_client().await;
+        limit_s3_request(
+            s3_client
+                .head_bucket()
+                .bucket(&**OPENSCRAPERS_S3_OBJECT_BUCKET)
+                .send(),
+        )
+        .await
+        .context("the openscrapers bucket can't be reached")?;
+        Ok(())
+    }
+}
+
+pub struct HealthGate<C> {
+    check: C,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    recheck_after: Duration,
+    last_ready: Mutex<Option<Instant>>,
+}
+
+impl<C: ReadinessCheck> HealthGate<C> {
+    pub fn new(
+        check: C,
+        initial_backoff: Duration,
+        max_backoff: Duration,
+        recheck_after: Duration,
+    ) -> Self {
+        HealthGate {
+            check,
+            initial_backoff,
+            max_backoff,
+            recheck_after,
+            last_ready: Mutex::new(None),
+        }
+    }
+
+    /// Returns once the dependencies are ready, rechecking with exponential backoff for as long
+    /// as they aren't. Returns how many checks failed on the way.
+    pub async fn wait_until_ready(&self) -> usize {
+        let recently_ready = self
+            .last_ready
+            .lock()
+            .unwrap()
+            .is_some_and(|last_ready| last_ready.elapsed() < self.recheck_after);
+        if recently_ready {
+            return 0;
+        }
+
+        let mut backoff = self.initial_backoff;
+        let mut failed_checks = 0;
+        loop {
+            match self.check.check_ready().await {
+                Ok(()) => {
+                    *self.last_ready.lock().unwrap() = Some(Instant::now());
+                    if failed_checks > 0 {
+                        info!(failed_checks, "Dependencies recovered, resuming tasks");
+                    }
+                    return failed_checks;
+                }
+                Err(err) => {
+                    *self.last_ready.lock().unwrap() = None;
+                    failed_checks += 1;
+                    warn!(
+                        error = %err,
+                        failed_checks,
+                        backoff_ms = backoff.as_millis() as u64,
+                        "Dependencies not ready, pausing tasks"
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.max_backoff);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    };
+    use tokio::sync::mpsc;
+
+    #[derive(Default)]
+    struct SimulatedDependencies {
+        healthy: AtomicBool,
+        checks: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl ReadinessCheck for Arc<SimulatedDependencies> {
+        async fn check_ready(&self) -> anyhow::Result<()> {
+            self.checks.fetch_add(1, Ordering::SeqCst);
+            match self.healthy.load(Ordering::SeqCst) {
+                true => Ok(()),
+                false => anyhow::bail!("simulated outage"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_worker_stops_dequeuing_during_outage() {
+        let dependencies = Arc::new(SimulatedDependencies::default());
+        let gate = Arc::new(HealthGate::new(
+            dependencies.clone(),
+            Duration::from_millis(1),
+            Duration::from_millis(5),
+            Duration::ZERO,
+        ));
+        let (queue, mut queued_tasks) = mpsc::unbounded_channel();
+        for task in 0..3 {
+            queue.send(task).unwrap();
+        }
+        drop(queue);
+        let dequeued = Arc::new(AtomicUsize::new(0));
+        let ran = Arc::new(AtomicUsize::new(0));
+
+        // Like the real worker, one task at a time with the gate as the first step of each task.
+        let worker = tokio::spawn({
+            let (gate, dequeued, ran) = (gate.clone(), dequeued.clone(), ran.clone());
+            async move {
+                while let Some(_task) = queued_tasks.recv().await {
+                    dequeued.fetch_add(1, Ordering::SeqCst);
+                    gate.wait_until_ready().await;
+                    ran.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(dequeued.load(Ordering::SeqCst), 1);
+        assert_eq!(ran.load(Ordering::SeqCst), 0);
+        assert!(dependencies.checks.load(Ordering::SeqCst) > 1);
+
+        dependencies.healthy.store(true, Ordering::SeqCst);
+        worker.await.unwrap();
+        assert_eq!(dequeued.load(Ordering::SeqCst), 3);
+        assert_eq!(ran.load(Ordering::SeqCst), 3);
+    }
+}
@@ -1,55 +1,208 @@
 use std::{
-    collections::BTreeMap,
-    sync::atomic::{AtomicBool, Ordering},
+    collections::{BTreeMap, HashMap},
+    sync::{
+        Arc, LazyLock, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
 };
 
 use async_trait::async_trait;
-use dokito_types::{attachments::RawAttachment, env_vars::DIGITALOCEAN_S3};
+use dokito_types::{
+    attachments::{HashAlgorithm, RawAttachment, Sha256Hash},
+    env_vars::DIGITALOCEAN_S3,
+};
 use mycorrhiza_common::{
+    hash::Blake2bHash,
+    misc::is_env_var_true,
     s3_generic::cannonical_location::upload_object,
     tasks::{ExecuteUserTask, display_error_as_json},
 };
-use tokio::sync::{RwLock, RwLockReadGuard};
+use tokio::sync::{OnceCell, RwLock, RwLockReadGuard, Semaphore};
+use tracing::info;
 
+use crate::health_gate::WORKER_HEALTH_GATE;
 use crate::indexes::s3_storage_and_saving::{
-    CanonAttachIndex, generate_attachment_url_index, pull_index_from_s3,
+    CanonAttachIndex, CanonHashUrlIndex, generate_attachment_url_index, pull_hash_index_from_s3,
+    pull_index_from_s3,
 };
 
 pub type AttachIndex = BTreeMap<String, RawAttachment>;
 
+/// Every url an attachment has been seen at, keyed by [`AttachmentDigest::index_key`].
+pub type HashUrlIndex = BTreeMap<String, Vec<String>>;
+
+/// A digest an attachment can be looked up by in the hash index.
+#[derive(Clone, Copy, Debug)]
+pub enum AttachmentDigest {
+    Blake2b(Blake2bHash),
+    Sha256(Sha256Hash),
+}
+
+impl AttachmentDigest {
+    /// Parses a digest computed with `algorithm`, none when it isn't one.
+    pub fn parse(algorithm: HashAlgorithm, digest: &str) -> Option<Self> {
+        match algorithm {
+            HashAlgorithm::Blake2b => Blake2bHash::from_str(digest)
+                .ok()
+                .map(AttachmentDigest::Blake2b),
+            HashAlgorithm::Sha256 => Sha256Hash::from_str(digest)
+                .ok()
+                .map(AttachmentDigest::Sha256),
+        }
+    }
+
+    /// Blake2b digests are keyed by the bare hash, like every key written before other
+    /// algorithms existed, the rest get prefixed by their algorithm so equal digests from
+    /// different algorithms don't collide.
+    pub fn index_key(&self) -> String {
+        match self {
+            AttachmentDigest::Blake2b(hash) => hash.to_string(),
+            AttachmentDigest::Sha256(hash) => format!("{}:{hash}", HashAlgorithm::Sha256),
+        }
+    }
+}
+
+/// The hash index keys of every digest the attachment is known by.
+fn attachment_index_keys(raw_attachment: &RawAttachment) -> Vec<String> {
+    std::iter::once(AttachmentDigest::Blake2b(raw_attachment.hash))
+        .chain(raw_attachment.sha256_hash.map(AttachmentDigest::Sha256))
+        .map(|digest| digest.index_key())
+        .collect()
+}
+
 static GLOBAL_RAW_ATTACHMENT_URL_INDEX_CACHE: RwLock<AttachIndex> =
     RwLock::const_new(BTreeMap::new());
 
+static GLOBAL_HASH_URL_INDEX_CACHE: RwLock<HashUrlIndex> = RwLock::const_new(BTreeMap::new());
+
 static HAS_PULLED_FROM_CACHE_ONCE: AtomicBool = AtomicBool::new(false);
 
 pub async fn get_global_att_index() -> RwLockReadGuard<'static, AttachIndex> {
-    if !HAS_PULLED_FROM_CACHE_ONCE.load(Ordering::Relaxed) {
-        let new_index = pull_index_from_s3().await;
-        let mut guard = GLOBAL_RAW_ATTACHMENT_URL_INDEX_CACHE.write().await;
+    load_indexes_once(
+        &HAS_PULLED_FROM_CACHE_ONCE,
+        &GLOBAL_RAW_ATTACHMENT_URL_INDEX_CACHE,
+        &GLOBAL_HASH_URL_INDEX_CACHE,
+        pull_indexes_from_s3,
+    )
+    .await
+}
+
+async fn pull_indexes_from_s3() -> (AttachIndex, HashUrlIndex) {
+    let new_index = pull_index_from_s3().await;
+    // Indexes persisted before the reverse index existed only have the url side.
+    let new_hash_index = match pull_hash_index_from_s3().await {
+        Some(hash_index) => hash_index,
+        None => build_hash_url_index(&new_index),
+    };
+    (new_index, new_hash_index)
+}
+
+/// Fills the indexes with `pull` unless they have been filled since they were last cleared.
+async fn load_indexes_once<'a>(
+    loaded: &AtomicBool,
+    index: &'a RwLock<AttachIndex>,
+    hash_index: &RwLock<HashUrlIndex>,
+    pull: impl AsyncFn() -> (AttachIndex, HashUrlIndex),
+) -> RwLockReadGuard<'a, AttachIndex> {
+    if !loaded.load(Ordering::Relaxed) {
+        let (new_index, new_hash_index) = pull().await;
+        let mut guard = index.write().await;
+        let mut hash_guard = hash_index.write().await;
         *guard = new_index;
-        HAS_PULLED_FROM_CACHE_ONCE.store(true, Ordering::Relaxed);
+        *hash_guard = new_hash_index;
+        loaded.store(true, Ordering::Relaxed);
     }
 
-    GLOBAL_RAW_ATTACHMENT_URL_INDEX_CACHE.read().await
+    index.read().await
 }
+
+/// Empties the indexes and marks them unloaded, so the next lookup pulls them again. Returns how
+/// many urls were dropped.
+async fn clear_indexes(
+    loaded: &AtomicBool,
+    index: &RwLock<AttachIndex>,
+    hash_index: &RwLock<HashUrlIndex>,
+) -> usize {
+    let mut guard = index.write().await;
+    let mut hash_guard = hash_index.write().await;
+    let cleared_entries = guard.len();
+    guard.clear();
+    hash_guard.clear();
+    loaded.store(false, Ordering::Relaxed);
+    cleared_entries
+}
+
 pub async fn regenrate_url_attach_index() -> anyhow::Result<()> {
     let attach_index = generate_attachment_url_index().await?;
+    let hash_index = build_hash_url_index(&attach_index);
 
     let s3_client = DIGITALOCEAN_S3.make_s3_client().await;
     let canon_object = CanonAttachIndex(attach_index);
     let _res = upload_object(&s3_client, &(), &canon_object).await;
+    let canon_hash_object = CanonHashUrlIndex(hash_index);
+    let _res = upload_object(&s3_client, &(), &canon_hash_object).await;
     let attach_index = canon_object.0;
     let mut guard = GLOBAL_RAW_ATTACHMENT_URL_INDEX_CACHE.write().await;
+    let mut hash_guard = GLOBAL_HASH_URL_INDEX_CACHE.write().await;
     *guard = attach_index;
+    *hash_guard = canon_hash_object.0;
+    drop(hash_guard);
     drop(guard);
     Ok(())
 }
 
+pub fn build_hash_url_index(attach_index: &AttachIndex) -> HashUrlIndex {
+    let mut hash_index = HashUrlIndex::new();
+    for raw_attachment in attach_index.values() {
+        add_to_hash_url_index(&mut hash_index, raw_attachment);
+    }
+    hash_index
+}
+
+fn add_to_hash_url_index(hash_index: &mut HashUrlIndex, raw_attachment: &RawAttachment) {
+    for hash_key in attachment_index_keys(raw_attachment) {
+        let urls = hash_index.entry(hash_key).or_default();
+        if !urls.contains(&raw_attachment.url) {
+            urls.push(raw_attachment.url.clone());
+        }
+    }
+}
+
+fn remove_from_hash_url_index(hash_index: &mut HashUrlIndex, hash_key: &str, url: &str) {
+    if let Some(urls) = hash_index.get_mut(hash_key) {
+        urls.retain(|indexed_url| indexed_url != url);
+        if urls.is_empty() {
+            hash_index.remove(hash_key);
+        }
+    }
+}
+
+/// Adds the attachment to both indexes, dropping the url from its previous digests if the file
+/// behind it changed.
+async fn insert_into_indexes(
+    index: &RwLock<AttachIndex>,
+    hash_index: &RwLock<HashUrlIndex>,
+    raw_attachment: RawAttachment,
+) {
+    let mut guard = index.write().await;
+    let mut hash_guard = hash_index.write().await;
+    let hash_keys = attachment_index_keys(&raw_attachment);
+    add_to_hash_url_index(&mut hash_guard, &raw_attachment);
+    if let Some(replaced) = guard.insert(raw_attachment.url.clone(), raw_attachment) {
+        for stale_key in attachment_index_keys(&replaced) {
+            if !hash_keys.contains(&stale_key) {
+                remove_from_hash_url_index(&mut hash_guard, &stale_key, &replaced.url);
+            }
+        }
+    }
+}
+
 #[derive(Default, Clone, Copy)]
 pub struct RegenerateUrlAttachIndex {}
 #[async_trait]
 impl ExecuteUserTask for RegenerateUrlAttachIndex {
     async fn execute_task(self: Box<Self>) -> Result<serde_json::Value, serde_json::Value> {
+        WORKER_HEALTH_GATE.wait_until_ready().await;
         let res = regenrate_url_attach_index().await;
         match res {
             Ok(_) => Ok("Task Succeeded".into()),
@@ -73,12 +226,165 @@ pub async fn lookup_hash_from_url(url: &str) -> Option<RawAttachment> {
     result.cloned()
 }
 
+/// Every url the file with this digest has been seen at.
+pub async fn lookup_urls_from_hash(digest: &AttachmentDigest) -> Vec<String> {
+    drop(get_global_att_index().await);
+    let hash_guard = GLOBAL_HASH_URL_INDEX_CACHE.read().await;
+    hash_guard
+        .get(&digest.index_key())
+        .cloned()
+        .unwrap_or_default()
+}
+
+pub async fn add_attachment_to_url_index(raw_attachment: RawAttachment) {
+    // Makes sure the index was pulled from s3, so this entry doesn't get overwritten by it later.
+    drop(get_global_att_index().await);
+    insert_into_indexes(
+        &GLOBAL_RAW_ATTACHMENT_URL_INDEX_CACHE,
+        &GLOBAL_HASH_URL_INDEX_CACHE,
+        raw_attachment,
+    )
+    .await;
+}
+
+/// Downloads the file behind a url, and stores it and its metadata in s3.
+#[async_trait]
+pub trait AttachmentUrlFetcher: Sync {
+    async fn fetch_and_store(&self, url: &str) -> anyhow::Result<RawAttachment>;
+}
+
+/// When set, a url missing from the index gets downloaded and hashed on the spot instead of
+/// returning nothing.
+pub static ON_DEMAND_URL_HASHING: LazyLock<bool> =
+    LazyLock::new(|| is_env_var_true("ON_DEMAND_URL_HASHING"));
+
+static SIMULTANEOUS_ON_DEMAND_FETCHES: Semaphore = Semaphore::const_new(4);
+
+/// The on demand fetches still running, keyed by url, so lookups missing the same url at the
+/// same time wait on one fetch instead of each downloading the file.
+pub(crate) type InFlightFetches = Mutex<HashMap<String, Arc<OnceCell<RawAttachment>>>>;
+
+static IN_FLIGHT_ON_DEMAND_FETCHES: LazyLock<InFlightFetches> = LazyLock::new(Default::default);
+
+/// Same as [`lookup_hash_from_url`], except that with `ON_DEMAND_URL_HASHING` set a url missing
+/// from the index is fetched with `fetcher`, hashed and added to the index.
+pub async fn lookup_hash_from_url_or_fetch(
+    url: &str,
+    fetcher: &impl AttachmentUrlFetcher,
+) -> anyhow::Result<Option<RawAttachment>> {
+    drop(get_global_att_index().await);
+    lookup_or_fetch_in_index(
+        &GLOBAL_RAW_ATTACHMENT_URL_INDEX_CACHE,
+        &GLOBAL_HASH_URL_INDEX_CACHE,
+        &IN_FLIGHT_ON_DEMAND_FETCHES,
+        url,
+        fetcher,
+        *ON_DEMAND_URL_HASHING,
+    )
+    .await
+}
+
+pub(crate) async fn lookup_or_fetch_in_index(
+    index: &RwLock<AttachIndex>,
+    hash_index: &RwLock<HashUrlIndex>,
+    in_flight: &InFlightFetches,
+    url: &str,
+    fetcher: &impl AttachmentUrlFetcher,
+    on_demand: bool,
+) -> anyhow::Result<Option<RawAttachment>> {
+    if let Some(raw_attachment) = index.read().await.get(url) {
+        return Ok(Some(raw_attachment.clone()));
+    }
+    if !on_demand {
+        return Ok(None);
+    }
+    let fetch = in_flight
+        .lock()
+        .unwrap()
+        .entry(url.to_string())
+        .or_default()
+        .clone();
+    let fetched = fetch
+        .get_or_try_init(async || {
+            // A fetch of this url that finished since the lookup above is in the index already.
+            if let Some(raw_attachment) = index.read().await.get(url) {
+                return Ok(raw_attachment.clone());
+            }
+            let _permit = SIMULTANEOUS_ON_DEMAND_FETCHES.acquire().await?;
+            info!(%url, "Url missing from attachment index, fetching it on demand");
+            let raw_attachment = fetcher.fetch_and_store(url).await?;
+            insert_into_indexes(index, hash_index, raw_attachment.clone()).await;
+            Ok::<_, anyhow::Error>(raw_attachment)
+        })
+        .await
+        .cloned();
+    // Done either way, a finished fetch is in the index and a failed one is retried by the next
+    // lookup.
+    let mut in_flight = in_flight.lock().unwrap();
+    if in_flight
+        .get(url)
+        .is_some_and(|in_flight_fetch| Arc::ptr_eq(in_flight_fetch, &fetch))
+    {
+        in_flight.remove(url);
+    }
+    Ok(Some(fetched?))
+}
+
 use aide::{self, axum::IntoApiResponse};
-use axum::{extract::Path, response::Json};
+use axum::{
+    extract::{Path, Query},
+    response::Json,
+};
 use schemars::JsonSchema;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 use url::Url;
 
+const INDEX_SUMMARY_SAMPLE_SIZE: usize = 10;
+
+#[derive(Serialize, JsonSchema)]
+pub struct AttachIndexSummary {
+    /// Whether the index has been pulled from S3 since startup or the last clear.
+    pub loaded: bool,
+    pub entry_count: usize,
+    pub hash_count: usize,
+    /// The first few urls in the index.
+    pub sample_urls: Vec<String>,
+}
+
+/// Describes the in-memory attachment index as it is, without pulling it from S3 first.
+pub async fn handle_attachment_index_summary() -> Json<AttachIndexSummary> {
+    let guard = GLOBAL_RAW_ATTACHMENT_URL_INDEX_CACHE.read().await;
+    let hash_guard = GLOBAL_HASH_URL_INDEX_CACHE.read().await;
+    Json(AttachIndexSummary {
+        loaded: HAS_PULLED_FROM_CACHE_ONCE.load(Ordering::Relaxed),
+        entry_count: guard.len(),
+        hash_count: hash_guard.len(),
+        sample_urls: guard
+            .keys()
+            .take(INDEX_SUMMARY_SAMPLE_SIZE)
+            .cloned()
+            .collect(),
+    })
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ClearedAttachIndex {
+    pub cleared_entries: usize,
+}
+
+/// Drops the in-memory attachment index, the next lookup pulls it from S3 again.
+pub async fn handle_clear_attachment_index() -> Json<ClearedAttachIndex> {
+    let cleared_entries = clear_indexes(
+        &HAS_PULLED_FROM_CACHE_ONCE,
+        &GLOBAL_RAW_ATTACHMENT_URL_INDEX_CACHE,
+        &GLOBAL_HASH_URL_INDEX_CACHE,
+    )
+    .await;
+    info!(%cleared_entries, "Cleared the in-memory attachment index");
+    Json(ClearedAttachIndex { cleared_entries })
+}
+
 #[derive(Deserialize, JsonSchema)]
 pub struct UrlPath {
     /// The URL to lookup.
@@ -99,3 +405,215 @@ pub async fn handle_attachment_url_lookup(
         Err(_) => Err("Invalid URL format".to_string()),
     }
 }
+
+#[derive(Deserialize, JsonSchema)]
+pub struct HashPath {
+    /// The hash to lookup, computed with the `algorithm` of the query.
+    pub hash: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct HashAlgorithmQuery {
+    /// The algorithm the hash was computed with, blake2b unless given.
+    #[serde(default)]
+    pub algorithm: HashAlgorithm,
+}
+
+pub async fn handle_attachment_hash_lookup(
+    Path(HashPath { hash }): Path<HashPath>,
+    Query(HashAlgorithmQuery { algorithm }): Query<HashAlgorithmQuery>,
+) -> impl IntoApiResponse {
+    match AttachmentDigest::parse(algorithm, &hash) {
+        Some(digest) => {
+            let urls = lookup_urls_from_hash(&digest).await;
+            if urls.is_empty() {
+                Err("Hash not found in cache".to_string())
+            } else {
+                Ok(Json(urls))
+            }
+        }
+        None => Err("Invalid hash format".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    use crate::test_fixtures::make_attachment_metadata;
+
+    struct MockFetcher {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl AttachmentUrlFetcher for MockFetcher {
+        async fn fetch_and_store(&self, url: &str) -> anyhow::Result<RawAttachment> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            // Takes a moment, like a real download, so concurrent lookups overlap.
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            Ok(make_attachment_metadata(url))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_index_miss_fetches_and_indexes_on_demand() {
+        let index = RwLock::new(AttachIndex::new());
+        let hash_index = RwLock::new(HashUrlIndex::new());
+        let in_flight = InFlightFetches::default();
+        let fetcher = MockFetcher {
+            calls: AtomicUsize::new(0),
+        };
+        let url = "https://example.com/never_scraped.pdf";
+
+        let disabled =
+            lookup_or_fetch_in_index(&index, &hash_index, &in_flight, url, &fetcher, false)
+                .await
+                .unwrap();
+        assert!(disabled.is_none());
+        assert_eq!(fetcher.calls.load(Ordering::Relaxed), 0);
+
+        let fetched =
+            lookup_or_fetch_in_index(&index, &hash_index, &in_flight, url, &fetcher, true)
+                .await
+                .unwrap()
+                .expect("miss should be fetched on demand");
+        assert_eq!(
+            fetched.hash.to_string(),
+            Blake2bHash::from_bytes(url.as_bytes()).to_string()
+        );
+        assert!(index.read().await.contains_key(url));
+        assert_eq!(
+            hash_index.read().await.get(&fetched.hash.to_string()),
+            Some(&vec![url.to_string()])
+        );
+
+        // The second lookup is served from the index.
+        let cached = lookup_or_fetch_in_index(&index, &hash_index, &in_flight, url, &fetcher, true)
+            .await
+            .unwrap();
+        assert!(cached.is_some());
+        assert_eq!(fetcher.calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_misses_for_one_url_fetch_it_once() {
+        let index = RwLock::new(AttachIndex::new());
+        let hash_index = RwLock::new(HashUrlIndex::new());
+        let in_flight = InFlightFetches::default();
+        let fetcher = MockFetcher {
+            calls: AtomicUsize::new(0),
+        };
+        let url = "https://example.com/shared_by_two_dockets.pdf";
+        let lookup =
+            || lookup_or_fetch_in_index(&index, &hash_index, &in_flight, url, &fetcher, true);
+
+        let (first, second) = tokio::join!(lookup(), lookup());
+        assert_eq!(
+            first.unwrap().unwrap().hash.to_string(),
+            second.unwrap().unwrap().hash.to_string()
+        );
+        assert_eq!(fetcher.calls.load(Ordering::Relaxed), 1);
+        assert!(in_flight.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_clear_forces_reload_on_next_lookup() {
+        let loaded = AtomicBool::new(false);
+        let index = RwLock::new(AttachIndex::new());
+        let hash_index = RwLock::new(HashUrlIndex::new());
+        let pulls = AtomicUsize::new(0);
+        let url = "https://example.com/indexed.pdf";
+        let pull = async || {
+            pulls.fetch_add(1, Ordering::Relaxed);
+            let attach_index =
+                AttachIndex::from([(url.to_string(), make_attachment_metadata(url))]);
+            let hash_index = build_hash_url_index(&attach_index);
+            (attach_index, hash_index)
+        };
+
+        for _ in 0..2 {
+            let guard = load_indexes_once(&loaded, &index, &hash_index, &pull).await;
+            assert!(guard.contains_key(url));
+        }
+        assert_eq!(pulls.load(Ordering::Relaxed), 1);
+
+        assert_eq!(clear_indexes(&loaded, &index, &hash_index).await, 1);
+        assert!(index.read().await.is_empty());
+        assert!(hash_index.read().await.is_empty());
+
+        let guard = load_indexes_once(&loaded, &index, &hash_index, &pull).await;
+        assert!(guard.contains_key(url));
+        assert_eq!(pulls.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_hash_seen_at_two_urls_maps_to_both() {
+        let index = RwLock::new(AttachIndex::new());
+        let hash_index = RwLock::new(HashUrlIndex::new());
+        let first_url = "https://example.com/filing.pdf";
+        let second_url = "https://mirror.example.com/filing.pdf";
+        let shared_hash = Blake2bHash::from_bytes(b"same file");
+        for url in [first_url, second_url] {
+            let mut raw_attachment = make_attachment_metadata(url);
+            raw_attachment.hash = shared_hash;
+            insert_into_indexes(&index, &hash_index, raw_attachment).await;
+        }
+
+        let expected_urls = vec![first_url.to_string(), second_url.to_string()];
+        let hash_key = shared_hash.to_string();
+        assert_eq!(hash_index.read().await.get(&hash_key), Some(&expected_urls));
+        assert_eq!(
+            build_hash_url_index(&index.read().await).get(&hash_key),
+            Some(&expected_urls)
+        );
+
+        // Once the file behind a url changes, the old hash no longer points at it.
+        insert_into_indexes(&index, &hash_index, make_attachment_metadata(second_url)).await;
+        assert_eq!(
+            hash_index.read().await.get(&hash_key),
+            Some(&vec![first_url.to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sha256_tagged_attachment_round_trips_through_index() {
+        let index = RwLock::new(AttachIndex::new());
+        let hash_index = RwLock::new(HashUrlIndex::new());
+        let url = "https://partner.example.com/sha256.pdf";
+        let sha256_hash = Sha256Hash::from_bytes([7; 32]);
+
+        let mut sha256_attachment = make_attachment_metadata(url);
+        sha256_attachment.sha256_hash = Some(sha256_hash);
+        // As stored in the s3 metadata object.
+        let sha256_attachment: RawAttachment =
+            serde_json::from_value(serde_json::to_value(&sha256_attachment).unwrap()).unwrap();
+        let blake2b_hash = sha256_attachment.hash;
+        insert_into_indexes(&index, &hash_index, sha256_attachment).await;
+
+        let indexed = index.read().await.get(url).cloned().unwrap();
+        assert_eq!(indexed.sha256_hash, Some(sha256_hash));
+        let sha256_digest =
+            AttachmentDigest::parse(HashAlgorithm::Sha256, &sha256_hash.to_string()).unwrap();
+        let hash_guard = hash_index.read().await;
+        for digest in [sha256_digest, AttachmentDigest::Blake2b(blake2b_hash)] {
+            assert_eq!(
+                hash_guard.get(&digest.index_key()),
+                Some(&vec![url.to_string()])
+            );
+        }
+        assert_eq!(build_hash_url_index(&index.read().await), *hash_guard);
+        assert_ne!(
+            sha256_digest.index_key(),
+            AttachmentDigest::Blake2b(blake2b_hash).index_key()
+        );
+        assert!(AttachmentDigest::parse(HashAlgorithm::Sha256, "not-a-digest").is_none());
+
+        // Metadata written before sha256 digests were stored has none.
+        let legacy_json = serde_json::to_value(make_attachment_metadata(url)).unwrap();
+        assert!(legacy_json.get("sha256_hash").is_none());
+        let legacy: RawAttachment = serde_json::from_value(legacy_json).unwrap();
+        assert_eq!(legacy.sha256_hash, None);
+    }
+}
@@ -1,45 +1,51 @@
-use std::{collections::BTreeMap, path::Path, str::FromStr, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env,
+    str::FromStr,
+    sync::LazyLock,
+};
 
+use async_trait::async_trait;
 use aws_sdk_s3::Client;
 use dokito_types::{
     attachments::RawAttachment,
     env_vars::{DIGITALOCEAN_S3, OPENSCRAPERS_S3_OBJECT_BUCKET},
 };
+use futures_util::{StreamExt, stream};
 use mycorrhiza_common::{
     hash::Blake2bHash,
     s3_generic::{
         S3Credentials,
-        cannonical_location::{CannonicalS3ObjectLocation, download_openscrapers_object},
+        cannonical_location::{
+            CannonicalS3ObjectLocation, download_openscrapers_object, upload_object,
+        },
         fetchers_and_getters::S3DirectoryAddr,
     },
 };
 use serde::{Deserialize, Serialize};
 use tracing::{info, warn};
 
-use crate::indexes::attachment_url_index::AttachIndex;
+use crate::indexes::attachment_url_index::{AttachIndex, HashUrlIndex, build_hash_url_index};
+use crate::s3_key_layout::S3_KEY_LAYOUT;
+use crate::s3_stuff::limit_s3_request;
 
 async fn get_all_attachment_hashes(s3_client: &Client) -> anyhow::Result<Vec<Blake2bHash>> {
-    let dir = "raw/metadata/";
+    let dir = S3_KEY_LAYOUT.attachment_metadata_prefix();
     let bucket: &'static str = &OPENSCRAPERS_S3_OBJECT_BUCKET;
-    let attach_folder = S3DirectoryAddr {
-        s3_client,
-        bucket,
-        prefix: dir.into(),
-    };
-    let prefixes = attach_folder.list_all().await?;
+    let attach_folder = S3DirectoryAddr::new(s3_client, bucket, &dir);
+    let prefixes = limit_s3_request(attach_folder.list_all()).await?;
 
     let mut hashes = Vec::with_capacity(prefixes.len());
     for prefix in prefixes {
-        let path = Path::new(prefix.trim());
-        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
-        let stripped_filekey = stem.strip_prefix(dir).unwrap_or(&stem);
-        let stripped_filekey = stripped_filekey
-            .strip_suffix(".json")
-            .unwrap_or(stripped_filekey);
-        if let Ok(hash) = Blake2bHash::from_str(stripped_filekey) {
-            hashes.push(hash);
-        } else {
-            warn!(%stripped_filekey,"Encountered file name that could not be converted to a hash.")
+        let filekey = prefix.trim();
+        match S3_KEY_LAYOUT
+            .attachment_metadata_hash(filekey)
+            .map(Blake2bHash::from_str)
+        {
+            Some(Ok(hash)) => hashes.push(hash),
+            _ => {
+                warn!(%filekey,"Encountered file name that could not be converted to a hash.")
+            }
         }
     }
     Ok(hashes)
@@ -57,6 +63,14 @@ pub async fn pull_index_from_s3() -> AttachIndex {
     BTreeMap::new()
 }
 
+pub async fn pull_hash_index_from_s3() -> Option<HashUrlIndex> {
+    let s3_client = DIGITALOCEAN_S3.make_s3_client().await;
+    download_openscrapers_object::<CanonHashUrlIndex>(&s3_client, &())
+        .await
+        .ok()
+        .map(|fetched_index| fetched_index.0)
+}
+
 // async fn generate_attachment_url_index() -> anyhow::Result<AttachIndex> {
 //     let s3_client = Arc::new(DIGITALOCEAN_S3.make_s3_client().await);
 //     let hashlist = get_all_attachment_hashes(&s3_client).await?;
@@ -81,49 +95,133 @@ pub async fn pull_index_from_s3() -> AttachIndex {
 //     Ok(map)
 // }
 
-use tokio::sync::Semaphore;
+/// How many metadata objects get downloaded at once while building the index, kept low by
+/// default so small S3 quotas don't get rate limited.
+pub static ATTACHMENT_INDEX_CONCURRENCY: LazyLock<usize> = LazyLock::new(|| {
+    env::var("ATTACHMENT_INDEX_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&v| v > 0)
+        .unwrap_or(10)
+});
+
+/// How many hashes get processed between saves of the partially built index, so a generation
+/// that crashes picks up from the last save instead of starting over.
+pub static ATTACHMENT_INDEX_CHECKPOINT_INTERVAL: LazyLock<usize> = LazyLock::new(|| {
+    env::var("ATTACHMENT_INDEX_CHECKPOINT_INTERVAL")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&v| v > 0)
+        .unwrap_or(5000)
+});
+
+const INDEX_PROGRESS_LOG_INTERVAL: usize = 1000;
+
+/// Where the per-attachment metadata objects the index is built from live, and where the index
+/// gets checkpointed while it is being built.
+#[async_trait]
+pub trait AttachmentMetadataStore: Sync {
+    async fn list_hashes(&self) -> anyhow::Result<Vec<Blake2bHash>>;
+    async fn fetch_metadata(&self, hash: &Blake2bHash) -> anyhow::Result<RawAttachment>;
+    async fn save_checkpoint(&self, index: &AttachIndex) -> anyhow::Result<()>;
+}
+
+pub struct S3AttachmentMetadataStore {
+    pub s3_client: Client,
+}
+
+#[async_trait]
+impl AttachmentMetadataStore for S3AttachmentMetadataStore {
+    async fn list_hashes(&self) -> anyhow::Result<Vec<Blake2bHash>> {
+        get_all_attachment_hashes(&self.s3_client).await
+    }
+    async fn fetch_metadata(&self, hash: &Blake2bHash) -> anyhow::Result<RawAttachment> {
+        download_openscrapers_object::<RawAttachment>(&self.s3_client, hash).await
+    }
+    async fn save_checkpoint(&self, index: &AttachIndex) -> anyhow::Result<()> {
+        // The hash index goes along with it, since a stored hash index is trusted over rebuilding
+        // one from the url index.
+        let canon_hash_index = CanonHashUrlIndex(build_hash_url_index(index));
+        upload_object(&self.s3_client, &(), &canon_hash_index).await?;
+        let canon_index = CanonAttachIndex(index.clone());
+        upload_object(&self.s3_client, &(), &canon_index).await?;
+        Ok(())
+    }
+}
 
 pub async fn generate_attachment_url_index() -> anyhow::Result<AttachIndex> {
-    info!("Starting attachment index generation");
-    let s3_client = Arc::new(DIGITALOCEAN_S3.make_s3_client().await);
-    let hashlist = get_all_attachment_hashes(&s3_client).await?;
-    info!(hashlist_length = %hashlist.len(),"Got all hashes from directory.");
-
-    // Limit concurrency to 20
-    let semaphore = Arc::new(Semaphore::new(10));
-    let mut handles = Vec::with_capacity(hashlist.len());
-
-    for hash in hashlist {
-        let s3_clone = s3_client.clone();
-        let sem_clone = semaphore.clone();
-        // Spawn each task
-        let handle = tokio::spawn(async move {
-            // Acquire a permit before starting
-            let _permit = sem_clone.acquire().await.unwrap();
-            let res = download_openscrapers_object::<RawAttachment>(&s3_clone, &hash).await;
+    let store = S3AttachmentMetadataStore {
+        s3_client: DIGITALOCEAN_S3.make_s3_client().await,
+    };
+    // Picks up from the last checkpoint if an earlier generation didn't finish.
+    let existing_index = pull_index_from_s3().await;
+    build_attachment_url_index(
+        &store,
+        existing_index,
+        *ATTACHMENT_INDEX_CONCURRENCY,
+        *ATTACHMENT_INDEX_CHECKPOINT_INTERVAL,
+    )
+    .await
+}
+
+/// Extends `existing_index` with every attachment in the store, only fetching the metadata of
+/// hashes that aren't indexed yet. The index is checkpointed to the store every
+/// `checkpoint_interval` hashes.
+pub async fn build_attachment_url_index(
+    store: &impl AttachmentMetadataStore,
+    existing_index: AttachIndex,
+    concurrency: usize,
+    checkpoint_interval: usize,
+) -> anyhow::Result<AttachIndex> {
+    info!(%concurrency, %checkpoint_interval, already_indexed = existing_index.len(), "Starting attachment index generation");
+    let indexed_hashes = existing_index
+        .values()
+        .map(|att| att.hash.to_string())
+        .collect::<BTreeSet<_>>();
+    let hashlist = store
+        .list_hashes()
+        .await?
+        .into_iter()
+        .filter(|hash| !indexed_hashes.contains(&hash.to_string()))
+        .collect::<Vec<_>>();
+    let total = hashlist.len();
+    info!(hashlist_length = %total,"Got all unindexed hashes from directory.");
+
+    let mut results = stream::iter(hashlist)
+        .map(async |hash| {
+            let res = store.fetch_metadata(&hash).await;
             if let Err(e) = &res {
                 warn!(%hash,error=%e,"Encountered error while processing hash")
-            } else {
-                info!(%hash,"Got attachment info successfully")
             };
             res
-        });
+        })
+        .buffer_unordered(concurrency.max(1));
 
-        handles.push(handle);
-    }
-
-    // Collect unordered results
-    let mut map = AttachIndex::new();
-    for handle in handles {
-        match handle.await? {
-            Ok(att) => {
-                map.insert(att.url.clone(), att);
-            }
-            Err(_err) => {
-                // Ignore errors just like in your filter_map
+    let mut map = existing_index;
+    let mut completed = 0;
+    while let Some(res) = results.next().await {
+        completed += 1;
+        if completed % INDEX_PROGRESS_LOG_INTERVAL == 0 {
+            info!(%completed, %total, "Attachment index generation progress");
+        }
+        // Objects that fail to download are left out of the index, they get retried on the next
+        // regeneration.
+        if let Ok(att) = res {
+            map.insert(att.url.clone(), att);
+        }
+        if completed % checkpoint_interval.max(1) == 0 && completed < total {
+            // A failed checkpoint only loses the resume point, so generation carries on.
+            match store.save_checkpoint(&map).await {
+                Ok(()) => {
+                    info!(%completed, indexed = map.len(), "Saved attachment index checkpoint")
+                }
+                Err(e) => {
+                    warn!(%completed, error = %e, "Failed saving attachment index checkpoint")
+                }
             }
         }
     }
+    info!(indexed = map.len(), fetched = %total, "Finished attachment index generation");
 
     Ok(map)
 }
@@ -136,7 +234,25 @@ pub struct CanonAttachIndex(pub AttachIndex);
 impl CannonicalS3ObjectLocation for CanonAttachIndex {
     type AddressInfo = ();
     fn generate_object_key(_: &Self::AddressInfo) -> String {
-        "indexes/global/attachment_urls".to_string()
+        S3_KEY_LAYOUT.attachment_index_key()
+    }
+    fn generate_bucket(_: &Self::AddressInfo) -> &'static str {
+        &OPENSCRAPERS_S3_OBJECT_BUCKET
+    }
+    fn get_credentials(_: &Self::AddressInfo) -> &'static S3Credentials {
+        &DIGITALOCEAN_S3
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[repr(transparent)]
+#[serde(transparent)]
+pub struct CanonHashUrlIndex(pub HashUrlIndex);
+
+impl CannonicalS3ObjectLocation for CanonHashUrlIndex {
+    type AddressInfo = ();
+    fn generate_object_key(_: &Self::AddressInfo) -> String {
+        S3_KEY_LAYOUT.attachment_hash_index_key()
     }
     fn generate_bucket(_: &Self::AddressInfo) -> &'static str {
         &OPENSCRAPERS_S3_OBJECT_BUCKET
@@ -145,3 +261,133 @@ impl CannonicalS3ObjectLocation for CanonAttachIndex {
         &DIGITALOCEAN_S3
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::make_attachment_metadata;
+    use std::sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    };
+    use std::time::Duration;
+
+    struct CountingStore {
+        hash_count: usize,
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+        fetched: Mutex<Vec<String>>,
+        checkpoints: Mutex<Vec<AttachIndex>>,
+        /// Fetches past this many never finish, like the generation crashed there.
+        hang_after: Option<usize>,
+    }
+
+    impl CountingStore {
+        fn new(hash_count: usize) -> Self {
+            CountingStore {
+                hash_count,
+                in_flight: AtomicUsize::new(0),
+                max_in_flight: AtomicUsize::new(0),
+                fetched: Mutex::new(vec![]),
+                checkpoints: Mutex::new(vec![]),
+                hang_after: None,
+            }
+        }
+    }
+
+    fn url_for_hash(hash: &Blake2bHash) -> String {
+        format!("https://example.com/{hash}.pdf")
+    }
+
+    #[async_trait]
+    impl AttachmentMetadataStore for CountingStore {
+        async fn list_hashes(&self) -> anyhow::Result<Vec<Blake2bHash>> {
+            Ok((0..self.hash_count)
+                .map(|i| Blake2bHash::from_bytes(&i.to_le_bytes()))
+                .collect())
+        }
+        async fn fetch_metadata(&self, hash: &Blake2bHash) -> anyhow::Result<RawAttachment> {
+            if self
+                .hang_after
+                .is_some_and(|hang_after| self.fetched.lock().unwrap().len() >= hang_after)
+            {
+                std::future::pending::<()>().await;
+            }
+            let now_in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight
+                .fetch_max(now_in_flight, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            self.fetched.lock().unwrap().push(hash.to_string());
+            let mut metadata = make_attachment_metadata(&url_for_hash(hash));
+            metadata.hash = *hash;
+            Ok(metadata)
+        }
+        async fn save_checkpoint(&self, index: &AttachIndex) -> anyhow::Result<()> {
+            self.checkpoints.lock().unwrap().push(index.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_index_generation_uses_configured_concurrency() {
+        let store = CountingStore::new(30);
+        let index = build_attachment_url_index(&store, AttachIndex::new(), 3, 1000)
+            .await
+            .unwrap();
+        assert_eq!(index.len(), 30);
+        assert_eq!(store.max_in_flight.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_index_generation_only_fetches_unindexed_hashes() {
+        let store = CountingStore::new(10);
+        let all_hashes = store.list_hashes().await.unwrap();
+        let existing_index = all_hashes[..8]
+            .iter()
+            .map(|hash| {
+                let mut metadata = make_attachment_metadata(&url_for_hash(hash));
+                metadata.hash = *hash;
+                (metadata.url.clone(), metadata)
+            })
+            .collect::<AttachIndex>();
+
+        let index = build_attachment_url_index(&store, existing_index, 4, 1000)
+            .await
+            .unwrap();
+        assert_eq!(index.len(), 10);
+        let mut fetched = store.fetched.lock().unwrap().clone();
+        fetched.sort();
+        let mut expected = all_hashes[8..]
+            .iter()
+            .map(|hash| hash.to_string())
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(fetched, expected);
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_generation_resumes_from_checkpoint() {
+        let mut crashing_store = CountingStore::new(30);
+        crashing_store.hang_after = Some(25);
+        let interrupted = tokio::time::timeout(
+            Duration::from_millis(200),
+            build_attachment_url_index(&crashing_store, AttachIndex::new(), 1, 10),
+        )
+        .await;
+        assert!(interrupted.is_err());
+        let checkpoints = crashing_store.checkpoints.lock().unwrap().clone();
+        let checkpoint_sizes = checkpoints.iter().map(|c| c.len()).collect::<Vec<_>>();
+        assert_eq!(checkpoint_sizes, vec![10, 20]);
+
+        let restarted_store = CountingStore::new(30);
+        let last_checkpoint = checkpoints.last().unwrap().clone();
+        let index = build_attachment_url_index(&restarted_store, last_checkpoint, 1, 10)
+            .await
+            .unwrap();
+        assert_eq!(index.len(), 30);
+        assert_eq!(restarted_store.fetched.lock().unwrap().len(), 10);
+        // The last batch finishes the build, so it isn't checkpointed on top.
+        assert!(restarted_store.checkpoints.lock().unwrap().is_empty());
+    }
+}
@@ -1,7 +1,13 @@
+use std::{collections::BTreeMap, env, sync::LazyLock};
+
 use dokito_types::jurisdictions::JurisdictionInfo;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tracing::warn;
+
+use crate::extra_metadata::{MATTER_SUBTYPE, MATTER_TYPE};
+use crate::processing::docket_dates::OpenedDateSource;
 
 #[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -34,7 +40,49 @@ impl FixedJurisdiction {
         }
     }
 
+    /// The jurisdiction's `OPENED_DATE_SOURCES` entry, or else
+    /// [`OpenedDateSource::LatestFilingNever`].
+    pub fn get_opened_date_source(&self) -> OpenedDateSource {
+        self.opened_date_source_with(&OPENED_DATE_SOURCES)
+    }
+
+    fn opened_date_source_with(
+        &self,
+        sources: &BTreeMap<String, OpenedDateSource>,
+    ) -> OpenedDateSource {
+        sources
+            .get(self.get_jurisdiction_info_name())
+            .copied()
+            .unwrap_or(OpenedDateSource::LatestFilingNever)
+    }
+
+    /// The `extra_metadata` fields whose values replace the scraped case type and subtype, for
+    /// jurisdictions where the scraper's own type fields are less specific.
+    pub fn get_case_type_metadata_keys(&self) -> Option<CaseTypeMetadataKeys> {
+        match self {
+            FixedJurisdiction::NewYorkPuc => Some(CaseTypeMetadataKeys {
+                type_key: MATTER_TYPE,
+                subtype_key: MATTER_SUBTYPE,
+            }),
+            FixedJurisdiction::ColoradoPuc => None,
+            FixedJurisdiction::CaliforniaPuc => None,
+            FixedJurisdiction::UtahDogmCoal => None,
+        }
+    }
+
+    /// The jurisdiction's `POSTGRES_SCHEMA_OVERRIDES` entry, or else its built-in schema.
     pub fn get_postgres_schema_name(&self) -> &'static str {
+        self.postgres_schema_name_with(&POSTGRES_SCHEMA_OVERRIDES)
+    }
+
+    fn postgres_schema_name_with<'a>(&self, overrides: &'a BTreeMap<String, String>) -> &'a str {
+        overrides
+            .get(self.get_jurisdiction_info_name())
+            .map(String::as_str)
+            .unwrap_or(self.default_postgres_schema_name())
+    }
+
+    fn default_postgres_schema_name(&self) -> &'static str {
         match self {
             FixedJurisdiction::NewYorkPuc => "ny_puc_data",
             FixedJurisdiction::ColoradoPuc => "co_puc_data",
@@ -44,7 +92,70 @@ impl FixedJurisdiction {
     }
 }
 
-const ALL_FIXED_JURISDICTIONS: &[FixedJurisdiction] = &[
+/// Schemas to use instead of the built-in ones, keyed by jurisdiction name, ie
+/// `POSTGRES_SCHEMA_OVERRIDES=ny_puc=tenant_a_ny_puc_data,co_puc=tenant_a_co_puc_data`.
+pub static POSTGRES_SCHEMA_OVERRIDES: LazyLock<BTreeMap<String, String>> = LazyLock::new(|| {
+    env::var("POSTGRES_SCHEMA_OVERRIDES")
+        .map(|overrides| parse_schema_overrides(&overrides))
+        .unwrap_or_default()
+});
+
+fn parse_schema_overrides(overrides: &str) -> BTreeMap<String, String> {
+    overrides
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let parsed = entry
+                .split_once('=')
+                .map(|(jurisdiction, pg_schema)| (jurisdiction.trim(), pg_schema.trim()))
+                .filter(|(jurisdiction, pg_schema)| {
+                    !jurisdiction.is_empty() && validate_schema_name(pg_schema).is_ok()
+                });
+            if parsed.is_none() {
+                warn!(%entry, "Ignoring malformed or invalid postgres schema override");
+            }
+            parsed
+                .map(|(jurisdiction, pg_schema)| (jurisdiction.to_string(), pg_schema.to_string()))
+        })
+        .collect()
+}
+
+/// Where each jurisdiction's opened dates come from, keyed by jurisdiction name, ie
+/// `OPENED_DATE_SOURCES=ny_puc=trust_raw,co_puc=earliest_filing`.
+pub static OPENED_DATE_SOURCES: LazyLock<BTreeMap<String, OpenedDateSource>> =
+    LazyLock::new(|| {
+        env::var("OPENED_DATE_SOURCES")
+            .map(|sources| parse_opened_date_sources(&sources))
+            .unwrap_or_default()
+    });
+
+fn parse_opened_date_sources(sources: &str) -> BTreeMap<String, OpenedDateSource> {
+    sources
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let parsed = entry.split_once('=').and_then(|(jurisdiction, source)| {
+                let jurisdiction = jurisdiction.trim();
+                let source = OpenedDateSource::from_config_name(source.trim())?;
+                (!jurisdiction.is_empty()).then(|| (jurisdiction.to_string(), source))
+            });
+            if parsed.is_none() {
+                warn!(%entry, "Ignoring malformed opened date source");
+            }
+            parsed
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CaseTypeMetadataKeys {
+    pub type_key: &'static str,
+    pub subtype_key: &'static str,
+}
+
+pub const ALL_FIXED_JURISDICTIONS: &[FixedJurisdiction] = &[
     FixedJurisdiction::NewYorkPuc,
     FixedJurisdiction::ColoradoPuc,
     FixedJurisdiction::CaliforniaPuc,
@@ -79,3 +190,145 @@ impl From<FixedJurisdiction> for JurisdictionInfo {
         }
     }
 }
+
+#[derive(Error, Debug)]
+#[error("Refusing to use {0:?} as a postgres schema name, it must match ^[a-z_][a-z0-9_]*$")]
+pub struct InvalidSchemaName(pub String);
+
+/// Checks that a schema name is a plain lowercase identifier, every schema name has to go
+/// through this before it is interpolated into `format!`-built SQL.
+pub fn validate_schema_name(pg_schema: &str) -> Result<&str, InvalidSchemaName> {
+    let mut chars = pg_schema.chars();
+    let valid_start = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_');
+    let valid_rest = chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    match valid_start && valid_rest {
+        true => Ok(pg_schema),
+        false => Err(InvalidSchemaName(pg_schema.to_string())),
+    }
+}
+
+pub trait JurisdictionInfoExt {
+    /// Same as `JurisdictionInfo::new_usa`, for jurisdictions outside the US.
+    fn new_with_country(jurisdiction: &str, state: &str, country: &str) -> Self;
+    /// The schema the jurisdiction gets when nothing overrides it. This ignores
+    /// `POSTGRES_SCHEMA_OVERRIDES`, so jurisdictions we store should go through
+    /// [`FixedJurisdiction::get_postgres_schema_name`] instead.
+    fn default_postgres_schema_name(&self) -> String;
+}
+
+impl JurisdictionInfoExt for JurisdictionInfo {
+    fn new_with_country(jurisdiction: &str, state: &str, country: &str) -> Self {
+        JurisdictionInfo {
+            country: country.to_string(),
+            state: state.to_string(),
+            jurisdiction: jurisdiction.to_string(),
+        }
+    }
+
+    // US jurisdictions keep their unprefixed schema names, so the existing schemas stay valid.
+    fn default_postgres_schema_name(&self) -> String {
+        let jurisdiction = &self.jurisdiction;
+        match &*self.country {
+            "usa" => format!("{jurisdiction}_data"),
+            country => format!("{country}_{jurisdiction}_data"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_jurisdiction_schema_names_match_jurisdiction_info() {
+        for fixed_jur in ALL_FIXED_JURISDICTIONS {
+            let info = JurisdictionInfo::from(*fixed_jur);
+            assert_eq!(
+                info.default_postgres_schema_name(),
+                fixed_jur.default_postgres_schema_name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_schema_override_replaces_default_schema() {
+        let overrides = parse_schema_overrides(
+            "ny_puc=tenant_a_ny_puc_data, co_puc=tenant_a.co_puc_data, ca_puc=",
+        );
+        assert_eq!(
+            overrides.keys().collect::<Vec<_>>(),
+            vec!["ny_puc"],
+            "invalid schema names should be dropped"
+        );
+        assert_eq!(
+            FixedJurisdiction::NewYorkPuc.postgres_schema_name_with(&overrides),
+            "tenant_a_ny_puc_data"
+        );
+        assert_eq!(
+            FixedJurisdiction::ColoradoPuc.postgres_schema_name_with(&overrides),
+            "co_puc_data"
+        );
+        assert_eq!(
+            FixedJurisdiction::NewYorkPuc.postgres_schema_name_with(&BTreeMap::new()),
+            "ny_puc_data"
+        );
+    }
+
+    #[test]
+    fn test_opened_date_source_is_configured_per_jurisdiction() {
+        let sources = parse_opened_date_sources(
+            "ny_puc=trust_raw, co_puc=earliest_filing, ca_puc=newest, ut_dogm_coal",
+        );
+        assert_eq!(
+            sources.keys().collect::<Vec<_>>(),
+            vec!["co_puc", "ny_puc"],
+            "unknown sources and malformed entries should be dropped"
+        );
+        assert_eq!(
+            FixedJurisdiction::NewYorkPuc.opened_date_source_with(&sources),
+            OpenedDateSource::TrustRaw
+        );
+        assert_eq!(
+            FixedJurisdiction::ColoradoPuc.opened_date_source_with(&sources),
+            OpenedDateSource::EarliestFiling
+        );
+        assert_eq!(
+            FixedJurisdiction::CaliforniaPuc.opened_date_source_with(&sources),
+            OpenedDateSource::LatestFilingNever
+        );
+    }
+
+    #[test]
+    fn test_non_usa_jurisdiction_gets_distinct_schema_name() {
+        let usa = JurisdictionInfo::new_usa("on_puc", "on");
+        let canada = JurisdictionInfo::new_with_country("on_puc", "on", "can");
+        assert_eq!(canada.country, "can");
+        assert_eq!(canada.default_postgres_schema_name(), "can_on_puc_data");
+        assert_ne!(
+            canada.default_postgres_schema_name(),
+            usa.default_postgres_schema_name()
+        );
+        assert!(FixedJurisdiction::try_from(&canada).is_err());
+    }
+
+    #[test]
+    fn test_schema_names_are_validated() {
+        for fixed_jur in ALL_FIXED_JURISDICTIONS {
+            let pg_schema = fixed_jur.get_postgres_schema_name();
+            assert_eq!(validate_schema_name(pg_schema).unwrap(), pg_schema);
+        }
+        assert!(validate_schema_name("_private_2").is_ok());
+        for malicious in [
+            "ny_puc_data; DROP SCHEMA public CASCADE; --",
+            "ny_puc_data.dockets",
+            "\"ny_puc_data\"",
+            "NY_PUC_DATA",
+            "2fast",
+            "",
+        ] {
+            assert!(validate_schema_name(malicious).is_err(), "{malicious}");
+        }
+    }
+}
@@ -9,9 +9,18 @@ use mycorrhiza_common::{
 use tracing::info;
 
 use crate::{
-    server::define_routes,
-    sql_ingester_tasks::dokito_sql_connection::get_dokito_pool,
-    types::env_vars::{DIGITALOCEAN_S3, OPENSCRAPERS_S3_OBJECT_BUCKET},
+    processing::llm_prompts::LLM_KILL_SWITCH,
+    s3_key_layout::S3_KEY_LAYOUT,
+    server::{define_routes, health_routes::PROMETHEUS_HANDLE},
+    sql_ingester_tasks::{
+        dokito_sql_connection::{
+            POSTGRES_MIN_CONNECTIONS, POSTGRES_WARMUP, get_dokito_pool, warm_up_pool,
+        },
+        recreate_dokito_table_schema::{
+            POSTGRES_MIGRATE_ON_STARTUP, migrate_all_jurisdiction_schemas,
+        },
+    },
+    types::env_vars::{DIGITALOCEAN_S3, OPENSCRAPERS_S3_OBJECT_BUCKET, validate_required_env_vars},
 };
 use axum::extract::DefaultBodyLimit;
 use tower_http::cors::{Any, CorsLayer};
@@ -23,13 +32,19 @@ use std::{
 
 mod case_worker;
 mod data_processing_traits;
+mod extra_metadata;
+mod health_gate;
 mod indexes;
 mod jurisdiction_schema_mapping;
 mod openscraper_data_traits;
 mod processing;
+mod s3_key_layout;
 mod s3_stuff;
 mod server;
 mod sql_ingester_tasks;
+#[cfg(test)]
+mod test_fixtures;
+mod throughput;
 mod types;
 // use opentelemetry::global::{self, BoxedTracer, ObjectSafeTracerProvider, tracer};
 
@@ -47,10 +62,22 @@ static PORT: LazyLock<u16> = LazyLock::new(|| {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    validate_required_env_vars()?;
     let _ = *DIGITALOCEAN_S3;
     let _ = *OPENSCRAPERS_S3_OBJECT_BUCKET;
+    let _ = &*S3_KEY_LAYOUT;
     let _ = *DEEPINFRA_API_KEY;
-    let _ = get_dokito_pool().await?;
+    let _ = &*PROMETHEUS_HANDLE;
+    if LLM_KILL_SWITCH.is_on() {
+        tracing::warn!("LLM kill-switch is on, every LLM call will use its naive fallback");
+    }
+    let pool = get_dokito_pool().await?;
+    if *POSTGRES_WARMUP {
+        warm_up_pool(pool, *POSTGRES_MIN_CONNECTIONS).await?;
+    }
+    if *POSTGRES_MIGRATE_ON_STARTUP {
+        migrate_all_jurisdiction_schemas().await;
+    }
     if let Err(e) = do_i_have_internet() {
         tracing::error!(err = %e,"NO INTERNET DETECTED");
         panic!("NO INTERNET DETECTED");
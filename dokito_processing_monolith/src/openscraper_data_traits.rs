@@ -1,4 +1,7 @@
-use std::convert::Infallible;
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::LazyLock;
+use std::time::Duration;
 
 use chrono::{NaiveDate, Utc};
 use dokito_types::processed::ProcessedGenericHuman;
@@ -7,29 +10,51 @@ use futures::future::join_all;
 use futures::join;
 use futures_util::{StreamExt, stream};
 use non_empty_string::NonEmptyString;
+use serde_json::json;
 use thiserror::Error;
 use tokio::sync::Semaphore;
 use tracing::warn;
 use uuid::Uuid;
 
 use crate::data_processing_traits::{ProcessFrom, Revalidate, RevalidationOutcome};
+use crate::extra_metadata::{
+    DROPPED_FILINGS, ExtraMetadataExt, INTERVENORS, ORIGINAL_FILING_TYPE, RAW_AUTHORS_FINGERPRINT,
+    RESPONDENTS,
+};
 use crate::indexes::attachment_url_index::lookup_hash_from_url;
 use crate::jurisdiction_schema_mapping::FixedJurisdiction;
+use crate::processing::PROCESSOR_VERSION;
+use crate::processing::docket_dates::{INFER_CLOSED_DATE, compute_opened_date, infer_closed_date};
+use crate::processing::filing_types::normalize_filing_type;
+use crate::processing::hearing_officers::parse_hearing_officers;
 use crate::processing::llm_prompts::{
-    clean_up_organization_name_list, split_and_fix_organization_names_blob,
+    LlmCallContext, clean_up_organization_name_list, split_and_fix_organization_names_blob,
 };
 use crate::processing::match_raw_processed::{
-    match_raw_attaches_to_processed_attaches, match_raw_fillings_to_processed_fillings,
+    cached_authors_are_stale, match_raw_attaches_to_processed_attaches,
+    match_raw_fillings_to_processed_fillings, raw_authors_fingerprint,
 };
-use crate::sql_ingester_tasks::database_author_association::{
-    associate_individual_author_with_name, associate_organization_with_name,
+use crate::sql_ingester_tasks::database_author_association::AuthorAssociationMode;
+use crate::sql_ingester_tasks::dokito_sql_connection::{
+    InitializePostgresError, get_dokito_pool_for,
 };
-use crate::sql_ingester_tasks::dokito_sql_connection::get_dokito_pool;
 use crate::types::processed::{
-    ProcessedGenericAttachment, ProcessedGenericDocket, ProcessedGenericFiling,
+    PetitionerRole, ProcessedGenericAttachment, ProcessedGenericDocket, ProcessedGenericFiling,
+    ProcessedGenericPetitioner,
 };
 use crate::types::raw::{RawGenericAttachment, RawGenericDocket, RawGenericFiling};
 
+/// Splits a type given as "Type - Subtype", the way some scrapers report both halves together.
+/// Only the first separator splits, so "A - B - C" has the subtype "B - C".
+pub fn split_combined_type(combined_type: &str) -> Option<(String, String)> {
+    let (type_half, subtype_half) = combined_type.split_once(" - ")?;
+    let (type_half, subtype_half) = (type_half.trim(), subtype_half.trim());
+    if type_half.is_empty() || subtype_half.is_empty() {
+        return None;
+    }
+    Some((type_half.to_string(), subtype_half.to_string()))
+}
+
 impl Revalidate for ProcessedGenericDocket {
     async fn revalidate(&mut self) -> RevalidationOutcome {
         let mut did_change = RevalidationOutcome::NoChanges;
@@ -37,16 +62,19 @@ impl Revalidate for ProcessedGenericDocket {
             self.object_uuid = Uuid::new_v4();
             did_change = RevalidationOutcome::DidChange
         }
-        if self.case_subtype.is_empty() {
-            let x = self.case_type.split(" - ").collect::<Vec<_>>();
-            if x.len() == 2 {
-                let case_type = x[0].trim().to_string();
-                let case_subtype = x[1].trim().to_string();
-                self.case_type = case_type;
-                self.case_subtype = case_subtype;
+        if self.case_subtype.is_empty()
+            && let Some((case_type, case_subtype)) = split_combined_type(&self.case_type)
+        {
+            self.case_type = case_type;
+            self.case_subtype = case_subtype;
+            did_change = RevalidationOutcome::DidChange;
+        };
+        if self.hearing_officers.is_empty() {
+            self.hearing_officers = parse_hearing_officers(&self.hearing_officer);
+            if !self.hearing_officers.is_empty() {
                 did_change = RevalidationOutcome::DidChange;
             }
-        };
+        }
         for filling in self.filings.iter_mut() {
             let did_filling_change = filling.revalidate().await;
             did_change = did_change.or(&did_filling_change);
@@ -89,207 +117,457 @@ impl Revalidate for ProcessedGenericAttachment {
             self.object_uuid = Uuid::new_v4();
             did_change = RevalidationOutcome::DidChange
         }
+        if self.attachment_subtype.is_empty()
+            && let Some((attachment_type, attachment_subtype)) =
+                split_combined_type(&self.attachment_type)
+        {
+            self.attachment_type = attachment_type;
+            self.attachment_subtype = attachment_subtype;
+            did_change = RevalidationOutcome::DidChange;
+        };
         if self.hash.is_none() && !self.url.is_empty() {
             let url = &*self.url;
             let opt_raw_attach = lookup_hash_from_url(url).await;
             if let Some(raw_attach) = opt_raw_attach {
                 self.hash = Some(raw_attach.hash);
+                did_change = RevalidationOutcome::DidChange;
             }
         }
         did_change
     }
 }
 impl ProcessFrom<RawGenericDocket> for ProcessedGenericDocket {
-    type ParseError = Infallible;
+    type ParseError = ProcessingError;
     type ExtraData = FixedJurisdiction;
     async fn process_from(
         input: RawGenericDocket,
         cached: Option<Self>,
         fixed_jurisdiction: Self::ExtraData,
     ) -> Result<Self, Self::ParseError> {
-        let object_uuid = cached
-            .as_ref()
-            .map(|v| v.object_uuid)
-            .unwrap_or_else(Uuid::new_v4);
-        let opened_date_from_fillings = {
-            let original_date = input.opened_date;
-            let mut min_date = original_date;
-            for filling_date in input
-                .filings
-                .iter()
-                .filter_map(|filling| filling.filed_date)
-            {
-                if let Some(real_min_date) = min_date
-                    && filling_date < real_min_date
-                {
-                    min_date = Some(filling_date);
-                    if let Some(real_original_date) = original_date {
-                        warn!(docket_opened_date =%real_original_date, oldest_date_found=%filling_date,"Found filling with date older then the docket opened date");
-                    };
+        process_docket(
+            input,
+            cached,
+            fixed_jurisdiction,
+            AuthorAssociationMode::Upsert,
+        )
+        .await
+    }
+}
+
+/// Processes a docket the same way [`ProcessFrom`] does, with `author_association` deciding whether
+/// authors missing from postgres get written to it.
+pub async fn process_docket(
+    input: RawGenericDocket,
+    cached: Option<ProcessedGenericDocket>,
+    fixed_jurisdiction: FixedJurisdiction,
+    author_association: AuthorAssociationMode,
+) -> Result<ProcessedGenericDocket, ProcessingError> {
+    let object_uuid = cached
+        .as_ref()
+        .map(|v| v.object_uuid)
+        .unwrap_or_else(Uuid::new_v4);
+    let opened_date = compute_opened_date(
+        fixed_jurisdiction.get_opened_date_source(),
+        input.opened_date,
+        input
+            .filings
+            .iter()
+            .filter_map(|filling| filling.filed_date),
+    )
+    // This should almost never happen, because the chances of corruption happening on the
+    // docket date, and all the filling dates are very small.
+    .unwrap_or(NaiveDate::MAX);
+    let docket_govid = input.case_govid.to_string();
+    let petitioner_blobs = petitioner_blobs_by_role(&input);
+    let cached_fillings = cached.map(|d| d.filings);
+    let matched_fillings = match_raw_fillings_to_processed_fillings(input.filings, cached_fillings);
+    let processed_fillings_futures =
+        matched_fillings
+            .into_iter()
+            .enumerate()
+            .map(async |(index, (f_raw, f_cached))| {
+                let filling_index_data = IndexExtraData {
+                    index: index as u64,
+                    jurisdiction: fixed_jurisdiction,
+                    docket_govid: docket_govid.clone(),
+                    author_association,
                 };
-            }
-            // This should almost never happen, because the chances of corruption happening on the
-            // docket date, and all the filling dates are very small.
-            min_date.unwrap_or(NaiveDate::MAX)
-        };
-        let cached_fillings = cached.map(|d| d.filings);
-        let matched_fillings =
-            match_raw_fillings_to_processed_fillings(input.filings, cached_fillings);
-        let processed_fillings_futures =
-            matched_fillings
-                .into_iter()
-                .enumerate()
-                .map(async |(index, (f_raw, f_cached))| {
-                    let filling_index_data = IndexExtraData {
-                        index: index as u64,
-                        jurisdiction: fixed_jurisdiction,
-                    };
-                    let res =
-                        ProcessedGenericFiling::process_from(f_raw, f_cached, filling_index_data)
-                            .await;
-                    let Ok(val) = res;
-                    val
-                });
-        // Everything gets processed at once since the limiting factor on fillings is global. This
-        // is to make it so that it doesnt overwhelm the system trying to process 5 dockets with
-        // 10,000 fillings, but it can process 60 dockets at the same time with one filling each.
-        let mut processed_fillings = join_all(processed_fillings_futures).await;
-
-        fn parse_first_name_and_title(raw_first_name: &str) -> (String, String) {
-            let trimmed = raw_first_name.trim();
-            let capitals: Vec<(usize, char)> = trimmed
-                .char_indices()
-                .filter(|(_, c)| c.is_uppercase())
-                .collect();
-
-            if capitals.len() > 2 {
-                // Split on the second capital letter
-                let split_pos = capitals[1].0;
-                let first_name = trimmed[..split_pos].trim().to_string();
-                let title = trimmed[split_pos..].trim().to_string();
-                (first_name, title)
-            } else {
-                (trimmed.to_string(), String::new())
-            }
-        }
+                let filling_govid = f_raw.filling_govid.clone();
+                let result =
+                    ProcessedGenericFiling::process_from(f_raw, f_cached, filling_index_data).await;
+                (filling_govid, result)
+            });
+    // Everything gets processed at once since the limiting factor on fillings is global. This
+    // is to make it so that it doesnt overwhelm the system trying to process 5 dockets with
+    // 10,000 fillings, but it can process 60 dockets at the same time with one filling each.
+    let filling_results = join_all(processed_fillings_futures).await;
+    let (mut processed_fillings, dropped_fillings) =
+        keep_successful_fillings(input.case_govid.as_str(), filling_results);
 
-        fn clean_last_name(raw_last_name: &str) -> String {
-            raw_last_name
-                .trim()
-                .trim_end_matches(',')
-                .trim()
-                .to_string()
-        }
-        fn raw_party_to_human(rawparty: RawGenericParty) -> Option<ProcessedGenericHuman> {
-            let raw_name = &*rawparty.name;
-
-            // Case 1: not human
-            if rawparty.artifical_person_type != RawArtificalPersonType::Human {
-                let party_type = rawparty.artifical_person_type;
-                warn!(?party_type, %raw_name, "Encountered non-human party, skipping.");
-                return None;
-            }
+    fn parse_first_name_and_title(raw_first_name: &str) -> (String, String) {
+        let trimmed = raw_first_name.trim();
+        let capitals: Vec<(usize, char)> = trimmed
+            .char_indices()
+            .filter(|(_, c)| c.is_uppercase())
+            .collect();
 
-            // Case 2: human but name is invalid
-            let Ok(nonempty_name) = NonEmptyString::try_from(raw_name.to_string()) else {
-                let party_type = rawparty.artifical_person_type;
-                let first_name = &*rawparty.western_human_first_name;
-                let last_name = &*rawparty.western_human_last_name;
-                warn!(
-                    ?party_type,
-                    %raw_name,
-                    %first_name,
-                    %last_name,
-                    "Encountered human with invalid or missing name."
-                );
-                return None;
-            };
+        if capitals.len() > 2 {
+            // Split on the second capital letter
+            let split_pos = capitals[1].0;
+            let first_name = trimmed[..split_pos].trim().to_string();
+            let title = trimmed[split_pos..].trim().to_string();
+            (first_name, title)
+        } else {
+            (trimmed.to_string(), String::new())
+        }
+    }
 
-            let (clean_first_name, extracted_title) =
-                parse_first_name_and_title(&rawparty.western_human_first_name);
-            let clean_last_name = clean_last_name(&rawparty.western_human_last_name);
+    fn clean_last_name(raw_last_name: &str) -> String {
+        raw_last_name
+            .trim()
+            .trim_end_matches(',')
+            .trim()
+            .to_string()
+    }
+    fn raw_party_to_human(rawparty: RawGenericParty) -> Option<ProcessedGenericHuman> {
+        let raw_name = &*rawparty.name;
+        let Ok(nonempty_name) = NonEmptyString::try_from(raw_name.to_string()) else {
+            let party_type = rawparty.artifical_person_type;
+            let first_name = &*rawparty.western_human_first_name;
+            let last_name = &*rawparty.western_human_last_name;
+            warn!(
+                ?party_type,
+                %raw_name,
+                %first_name,
+                %last_name,
+                "Encountered human with invalid or missing name."
+            );
+            return None;
+        };
 
-            let final_title =
-                if rawparty.human_title.trim().is_empty() && !extracted_title.is_empty() {
-                    extracted_title
-                } else {
-                    rawparty.human_title
-                };
+        let (clean_first_name, extracted_title) =
+            parse_first_name_and_title(&rawparty.western_human_first_name);
+        let clean_last_name = clean_last_name(&rawparty.western_human_last_name);
 
-            let processed_party_huamn = ProcessedGenericHuman {
-                object_uuid: Uuid::nil(),
-                human_name: nonempty_name,
-                western_first_name: clean_first_name,
-                western_last_name: clean_last_name,
-                contact_emails: vec![rawparty.contact_email],
-                contact_phone_numbers: vec![rawparty.contact_phone],
-                contact_addresses: vec![rawparty.contact_address],
-                representing_company: None,
-                employed_by: None,
-                title: final_title,
-            };
-            Some(processed_party_huamn)
-        }
-        let actual_industry = if input.industry.starts_with("Matter Number:") {
-            "".to_string()
+        let final_title = if rawparty.human_title.trim().is_empty() && !extracted_title.is_empty() {
+            extracted_title
         } else {
-            input.industry
+            rawparty.human_title
         };
-        let raw_parties = input.case_parties;
-        let raw_parties_length = raw_parties.len();
-        tracing::info!(%raw_parties_length,"Raw Case has a certain amount of input.case_parites");
 
-        let mut processed_parties = raw_parties
-            .into_iter()
-            .filter_map(raw_party_to_human)
-            .collect::<Vec<_>>();
-        tracing::info!(case_parties_length = %processed_parties.len(),"Processed parties has final length");
-        let pool = get_dokito_pool().await.unwrap();
+        let processed_party_huamn = ProcessedGenericHuman {
+            object_uuid: Uuid::nil(),
+            human_name: nonempty_name,
+            western_first_name: clean_first_name,
+            western_last_name: clean_last_name,
+            contact_emails: vec![rawparty.contact_email],
+            contact_phone_numbers: vec![rawparty.contact_phone],
+            contact_addresses: vec![rawparty.contact_address],
+            representing_company: None,
+            employed_by: None,
+            title: final_title,
+        };
+        Some(processed_party_huamn)
+    }
+    let actual_industry = if input.industry.starts_with("Matter Number:") {
+        "".to_string()
+    } else {
+        input.industry
+    };
+    let RoutedRawParties {
+        humans: human_parties,
+        organizations: organization_parties,
+    } = route_raw_parties(input.case_parties);
+    tracing::info!(
+        human_parties = human_parties.len(),
+        organization_parties = organization_parties.len(),
+        "Routed raw case parties by person type"
+    );
 
-        for party in processed_parties.iter_mut() {
-            let _res = associate_individual_author_with_name(party, fixed_jurisdiction, pool).await;
-        }
-        assert_eq!(
-            raw_parties_length,
-            processed_parties.len(),
-            "raw parties should have the same length as the final parties"
+    let mut processed_parties = human_parties
+        .into_iter()
+        .filter_map(raw_party_to_human)
+        .collect::<Vec<_>>();
+    tracing::info!(case_parties_length = %processed_parties.len(),"Processed parties has final length");
+    let pool = get_dokito_pool_for(fixed_jurisdiction).await?;
+    let pg_schema = fixed_jurisdiction.get_postgres_schema_name();
+
+    for party in processed_parties.iter_mut() {
+        author_association
+            .associate_individual(party, pg_schema, pool)
+            .await
+            .map_err(|source| ProcessingError::AuthorAssociation {
+                author_name: party.human_name.to_string(),
+                source,
+            })?;
+    }
+    processed_fillings.sort_by_key(|v| v.index_in_docket);
+    let mut llmed_petitioner_list = vec![];
+    for (role, org_blob) in petitioner_blobs {
+        let orgs = split_and_fix_organization_names_blob(
+            &org_blob,
+            LlmCallContext {
+                docket_govid: Some(&docket_govid),
+                filling_govid: None,
+            },
+        )
+        .await;
+        llmed_petitioner_list.extend(
+            orgs.into_iter()
+                .map(|organization| ProcessedGenericPetitioner { organization, role }),
         );
-        processed_fillings.sort_by_key(|v| v.index_in_docket);
-        let llmed_petitioner_list = split_and_fix_organization_names_blob(&input.petitioner).await;
-        let final_processed_docket = ProcessedGenericDocket {
-            object_uuid,
-            case_parties: processed_parties,
-            processed_at: Utc::now(),
-            case_govid: input.case_govid,
-            filings: processed_fillings,
-            opened_date: opened_date_from_fillings,
-            case_name: input.case_name,
-            case_url: input.case_url,
-            industry: actual_industry,
-            case_type: input.case_type,
-            case_subtype: input.case_subtype,
-            indexed_at: input.indexed_at,
-            closed_date: input.closed_date,
-            description: input.description,
-            extra_metadata: input.extra_metadata,
-            hearing_officer: input.hearing_officer,
-            petitioner_list: llmed_petitioner_list,
+    }
+    let party_petitioners =
+        organization_party_petitioners(organization_parties, &llmed_petitioner_list);
+    llmed_petitioner_list.extend(party_petitioners);
+    let mut final_processed_docket = ProcessedGenericDocket {
+        object_uuid,
+        case_parties: processed_parties,
+        processed_at: Utc::now(),
+        processor_version: PROCESSOR_VERSION.to_string(),
+        case_govid: input.case_govid,
+        filings: processed_fillings,
+        opened_date,
+        case_name: input.case_name,
+        case_url: input.case_url,
+        industry: actual_industry,
+        case_type: input.case_type,
+        case_subtype: input.case_subtype,
+        indexed_at: input.indexed_at,
+        closed_date: input.closed_date,
+        description: input.description,
+        extra_metadata: input.extra_metadata,
+        hearing_officers: parse_hearing_officers(&input.hearing_officer),
+        hearing_officer: input.hearing_officer,
+        petitioner_list: llmed_petitioner_list,
+    };
+    if !dropped_fillings.is_empty() {
+        final_processed_docket
+            .extra_metadata
+            .insert(DROPPED_FILINGS.to_string(), json!(dropped_fillings));
+    }
+    apply_case_type_metadata(&mut final_processed_docket, fixed_jurisdiction);
+    if let Some(inferred_closed_date) =
+        infer_closed_date(&mut final_processed_docket, *INFER_CLOSED_DATE)
+    {
+        tracing::info!(docket_govid = %final_processed_docket.case_govid, %inferred_closed_date, "Inferred closed date from latest filling");
+    }
+    Ok(final_processed_docket)
+}
+
+/// The org blob of every role the raw docket lists, the petitioner field plus the other roles
+/// scrapers put in the extra metadata.
+fn petitioner_blobs_by_role(input: &RawGenericDocket) -> Vec<(PetitionerRole, String)> {
+    let metadata_blob = move |key: &str| {
+        input
+            .extra_metadata
+            .get(key)
+            .and_then(serde_json::Value::as_str)
+            .filter(|blob| !blob.trim().is_empty())
+            .map(str::to_string)
+    };
+    let mut blobs = vec![(PetitionerRole::Petitioner, input.petitioner.clone())];
+    blobs.extend(metadata_blob(INTERVENORS).map(|blob| (PetitionerRole::Intervenor, blob)));
+    blobs.extend(metadata_blob(RESPONDENTS).map(|blob| (PetitionerRole::Respondent, blob)));
+    blobs
+}
+
+/// The raw case parties of a docket, split by who they are.
+#[derive(Debug, Default)]
+pub struct RoutedRawParties {
+    pub humans: Vec<RawGenericParty>,
+    pub organizations: Vec<RawGenericParty>,
+}
+
+/// Humans become the docket's case parties and organizations go through the organization
+/// handling. A party whose type the scraper couldn't tell counts as a human when it came with a
+/// first or last name, and as an organization otherwise.
+pub fn route_raw_parties(raw_parties: Vec<RawGenericParty>) -> RoutedRawParties {
+    let mut routed = RoutedRawParties::default();
+    for party in raw_parties {
+        let is_human = match party.artifical_person_type {
+            RawArtificalPersonType::Human => true,
+            RawArtificalPersonType::Organization => false,
+            RawArtificalPersonType::Unknown => {
+                !party.western_human_first_name.trim().is_empty()
+                    || !party.western_human_last_name.trim().is_empty()
+            }
         };
-        Ok(final_processed_docket)
+        match is_human {
+            true => routed.humans.push(party),
+            false => routed.organizations.push(party),
+        }
+    }
+    routed
+}
+
+/// The role an organization party's title names, scrapers put the role an organization plays in
+/// the docket there since organizations have no title of their own.
+fn raw_party_role(party: &RawGenericParty) -> Option<PetitionerRole> {
+    match party.human_title.trim().to_lowercase().as_str() {
+        "petitioner" => Some(PetitionerRole::Petitioner),
+        "intervenor" => Some(PetitionerRole::Intervenor),
+        "respondent" => Some(PetitionerRole::Respondent),
+        _ => None,
     }
 }
 
+/// Organization parties join the docket's orgs in the role the scraper gave them, unless the
+/// docket already lists them in some role. Parties without a known role are left out instead of
+/// guessing one.
+pub(crate) fn organization_party_petitioners(
+    organization_parties: Vec<RawGenericParty>,
+    listed: &[ProcessedGenericPetitioner],
+) -> Vec<ProcessedGenericPetitioner> {
+    let mut names_by_role: Vec<(PetitionerRole, Vec<String>)> = vec![];
+    for party in organization_parties {
+        let Some(role) = raw_party_role(&party) else {
+            tracing::info!(party = %party.name, title = %party.human_title, "Organization party has no known role, leaving it off the petitioner list");
+            continue;
+        };
+        match names_by_role.iter_mut().find(|(r, _)| *r == role) {
+            Some((_, names)) => names.push(party.name.to_string()),
+            None => names_by_role.push((role, vec![party.name.to_string()])),
+        }
+    }
+    let mut petitioners: Vec<ProcessedGenericPetitioner> = vec![];
+    for (role, names) in names_by_role {
+        for organization in clean_up_organization_name_list(names) {
+            let already_listed = listed.iter().chain(petitioners.iter()).any(|petitioner| {
+                petitioner.organization.truncated_org_name == organization.truncated_org_name
+                    && petitioner.organization.org_suffix == organization.org_suffix
+            });
+            if !already_listed {
+                petitioners.push(ProcessedGenericPetitioner { organization, role });
+            }
+        }
+    }
+    petitioners
+}
+
+/// Overrides the case type and subtype with the jurisdiction's metadata fields, but only when the
+/// scraper filled in both, so a type never gets paired with a subtype from a different source.
+pub fn apply_case_type_metadata(
+    docket: &mut ProcessedGenericDocket,
+    fixed_jurisdiction: FixedJurisdiction,
+) {
+    let Some(keys) = fixed_jurisdiction.get_case_type_metadata_keys() else {
+        return;
+    };
+    if let Some(case_type) = docket.extra_metadata_str(keys.type_key)
+        && let Some(case_subtype) = docket.extra_metadata_str(keys.subtype_key)
+    {
+        docket.case_type = case_type.to_string();
+        docket.case_subtype = case_subtype.to_string();
+    }
+}
+
+/// Drops the fillings that failed to process, so one bad filling doesn't take the rest of the
+/// docket down with it. Returns the kept fillings, and why each dropped one failed keyed by its
+/// govid.
+pub fn keep_successful_fillings<E: std::error::Error>(
+    docket_govid: &str,
+    filling_results: Vec<(String, Result<ProcessedGenericFiling, E>)>,
+) -> (Vec<ProcessedGenericFiling>, BTreeMap<String, String>) {
+    let mut kept = Vec::with_capacity(filling_results.len());
+    let mut dropped = BTreeMap::new();
+    for (index, (filling_govid, res)) in filling_results.into_iter().enumerate() {
+        match res {
+            Ok(filling) => kept.push(filling),
+            Err(err) => {
+                let error = error_with_sources(&err);
+                tracing::error!(%docket_govid, %filling_govid, filling_index = index, %error, "Failed to process filling, leaving it out of the docket");
+                dropped.insert(filling_govid, error);
+            }
+        }
+    }
+    (kept, dropped)
+}
+
+/// The error followed by every error that caused it, like anyhow's `{:#}`.
+fn error_with_sources(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {cause}"));
+        source = cause.source();
+    }
+    message
+}
+
+/// Drops the attachments that failed to process, so one bad attachment doesn't take the rest of
+/// the filling down with it.
+pub fn keep_successful_attachments<E: std::fmt::Display>(
+    docket_govid: &str,
+    filling_govid: &str,
+    attachment_results: Vec<Result<ProcessedGenericAttachment, E>>,
+) -> Vec<ProcessedGenericAttachment> {
+    attachment_results
+        .into_iter()
+        .filter_map(|res| match res {
+            Ok(attachment) => Some(attachment),
+            Err(err) => {
+                warn!(%docket_govid, %filling_govid, error = %err, "Failed to process attachment, leaving it out of the filling");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Everything that can stop a raw docket, filling or attachment from being processed.
 #[derive(Error, Debug)]
-enum ProcessError {
-    #[error("Encountered error syncing data with postgres.")]
-    PostgresError,
+pub enum ProcessingError {
+    #[error("Postgres pool was unavailable during processing")]
+    PoolUnavailable(#[from] InitializePostgresError),
+    #[error("LLM call failed while trying to {task}")]
+    LlmFailure {
+        task: &'static str,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("Could not associate author {author_name} with postgres")]
+    AuthorAssociation {
+        author_name: String,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("Processing took longer than the {0:?} allowed per docket")]
+    TimedOut(Duration),
+    #[error("Attachment url {url:?} is not a valid url")]
+    InvalidAttachmentUrl {
+        url: String,
+        #[source]
+        source: url::ParseError,
+    },
 }
 
 // TODO: Might be a good idea to have a semaphore for each
 static GLOBAL_SIMULTANEOUS_FILE_PROCESSING: Semaphore = Semaphore::const_new(50);
 
+/// How many of a filling's author associations query postgres at once, so a filling with
+/// hundreds of authors doesn't take hundreds of connections.
+pub static AUTHOR_ASSOCIATION_CONCURRENCY: LazyLock<usize> = LazyLock::new(|| {
+    env::var("AUTHOR_ASSOCIATION_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&v| v > 0)
+        .unwrap_or(8)
+});
+
+/// Like `join_all`, but each future waits for one of `permits` before it starts.
+pub(crate) async fn join_all_with_permits<F: Future>(
+    permits: &Semaphore,
+    futures: impl IntoIterator<Item = F>,
+) -> Vec<F::Output> {
+    join_all(futures.into_iter().map(async |future| {
+        let _permit = permits.acquire().await.unwrap();
+        future.await
+    }))
+    .await
+}
+
 impl ProcessFrom<RawGenericFiling> for ProcessedGenericFiling {
-    type ParseError = Infallible;
+    type ParseError = ProcessingError;
     type ExtraData = IndexExtraData;
     async fn process_from(
         input: RawGenericFiling,
@@ -301,9 +579,17 @@ impl ProcessFrom<RawGenericFiling> for ProcessedGenericFiling {
             .as_ref()
             .map(|v| v.object_uuid)
             .unwrap_or_else(Uuid::new_v4);
-        let _pg_pool = get_dokito_pool().await.unwrap();
+        let authors_fingerprint = raw_authors_fingerprint(&input);
         let (processed_attach_map, cached_orgauthorlist, cached_individualauthorllist) =
             match cached {
+                Some(filling) if cached_authors_are_stale(&input, &filling) => {
+                    tracing::info!(
+                        docket_govid = %index_data.docket_govid,
+                        filling_govid = %input.filling_govid,
+                        "Raw authors changed since the filling was cached, reprocessing them"
+                    );
+                    (Some(filling.attachments), None, None)
+                }
                 Some(filling) => (
                     Some(filling.attachments),
                     Some(filling.organization_authors),
@@ -316,34 +602,43 @@ impl ProcessFrom<RawGenericFiling> for ProcessedGenericFiling {
             match_raw_attaches_to_processed_attaches(input.attachments, processed_attach_map);
         // Async match the raw attachments with the cached versions, and process them async 5 at a
         // time.
-        let mut processed_attachments = stream::iter(matched_attach_list.into_iter())
+        let attachment_results = stream::iter(matched_attach_list.into_iter())
             .enumerate()
             .map(|(attach_index, (raw_attach, cached_attach))| {
                 let attach_index_data = IndexExtraData {
                     index: attach_index as u64,
                     jurisdiction: index_data.jurisdiction,
+                    docket_govid: index_data.docket_govid.clone(),
+                    author_association: index_data.author_association,
                 };
-                async {
-                    let res = ProcessedGenericAttachment::process_from(
-                        raw_attach,
-                        cached_attach,
-                        attach_index_data,
-                    )
-                    .await;
-                    let Ok(val) = res;
-                    val
-                }
+                ProcessedGenericAttachment::process_from(
+                    raw_attach,
+                    cached_attach,
+                    attach_index_data,
+                )
             })
             .buffer_unordered(5)
             .collect::<Vec<_>>()
             .await;
+        let mut processed_attachments = keep_successful_attachments(
+            &index_data.docket_govid,
+            &input.filling_govid,
+            attachment_results,
+        );
         processed_attachments.sort_by_key(|att| att.index_in_filling);
         // Process org and individual author names.
         let mut organization_authors = {
             if let Some(org_authors) = cached_orgauthorlist {
                 org_authors
             } else if input.organization_authors.is_empty() {
-                split_and_fix_organization_names_blob(&input.organization_authors_blob).await
+                split_and_fix_organization_names_blob(
+                    &input.organization_authors_blob,
+                    LlmCallContext {
+                        docket_govid: Some(&index_data.docket_govid),
+                        filling_govid: Some(&input.filling_govid),
+                    },
+                )
+                .await
             } else {
                 clean_up_organization_name_list(input.organization_authors)
             }
@@ -359,16 +654,52 @@ impl ProcessFrom<RawGenericFiling> for ProcessedGenericFiling {
                 vec![]
             }
         };
-        let fixed_jur = index_data.jurisdiction;
-        let pool = get_dokito_pool().await.unwrap();
+        let pg_schema = index_data.jurisdiction.get_postgres_schema_name();
+        let pool = get_dokito_pool_for(index_data.jurisdiction).await?;
 
-        let org_futures = organization_authors
-            .iter_mut()
-            .map(|org| associate_organization_with_name(org, fixed_jur, pool));
-        let human_futures = individual_authors
-            .iter_mut()
-            .map(|human| associate_individual_author_with_name(human, fixed_jur, pool));
-        let _res = join!(join_all(org_futures), join_all(human_futures));
+        let org_futures = organization_authors.iter_mut().map(async |org| {
+            index_data
+                .author_association
+                .associate_organization(org, pg_schema, pool)
+                .await
+                .map_err(|source| ProcessingError::AuthorAssociation {
+                    author_name: org.truncated_org_name.to_string(),
+                    source,
+                })
+        });
+        let human_futures = individual_authors.iter_mut().map(async |human| {
+            index_data
+                .author_association
+                .associate_individual(human, pg_schema, pool)
+                .await
+                .map_err(|source| ProcessingError::AuthorAssociation {
+                    author_name: human.human_name.to_string(),
+                    source,
+                })
+        });
+        let association_permits = Semaphore::new(*AUTHOR_ASSOCIATION_CONCURRENCY);
+        let (org_results, human_results) = join!(
+            join_all_with_permits(&association_permits, org_futures),
+            join_all_with_permits(&association_permits, human_futures)
+        );
+        for res in org_results.into_iter().chain(human_results) {
+            res?;
+        }
+
+        // Super hacky workaround until I can change the input type.
+        let mut extra_metadata: BTreeMap<String, serde_json::Value> =
+            input.extra_metadata.clone().into_iter().collect();
+        extra_metadata.insert(
+            RAW_AUTHORS_FINGERPRINT.to_string(),
+            authors_fingerprint.into(),
+        );
+        let filing_type = normalize_filing_type(&input.filing_type);
+        if filing_type != input.filing_type {
+            extra_metadata.insert(
+                ORIGINAL_FILING_TYPE.to_string(),
+                input.filing_type.clone().into(),
+            );
+        }
 
         let proc_filling = Self {
             object_uuid,
@@ -378,10 +709,9 @@ impl ProcessFrom<RawGenericFiling> for ProcessedGenericFiling {
             name: input.name.clone(),
             filling_govid: input.filling_govid.clone(),
             filling_url: input.filling_url.clone(),
-            filing_type: input.filing_type.clone(),
+            filing_type,
             description: input.description.clone(),
-            // Super hacky workaround until I can change the input type.
-            extra_metadata: input.extra_metadata.clone().into_iter().collect(),
+            extra_metadata,
             organization_authors,
             individual_authors,
         };
@@ -392,15 +722,27 @@ impl ProcessFrom<RawGenericFiling> for ProcessedGenericFiling {
 pub struct IndexExtraData {
     index: u64,
     jurisdiction: FixedJurisdiction,
+    docket_govid: String,
+    author_association: AuthorAssociationMode,
 }
 impl ProcessFrom<RawGenericAttachment> for ProcessedGenericAttachment {
-    type ParseError = Infallible;
+    type ParseError = ProcessingError;
     type ExtraData = IndexExtraData;
     async fn process_from(
         input: RawGenericAttachment,
         cached: Option<Self>,
         index_data: Self::ExtraData,
     ) -> Result<Self, Self::ParseError> {
+        // An empty url just means the scraper never found one, but anything else has to be
+        // something the attachment could actually be downloaded from.
+        if !input.url.is_empty()
+            && let Err(source) = url::Url::parse(&input.url)
+        {
+            return Err(ProcessingError::InvalidAttachmentUrl {
+                url: input.url,
+                source,
+            });
+        }
         let uuid = cached
             .as_ref()
             .map(|val| val.object_uuid)
@@ -1,32 +1,62 @@
 use crate::data_processing_traits::{DownloadIncomplete, RevalidationOutcome};
-use crate::indexes::attachment_url_index::lookup_hash_from_url;
+use crate::extra_metadata::ExtraMetadataExt;
+use crate::indexes::attachment_url_index::{
+    AttachmentUrlFetcher, add_attachment_to_url_index, lookup_hash_from_url,
+};
 use crate::jurisdiction_schema_mapping::FixedJurisdiction;
 use crate::processing::file_fetching::{FileDownloadError, RequestMethod};
 use crate::s3_stuff::{
     generate_s3_object_uri_from_key, get_raw_attach_file_key, get_s3_json_uri,
     push_raw_attach_file_to_s3, upload_object,
 };
+use crate::types::attachments::{HashAlgorithm, Sha256Hash};
 use crate::types::processed::ProcessedGenericAttachment;
 use crate::types::{attachments::RawAttachment, jurisdictions::JurisdictionInfo};
+use async_trait::async_trait;
 use aws_sdk_s3::Client as S3Client;
 use chrono::Utc;
-use mycorrhiza_common::file_extension::FileExtension;
+use mycorrhiza_common::file_extension::{FileExtension, StaticExtension};
 use mycorrhiza_common::hash::Blake2bHash;
 use non_empty_string::{NonEmptyString, non_empty_string};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::env;
 use std::str::FromStr;
+use std::sync::LazyLock;
 use std::time::Duration;
 use tokio::sync::Semaphore;
 use tokio::time::sleep;
 use tracing::{debug, info};
 
-use super::file_fetching::{AdvancedFetchData, FileDownloadResult, InternetFileFetch};
+use super::file_fetching::{
+    AdvancedFetchData, FileDownloadResult, InternetFileFetch, UrlWithHeaders,
+    attachment_fetch_headers,
+};
 
 const ATTACHMENT_DOWNLOAD_TRIES: usize = 2;
 const DOWNLOAD_RETRY_DELAY_SECONDS: u64 = 2;
 
+/// The algorithm downloaded attachments get hashed with for the systems we exchange attachments
+/// with, from `ATTACHMENT_HASH_ALGORITHM`. Every attachment keeps its blake2b hash regardless,
+/// since that's what its S3 keys and the documents table go by.
+pub static ATTACHMENT_HASH_ALGORITHM: LazyLock<HashAlgorithm> = LazyLock::new(|| {
+    env::var("ATTACHMENT_HASH_ALGORITHM")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_default()
+});
+
+/// The SHA-256 digest of `file_contents` when `algorithm` asks for one, blake2b is already on
+/// every attachment.
+pub fn sha256_hash_for(algorithm: HashAlgorithm, file_contents: &[u8]) -> Option<Sha256Hash> {
+    match algorithm {
+        HashAlgorithm::Blake2b => None,
+        HashAlgorithm::Sha256 => Some(Sha256Hash::from_bytes(Sha256::digest(file_contents).into())),
+    }
+}
+
 // For context it was previously this
 // pub type OpenscrapersExtraData = (S3Client, JurisdictionInfo);
 
@@ -35,6 +65,39 @@ pub struct OpenscrapersExtraData {
     pub s3_client: S3Client,
     pub jurisdiction_info: JurisdictionInfo,
     pub fixed_jurisdiction: FixedJurisdiction,
+    pub attachment_fetch_order: AttachmentFetchOrder,
+}
+
+/// The order the attachments of a docket missing a hash get downloaded in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentFetchOrder {
+    /// The order they are stored in the docket.
+    #[default]
+    AsIs,
+    /// Smallest first by the size the scraper reported, for quick wins. Attachments without a
+    /// reported size go last.
+    SmallestFirst,
+    /// PDFs first, everything else after.
+    PdfFirst,
+}
+
+impl AttachmentFetchOrder {
+    /// Sorts stably, so attachments that rank the same keep their stored order.
+    pub fn sort(self, attachments: &mut [&mut ProcessedGenericAttachment]) {
+        match self {
+            AttachmentFetchOrder::AsIs => {}
+            AttachmentFetchOrder::SmallestFirst => {
+                attachments.sort_by_key(|att| att.file_size_bytes().unwrap_or(u64::MAX))
+            }
+            AttachmentFetchOrder::PdfFirst => attachments.sort_by_key(|att| {
+                !matches!(
+                    att.document_extension,
+                    FileExtension::Static(StaticExtension::Pdf)
+                )
+            }),
+        }
+    }
 }
 
 impl DownloadIncomplete for ProcessedGenericAttachment {
@@ -43,8 +106,6 @@ impl DownloadIncomplete for ProcessedGenericAttachment {
         &mut self,
         extra_data: Self::ExtraData,
     ) -> anyhow::Result<RevalidationOutcome> {
-        let name = NonEmptyString::from_str(&self.name)
-            .unwrap_or_else(|_| non_empty_string!("unknown_filename"));
         if self.hash.is_some() {
             return Ok(RevalidationOutcome::NoChanges);
         }
@@ -53,36 +114,81 @@ impl DownloadIncomplete for ProcessedGenericAttachment {
             self.hash = Some(cached_attach.hash);
             return Ok(RevalidationOutcome::DidChange);
         };
-        debug!(url=%self.url,"Trying to download attachment file.");
-        let extension = &self.document_extension;
+        let downloader = UrlAttachmentDownloader::for_attachment(
+            extra_data.s3_client,
+            extra_data.jurisdiction_info,
+            self,
+        );
+        let raw_attachment = downloader.fetch_and_store(&self.url).await?;
+        let hash = raw_attachment.hash;
+        add_attachment_to_url_index(raw_attachment).await;
+        self.hash = Some(hash);
+        debug!(%hash, url = %self.url,"Successfully downloaded attachment and saved everything to s3.");
+        Ok(RevalidationOutcome::DidChange)
+    }
+}
+
+/// Downloads an attachment by url and ships the file and its metadata out to s3.
+pub struct UrlAttachmentDownloader {
+    pub s3_client: S3Client,
+    pub jurisdiction_info: JurisdictionInfo,
+    pub extension: FileExtension,
+    pub name: NonEmptyString,
+}
+
+impl UrlAttachmentDownloader {
+    /// Downloads the file behind `attachment`'s url, stored under its name and extension.
+    pub fn for_attachment(
+        s3_client: S3Client,
+        jurisdiction_info: JurisdictionInfo,
+        attachment: &ProcessedGenericAttachment,
+    ) -> Self {
+        let name = NonEmptyString::from_str(&attachment.name)
+            .unwrap_or_else(|_| non_empty_string!("unknown_filename"));
+        UrlAttachmentDownloader {
+            s3_client,
+            jurisdiction_info,
+            extension: attachment.document_extension.clone(),
+            name,
+        }
+    }
+}
 
+#[async_trait]
+impl AttachmentUrlFetcher for UrlAttachmentDownloader {
+    async fn fetch_and_store(&self, url: &str) -> anyhow::Result<RawAttachment> {
+        debug!(%url,"Trying to download attachment file.");
         let FileDownloadResult {
             data: file_contents,
             filename: server_filename,
-        } = download_file_content_validated_with_retries(&self.url, extension).await?;
+        } = match attachment_fetch_headers(&self.jurisdiction_info) {
+            Some(headers) => {
+                let fetch = UrlWithHeaders { url, headers };
+                download_file_content_validated_with_retries(&fetch, &self.extension).await?
+            }
+            None => download_file_content_validated_with_retries(url, &self.extension).await?,
+        };
         let hash = Blake2bHash::from_bytes(&file_contents);
-        debug!(%hash, url=%self.url,"Successfully downloaded file.");
+        debug!(%hash, %url,"Successfully downloaded file.");
 
         let metadata = server_filename
             .map(|exant_filename| HashMap::from([("server_filename".to_string(), exant_filename)]))
             .unwrap_or_default();
 
         let raw_attachment = RawAttachment {
-            jurisdiction_info: extra_data.jurisdiction_info.clone(),
-            url: self.url.clone(),
+            jurisdiction_info: self.jurisdiction_info.clone(),
+            url: url.to_string(),
             hash,
+            sha256_hash: sha256_hash_for(*ATTACHMENT_HASH_ALGORITHM, &file_contents),
             file_size_bytes: file_contents.len() as u64,
-            name,
-            extension: extension.clone(),
+            name: self.name.clone(),
+            extension: self.extension.clone(),
             text_objects: vec![],
             date_added: Utc::now(),
             date_updated: Utc::now(),
             extra_metadata: metadata,
         };
-        shipout_attachment_to_s3(file_contents, raw_attachment, &extra_data.s3_client).await?;
-        self.hash = Some(hash);
-        debug!(%hash, url = %self.url,"Successfully downloaded attachment and saved everything to s3.");
-        Ok(RevalidationOutcome::DidChange)
+        shipout_attachment_to_s3(file_contents, raw_attachment, &self.s3_client).await
     }
 }
 
@@ -156,6 +262,7 @@ pub async fn process_attachment_with_direct_request(
     let raw_attachment = RawAttachment {
         jurisdiction_info: direct_info.jurisdiction_info.clone(),
         hash,
+        sha256_hash: sha256_hash_for(*ATTACHMENT_HASH_ALGORITHM, &file_contents),
         file_size_bytes: file_contents.len() as u64,
         url: url_value.unwrap_or_default(),
         name: actual_filename.clone(),
@@ -169,7 +276,10 @@ pub async fn process_attachment_with_direct_request(
         attachment: raw_attachment.clone(),
         hash,
         server_file_name: server_filename,
-        file_s3_uri: generate_s3_object_uri_from_key(&get_raw_attach_file_key(hash)),
+        file_s3_uri: generate_s3_object_uri_from_key(
+            &get_raw_attach_file_key(hash),
+            &direct_info.jurisdiction_info,
+        ),
         object_s3_uri: get_s3_json_uri::<RawAttachment>(&hash),
     };
     let mut return_info_clone = return_info.clone();
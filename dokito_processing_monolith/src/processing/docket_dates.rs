@@ -0,0 +1,89 @@
+use std::sync::LazyLock;
+
+use chrono::NaiveDate;
+use mycorrhiza_common::misc::is_env_var_true;
+use tracing::warn;
+
+use crate::types::processed::ProcessedGenericDocket;
+
+/// Off by default, since a docket whose type says it concluded can still get late fillings after
+/// the real closing date.
+pub static INFER_CLOSED_DATE: LazyLock<bool> =
+    LazyLock::new(|| is_env_var_true("INFER_CLOSED_DATE"));
+
+const CLOSED_CASE_KEYWORDS: &[&str] = &[
+    "closed",
+    "concluded",
+    "dismissed",
+    "withdrawn",
+    "terminated",
+];
+
+fn docket_indicates_closure(docket: &ProcessedGenericDocket) -> bool {
+    [&docket.case_type, &docket.case_subtype]
+        .iter()
+        .any(|field| {
+            let lowercase = field.to_lowercase();
+            CLOSED_CASE_KEYWORDS
+                .iter()
+                .any(|keyword| lowercase.contains(keyword))
+        })
+}
+
+/// Fills in a missing closed_date with the latest filing date, but only for dockets whose type or
+/// subtype says they are closed. Returns the inferred date if one was set.
+pub fn infer_closed_date(docket: &mut ProcessedGenericDocket, enabled: bool) -> Option<NaiveDate> {
+    if !enabled || docket.closed_date.is_some() || !docket_indicates_closure(docket) {
+        return None;
+    }
+    let latest_filing_date = docket.filings.iter().filter_map(|f| f.filed_date).max()?;
+    docket.closed_date = Some(latest_filing_date);
+    Some(latest_filing_date)
+}
+
+/// Where a docket's opened_date comes from, since some jurisdictions have reliable opened dates
+/// in their raw data and others don't.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpenedDateSource {
+    /// Use the raw opened date, falling back to the earliest filling without one.
+    TrustRaw,
+    /// Use the earliest filling date, falling back to the raw opened date without fillings.
+    EarliestFiling,
+    /// Use the earliest of the raw opened date and the filling dates, so the docket never opens
+    /// after one of its fillings.
+    LatestFilingNever,
+}
+
+impl OpenedDateSource {
+    /// Parses the names used in `OPENED_DATE_SOURCES`, ie `trust_raw`.
+    pub fn from_config_name(name: &str) -> Option<Self> {
+        match name {
+            "trust_raw" => Some(OpenedDateSource::TrustRaw),
+            "earliest_filing" => Some(OpenedDateSource::EarliestFiling),
+            "latest_filing_never" => Some(OpenedDateSource::LatestFilingNever),
+            _ => None,
+        }
+    }
+}
+
+pub fn compute_opened_date(
+    source: OpenedDateSource,
+    raw_opened_date: Option<NaiveDate>,
+    filling_dates: impl IntoIterator<Item = NaiveDate>,
+) -> Option<NaiveDate> {
+    let earliest_filling = filling_dates.into_iter().min();
+    if let Some(raw_date) = raw_opened_date
+        && let Some(filling_date) = earliest_filling
+        && filling_date < raw_date
+    {
+        warn!(docket_opened_date =%raw_date, oldest_date_found=%filling_date,"Found filling with date older then the docket opened date");
+    }
+    match source {
+        OpenedDateSource::TrustRaw => raw_opened_date.or(earliest_filling),
+        OpenedDateSource::EarliestFiling => earliest_filling.or(raw_opened_date),
+        OpenedDateSource::LatestFilingNever => match (raw_opened_date, earliest_filling) {
+            (Some(raw_date), Some(filling_date)) => Some(raw_date.min(filling_date)),
+            (raw_date, filling_date) => raw_date.or(filling_date),
+        },
+    }
+}
@@ -1,14 +1,23 @@
-use std::{collections::HashMap, fmt::Debug, str::FromStr, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    fmt::Debug,
+    str::FromStr,
+    sync::LazyLock,
+    time::Duration,
+};
 
 use anyhow::anyhow;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use thiserror::Error;
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
 use mycorrhiza_common::file_extension::FileValidationError;
 
+use crate::types::jurisdictions::JurisdictionInfo;
+
 #[derive(Debug, Clone)]
 pub struct FileDownloadResult {
     pub data: Vec<u8>,
@@ -30,6 +39,8 @@ pub enum FileDownloadError {
     Network(reqwest::Error),
     #[error("File download timed out: {0}")]
     Timeout(reqwest::Error),
+    #[error("File download exceeded the {max_bytes} byte size cap")]
+    TooLarge { max_bytes: u64 },
     #[error("File download failed with an unknown error: {0}")]
     Unknown(#[from] anyhow::Error),
 }
@@ -44,12 +55,51 @@ impl FileDownloadError {
             Self::InvalidReturnData(_) => true,
             Self::Network(_) => true,
             Self::Timeout(_) => true,
+            Self::TooLarge { .. } => false,
             Self::Unknown(_) => false,
         }
     }
 }
 
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(40);
+
+/// Downloads bigger than this get aborted, so a single huge attachment can't exhaust memory.
+pub static MAX_ATTACHMENT_DOWNLOAD_BYTES: LazyLock<u64> = LazyLock::new(|| {
+    env::var("MAX_ATTACHMENT_DOWNLOAD_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1024 * 1024 * 1024)
+});
+
+/// Request headers sent when fetching the attachment urls of jurisdictions whose document portals
+/// want auth headers or cookies, as JSON keyed by jurisdiction name, ie
+/// `ATTACHMENT_FETCH_HEADERS={"co_puc":{"Cookie":"session=..."}}`.
+pub static ATTACHMENT_FETCH_HEADERS: LazyLock<BTreeMap<String, HashMap<String, String>>> =
+    LazyLock::new(|| {
+        let Ok(headers) = env::var("ATTACHMENT_FETCH_HEADERS") else {
+            return BTreeMap::new();
+        };
+        serde_json::from_str(&headers).unwrap_or_else(|err| {
+            warn!(%err, "Ignoring malformed ATTACHMENT_FETCH_HEADERS");
+            BTreeMap::new()
+        })
+    });
+
+fn fetch_headers_for<'a>(
+    jurisdiction: &JurisdictionInfo,
+    configured: &'a BTreeMap<String, HashMap<String, String>>,
+) -> Option<&'a HashMap<String, String>> {
+    configured.get(&*jurisdiction.jurisdiction)
+}
+
+/// The headers to send along when fetching an attachment url of `jurisdiction`, if it has any
+/// configured.
+pub fn attachment_fetch_headers(
+    jurisdiction: &JurisdictionInfo,
+) -> Option<&'static HashMap<String, String>> {
+    fetch_headers_for(jurisdiction, &ATTACHMENT_FETCH_HEADERS)
+}
+
 pub trait InternetFileFetch: Debug {
     // New methods that return filename along with data
     async fn download_file_with_timeout(
@@ -158,6 +208,36 @@ impl<T: AsRef<str> + Debug + ?Sized> InternetFileFetch for T {
     }
 }
 
+/// A url fetched with extra request headers. They usually hold credentials, so `Debug` only shows
+/// the url and they stay out of the logs.
+pub struct UrlWithHeaders<'a> {
+    pub url: &'a str,
+    pub headers: &'a HashMap<String, String>,
+}
+
+impl Debug for UrlWithHeaders<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UrlWithHeaders")
+            .field("url", &self.url)
+            .finish_non_exhaustive()
+    }
+}
+
+impl InternetFileFetch for UrlWithHeaders<'_> {
+    async fn download_file_with_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<FileDownloadResult, FileDownloadError> {
+        let advanced_data = AdvancedFetchData {
+            url: self.url.to_owned(),
+            headers: Some(self.headers.clone()),
+            request_body: None,
+            request_type: RequestMethod::Get,
+        };
+        advanced_data.download_file_with_timeout(timeout).await
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
 pub enum RequestMethod {
     #[default]
@@ -177,6 +257,17 @@ impl InternetFileFetch for AdvancedFetchData {
     async fn download_file_with_timeout(
         &self,
         timeout: Duration,
+    ) -> Result<FileDownloadResult, FileDownloadError> {
+        self.download_file_with_size_cap(timeout, *MAX_ATTACHMENT_DOWNLOAD_BYTES)
+            .await
+    }
+}
+
+impl AdvancedFetchData {
+    async fn download_file_with_size_cap(
+        &self,
+        timeout: Duration,
+        max_bytes: u64,
     ) -> Result<FileDownloadResult, FileDownloadError> {
         let client = reqwest::Client::new();
 
@@ -202,7 +293,7 @@ impl InternetFileFetch for AdvancedFetchData {
             .send()
             .await;
 
-        let response = match response_result {
+        let mut response = match response_result {
             Ok(res) => res,
             Err(err) => {
                 tracing::error!(%err,"Encountered network error getting file.");
@@ -231,11 +322,25 @@ impl InternetFileFetch for AdvancedFetchData {
         let filename = extract_filename_from_headers(response.headers())
             .or_else(|| extract_filename_from_url(&self.url));
 
-        let bytes = response
-            .bytes()
+        if let Some(content_length) = response.content_length()
+            && content_length > max_bytes
+        {
+            warn!(url = %self.url, %content_length, %max_bytes, "Skipping attachment download larger than the size cap");
+            return Err(FileDownloadError::TooLarge { max_bytes });
+        }
+        // The content length can be missing or wrong, so the cap is also enforced while reading.
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
             .await
             .map_err(|err| FileDownloadError::Unknown(err.into()))?
-            .to_vec();
+        {
+            if (bytes.len() + chunk.len()) as u64 > max_bytes {
+                warn!(url = %self.url, %max_bytes, "Aborted attachment download that exceeded the size cap");
+                return Err(FileDownloadError::TooLarge { max_bytes });
+            }
+            bytes.extend_from_slice(&chunk);
+        }
 
         if let Some(ref fname) = filename {
             debug!("Detected filename: {}", fname);
@@ -247,3 +352,88 @@ impl InternetFileFetch for AdvancedFetchData {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router,
+        http::{HeaderMap, StatusCode},
+        routing::get,
+    };
+
+    async fn serve_file_of_size(size: usize) -> String {
+        serve(Router::new().route("/file.pdf", get(async move || vec![b'a'; size]))).await
+    }
+
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{addr}/file.pdf")
+    }
+
+    fn get_request(url: String) -> AdvancedFetchData {
+        AdvancedFetchData {
+            url,
+            request_type: RequestMethod::Get,
+            request_body: None,
+            headers: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_oversized_download_is_aborted() {
+        let fetch = get_request(serve_file_of_size(4096).await);
+
+        let err = fetch
+            .download_file_with_size_cap(Duration::from_secs(5), 1024)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            FileDownloadError::TooLarge { max_bytes: 1024 }
+        ));
+        assert!(!err.is_retryable());
+
+        let result = fetch
+            .download_file_with_size_cap(Duration::from_secs(5), 8192)
+            .await
+            .unwrap();
+        assert_eq!(result.data.len(), 4096);
+    }
+
+    #[tokio::test]
+    async fn test_configured_headers_are_sent_for_their_jurisdiction() {
+        let url = serve(Router::new().route(
+            "/file.pdf",
+            get(async |headers: HeaderMap| -> Result<Vec<u8>, StatusCode> {
+                match headers.get("authorization").and_then(|v| v.to_str().ok()) {
+                    Some("Bearer portal-token") => Ok(vec![b'a'; 16]),
+                    _ => Err(StatusCode::FORBIDDEN),
+                }
+            }),
+        ))
+        .await;
+        let configured = BTreeMap::from([(
+            "co_puc".to_string(),
+            HashMap::from([(
+                "Authorization".to_string(),
+                "Bearer portal-token".to_string(),
+            )]),
+        )]);
+
+        let co_puc = JurisdictionInfo::new_usa("co_puc", "co");
+        let fetch = UrlWithHeaders {
+            url: &url,
+            headers: fetch_headers_for(&co_puc, &configured).unwrap(),
+        };
+        assert_eq!(fetch.download_file().await.unwrap().data.len(), 16);
+        assert!(!format!("{fetch:?}").contains("portal-token"));
+
+        let other = JurisdictionInfo::new_usa("ut_dogm_coal", "ut");
+        assert!(fetch_headers_for(&other, &configured).is_none());
+        let err = url.download_file().await.unwrap_err();
+        assert!(matches!(err, FileDownloadError::BadResponseCode(403)));
+    }
+}
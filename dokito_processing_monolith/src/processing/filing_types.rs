@@ -0,0 +1,44 @@
+use tracing::warn;
+
+// Keywords are matched against the lowercased words of the raw filing type, the first word with a
+// match decides the category. So "COMMENT/REPLY" is a comment.
+const FILING_TYPE_KEYWORDS: &[(&str, &[&str])] = &[
+    ("Comment", &["comment", "comments"]),
+    ("Reply", &["reply", "replies", "response", "responses"]),
+    ("Motion", &["motion", "motions"]),
+    ("Order", &["order", "orders", "ruling", "rulings"]),
+    ("Notice", &["notice", "notices", "notification"]),
+    ("Testimony", &["testimony", "testimonies"]),
+    ("Brief", &["brief", "briefs"]),
+    ("Petition", &["petition", "petitions"]),
+    ("Correspondence", &["letter", "letters", "correspondence"]),
+    ("Report", &["report", "reports"]),
+    ("Tariff", &["tariff", "tariffs"]),
+    ("Application", &["application", "applications"]),
+    ("Exhibit", &["exhibit", "exhibits"]),
+    ("Transcript", &["transcript", "transcripts"]),
+];
+
+/// Maps the many spellings of a filing type used across jurisdictions onto a canonical category.
+/// Unknown filing types are returned unchanged.
+pub fn normalize_filing_type(raw_filing_type: &str) -> String {
+    let lowercase = raw_filing_type.trim().to_lowercase();
+    if lowercase.is_empty() {
+        return raw_filing_type.to_string();
+    }
+    let canonical = lowercase
+        .split(|c: char| !c.is_alphanumeric())
+        .find_map(|word| {
+            FILING_TYPE_KEYWORDS
+                .iter()
+                .find(|(_, keywords)| keywords.contains(&word))
+                .map(|(category, _)| *category)
+        });
+    match canonical {
+        Some(category) => category.to_string(),
+        None => {
+            warn!(%raw_filing_type, "Could not normalize unknown filing type");
+            raw_filing_type.to_string()
+        }
+    }
+}
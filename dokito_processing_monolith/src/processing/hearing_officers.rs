@@ -0,0 +1,107 @@
+use non_empty_string::NonEmptyString;
+use uuid::Uuid;
+
+use crate::types::processed::ProcessedGenericHuman;
+
+// Matched as whole words against the lowercased start of each officer, who can carry several of
+// them, like "Hon. Judge".
+const OFFICER_TITLES: &[&str] = &[
+    "chief administrative law judge",
+    "administrative law judges",
+    "administrative law judge",
+    "presiding officer",
+    "hearing examiner",
+    "hearing officer",
+    "commissioner",
+    "honorable",
+    "judges",
+    "judge",
+    "chair",
+    "hon.",
+    "hon",
+    "alj",
+    "dr.",
+    "mrs.",
+    "mr.",
+    "ms.",
+];
+
+// Trailing parts of a name that are neither the first nor the last name.
+const NAME_SUFFIXES: &[&str] = &[
+    "jr.", "jr", "sr.", "sr", "ii", "iii", "iv", "esq.", "esq", "alj",
+];
+
+/// Splits the raw hearing officer string of a docket into one human per officer, with their title
+/// and first and last names. Officers can be separated by ";", "/", "&" or "and", and names given
+/// as "Last, First" are flipped around. The humans still need to be associated with a uuid.
+pub fn parse_hearing_officers(raw_hearing_officer: &str) -> Vec<ProcessedGenericHuman> {
+    let normalized = raw_hearing_officer
+        .replace(" and ", ";")
+        .replace(" And ", ";")
+        .replace(['&', '/', '\n'], ";");
+    normalized
+        .split(';')
+        .flat_map(split_comma_separated_officers)
+        .filter_map(|officer| parse_hearing_officer(&officer))
+        .collect()
+}
+
+/// A comma either separates officers, "Angela Lee, Kevin Casutto", or flips a single name,
+/// "Lee, Angela J.". It only counts as a flipped name when the half before it is a single word.
+fn split_comma_separated_officers(officers: &str) -> Vec<String> {
+    let parts = officers
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty() && !is_name_suffix(part))
+        .collect::<Vec<_>>();
+    match parts.as_slice() {
+        [last, first] if !last.contains(' ') => {
+            vec![format!("{first} {last}")]
+        }
+        _ => parts.into_iter().map(str::to_string).collect(),
+    }
+}
+
+fn is_name_suffix(word: &str) -> bool {
+    NAME_SUFFIXES.contains(&word.to_lowercase().as_str())
+}
+
+fn parse_hearing_officer(officer: &str) -> Option<ProcessedGenericHuman> {
+    let mut name = officer.trim();
+    let mut titles = vec![];
+    while let Some(title_len) = leading_title_len(name) {
+        titles.push(name[..title_len].trim_end_matches('.').to_string());
+        name = name[title_len..].trim_start();
+    }
+    let words = name
+        .split_whitespace()
+        .filter(|word| !is_name_suffix(word))
+        .collect::<Vec<_>>();
+    let (first_name, last_name) = match words.as_slice() {
+        [] => return None,
+        [only_name] => (String::new(), only_name.to_string()),
+        [first_name, .., last_name] => (first_name.to_string(), last_name.to_string()),
+    };
+    Some(ProcessedGenericHuman {
+        human_name: NonEmptyString::try_from(words.join(" ")).ok()?,
+        object_uuid: Uuid::nil(),
+        western_first_name: first_name,
+        western_last_name: last_name,
+        contact_emails: vec![],
+        contact_phone_numbers: vec![],
+        contact_addresses: vec![],
+        representing_company: None,
+        employed_by: None,
+        title: titles.join(" "),
+    })
+}
+
+/// How many bytes of `name` a leading title takes up, only matching whole words.
+fn leading_title_len(name: &str) -> Option<usize> {
+    let lowercase = name.to_lowercase();
+    OFFICER_TITLES.iter().find_map(|title| {
+        let rest = lowercase.strip_prefix(title)?;
+        let ends_on_word = title.ends_with('.') || rest.is_empty() || rest.starts_with(' ');
+        (ends_on_word && !rest.trim().is_empty()).then_some(title.len())
+    })
+}
@@ -1,11 +1,93 @@
+use std::{
+    env,
+    sync::{
+        LazyLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
 use dokito_types::processed::{OrganizationType, ProcessedGenericOrganization};
-use mycorrhiza_common::llm_deepinfra::{cheap_prompt, strip_think};
+use metrics::histogram;
+use mycorrhiza_common::{
+    llm_deepinfra::{cheap_prompt, strip_think},
+    misc::is_env_var_true,
+};
 use non_empty_string::NonEmptyString;
 use serde::Serialize;
+use tokio::time::timeout;
 use uuid::Uuid;
 
+use crate::openscraper_data_traits::ProcessingError;
+use crate::sql_ingester_tasks::nypuc_ingest::truncate_safe;
+
+/// Seconds spent turning an organization blob into organizations, labelled with an `outcome` of
+/// `success`, `fallback`, `timeout` or `disabled`.
+pub const LLM_ORG_SPLIT_DURATION_SECONDS: &str = "dokito_llm_org_split_duration_seconds";
+
+/// Seconds spent cleaning up the names the LLM split an organization blob into.
+pub const ORG_NAME_CLEANUP_DURATION_SECONDS: &str = "dokito_org_name_cleanup_duration_seconds";
+
+const DEFAULT_LLM_ORG_SPLIT_TIMEOUT_SECS: u64 = 60;
+
+/// How long the LLM gets to split an organization blob before the naive fallback is used.
+pub static LLM_ORG_SPLIT_TIMEOUT: LazyLock<Duration> = LazyLock::new(|| {
+    let secs = env::var("LLM_ORG_SPLIT_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_LLM_ORG_SPLIT_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+});
+
+/// Forces the naive fallbacks instead of any LLM call while it is on, so they can be stopped
+/// during a DeepInfra outage or cost spike without a redeploy.
+pub struct LlmKillSwitch(AtomicBool);
+
+impl LlmKillSwitch {
+    pub const fn new(on: bool) -> Self {
+        Self(AtomicBool::new(on))
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, on: bool) {
+        self.0.store(on, Ordering::Relaxed);
+    }
+}
+
+/// Starts on when `DISABLE_LLM_CALLS` is set, and can be flipped through the admin api.
+pub static LLM_KILL_SWITCH: LazyLock<LlmKillSwitch> =
+    LazyLock::new(|| LlmKillSwitch::new(is_env_var_true("DISABLE_LLM_CALLS")));
+
+/// When set, every LLM call logs what it was sent and what came back under [`LLM_AUDIT_TARGET`],
+/// so a mangled name can be traced back to the call that produced it. Off by default since it
+/// logs once per docket and filling.
+pub static LLM_AUDIT_LOGGING: LazyLock<bool> =
+    LazyLock::new(|| is_env_var_true("LLM_AUDIT_LOGGING"));
+
+/// The tracing target audit entries are logged under.
+pub const LLM_AUDIT_TARGET: &str = "dokito::llm_audit";
+
+/// Audited inputs and outputs are cut down to this many bytes.
+const LLM_AUDIT_MAX_BYTES: usize = 2000;
 
-pub async fn org_split_from_dump(org_dump: &str) -> anyhow::Result<Vec<String>> {
+/// Which docket and filling an LLM call was made for, logged along with it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LlmCallContext<'a> {
+    pub docket_govid: Option<&'a str>,
+    pub filling_govid: Option<&'a str>,
+}
+
+fn truncate_for_audit(text: &str) -> String {
+    match truncate_safe(text, LLM_AUDIT_MAX_BYTES) {
+        truncated if truncated.len() < text.len() => format!("{truncated}..."),
+        _ => text.to_string(),
+    }
+}
+
+pub async fn org_split_from_dump(org_dump: &str) -> Result<Vec<String>, ProcessingError> {
     let prompt = format!(
         r#"We have an unformatted list of individuals and or organizations, try and parse them out as a json serializable list of organizations like so, we are also trying to match the organizations on their name, so removing the d/b/a suffix is important. YOUR RESPONSE MUST BE JSON SERIALIZABLE AND CONTAIN NO OTHER TEXT:
 Example 1:
@@ -40,33 +122,104 @@ Final:
 Response:
 "#
     );
-    let result = cheap_prompt(&prompt).await.map_err(anyhow::Error::from)?;
+    let to_llm_failure = |source: anyhow::Error| ProcessingError::LlmFailure {
+        task: "split an organization blob into names",
+        source,
+    };
+    let result = cheap_prompt(&prompt)
+        .await
+        .map_err(|e| to_llm_failure(e.into()))?;
     let json_res = serde_json::from_slice::<Vec<String>>(strip_think(&result).as_bytes());
-    json_res.map_err(anyhow::Error::from)
+    json_res.map_err(|e| to_llm_failure(e.into()))
 }
 
 pub async fn split_and_fix_organization_names_blob(
     org_blob: &str,
+    context: LlmCallContext<'_>,
 ) -> Vec<ProcessedGenericOrganization> {
+    split_and_fix_organization_names_blob_with(
+        org_blob,
+        context,
+        &LLM_KILL_SWITCH,
+        *LLM_AUDIT_LOGGING,
+        org_split_from_dump,
+    )
+    .await
+}
+
+/// Does the work of `split_and_fix_organization_names_blob` with the given LLM call, recording
+/// how long it took in `LLM_ORG_SPLIT_DURATION_SECONDS`. The LLM is not called at all while
+/// `kill_switch` is on, and its input and output are logged when `audit_logging` is.
+pub(crate) async fn split_and_fix_organization_names_blob_with<'a, F>(
+    org_blob: &'a str,
+    context: LlmCallContext<'_>,
+    kill_switch: &LlmKillSwitch,
+    audit_logging: bool,
+    llm_split: impl FnOnce(&'a str) -> F,
+) -> Vec<ProcessedGenericOrganization>
+where
+    F: Future<Output = Result<Vec<String>, ProcessingError>>,
+{
     if org_blob.is_empty() {
         return Vec::new();
     }
-    let Ok(llm_parsed_names) = org_split_from_dump(org_blob).await else {
-        return clean_organization_name(org_blob.to_string())
+    let started_at = Instant::now();
+    // The LLM is only a nicety here, so its failures fall back to treating the blob as one name
+    // instead of failing the filling or docket.
+    let llm_result = if kill_switch.is_on() {
+        None
+    } else {
+        Some(timeout(*LLM_ORG_SPLIT_TIMEOUT, llm_split(org_blob)).await)
+    };
+    let (outcome, llm_parsed_names) = match llm_result {
+        None => ("disabled", None),
+        Some(Ok(Ok(names))) => ("success", Some(names)),
+        Some(Ok(Err(err))) => {
+            tracing::warn!(%org_blob, error = ?err, "Falling back to the whole organization blob as a single name.");
+            ("fallback", None)
+        }
+        Some(Err(_)) => {
+            tracing::warn!(%org_blob, timeout = ?*LLM_ORG_SPLIT_TIMEOUT, "LLM timed out, falling back to the whole organization blob as a single name.");
+            ("timeout", None)
+        }
+    };
+    if audit_logging && outcome != "disabled" {
+        let response = serde_json::to_string(&llm_parsed_names).unwrap_or_default();
+        tracing::info!(
+            target: LLM_AUDIT_TARGET,
+            task = "org_split",
+            docket_govid = context.docket_govid,
+            filling_govid = context.filling_govid,
+            outcome,
+            prompt = %truncate_for_audit(org_blob),
+            response = %truncate_for_audit(&response),
+            "LLM call audit"
+        );
+    }
+    let organizations = match llm_parsed_names {
+        Some(names) => {
+            tracing::debug!(previous_name=%org_blob, new_list =?names,"Parsed list into a bunch of llm names.");
+            clean_up_organization_name_list(names)
+        }
+        None => clean_organization_name(org_blob.to_string())
             .map(|val| vec![val])
-            .unwrap_or_default();
+            .unwrap_or_default(),
     };
-    tracing::debug!(previous_name=%org_blob, new_list =?llm_parsed_names,"Parsed list into a bunch of llm names.");
-    clean_up_organization_name_list(llm_parsed_names)
+    histogram!(LLM_ORG_SPLIT_DURATION_SECONDS, "outcome" => outcome)
+        .record(started_at.elapsed().as_secs_f64());
+    organizations
 }
 
 pub fn clean_up_organization_name_list(
     raw_llmed_list: Vec<String>,
 ) -> Vec<ProcessedGenericOrganization> {
-    raw_llmed_list
+    let started_at = Instant::now();
+    let organizations = raw_llmed_list
         .into_iter()
         .filter_map(clean_organization_name)
-        .collect()
+        .collect();
+    histogram!(ORG_NAME_CLEANUP_DURATION_SECONDS).record(started_at.elapsed().as_secs_f64());
+    organizations
 }
 
 // The suffix should be a standardized lowercase string like ["llc", "co", "corp", "inc", "company", "ltd", "lp", "llp"]
@@ -145,6 +298,9 @@ pub async fn guess_at_filling_title<T: AsRef<str> + Serialize>(attachment_names:
     {
         return first.as_ref().to_string();
     };
+    if LLM_KILL_SWITCH.is_on() {
+        return "".to_string();
+    }
     let Ok(serialized_attach_names) = serde_json::to_string(attachment_names) else {
         return "".to_string();
     };
@@ -1,16 +1,80 @@
 use std::collections::HashMap;
 
+use mycorrhiza_common::hash::Blake2bHash;
+use serde_json::json;
 use uuid::Uuid;
 
+use crate::extra_metadata::ExtraMetadataExt;
 use crate::types::{
     processed::{ProcessedGenericAttachment, ProcessedGenericFiling},
     raw::{RawGenericAttachment, RawGenericFiling},
 };
 
+/// A run of a govid, digit runs compare by their value so "9" sorts before "10".
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum GovidRun {
+    /// The digits without leading zeros, compared by length first so any length of number works.
+    Number {
+        len: usize,
+        digits: String,
+    },
+    Text(String),
+}
+
+fn natural_govid_key(govid: &str) -> Vec<GovidRun> {
+    let mut runs = Vec::new();
+    let mut rest = govid;
+    while let Some(first) = rest.chars().next() {
+        let is_digit = first.is_ascii_digit();
+        let run_end = rest
+            .find(|c: char| c.is_ascii_digit() != is_digit)
+            .unwrap_or(rest.len());
+        let (run, tail) = rest.split_at(run_end);
+        runs.push(match is_digit {
+            true => {
+                let digits = run.trim_start_matches('0');
+                GovidRun::Number {
+                    len: digits.len(),
+                    digits: digits.to_string(),
+                }
+            }
+            false => GovidRun::Text(run.to_string()),
+        });
+        rest = tail;
+    }
+    runs
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum AttachIndexKey {
+    /// The full govid breaks ties between govids like "01" and "1" that read as the same number.
+    Govid(Vec<GovidRun>, String),
+    Hash(String),
+}
+
+/// Returns the raw attachments in the order their processed indexes get assigned in, sorted by
+/// govid in natural order or else hash so reprocessing after the scraper reorders them doesn't
+/// reshuffle the indexes. Attachments with neither keep their raw order, after the rest.
+fn sort_attaches_for_indexing(raw_attaches: &mut [RawGenericAttachment]) {
+    raw_attaches.sort_by_cached_key(|attach| {
+        let stable_key = if !attach.attachment_govid.is_empty() {
+            let govid = attach.attachment_govid.to_string();
+            Some(AttachIndexKey::Govid(natural_govid_key(&govid), govid))
+        } else {
+            attach
+                .hash
+                .as_ref()
+                .map(|hash| AttachIndexKey::Hash(hash.to_string()))
+        };
+        (stable_key.is_none(), stable_key)
+    });
+}
+
 pub fn match_raw_attaches_to_processed_attaches(
-    raw_attaches: Vec<RawGenericAttachment>,
+    mut raw_attaches: Vec<RawGenericAttachment>,
     processed_attaches: Option<Vec<ProcessedGenericAttachment>>,
 ) -> Vec<(RawGenericAttachment, Option<ProcessedGenericAttachment>)> {
+    sort_attaches_for_indexing(&mut raw_attaches);
     // the raw generic attachments and the processed attachments each
     let Some(processed_attaches) = processed_attaches else {
         return raw_attaches.into_iter().map(|att| (att, None)).collect();
@@ -95,3 +159,26 @@ fn match_individual_filling<'a>(
     }
     None
 }
+
+/// Hash of every scraped field the authors of a filling get processed from.
+pub fn raw_authors_fingerprint(raw_filling: &RawGenericFiling) -> String {
+    let author_fields = json!([
+        raw_filling.organization_authors,
+        raw_filling.organization_authors_blob,
+        raw_filling.individual_authors,
+        raw_filling.individual_authors_blob,
+    ]);
+    Blake2bHash::from_bytes(author_fields.to_string().as_bytes()).to_string()
+}
+
+/// Whether the scraped authors changed since the cached filling was processed, so its authors
+/// can't be reused. Fillings cached before the fingerprint was recorded are trusted as they are,
+/// rather than sending every cached filling back through the llm at once.
+pub fn cached_authors_are_stale(
+    raw_filling: &RawGenericFiling,
+    cached_filling: &ProcessedGenericFiling,
+) -> bool {
+    cached_filling
+        .raw_authors_fingerprint()
+        .is_some_and(|fingerprint| fingerprint != raw_authors_fingerprint(raw_filling))
+}
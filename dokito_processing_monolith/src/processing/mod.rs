@@ -1,25 +1,44 @@
 use crate::data_processing_traits::{
     DownloadIncomplete, ProcessFrom, Revalidate, RevalidationOutcome,
 };
+use crate::health_gate::WORKER_HEALTH_GATE;
 use crate::jurisdiction_schema_mapping::FixedJurisdiction;
-use crate::processing::attachments::OpenscrapersExtraData;
-use crate::s3_stuff::{DocketAddress, download_openscrapers_object, make_s3_client, upload_object};
+use crate::openscraper_data_traits::{ProcessingError, process_docket};
+use crate::processing::attachments::{AttachmentFetchOrder, OpenscrapersExtraData};
+use crate::s3_stuff::{
+    DocketAddress, download_openscrapers_object, make_s3_client, upload_object,
+    upload_object_if_changed,
+};
+use crate::sql_ingester_tasks::database_author_association::AuthorAssociationMode;
+use crate::throughput::{PROCESSING_THROUGHPUT, ThroughputStage};
 use crate::types::jurisdictions::JurisdictionInfo;
 use crate::types::processed::{ProcessedGenericAttachment, ProcessedGenericDocket};
 use crate::types::raw::RawGenericDocket;
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use futures_util::{StreamExt, stream};
 use mycorrhiza_common::tasks::{ExecuteUserTask, map_err_as_json};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tracing::info;
+use std::env;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tracing::{info, warn};
 
 pub mod attachments;
+pub mod docket_dates;
 pub mod file_fetching;
+pub mod filing_types;
+pub mod hearing_officers;
 pub mod llm_prompts;
 pub mod match_raw_processed;
 pub mod reparse_all;
+pub mod reprocess_diff;
+
+/// Crate version plus the git hash it was built from, stamped onto every processed docket so
+/// dockets touched by a buggy build can be found and reprocessed.
+pub const PROCESSOR_VERSION: &str =
+    concat!(env!("CARGO_PKG_VERSION"), "+", env!("DOKITO_GIT_HASH"));
 
 #[derive(Serialize)]
 struct CrimsonPDFIngestParamsS3 {
@@ -41,6 +60,7 @@ struct CrimsonStatusResponse {
 
 pub fn make_reflist_of_attachments_without_hash(
     case: &mut ProcessedGenericDocket,
+    fetch_order: AttachmentFetchOrder,
 ) -> Vec<&mut ProcessedGenericAttachment> {
     let mut case_refs = Vec::with_capacity(case.filings.len());
     for filling in case.filings.iter_mut() {
@@ -50,6 +70,7 @@ pub fn make_reflist_of_attachments_without_hash(
             }
         }
     }
+    fetch_order.sort(&mut case_refs);
     case_refs
 }
 
@@ -61,7 +82,8 @@ impl DownloadIncomplete for ProcessedGenericDocket {
     ) -> anyhow::Result<RevalidationOutcome> {
         let _ = self.revalidate().await;
         // info!(govid=%self.case_govid, jurisdiction=%extra.jurisdiction_info.jurisdiction, opened_date = %self.opened_date, uuid = %self.object_uuid,"Attempting to download attachments for docket");
-        let attachment_refs = make_reflist_of_attachments_without_hash(self);
+        let attachment_refs =
+            make_reflist_of_attachments_without_hash(self, extra.attachment_fetch_order);
         let futures_stream = stream::iter(attachment_refs.into_iter().map(|val| {
             let extra_clone = extra.clone();
             async move {
@@ -89,9 +111,47 @@ impl DownloadIncomplete for ProcessedGenericDocket {
     }
 }
 
+/// Longest a single docket gets to process, so one pathological docket can't hold a worker slot
+/// forever.
+pub static PROCESS_CASE_TIMEOUT_SECS: LazyLock<u64> = LazyLock::new(|| {
+    env::var("PROCESS_CASE_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&v| v > 0)
+        .unwrap_or(1800)
+});
+
 pub async fn process_case(
     raw_case: RawGenericDocket,
     extra_data: OpenscrapersExtraData,
+) -> anyhow::Result<ProcessedGenericDocket> {
+    let docket_govid = raw_case.case_govid.to_string();
+    with_processing_timeout(
+        &docket_govid,
+        Duration::from_secs(*PROCESS_CASE_TIMEOUT_SECS),
+        process_case_without_timeout(raw_case, extra_data),
+    )
+    .await
+}
+
+/// Gives up on processing once `timeout` passes, dropping whatever it was waiting on.
+pub(crate) async fn with_processing_timeout<T>(
+    docket_govid: &str,
+    timeout: Duration,
+    processing: impl Future<Output = anyhow::Result<T>>,
+) -> anyhow::Result<T> {
+    match tokio::time::timeout(timeout, processing).await {
+        Ok(result) => result,
+        Err(_elapsed) => {
+            warn!(%docket_govid, timeout_secs = timeout.as_secs(), "Docket processing timed out");
+            Err(docket_processing_error(docket_govid, ProcessingError::TimedOut(timeout)))
+        }
+    }
+}
+
+async fn process_case_without_timeout(
+    raw_case: RawGenericDocket,
+    extra_data: OpenscrapersExtraData,
 ) -> anyhow::Result<ProcessedGenericDocket> {
     let s3_client = &extra_data.s3_client;
     let jur_info = &extra_data.jurisdiction_info;
@@ -105,7 +165,9 @@ pub async fn process_case(
         docket_govid: raw_case.case_govid.to_string(),
         jurisdiction: jur_info.to_owned(),
     };
-    let s3_result = upload_object(s3_client, &docket_address, &raw_case).await;
+    // An unchanged raw docket isn't uploaded again, its LastModified is what tells whether the
+    // processed docket is behind it.
+    let s3_result = upload_object_if_changed(s3_client, &docket_address, &raw_case).await;
     if let Err(err) = s3_result {
         tracing::error!(
             case_num=%raw_case.case_govid, 
@@ -121,10 +183,13 @@ pub async fn process_case(
             .ok();
 
     let mut processed_case =
-        ProcessedGenericDocket::process_from(raw_case, processed_case_cache, extra_data.fixed_jurisdiction).await?;
+        ProcessedGenericDocket::process_from(raw_case, processed_case_cache, extra_data.fixed_jurisdiction)
+            .await
+            .map_err(|err| docket_processing_error(&docket_address.docket_govid, err))?;
     let _outcome = processed_case.revalidate().await;
 
     upload_object(s3_client, &docket_address, &processed_case).await?;
+    PROCESSING_THROUGHPUT.record_docket(ThroughputStage::Processed, &processed_case);
 
     tracing::info!(
         case_num=%processed_case.case_govid,
@@ -136,13 +201,80 @@ pub async fn process_case(
     Ok(processed_case)
 }
 
+/// Processes a docket bound for a target schema rather than the jurisdiction's own. Authors are
+/// only looked up, so nothing gets written to the jurisdiction's schema, and the result isn't
+/// uploaded since the processed docket in S3 has to stay the one production was ingested from.
+pub async fn process_case_for_target_schema(
+    raw_case: RawGenericDocket,
+    extra_data: OpenscrapersExtraData,
+) -> anyhow::Result<ProcessedGenericDocket> {
+    let docket_address = DocketAddress {
+        docket_govid: raw_case.case_govid.to_string(),
+        jurisdiction: extra_data.jurisdiction_info,
+    };
+    let processing = async {
+        let processed_case_cache = download_openscrapers_object::<ProcessedGenericDocket>(
+            &extra_data.s3_client,
+            &docket_address,
+        )
+        .await
+        .ok();
+        let mut processed_case = process_docket(
+            raw_case,
+            processed_case_cache,
+            extra_data.fixed_jurisdiction,
+            AuthorAssociationMode::ReadOnly,
+        )
+        .await
+        .map_err(|err| docket_processing_error(&docket_address.docket_govid, err))?;
+        let _outcome = processed_case.revalidate().await;
+        Ok(processed_case)
+    };
+    with_processing_timeout(
+        &docket_address.docket_govid,
+        Duration::from_secs(*PROCESS_CASE_TIMEOUT_SECS),
+        processing,
+    )
+    .await
+}
+
+/// Adds the docket to a processing failure, while keeping the [`ProcessingError`] downcastable so
+/// callers can still tell which stage failed.
+pub(crate) fn docket_processing_error(docket_govid: &str, err: ProcessingError) -> anyhow::Error {
+    anyhow::Error::new(err).context(format!("Failed to process docket {docket_govid}"))
+}
+
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct ReprocessDocketInfo {
     pub docket_govid: String,
     pub jurisdiction: JurisdictionInfo,
     pub only_process_missing: bool,
     pub ignore_cachced_if_older_than: Option<DateTime<Utc>>,
+    /// Leave dockets whose stored processed docket is closed as they are.
+    #[serde(default)]
+    pub skip_closed: bool,
+    /// With `skip_closed`, only skip dockets closed at least this many days ago.
+    #[serde(default)]
+    pub skip_closed_after_days: Option<u32>,
 }
+
+impl ReprocessDocketInfo {
+    /// Whether the docket was closed long enough ago that reprocessing it isn't worth the LLM
+    /// calls. Dockets without a stored processed docket, or without a closed date, are never
+    /// skipped.
+    pub fn skips_closed_docket(
+        &self,
+        stored_docket: Option<&ProcessedGenericDocket>,
+        today: NaiveDate,
+    ) -> bool {
+        let Some(closed_date) = stored_docket.and_then(|docket| docket.closed_date) else {
+            return false;
+        };
+        let min_days_closed = i64::from(self.skip_closed_after_days.unwrap_or(0));
+        self.skip_closed && (today - closed_date).num_days() >= min_days_closed
+    }
+}
+
 #[async_trait]
 impl ExecuteUserTask for ReprocessDocketInfo {
     async fn execute_task(self: Box<Self>) -> Result<serde_json::Value, serde_json::Value> {
@@ -152,6 +284,7 @@ impl ExecuteUserTask for ReprocessDocketInfo {
                     .to_string(),
             ));
         };
+        WORKER_HEALTH_GATE.wait_until_ready().await;
         // let self = *self;
         let s3_client = make_s3_client().await;
         let docket_address = DocketAddress {
@@ -167,6 +300,9 @@ impl ExecuteUserTask for ReprocessDocketInfo {
             download_openscrapers_object::<ProcessedGenericDocket>(&s3_client, &docket_address)
                 .await
                 .ok();
+        if self.skips_closed_docket(cached_docket.as_ref(), Utc::now().date_naive()) {
+            return Ok("Docket is closed, skipping".into());
+        }
         if let Some(skip_date) = self.ignore_cachced_if_older_than
             && let Some(cached_date) = cached_docket.as_ref().map(|d| d.processed_at)
             && cached_date < skip_date
@@ -176,11 +312,28 @@ impl ExecuteUserTask for ReprocessDocketInfo {
         if cached_docket.is_some() && self.only_process_missing {
             return Ok("Found cached case, skipping".into());
         };
-        let Ok(processed_case) =
-            ProcessedGenericDocket::process_from(raw_case, cached_docket, fixed_jurisdiction).await;
+        let processing = async {
+            ProcessedGenericDocket::process_from(raw_case, cached_docket, fixed_jurisdiction)
+                .await
+                .map_err(|err| docket_processing_error(&docket_address.docket_govid, err))
+        };
+        let processed_case = match with_processing_timeout(
+            &docket_address.docket_govid,
+            Duration::from_secs(*PROCESS_CASE_TIMEOUT_SECS),
+            processing,
+        )
+        .await
+        {
+            Ok(processed_case) => processed_case,
+            Err(err) => {
+                tracing::error!(error = ?err, "Failed to process case");
+                return Err(format!("{err:#}").into());
+            }
+        };
         tracing::info!(docket_govid=%processed_case.case_govid,"Successfully processed case");
         let upload_res = upload_object(&s3_client, &docket_address, &processed_case).await;
         map_err_as_json(upload_res)?;
+        PROCESSING_THROUGHPUT.record_docket(ThroughputStage::Processed, &processed_case);
         Ok("Successfully processed task".into())
     }
     fn get_task_label_static() -> &'static str
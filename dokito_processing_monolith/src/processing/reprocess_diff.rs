@@ -0,0 +1,259 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::types::processed::{
+    PetitionerRole, ProcessedGenericAttachment, ProcessedGenericDocket, ProcessedGenericFiling,
+    ProcessedGenericOrganization, ProcessedGenericPetitioner,
+};
+
+// Fields that are different on every run, or that get diffed on their own below.
+const IGNORED_DOCKET_FIELDS: &[&str] = &[
+    "processed_at",
+    "processor_version",
+    "filings",
+    "petitioner_list",
+];
+const IGNORED_FILING_FIELDS: &[&str] = &["attachments", "organization_authors"];
+
+#[derive(Debug, Serialize, JsonSchema, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub stored: Value,
+    pub reprocessed: Value,
+}
+
+#[derive(Debug, Default, Serialize, JsonSchema, PartialEq)]
+pub struct OrgNameChanges {
+    /// Names only the reprocessed docket has.
+    pub added: Vec<String>,
+    /// Names only the stored docket has.
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AttachmentDiff {
+    pub index_in_filling: u64,
+    pub attachment_govid: String,
+    pub field_changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct FilingDiff {
+    pub filling_govid: String,
+    pub index_in_docket: u64,
+    pub field_changes: Vec<FieldChange>,
+    pub organization_authors: OrgNameChanges,
+    pub changed_attachments: Vec<AttachmentDiff>,
+    pub added_attachments: Vec<String>,
+    pub removed_attachments: Vec<String>,
+}
+
+/// What reprocessing a docket would change compared to the processed docket in S3. Uuids are
+/// left out, a fresh run assigns new ones.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DocketDiff {
+    pub docket_govid: String,
+    pub unchanged: bool,
+    pub field_changes: Vec<FieldChange>,
+    pub petitioners: OrgNameChanges,
+    pub changed_fillings: Vec<FilingDiff>,
+    /// Fillings only the reprocessed docket has, by govid (or `#index` without one).
+    pub added_fillings: Vec<String>,
+    pub removed_fillings: Vec<String>,
+}
+
+pub fn diff_processed_dockets(
+    stored: &ProcessedGenericDocket,
+    reprocessed: &ProcessedGenericDocket,
+) -> DocketDiff {
+    let field_changes = field_changes(stored, reprocessed, IGNORED_DOCKET_FIELDS);
+    let petitioners =
+        petitioner_name_changes(&stored.petitioner_list, &reprocessed.petitioner_list);
+    let (matched, added_fillings, removed_fillings) =
+        match_by_key(&stored.filings, &reprocessed.filings, |filling| {
+            match_key(&filling.filling_govid, filling.index_in_docket)
+        });
+    let changed_fillings = matched
+        .into_iter()
+        .filter_map(|(stored, reprocessed)| diff_fillings(stored, reprocessed))
+        .collect::<Vec<_>>();
+    DocketDiff {
+        docket_govid: reprocessed.case_govid.to_string(),
+        unchanged: field_changes.is_empty()
+            && petitioners == OrgNameChanges::default()
+            && changed_fillings.is_empty()
+            && added_fillings.is_empty()
+            && removed_fillings.is_empty(),
+        field_changes,
+        petitioners,
+        changed_fillings,
+        added_fillings,
+        removed_fillings,
+    }
+}
+
+fn diff_fillings(
+    stored: &ProcessedGenericFiling,
+    reprocessed: &ProcessedGenericFiling,
+) -> Option<FilingDiff> {
+    let field_changes = field_changes(stored, reprocessed, IGNORED_FILING_FIELDS);
+    let organization_authors = org_name_changes(
+        &stored.organization_authors,
+        &reprocessed.organization_authors,
+    );
+    let (matched, added_attachments, removed_attachments) = match_by_key(
+        &stored.attachments,
+        &reprocessed.attachments,
+        |attachment| match_key(&attachment.attachment_govid, attachment.index_in_filling),
+    );
+    let changed_attachments = matched
+        .into_iter()
+        .filter_map(|(stored, reprocessed)| diff_attachments(stored, reprocessed))
+        .collect::<Vec<_>>();
+    let unchanged = field_changes.is_empty()
+        && organization_authors == OrgNameChanges::default()
+        && changed_attachments.is_empty()
+        && added_attachments.is_empty()
+        && removed_attachments.is_empty();
+    (!unchanged).then(|| FilingDiff {
+        filling_govid: reprocessed.filling_govid.clone(),
+        index_in_docket: reprocessed.index_in_docket,
+        field_changes,
+        organization_authors,
+        changed_attachments,
+        added_attachments,
+        removed_attachments,
+    })
+}
+
+fn diff_attachments(
+    stored: &ProcessedGenericAttachment,
+    reprocessed: &ProcessedGenericAttachment,
+) -> Option<AttachmentDiff> {
+    let field_changes = field_changes(stored, reprocessed, &[]);
+    (!field_changes.is_empty()).then(|| AttachmentDiff {
+        index_in_filling: reprocessed.index_in_filling,
+        attachment_govid: reprocessed.attachment_govid.clone(),
+        field_changes,
+    })
+}
+
+fn match_key(govid: &str, index: u64) -> String {
+    match govid.is_empty() {
+        false => govid.to_string(),
+        true => format!("#{index}"),
+    }
+}
+
+/// Pairs up stored and reprocessed items by key, returning the pairs along with the keys only
+/// the reprocessed side has and the keys only the stored side has.
+fn match_by_key<'a, T>(
+    stored: &'a [T],
+    reprocessed: &'a [T],
+    key: impl Fn(&T) -> String,
+) -> (Vec<(&'a T, &'a T)>, Vec<String>, Vec<String>) {
+    let mut stored_by_key = stored
+        .iter()
+        .map(|item| (key(item), item))
+        .collect::<BTreeMap<_, _>>();
+    let mut matched = vec![];
+    let mut added = vec![];
+    for item in reprocessed {
+        let item_key = key(item);
+        match stored_by_key.remove(&item_key) {
+            Some(stored_item) => matched.push((stored_item, item)),
+            None => added.push(item_key),
+        }
+    }
+    (matched, added, stored_by_key.into_keys().collect())
+}
+
+fn org_name(org: &ProcessedGenericOrganization) -> String {
+    format!("{} {}", org.truncated_org_name, org.org_suffix)
+        .trim()
+        .to_string()
+}
+
+fn org_name_changes(
+    stored: &[ProcessedGenericOrganization],
+    reprocessed: &[ProcessedGenericOrganization],
+) -> OrgNameChanges {
+    let org_names = |orgs: &[ProcessedGenericOrganization]| orgs.iter().map(org_name).collect();
+    name_changes(org_names(stored), org_names(reprocessed))
+}
+
+/// Like `org_name_changes`, with anyone listed under a role other than petitioner named along
+/// with it, so a changed role shows up as a removed and an added name.
+fn petitioner_name_changes(
+    stored: &[ProcessedGenericPetitioner],
+    reprocessed: &[ProcessedGenericPetitioner],
+) -> OrgNameChanges {
+    let petitioner_names = |petitioners: &[ProcessedGenericPetitioner]| {
+        petitioners
+            .iter()
+            .map(|petitioner| match petitioner.role {
+                PetitionerRole::Petitioner => org_name(&petitioner.organization),
+                role => format!("{} ({})", org_name(&petitioner.organization), role.as_str()),
+            })
+            .collect()
+    };
+    name_changes(petitioner_names(stored), petitioner_names(reprocessed))
+}
+
+fn name_changes(stored: BTreeSet<String>, reprocessed: BTreeSet<String>) -> OrgNameChanges {
+    OrgNameChanges {
+        added: reprocessed.difference(&stored).cloned().collect(),
+        removed: stored.difference(&reprocessed).cloned().collect(),
+    }
+}
+
+/// Compares the top level fields of two serialized objects, ignoring uuids at any depth.
+fn field_changes(
+    stored: &impl Serialize,
+    reprocessed: &impl Serialize,
+    ignored_fields: &[&str],
+) -> Vec<FieldChange> {
+    let mut stored = as_fields(stored);
+    let mut reprocessed = as_fields(reprocessed);
+    let field_names = stored
+        .keys()
+        .chain(reprocessed.keys())
+        .filter(|field| !ignored_fields.contains(&field.as_str()))
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    field_names
+        .into_iter()
+        .filter_map(|field| {
+            let stored = stored.remove(&field).unwrap_or(Value::Null);
+            let reprocessed = reprocessed.remove(&field).unwrap_or(Value::Null);
+            (stored != reprocessed).then_some(FieldChange {
+                field,
+                stored,
+                reprocessed,
+            })
+        })
+        .collect()
+}
+
+fn as_fields(value: &impl Serialize) -> serde_json::Map<String, Value> {
+    let mut value = serde_json::to_value(value).unwrap_or(Value::Null);
+    strip_uuids(&mut value);
+    match value {
+        Value::Object(fields) => fields,
+        _ => serde_json::Map::new(),
+    }
+}
+
+fn strip_uuids(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            fields.remove("object_uuid");
+            fields.values_mut().for_each(strip_uuids);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_uuids),
+        _ => {}
+    }
+}
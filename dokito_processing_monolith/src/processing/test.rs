@@ -1,3 +1,45 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::NaiveDate;
+use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+use mycorrhiza_common::hash::Blake2bHash;
+use non_empty_string::non_empty_string;
+use uuid::Uuid;
+
+use crate::data_processing_traits::ProcessFrom;
+use crate::extra_metadata::ExtraMetadataExt;
+use crate::jurisdiction_schema_mapping::FixedJurisdiction;
+use crate::openscraper_data_traits::{
+    ProcessingError, apply_case_type_metadata, join_all_with_permits, keep_successful_attachments,
+    keep_successful_fillings, organization_party_petitioners, route_raw_parties,
+};
+use crate::processing::attachments::{
+    AttachmentFetchOrder, OpenscrapersExtraData, sha256_hash_for,
+};
+use crate::processing::docket_dates::{OpenedDateSource, compute_opened_date, infer_closed_date};
+use crate::processing::filing_types::normalize_filing_type;
+use crate::processing::hearing_officers::parse_hearing_officers;
+use crate::processing::llm_prompts::{
+    LLM_AUDIT_TARGET, LLM_ORG_SPLIT_DURATION_SECONDS, LlmCallContext, LlmKillSwitch,
+    ORG_NAME_CLEANUP_DURATION_SECONDS, split_and_fix_organization_names_blob_with,
+};
+use crate::processing::reprocess_diff::diff_processed_dockets;
+use crate::processing::{
+    PROCESSOR_VERSION, ReprocessDocketInfo, docket_processing_error,
+    make_reflist_of_attachments_without_hash, process_case, with_processing_timeout,
+};
+use crate::s3_stuff::{DocketAddress, delete_openscrapers_s3_object, make_s3_client};
+use crate::sql_ingester_tasks::dokito_sql_connection::InitializePostgresError;
+use crate::test_fixtures::{
+    capture_logs, make_processed_attachment, make_processed_docket, make_processed_filing,
+    make_processed_petitioner, make_raw_attachment, make_raw_docket, make_raw_filing,
+    make_raw_party,
+};
+use crate::types::attachments::HashAlgorithm;
+use crate::types::processed::{PetitionerRole, ProcessedGenericDocket};
+use crate::types::raw::{RawArtificalPersonType, RawGenericDocket, RawGenericParty};
+
 // use std::collections::HashMap;
 //
 // use crate::s3_stuff::make_s3_client;
@@ -122,3 +164,905 @@
 //     // let result = process_case(&casewith, &s3_client).await;
 //     // assert!(result.is_ok());
 // }
+
+#[tokio::test]
+async fn test_processor_version_stamped_on_processed_docket() {
+    let raw_docket = make_raw_docket("TEST-VERSION-1", vec![]);
+    let processed =
+        ProcessedGenericDocket::process_from(raw_docket, None, FixedJurisdiction::NewYorkPuc)
+            .await
+            .expect("Processing should succeed");
+    assert_eq!(processed.processor_version, PROCESSOR_VERSION);
+    assert!(PROCESSOR_VERSION.starts_with(env!("CARGO_PKG_VERSION")));
+}
+
+#[tokio::test]
+async fn test_ny_puc_matter_type_populates_case_type_during_processing() {
+    let mut raw_docket = make_raw_docket("TEST-MATTER-TYPE-1", vec![]);
+    raw_docket.case_type = "Case".to_string();
+    raw_docket.case_subtype = String::new();
+    raw_docket
+        .extra_metadata
+        .insert("matter_type".to_string(), "Tariff".into());
+    raw_docket
+        .extra_metadata
+        .insert("matter_subtype".to_string(), "Major Rate Case".into());
+
+    let processed =
+        ProcessedGenericDocket::process_from(raw_docket, None, FixedJurisdiction::NewYorkPuc)
+            .await
+            .expect("Processing should succeed");
+    assert_eq!(processed.case_type, "Tariff");
+    assert_eq!(processed.case_subtype, "Major Rate Case");
+}
+
+#[tokio::test]
+async fn test_reordered_raw_attachments_keep_their_indexes() {
+    let raw_filing_with_order = |govids: &[&str]| {
+        let attachments = govids
+            .iter()
+            .map(|govid| {
+                let mut attachment =
+                    make_raw_attachment(govid, &format!("https://example.com/{govid}.pdf"));
+                attachment.attachment_govid = govid.to_string();
+                attachment
+            })
+            .collect();
+        make_raw_docket(
+            "TEST-ATTACH-ORDER-1",
+            vec![make_raw_filing("1", "2024-01-02", attachments)],
+        )
+    };
+    let index_by_govid = |docket: &ProcessedGenericDocket| {
+        docket.filings[0]
+            .attachments
+            .iter()
+            .map(|att| {
+                (
+                    att.attachment_govid.clone(),
+                    (att.index_in_filling, att.object_uuid),
+                )
+            })
+            .collect::<BTreeMap<_, _>>()
+    };
+
+    let first = ProcessedGenericDocket::process_from(
+        raw_filing_with_order(&["C", "A", "B"]),
+        None,
+        FixedJurisdiction::NewYorkPuc,
+    )
+    .await
+    .expect("Processing should succeed");
+    let reprocessed = ProcessedGenericDocket::process_from(
+        raw_filing_with_order(&["B", "C", "A"]),
+        Some(first.clone()),
+        FixedJurisdiction::NewYorkPuc,
+    )
+    .await
+    .expect("Reprocessing should succeed");
+
+    assert_eq!(index_by_govid(&first), index_by_govid(&reprocessed));
+    let reprocessed_govids = reprocessed.filings[0]
+        .attachments
+        .iter()
+        .map(|att| att.attachment_govid.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(reprocessed_govids, vec!["A", "B", "C"]);
+}
+
+#[tokio::test]
+async fn test_attachment_govids_are_indexed_in_numeric_order() {
+    let attachments = ["10", "9", "A-2", "A-10", "1"]
+        .iter()
+        .map(|govid| {
+            let mut attachment =
+                make_raw_attachment(govid, &format!("https://example.com/{govid}.pdf"));
+            attachment.attachment_govid = govid.to_string();
+            attachment
+        })
+        .collect();
+    let raw_docket = make_raw_docket(
+        "TEST-ATTACH-ORDER-2",
+        vec![make_raw_filing("1", "2024-01-02", attachments)],
+    );
+
+    let processed =
+        ProcessedGenericDocket::process_from(raw_docket, None, FixedJurisdiction::NewYorkPuc)
+            .await
+            .expect("Processing should succeed");
+    let govids = processed.filings[0]
+        .attachments
+        .iter()
+        .map(|att| att.attachment_govid.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(govids, vec!["1", "9", "10", "A-2", "A-10"]);
+}
+
+#[test]
+fn test_case_type_metadata_only_applies_to_declaring_jurisdictions() {
+    let opened_date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let mut docket = make_processed_docket("TEST-MATTER-TYPE-2", opened_date, vec![]);
+    docket.case_type = "Case".to_string();
+    docket
+        .extra_metadata
+        .insert("matter_type".to_string(), "Tariff".into());
+    docket
+        .extra_metadata
+        .insert("matter_subtype".to_string(), "Major Rate Case".into());
+
+    apply_case_type_metadata(&mut docket, FixedJurisdiction::ColoradoPuc);
+    assert_eq!(docket.case_type, "Case");
+
+    // Without both fields there is nothing to override with.
+    docket.extra_metadata.remove("matter_subtype");
+    apply_case_type_metadata(&mut docket, FixedJurisdiction::NewYorkPuc);
+    assert_eq!(docket.case_type, "Case");
+}
+
+#[test]
+fn test_filing_type_variants_normalize_to_canonical_category() {
+    assert_eq!(normalize_filing_type("Comment"), "Comment");
+    assert_eq!(normalize_filing_type("comments"), "Comment");
+    assert_eq!(normalize_filing_type("COMMENT/REPLY"), "Comment");
+    assert_eq!(normalize_filing_type("Public Comments"), "Comment");
+    assert_eq!(normalize_filing_type("Reply Comments"), "Reply");
+    assert_eq!(normalize_filing_type("  MOTION  "), "Motion");
+    assert_eq!(normalize_filing_type("Letter"), "Correspondence");
+}
+
+#[test]
+fn test_unknown_filing_type_passes_through_unchanged() {
+    assert_eq!(
+        normalize_filing_type("Affidavit of Service"),
+        "Affidavit of Service"
+    );
+    assert_eq!(normalize_filing_type(""), "");
+}
+
+#[test]
+fn test_hearing_officer_formats_are_parsed() {
+    let parsed = |raw: &str| {
+        parse_hearing_officers(raw)
+            .into_iter()
+            .map(|officer| {
+                (
+                    officer.title,
+                    officer.western_first_name,
+                    officer.western_last_name,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let officer = |title: &str, first: &str, last: &str| {
+        (title.to_string(), first.to_string(), last.to_string())
+    };
+
+    assert_eq!(
+        parsed("Hon. Angela Lee"),
+        vec![officer("Hon", "Angela", "Lee")]
+    );
+    assert_eq!(
+        parsed("Administrative Law Judge Kevin J. Casutto"),
+        vec![officer("Administrative Law Judge", "Kevin", "Casutto")]
+    );
+    assert_eq!(parsed("Lee, Angela"), vec![officer("", "Angela", "Lee")]);
+    assert_eq!(
+        parsed("Angela Lee, ALJ"),
+        vec![officer("", "Angela", "Lee")]
+    );
+    assert_eq!(
+        parsed("ALJ Angela Lee and ALJ Kevin Casutto"),
+        vec![
+            officer("ALJ", "Angela", "Lee"),
+            officer("ALJ", "Kevin", "Casutto"),
+        ]
+    );
+    assert_eq!(
+        parsed("Angela Lee; Kevin Casutto / Maria Diaz Jr."),
+        vec![
+            officer("", "Angela", "Lee"),
+            officer("", "Kevin", "Casutto"),
+            officer("", "Maria", "Diaz"),
+        ]
+    );
+    assert_eq!(
+        parsed("Angela Lee, Kevin Casutto"),
+        vec![
+            officer("", "Angela", "Lee"),
+            officer("", "Kevin", "Casutto")
+        ]
+    );
+    assert_eq!(parsed("Judge"), vec![officer("", "", "Judge")]);
+    assert!(parsed("  ").is_empty());
+}
+
+fn make_docket_with_fillings_on(case_subtype: &str, dates: &[NaiveDate]) -> ProcessedGenericDocket {
+    let filings = dates
+        .iter()
+        .enumerate()
+        .map(|(i, date)| make_processed_filing(&i.to_string(), *date, vec![]))
+        .collect();
+    let mut docket = make_processed_docket("TEST-CLOSED-1", dates[0], filings);
+    docket.case_subtype = case_subtype.to_string();
+    docket
+}
+
+#[test]
+fn test_closed_date_inferred_only_when_enabled_and_docket_closed() {
+    let first = NaiveDate::from_ymd_opt(2023, 1, 5).unwrap();
+    let latest = NaiveDate::from_ymd_opt(2023, 6, 7).unwrap();
+    let dates = [first, latest, first];
+
+    let mut disabled = make_docket_with_fillings_on("Case Closed", &dates);
+    assert_eq!(infer_closed_date(&mut disabled, false), None);
+    assert_eq!(disabled.closed_date, None);
+
+    let mut still_open = make_docket_with_fillings_on("Rate Case", &dates);
+    assert_eq!(infer_closed_date(&mut still_open, true), None);
+    assert_eq!(still_open.closed_date, None);
+
+    let mut explicit = make_docket_with_fillings_on("Case Closed", &dates);
+    explicit.closed_date = Some(first);
+    assert_eq!(infer_closed_date(&mut explicit, true), None);
+    assert_eq!(explicit.closed_date, Some(first));
+
+    let mut closed = make_docket_with_fillings_on("Case Closed", &dates);
+    assert_eq!(infer_closed_date(&mut closed, true), Some(latest));
+    assert_eq!(closed.closed_date, Some(latest));
+}
+
+#[test]
+fn test_failed_filling_is_dropped_without_voiding_docket() {
+    let filed_date = NaiveDate::from_ymd_opt(2024, 2, 3).unwrap();
+    let results = vec![
+        (
+            "1".to_string(),
+            Ok(make_processed_filing("1", filed_date, vec![])),
+        ),
+        (
+            "2".to_string(),
+            Err(ProcessingError::PoolUnavailable(InitializePostgresError {})),
+        ),
+        (
+            "3".to_string(),
+            Ok(make_processed_filing("3", filed_date, vec![])),
+        ),
+    ];
+    let (fillings, dropped) = keep_successful_fillings("TEST-ISOLATION-1", results);
+    let govids = fillings
+        .iter()
+        .map(|f| f.filling_govid.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(govids, vec!["1", "3"]);
+    assert_eq!(dropped.keys().collect::<Vec<_>>(), vec!["2"]);
+}
+
+#[tokio::test]
+async fn test_process_case_reports_fillings_dropped_by_author_association() {
+    let s3_client = make_s3_client().await;
+    let fixed_jur = FixedJurisdiction::NewYorkPuc;
+    let docket_govid = format!("TEST-DROPPED-{}", Uuid::new_v4());
+    let mut bad_author_filling = make_raw_filing("2", "2024-03-04", vec![]);
+    // Postgres rejects NUL bytes in text, so associating this author fails.
+    bad_author_filling.organization_authors = vec!["Broken\0Power Corp".to_string()];
+    let raw_docket = make_raw_docket(
+        &docket_govid,
+        vec![
+            make_raw_filing("1", "2024-03-04", vec![]),
+            bad_author_filling,
+        ],
+    );
+    let extra_data = OpenscrapersExtraData {
+        s3_client: s3_client.clone(),
+        jurisdiction_info: fixed_jur.into(),
+        fixed_jurisdiction: fixed_jur,
+        attachment_fetch_order: AttachmentFetchOrder::default(),
+    };
+
+    let processed = process_case(raw_docket, extra_data).await;
+
+    let addr = DocketAddress {
+        docket_govid,
+        jurisdiction: fixed_jur.into(),
+    };
+    delete_openscrapers_s3_object::<RawGenericDocket>(&s3_client, &addr)
+        .await
+        .unwrap();
+    let _ = delete_openscrapers_s3_object::<ProcessedGenericDocket>(&s3_client, &addr).await;
+    let processed = processed.expect("A dropped filling shouldn't fail the docket");
+    let govids = processed
+        .filings
+        .iter()
+        .map(|f| f.filling_govid.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(govids, vec!["1"]);
+    let dropped = processed
+        .dropped_filings()
+        .expect("The dropped filling should be recorded on the docket");
+    assert_eq!(dropped.keys().collect::<Vec<_>>(), vec!["2"]);
+    let reason = dropped["2"].as_str().unwrap();
+    assert!(reason.contains("Could not associate author"), "{reason}");
+}
+
+#[test]
+fn test_failed_attachment_is_dropped_without_voiding_filling() {
+    let results = vec![
+        Ok(make_processed_attachment(
+            "Comments",
+            "https://example.com/comments.pdf",
+        )),
+        Err("hash lookup failed".to_string()),
+        Ok(make_processed_attachment(
+            "Exhibit A",
+            "https://example.com/exhibit.pdf",
+        )),
+    ];
+    let attachments = keep_successful_attachments("TEST-ISOLATION-2", "1", results);
+    let names = attachments
+        .iter()
+        .map(|attachment| attachment.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["Comments", "Exhibit A"]);
+}
+
+#[tokio::test]
+async fn test_failed_attachment_is_dropped_from_processed_filling() {
+    let raw_docket = make_raw_docket(
+        "TEST-ISOLATION-3",
+        vec![make_raw_filing(
+            "1",
+            "2024-01-02",
+            vec![
+                make_raw_attachment("Comments", "https://example.com/comments.pdf"),
+                make_raw_attachment("Broken", "not a url"),
+                make_raw_attachment("Exhibit A", "https://example.com/exhibit.pdf"),
+            ],
+        )],
+    );
+    let processed =
+        ProcessedGenericDocket::process_from(raw_docket, None, FixedJurisdiction::NewYorkPuc)
+            .await
+            .expect("A failed attachment shouldn't fail the docket");
+    assert_eq!(processed.filings.len(), 1);
+    let names = processed.filings[0]
+        .attachments
+        .iter()
+        .map(|attachment| attachment.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["Comments", "Exhibit A"]);
+}
+
+#[test]
+fn test_sha256_hash_is_only_computed_when_configured() {
+    assert_eq!("sha256".parse::<HashAlgorithm>(), Ok(HashAlgorithm::Sha256));
+    assert!(sha256_hash_for(HashAlgorithm::Blake2b, b"abc").is_none());
+    let sha256_hash = sha256_hash_for(HashAlgorithm::Sha256, b"abc").unwrap();
+    assert_eq!(
+        sha256_hash.to_string(),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
+
+#[test]
+fn test_opened_date_follows_jurisdiction_preference() {
+    let raw = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+    let earliest_filling = NaiveDate::from_ymd_opt(2023, 1, 15).unwrap();
+    let later_filling = NaiveDate::from_ymd_opt(2023, 5, 20).unwrap();
+    let fillings = [later_filling, earliest_filling];
+
+    assert_eq!(
+        compute_opened_date(OpenedDateSource::TrustRaw, Some(raw), fillings),
+        Some(raw)
+    );
+    assert_eq!(
+        compute_opened_date(OpenedDateSource::EarliestFiling, Some(raw), fillings),
+        Some(earliest_filling)
+    );
+    assert_eq!(
+        compute_opened_date(OpenedDateSource::LatestFilingNever, Some(raw), fillings),
+        Some(earliest_filling)
+    );
+    assert_eq!(
+        compute_opened_date(
+            OpenedDateSource::LatestFilingNever,
+            Some(raw),
+            [later_filling]
+        ),
+        Some(raw)
+    );
+}
+
+#[test]
+fn test_opened_date_falls_back_when_one_source_missing() {
+    let raw = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+    let filling = NaiveDate::from_ymd_opt(2023, 1, 15).unwrap();
+
+    assert_eq!(
+        compute_opened_date(OpenedDateSource::TrustRaw, None, [filling]),
+        Some(filling)
+    );
+    assert_eq!(
+        compute_opened_date(OpenedDateSource::EarliestFiling, Some(raw), []),
+        Some(raw)
+    );
+    assert_eq!(
+        compute_opened_date(OpenedDateSource::LatestFilingNever, None, [filling]),
+        Some(filling)
+    );
+    assert_eq!(
+        compute_opened_date(OpenedDateSource::LatestFilingNever, None, []),
+        None
+    );
+}
+
+#[test]
+fn test_processing_errors_keep_their_variant_and_context() {
+    let cases = [
+        (
+            ProcessingError::PoolUnavailable(InitializePostgresError {}),
+            "Could not initialize postgres pool",
+        ),
+        (
+            ProcessingError::LlmFailure {
+                task: "split an organization blob into names",
+                source: anyhow::anyhow!("deepinfra returned 503"),
+            },
+            "deepinfra returned 503",
+        ),
+        (
+            ProcessingError::AuthorAssociation {
+                author_name: "Example Power Corp".to_string(),
+                source: anyhow::anyhow!("relation does not exist"),
+            },
+            "Example Power Corp",
+        ),
+        (
+            ProcessingError::TimedOut(std::time::Duration::from_secs(1800)),
+            "1800s",
+        ),
+    ];
+    for (processing_err, expected_detail) in cases {
+        let variant_name = format!("{processing_err:?}");
+        let err = docket_processing_error("TEST-ERRORS-1", processing_err);
+        let message = format!("{err:#}");
+        assert!(message.contains("TEST-ERRORS-1"), "{message}");
+        assert!(message.contains(expected_detail), "{message}");
+        let downcast = err
+            .downcast_ref::<ProcessingError>()
+            .expect("The processing error should stay downcastable");
+        assert_eq!(format!("{downcast:?}"), variant_name);
+    }
+}
+
+#[test]
+fn test_case_parties_are_routed_by_person_type() {
+    let with_title = |mut party: RawGenericParty, title: &str| {
+        party.human_title = title.to_string();
+        party
+    };
+    let raw_parties = vec![
+        make_raw_party("Jane Doe", RawArtificalPersonType::Human, "Jane", "Doe"),
+        with_title(
+            make_raw_party(
+                "Example Power Corp",
+                RawArtificalPersonType::Organization,
+                "",
+                "",
+            ),
+            "Respondent",
+        ),
+        make_raw_party("John Roe", RawArtificalPersonType::Unknown, "John", "Roe"),
+        with_title(
+            make_raw_party("Sample Gas", RawArtificalPersonType::Unknown, "", ""),
+            "intervenor",
+        ),
+        make_raw_party(
+            "Unlisted Energy LLC",
+            RawArtificalPersonType::Organization,
+            "",
+            "",
+        ),
+    ];
+    let routed = route_raw_parties(raw_parties);
+    let names = |parties: &[RawGenericParty]| {
+        parties
+            .iter()
+            .map(|party| party.name.to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&routed.humans), vec!["Jane Doe", "John Roe"]);
+    assert_eq!(
+        names(&routed.organizations),
+        vec!["Example Power Corp", "Sample Gas", "Unlisted Energy LLC"]
+    );
+
+    // Orgs the docket already lists keep their role instead of being added again, and orgs
+    // without a role are left out.
+    let listed = vec![make_processed_petitioner(
+        "Sample Gas",
+        PetitionerRole::Petitioner,
+    )];
+    let party_petitioners = organization_party_petitioners(routed.organizations, &listed);
+    assert_eq!(party_petitioners.len(), 1);
+    assert_eq!(
+        party_petitioners[0]
+            .organization
+            .truncated_org_name
+            .as_str(),
+        "Example Power"
+    );
+    assert_eq!(party_petitioners[0].organization.org_suffix, "corp");
+    assert_eq!(party_petitioners[0].role, PetitionerRole::Respondent);
+}
+
+#[tokio::test]
+async fn test_author_associations_stay_under_the_concurrency_bound() {
+    let permits = tokio::sync::Semaphore::new(4);
+    let in_flight = AtomicUsize::new(0);
+    let most_in_flight = AtomicUsize::new(0);
+    let associate = |author: usize| {
+        let (in_flight, most_in_flight) = (&in_flight, &most_in_flight);
+        async move {
+            let now_in_flight = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            most_in_flight.fetch_max(now_in_flight, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            author
+        }
+    };
+    // Orgs and humans share the bound, like in a filling.
+    let (orgs, humans) = futures::join!(
+        join_all_with_permits(&permits, (0..150).map(associate)),
+        join_all_with_permits(&permits, (150..300).map(associate))
+    );
+    assert_eq!(orgs.len() + humans.len(), 300);
+    assert_eq!(orgs[149], 149);
+    assert!(most_in_flight.load(Ordering::SeqCst) <= 4);
+    assert!(most_in_flight.load(Ordering::SeqCst) > 1);
+}
+
+#[tokio::test]
+async fn test_slow_processing_times_out_as_a_failure() {
+    let slow_processing = async {
+        tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+        Ok(())
+    };
+    let started = std::time::Instant::now();
+    let err = with_processing_timeout(
+        "TEST-SLOW-1",
+        std::time::Duration::from_millis(50),
+        slow_processing,
+    )
+    .await
+    .expect_err("processing should have timed out");
+    assert!(started.elapsed() < std::time::Duration::from_secs(10));
+    assert!(format!("{err:#}").contains("TEST-SLOW-1"));
+    assert!(matches!(
+        err.downcast_ref::<ProcessingError>(),
+        Some(ProcessingError::TimedOut(_))
+    ));
+}
+
+#[test]
+fn test_org_split_records_llm_latency_by_outcome() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let org_blob = "Example Power Corp, Sample Gas Company";
+    let kill_switch = LlmKillSwitch::new(false);
+
+    let (split_orgs, fallback_orgs) = metrics::with_local_recorder(&recorder, || {
+        runtime.block_on(async {
+            let split_orgs = split_and_fix_organization_names_blob_with(
+                org_blob,
+                LlmCallContext::default(),
+                &kill_switch,
+                false,
+                |_| async {
+                    Ok(vec![
+                        "Example Power Corp".to_string(),
+                        "Sample Gas Company".to_string(),
+                    ])
+                },
+            )
+            .await;
+            let fallback_orgs = split_and_fix_organization_names_blob_with(
+                org_blob,
+                LlmCallContext::default(),
+                &kill_switch,
+                false,
+                |_| async {
+                    Err(ProcessingError::LlmFailure {
+                        task: "split an organization blob into names",
+                        source: anyhow::anyhow!("deepinfra returned 503"),
+                    })
+                },
+            )
+            .await;
+            (split_orgs, fallback_orgs)
+        })
+    });
+    assert_eq!(split_orgs.len(), 2);
+    assert_eq!(fallback_orgs.len(), 1);
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    // Only the successful split has LLM names to clean up, the fallback cleans the blob itself.
+    let cleanup_observations = snapshot
+        .iter()
+        .filter(|(key, ..)| key.key().name() == ORG_NAME_CLEANUP_DURATION_SECONDS)
+        .map(|(.., value)| match value {
+            DebugValue::Histogram(durations) => durations.len(),
+            _ => 0,
+        })
+        .sum::<usize>();
+    assert_eq!(cleanup_observations, 1);
+
+    let mut observations = snapshot
+        .into_iter()
+        .filter(|(key, ..)| key.key().name() == LLM_ORG_SPLIT_DURATION_SECONDS)
+        .filter_map(|(key, _, _, value)| match value {
+            DebugValue::Histogram(durations) => {
+                let outcome = key.key().labels().map(|l| l.value().to_string()).collect();
+                Some((outcome, durations.len()))
+            }
+            _ => None,
+        })
+        .collect::<Vec<(Vec<String>, usize)>>();
+    observations.sort();
+    assert_eq!(
+        observations,
+        vec![
+            (vec!["fallback".to_string()], 1),
+            (vec!["success".to_string()], 1),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_kill_switch_skips_llm_org_split() {
+    let org_blob = "Example Power Corp, Sample Gas Company";
+    let kill_switch = LlmKillSwitch::new(false);
+    let llm_calls = &AtomicUsize::new(0);
+    let llm_split = move |_: &str| async move {
+        llm_calls.fetch_add(1, Ordering::SeqCst);
+        Ok(vec![
+            "Example Power Corp".to_string(),
+            "Sample Gas Company".to_string(),
+        ])
+    };
+
+    kill_switch.set(true);
+    let disabled_orgs = split_and_fix_organization_names_blob_with(
+        org_blob,
+        LlmCallContext::default(),
+        &kill_switch,
+        false,
+        llm_split,
+    )
+    .await;
+    assert_eq!(llm_calls.load(Ordering::SeqCst), 0);
+    assert_eq!(disabled_orgs.len(), 1);
+    assert_eq!(
+        disabled_orgs[0].truncated_org_name.as_str(),
+        "Example Power Corp, Sample Gas"
+    );
+
+    kill_switch.set(false);
+    let split_orgs = split_and_fix_organization_names_blob_with(
+        org_blob,
+        LlmCallContext::default(),
+        &kill_switch,
+        false,
+        llm_split,
+    )
+    .await;
+    assert_eq!(llm_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(split_orgs.len(), 2);
+}
+
+#[tokio::test]
+async fn test_llm_audit_logging_records_call_with_context() {
+    let (logs, _guard) = capture_logs();
+    let audit_lines = || {
+        logs.lines()
+            .into_iter()
+            .filter(|line| line.contains(LLM_AUDIT_TARGET))
+            .collect::<Vec<_>>()
+    };
+    let org_blob = "Example Power Corp, Sample Gas Company";
+    let kill_switch = LlmKillSwitch::new(false);
+    let context = LlmCallContext {
+        docket_govid: Some("24-E-0001"),
+        filling_govid: Some("FILING-1"),
+    };
+    let llm_split = |_: &str| async {
+        Ok(vec![
+            "Example Power Corp".to_string(),
+            "Sample Gas Company".to_string(),
+        ])
+    };
+
+    split_and_fix_organization_names_blob_with(org_blob, context, &kill_switch, false, llm_split)
+        .await;
+    assert!(audit_lines().is_empty());
+
+    split_and_fix_organization_names_blob_with(org_blob, context, &kill_switch, true, llm_split)
+        .await;
+    let entries = audit_lines();
+    assert_eq!(entries.len(), 1);
+    let entry = &entries[0];
+    assert!(entry.contains(r#"docket_govid="24-E-0001""#));
+    assert!(entry.contains(r#"filling_govid="FILING-1""#));
+    assert!(entry.contains(r#"outcome="success""#));
+    assert!(entry.contains(&format!("prompt={org_blob}")));
+    assert!(entry.contains("Sample Gas Company"));
+}
+
+#[tokio::test]
+async fn test_reprocess_diff_reports_changed_org_name() {
+    let mut raw_filing = make_raw_filing("1", "2024-01-02", vec![]);
+    raw_filing.organization_authors = vec!["Acme Power LLC".to_string()];
+    let raw_docket = make_raw_docket("TEST-REPROCESS-DIFF-1", vec![raw_filing]);
+    let reprocessed =
+        ProcessedGenericDocket::process_from(raw_docket, None, FixedJurisdiction::NewYorkPuc)
+            .await
+            .expect("Processing should succeed");
+    // As if an older build had cleaned up the author name differently.
+    let mut stored = reprocessed.clone();
+    stored.filings[0].organization_authors[0].truncated_org_name =
+        non_empty_string!("Acme Power Company");
+    stored.filings[0].organization_authors[0].org_suffix = String::new();
+
+    let unchanged = diff_processed_dockets(&reprocessed, &reprocessed);
+    assert!(unchanged.unchanged);
+
+    let diff = diff_processed_dockets(&stored, &reprocessed);
+    assert!(!diff.unchanged);
+    assert!(diff.field_changes.is_empty());
+    assert_eq!(diff.changed_fillings.len(), 1);
+    let filling_diff = &diff.changed_fillings[0];
+    assert_eq!(filling_diff.filling_govid, "1");
+    assert_eq!(
+        filling_diff.organization_authors.added,
+        vec!["Acme Power llc"]
+    );
+    assert_eq!(
+        filling_diff.organization_authors.removed,
+        vec!["Acme Power Company"]
+    );
+    assert!(filling_diff.field_changes.is_empty());
+}
+
+#[test]
+fn test_skip_closed_only_skips_dockets_closed_long_enough() {
+    let today = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+    let opened_date = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+    let open_docket = make_processed_docket("OPEN-1", opened_date, vec![]);
+    let mut recently_closed = make_processed_docket("CLOSED-1", opened_date, vec![]);
+    recently_closed.closed_date = NaiveDate::from_ymd_opt(2024, 5, 25);
+    let mut long_closed = make_processed_docket("CLOSED-2", opened_date, vec![]);
+    long_closed.closed_date = NaiveDate::from_ymd_opt(2023, 12, 1);
+    let mut info = ReprocessDocketInfo {
+        docket_govid: String::new(),
+        jurisdiction: FixedJurisdiction::NewYorkPuc.into(),
+        only_process_missing: false,
+        ignore_cachced_if_older_than: None,
+        skip_closed: false,
+        skip_closed_after_days: None,
+    };
+    let skipped = |info: &ReprocessDocketInfo| {
+        [&open_docket, &recently_closed, &long_closed]
+            .map(|docket| info.skips_closed_docket(Some(docket), today))
+    };
+
+    assert_eq!(skipped(&info), [false, false, false]);
+    info.skip_closed = true;
+    assert_eq!(skipped(&info), [false, true, true]);
+    info.skip_closed_after_days = Some(30);
+    assert_eq!(skipped(&info), [false, false, true]);
+    assert!(!info.skips_closed_docket(None, today));
+}
+
+#[tokio::test]
+async fn test_changed_raw_authors_are_reprocessed_instead_of_cached() {
+    let raw_docket_with_authors = |org_authors: &[&str]| {
+        let mut raw_filing = make_raw_filing("1", "2024-01-02", vec![]);
+        raw_filing.organization_authors = org_authors.iter().map(|org| org.to_string()).collect();
+        make_raw_docket("TEST-AUTHOR-CHANGE-1", vec![raw_filing])
+    };
+    let org_names = |docket: &ProcessedGenericDocket| {
+        docket.filings[0]
+            .organization_authors
+            .iter()
+            .map(|org| org.truncated_org_name.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    let mut cached = ProcessedGenericDocket::process_from(
+        raw_docket_with_authors(&["Acme Power LLC"]),
+        None,
+        FixedJurisdiction::NewYorkPuc,
+    )
+    .await
+    .expect("Processing should succeed");
+    // Marks the cached author, so reusing it instead of reprocessing shows up.
+    cached.filings[0].organization_authors[0].truncated_org_name =
+        non_empty_string!("Cached Acme Power");
+
+    let unchanged = ProcessedGenericDocket::process_from(
+        raw_docket_with_authors(&["Acme Power LLC"]),
+        Some(cached.clone()),
+        FixedJurisdiction::NewYorkPuc,
+    )
+    .await
+    .expect("Reprocessing should succeed");
+    assert_eq!(org_names(&unchanged), vec!["Cached Acme Power"]);
+
+    let changed = ProcessedGenericDocket::process_from(
+        raw_docket_with_authors(&["Sample Gas Company"]),
+        Some(cached.clone()),
+        FixedJurisdiction::NewYorkPuc,
+    )
+    .await
+    .expect("Reprocessing should succeed");
+    assert_eq!(org_names(&changed), vec!["Sample Gas"]);
+    assert_eq!(
+        changed.filings[0].object_uuid,
+        cached.filings[0].object_uuid
+    );
+    assert_ne!(
+        changed.filings[0].raw_authors_fingerprint(),
+        cached.filings[0].raw_authors_fingerprint()
+    );
+}
+
+#[test]
+fn test_pdf_first_orders_pdf_attachments_before_others() {
+    let attachment = |name: &str, extension: &str| {
+        let mut attachment =
+            make_processed_attachment(name, &format!("https://example.com/{name}"));
+        attachment.document_extension = serde_json::from_value(extension.into()).unwrap();
+        attachment
+    };
+    let opened_date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let mut downloaded = make_processed_attachment("already-downloaded", "");
+    downloaded.hash = Some(Blake2bHash::from_bytes(b"already downloaded"));
+    let mut docket = make_processed_docket(
+        "TEST-FETCH-ORDER-1",
+        opened_date,
+        vec![
+            make_processed_filing(
+                "1",
+                opened_date,
+                vec![attachment("comments", "docx"), attachment("order", "pdf")],
+            ),
+            make_processed_filing(
+                "2",
+                opened_date,
+                vec![
+                    attachment("rates", "xlsx"),
+                    downloaded,
+                    attachment("exhibit", "pdf"),
+                ],
+            ),
+        ],
+    );
+    let fetch_order = |docket: &mut ProcessedGenericDocket, order| {
+        make_reflist_of_attachments_without_hash(docket, order)
+            .into_iter()
+            .map(|att| att.name.clone())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        fetch_order(&mut docket, AttachmentFetchOrder::AsIs),
+        vec!["comments", "order", "rates", "exhibit"]
+    );
+    assert_eq!(
+        fetch_order(&mut docket, AttachmentFetchOrder::PdfFirst),
+        vec!["order", "exhibit", "comments", "rates"]
+    );
+}
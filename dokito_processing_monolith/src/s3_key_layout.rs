@@ -0,0 +1,418 @@
+//! Templates for the S3 keys dokito stores its objects under, so a deployment migrating from a
+//! different layout can point dokito at its existing keys with env vars instead of code changes.
+
+use std::{env, sync::LazyLock};
+
+use mycorrhiza_common::hash::Blake2bHash;
+use thiserror::Error;
+
+use crate::{s3_stuff::DocketAddress, types::jurisdictions::JurisdictionInfo};
+
+const DOCKET_PLACEHOLDERS: &[&str] = &["country", "state", "jurisdiction", "govid"];
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum InvalidKeyTemplate {
+    #[error("{env_var} uses unknown placeholder {{{placeholder}}}, expected one of {allowed:?}")]
+    UnknownPlaceholder {
+        env_var: &'static str,
+        placeholder: String,
+        allowed: &'static [&'static str],
+    },
+    #[error("{env_var} has an unclosed placeholder")]
+    UnclosedPlaceholder { env_var: &'static str },
+    #[error("{env_var} must end with {{{placeholder}}}")]
+    MissingTrailingPlaceholder {
+        env_var: &'static str,
+        placeholder: &'static str,
+    },
+    #[error("{env_var} must contain {{{placeholder}}}")]
+    MissingPlaceholder {
+        env_var: &'static str,
+        placeholder: &'static str,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum TemplateSegment {
+    Literal(String),
+    Placeholder(String),
+}
+
+/// A key with `{placeholder}`s, validated against the placeholders its object can fill in.
+#[derive(Debug)]
+pub struct KeyTemplate {
+    segments: Vec<TemplateSegment>,
+}
+
+impl KeyTemplate {
+    fn parse(
+        env_var: &'static str,
+        template: &str,
+        allowed: &'static [&'static str],
+    ) -> Result<Self, InvalidKeyTemplate> {
+        let mut segments = vec![];
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                segments.push(TemplateSegment::Literal(rest[..start].to_string()));
+            }
+            let Some(len) = rest[start..].find('}') else {
+                return Err(InvalidKeyTemplate::UnclosedPlaceholder { env_var });
+            };
+            let placeholder = &rest[start + 1..start + len];
+            if !allowed.contains(&placeholder) {
+                return Err(InvalidKeyTemplate::UnknownPlaceholder {
+                    env_var,
+                    placeholder: placeholder.to_string(),
+                    allowed,
+                });
+            }
+            segments.push(TemplateSegment::Placeholder(placeholder.to_string()));
+            rest = &rest[start + len + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(TemplateSegment::Literal(rest.to_string()));
+        }
+        Ok(KeyTemplate { segments })
+    }
+
+    fn require(
+        self,
+        env_var: &'static str,
+        placeholder: &'static str,
+    ) -> Result<Self, InvalidKeyTemplate> {
+        let has_placeholder = self
+            .segments
+            .iter()
+            .any(|seg| *seg == TemplateSegment::Placeholder(placeholder.to_string()));
+        match has_placeholder {
+            true => Ok(self),
+            false => Err(InvalidKeyTemplate::MissingPlaceholder {
+                env_var,
+                placeholder,
+            }),
+        }
+    }
+
+    /// Listing a jurisdiction's dockets strips the listing prefix off each key to get the govid,
+    /// which only works if the govid is the end of the key.
+    fn require_trailing(
+        self,
+        env_var: &'static str,
+        placeholder: &'static str,
+    ) -> Result<Self, InvalidKeyTemplate> {
+        match self.segments.last() {
+            Some(TemplateSegment::Placeholder(last)) if last == placeholder => Ok(self),
+            _ => Err(InvalidKeyTemplate::MissingTrailingPlaceholder {
+                env_var,
+                placeholder,
+            }),
+        }
+    }
+
+    /// Renders the template up to the first placeholder missing from `values`, so leaving out the
+    /// trailing placeholder gives the prefix to list under.
+    fn render(&self, values: &[(&str, &str)]) -> String {
+        let mut rendered = String::new();
+        for segment in self.segments.iter() {
+            match segment {
+                TemplateSegment::Literal(literal) => rendered.push_str(literal),
+                TemplateSegment::Placeholder(placeholder) => {
+                    let Some((_, value)) = values
+                        .iter()
+                        .find(|(name, _)| *name == placeholder.as_str())
+                    else {
+                        break;
+                    };
+                    rendered.push_str(value);
+                }
+            }
+        }
+        rendered
+    }
+
+    /// Pulls `placeholder`'s value back out of a key rendered from the template. Only works when
+    /// it's the first placeholder and everything after it is literal.
+    fn extract<'k>(&self, key: &'k str, placeholder: &str) -> Option<&'k str> {
+        let position = self
+            .segments
+            .iter()
+            .position(|seg| matches!(seg, TemplateSegment::Placeholder(_)))?;
+        if self.segments[position] != TemplateSegment::Placeholder(placeholder.to_string()) {
+            return None;
+        }
+        let mut suffix = String::new();
+        for segment in self.segments[position + 1..].iter() {
+            match segment {
+                TemplateSegment::Literal(literal) => suffix.push_str(literal),
+                TemplateSegment::Placeholder(_) => return None,
+            }
+        }
+        key.strip_prefix(&self.render(&[]))?
+            .strip_suffix(suffix.as_str())
+    }
+}
+
+fn jurisdiction_values(jurisdiction: &JurisdictionInfo) -> [(&'static str, &str); 3] {
+    [
+        ("country", jurisdiction.country.as_str()),
+        ("state", jurisdiction.state.as_str()),
+        ("jurisdiction", jurisdiction.jurisdiction.as_str()),
+    ]
+}
+
+/// Where dokito's objects live in the openscrapers bucket. The `.json` suffix is added on top of
+/// the docket keys when they are uploaded.
+#[derive(Debug)]
+pub struct S3KeyLayout {
+    processed_docket: KeyTemplate,
+    raw_docket: KeyTemplate,
+    attachment_metadata: KeyTemplate,
+    attachment_index: KeyTemplate,
+}
+
+impl S3KeyLayout {
+    pub fn from_env() -> Result<Self, InvalidKeyTemplate> {
+        Self::from_lookup(|name| env::var(name).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, InvalidKeyTemplate> {
+        let template = |env_var: &'static str, default: &str| {
+            lookup(env_var)
+                .filter(|val| !val.is_empty())
+                .unwrap_or_else(|| default.to_string())
+        };
+        let docket_template = |env_var: &'static str, default: &str| {
+            KeyTemplate::parse(env_var, &template(env_var, default), DOCKET_PLACEHOLDERS)?
+                .require(env_var, "jurisdiction")?
+                .require_trailing(env_var, "govid")
+        };
+        Ok(S3KeyLayout {
+            processed_docket: docket_template(
+                "S3_PROCESSED_DOCKET_KEY_TEMPLATE",
+                "objects/{country}/{state}/{jurisdiction}/{govid}",
+            )?,
+            raw_docket: docket_template(
+                "S3_RAW_DOCKET_KEY_TEMPLATE",
+                "objects_raw/{country}/{state}/{jurisdiction}/{govid}",
+            )?,
+            attachment_metadata: KeyTemplate::parse(
+                "S3_ATTACHMENT_METADATA_KEY_TEMPLATE",
+                &template(
+                    "S3_ATTACHMENT_METADATA_KEY_TEMPLATE",
+                    "raw/metadata/{hash}.json",
+                ),
+                &["hash"],
+            )?
+            .require("S3_ATTACHMENT_METADATA_KEY_TEMPLATE", "hash")?,
+            attachment_index: KeyTemplate::parse(
+                "S3_ATTACHMENT_INDEX_KEY",
+                &template("S3_ATTACHMENT_INDEX_KEY", "indexes/global/attachment_urls"),
+                &[],
+            )?,
+        })
+    }
+
+    pub fn processed_docket_key(&self, addr: &DocketAddress) -> String {
+        let [country, state, jurisdiction] = jurisdiction_values(&addr.jurisdiction);
+        self.processed_docket.render(&[
+            country,
+            state,
+            jurisdiction,
+            ("govid", addr.docket_govid.as_str()),
+        ])
+    }
+
+    pub fn raw_docket_key(&self, addr: &DocketAddress) -> String {
+        let [country, state, jurisdiction] = jurisdiction_values(&addr.jurisdiction);
+        self.raw_docket.render(&[
+            country,
+            state,
+            jurisdiction,
+            ("govid", addr.docket_govid.as_str()),
+        ])
+    }
+
+    /// The prefix every processed docket of the jurisdiction is stored under, followed by its govid.
+    pub fn processed_docket_prefix(&self, jurisdiction: &JurisdictionInfo) -> String {
+        self.processed_docket
+            .render(&jurisdiction_values(jurisdiction))
+    }
+
+    /// The prefix every raw docket of the jurisdiction is stored under, followed by its govid.
+    pub fn raw_docket_prefix(&self, jurisdiction: &JurisdictionInfo) -> String {
+        self.raw_docket.render(&jurisdiction_values(jurisdiction))
+    }
+
+    pub fn attachment_metadata_key(&self, hash: &Blake2bHash) -> String {
+        self.attachment_metadata
+            .render(&[("hash", hash.to_string().as_str())])
+    }
+
+    /// The prefix shared by every attachment metadata object, ie everything before the hash.
+    pub fn attachment_metadata_prefix(&self) -> String {
+        self.attachment_metadata.render(&[])
+    }
+
+    /// The hash an attachment metadata key was rendered from, wherever the template puts it.
+    pub fn attachment_metadata_hash<'k>(&self, key: &'k str) -> Option<&'k str> {
+        self.attachment_metadata.extract(key, "hash")
+    }
+
+    pub fn attachment_index_key(&self) -> String {
+        self.attachment_index.render(&[])
+    }
+
+    /// The hash to urls index is stored next to the url index it's derived from.
+    pub fn attachment_hash_index_key(&self) -> String {
+        format!("{}_by_hash", self.attachment_index_key())
+    }
+}
+
+/// Panics on an invalid template the first time it's used, `main` touches it at startup so a bad
+/// layout never makes it past boot.
+pub static S3_KEY_LAYOUT: LazyLock<S3KeyLayout> = LazyLock::new(|| {
+    S3KeyLayout::from_env().unwrap_or_else(|err| panic!("Invalid S3 key layout: {err}"))
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout_from(vars: &[(&str, &str)]) -> Result<S3KeyLayout, InvalidKeyTemplate> {
+        S3KeyLayout::from_lookup(|name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, val)| val.to_string())
+        })
+    }
+
+    fn example_address() -> DocketAddress {
+        DocketAddress {
+            docket_govid: "24-E-0165".to_string(),
+            jurisdiction: JurisdictionInfo::new_usa("ny_puc", "ny"),
+        }
+    }
+
+    #[test]
+    fn test_default_layout_matches_existing_keys() {
+        let layout = layout_from(&[]).unwrap();
+        let addr = example_address();
+        assert_eq!(
+            layout.processed_docket_key(&addr),
+            "objects/usa/ny/ny_puc/24-E-0165"
+        );
+        assert_eq!(
+            layout.raw_docket_key(&addr),
+            "objects_raw/usa/ny/ny_puc/24-E-0165"
+        );
+        assert_eq!(
+            layout.processed_docket_prefix(&addr.jurisdiction),
+            "objects/usa/ny/ny_puc/"
+        );
+        let hash = Blake2bHash::from_bytes(b"example");
+        assert_eq!(
+            layout.attachment_metadata_key(&hash),
+            format!("raw/metadata/{hash}.json")
+        );
+        assert_eq!(layout.attachment_metadata_prefix(), "raw/metadata/");
+        assert_eq!(
+            layout.attachment_metadata_hash(&layout.attachment_metadata_key(&hash)),
+            Some(hash.to_string().as_str())
+        );
+        assert_eq!(
+            layout.attachment_index_key(),
+            "indexes/global/attachment_urls"
+        );
+        assert_eq!(
+            layout.attachment_hash_index_key(),
+            "indexes/global/attachment_urls_by_hash"
+        );
+    }
+
+    #[test]
+    fn test_custom_template_produces_expected_keys() {
+        let layout = layout_from(&[
+            (
+                "S3_PROCESSED_DOCKET_KEY_TEMPLATE",
+                "dockets/{jurisdiction}/processed/{govid}",
+            ),
+            (
+                "S3_RAW_DOCKET_KEY_TEMPLATE",
+                "dockets/{state}-{jurisdiction}/raw/{govid}",
+            ),
+            (
+                "S3_ATTACHMENT_METADATA_KEY_TEMPLATE",
+                "attachments/{hash}/meta.json",
+            ),
+            ("S3_ATTACHMENT_INDEX_KEY", "indexes/urls"),
+        ])
+        .unwrap();
+        let addr = example_address();
+        assert_eq!(
+            layout.processed_docket_key(&addr),
+            "dockets/ny_puc/processed/24-E-0165"
+        );
+        assert_eq!(
+            layout.raw_docket_key(&addr),
+            "dockets/ny-ny_puc/raw/24-E-0165"
+        );
+        assert_eq!(
+            layout.raw_docket_prefix(&addr.jurisdiction),
+            "dockets/ny-ny_puc/raw/"
+        );
+        let hash = Blake2bHash::from_bytes(b"example");
+        assert_eq!(
+            layout.attachment_metadata_key(&hash),
+            format!("attachments/{hash}/meta.json")
+        );
+        assert_eq!(layout.attachment_metadata_prefix(), "attachments/");
+        assert_eq!(
+            layout.attachment_metadata_hash(&format!("attachments/{hash}/meta.json")),
+            Some(hash.to_string().as_str())
+        );
+        assert_eq!(
+            layout.attachment_metadata_hash(&format!("attachments/{hash}/other.json")),
+            None
+        );
+        assert_eq!(
+            layout.processed_docket_prefix(&addr.jurisdiction),
+            "dockets/ny_puc/processed/"
+        );
+        assert_eq!(layout.attachment_index_key(), "indexes/urls");
+    }
+
+    #[test]
+    fn test_invalid_templates_are_rejected() {
+        let err = layout_from(&[("S3_RAW_DOCKET_KEY_TEMPLATE", "raw/{jurisdiction}/{docket}")])
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            InvalidKeyTemplate::UnknownPlaceholder { placeholder, .. } if placeholder == "docket"
+        ));
+        assert_eq!(
+            layout_from(&[(
+                "S3_PROCESSED_DOCKET_KEY_TEMPLATE",
+                "objects/{jurisdiction}/{govid}/docket"
+            )])
+            .unwrap_err(),
+            InvalidKeyTemplate::MissingTrailingPlaceholder {
+                env_var: "S3_PROCESSED_DOCKET_KEY_TEMPLATE",
+                placeholder: "govid",
+            }
+        );
+        assert_eq!(
+            layout_from(&[("S3_PROCESSED_DOCKET_KEY_TEMPLATE", "objects/{govid}")]).unwrap_err(),
+            InvalidKeyTemplate::MissingPlaceholder {
+                env_var: "S3_PROCESSED_DOCKET_KEY_TEMPLATE",
+                placeholder: "jurisdiction",
+            }
+        );
+        assert_eq!(
+            layout_from(&[("S3_ATTACHMENT_METADATA_KEY_TEMPLATE", "raw/{hash")]).unwrap_err(),
+            InvalidKeyTemplate::UnclosedPlaceholder {
+                env_var: "S3_ATTACHMENT_METADATA_KEY_TEMPLATE",
+            }
+        );
+    }
+}
@@ -1,10 +1,26 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::ops::Range;
+use std::sync::LazyLock;
+use std::time::Duration;
+
 use anyhow::anyhow;
-use futures_util::join;
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::error::{ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use flate2::read::GzDecoder;
+use futures::future::join_all;
+use futures_util::{Stream, StreamExt, join, stream};
 use mycorrhiza_common::s3_generic::fetchers_and_getters::{S3Addr, S3DirectoryAddr};
 use mycorrhiza_common::s3_generic::s3_uri::S3LocationWithCredentials;
 use non_empty_string::non_empty_string;
-use tracing::{debug, info};
+use thiserror::Error;
+use tokio::sync::Semaphore;
+use tracing::{debug, info, warn};
 
+use crate::s3_key_layout::S3_KEY_LAYOUT;
+use crate::sql_ingester_tasks::dokito_sql_connection::parse_jurisdiction_overrides;
 use crate::types::attachments::RawAttachment;
 use crate::types::env_vars::{DIGITALOCEAN_S3, OPENSCRAPERS_S3_OBJECT_BUCKET};
 use crate::types::jurisdictions::JurisdictionInfo;
@@ -22,7 +38,7 @@ pub fn get_raw_attach_file_key(hash: Blake2bHash) -> String {
 impl CannonicalS3ObjectLocation for RawAttachment {
     type AddressInfo = Blake2bHash;
     fn generate_object_key(hash: &Self::AddressInfo) -> String {
-        format!("raw/metadata/{hash}.json")
+        S3_KEY_LAYOUT.attachment_metadata_key(hash)
     }
 }
 
@@ -35,22 +51,14 @@ impl CannonicalS3ObjectLocation for RawGenericDocket {
     type AddressInfo = DocketAddress;
 
     fn generate_object_key(addr: &Self::AddressInfo) -> String {
-        let country = &*addr.jurisdiction.country;
-        let state = &*addr.jurisdiction.state;
-        let jurisdiction = &*addr.jurisdiction.jurisdiction;
-        let case_name = &*addr.docket_govid;
-        format!("objects_raw/{country}/{state}/{jurisdiction}/{case_name}")
+        S3_KEY_LAYOUT.raw_docket_key(addr)
     }
 }
 impl CannonicalS3ObjectLocation for ProcessedGenericDocket {
     type AddressInfo = DocketAddress;
 
     fn generate_object_key(addr: &Self::AddressInfo) -> String {
-        let country = &*addr.jurisdiction.country;
-        let state = &*addr.jurisdiction.state;
-        let jurisdiction = &*addr.jurisdiction.jurisdiction;
-        let case_name = &*addr.docket_govid;
-        format!("objects/{country}/{state}/{jurisdiction}/{case_name}")
+        S3_KEY_LAYOUT.processed_docket_key(addr)
     }
 }
 
@@ -71,13 +79,167 @@ pub fn get_s3_json_uri<T: CannonicalS3ObjectLocation>(addr: &T::AddressInfo) ->
         .to_string()
 }
 
+/// Most S3 requests in flight at once across every task, on top of each task's own bound, so
+/// running many tasks at once can't get the account throttled.
+pub static S3_MAX_CONCURRENT_REQUESTS: LazyLock<usize> = LazyLock::new(|| {
+    std::env::var("S3_MAX_CONCURRENT_REQUESTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&v| v > 0)
+        .unwrap_or(64)
+});
+
+static GLOBAL_S3_REQUEST_LIMITER: LazyLock<S3RequestLimiter> =
+    LazyLock::new(|| S3RequestLimiter::new(*S3_MAX_CONCURRENT_REQUESTS));
+
+pub struct S3RequestLimiter {
+    permits: Semaphore,
+}
+
+impl S3RequestLimiter {
+    pub fn new(max_concurrent_requests: usize) -> Self {
+        S3RequestLimiter {
+            permits: Semaphore::new(max_concurrent_requests.max(1)),
+        }
+    }
+
+    /// Runs `request` once a permit is free, holding it until the request finishes.
+    pub async fn run<T>(&self, request: impl Future<Output = T>) -> T {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("the S3 request semaphore is never closed");
+        request.await
+    }
+}
+
+/// Runs an S3 request under the global limit. Requests can't be nested inside one another, since
+/// the outer one would hold its permit while waiting on a permit for the inner one.
+pub async fn limit_s3_request<T>(request: impl Future<Output = T>) -> T {
+    GLOBAL_S3_REQUEST_LIMITER.run(request).await
+}
+
 pub async fn download_openscrapers_object<T: CannonicalS3ObjectLocation>(
     s3_client: &S3Client,
     addr: &T::AddressInfo,
 ) -> anyhow::Result<T> {
     let key = get_openscrapers_json_key::<T>(addr);
     let bucket = &**OPENSCRAPERS_S3_OBJECT_BUCKET;
-    S3Addr::new(s3_client, bucket, &key).download_json().await
+    let bytes = retry_with_backoff(
+        *S3_OBJECT_TRIES,
+        S3_RETRY_BASE_DELAY,
+        S3RequestError::is_transient,
+        async || {
+            limit_s3_request(async {
+                let object = s3_client
+                    .get_object()
+                    .bucket(bucket)
+                    .key(&key)
+                    .send()
+                    .await
+                    .map_err(|err| S3RequestError::from_sdk(&key, err))?;
+                // A body cut off partway is as transient as a failed request.
+                let body = object.body.collect().await.map_err(|err| S3RequestError {
+                    kind: S3ErrorKind::Transient,
+                    error: anyhow!("reading {key} failed: {err}"),
+                })?;
+                Ok(body.into_bytes())
+            })
+            .await
+        },
+    )
+    .await?;
+    parse_json_maybe_gzipped(&bytes)
+}
+
+const GZIP_MAGIC_BYTES: [u8; 2] = [0x1f, 0x8b];
+
+/// Scrapers sometimes upload their output gzipped under the same .json key, so the bytes are
+/// checked for the gzip header instead of trusting the key.
+fn parse_json_maybe_gzipped<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> anyhow::Result<T> {
+    if !bytes.starts_with(&GZIP_MAGIC_BYTES) {
+        return Ok(serde_json::from_slice(bytes)?);
+    }
+    let mut decompressed = Vec::new();
+    GzDecoder::new(bytes).read_to_end(&mut decompressed)?;
+    Ok(serde_json::from_slice(&decompressed)?)
+}
+
+/// How many times an object get or put is attempted before giving up on it.
+pub static S3_OBJECT_TRIES: LazyLock<usize> = LazyLock::new(|| {
+    std::env::var("S3_OBJECT_TRIES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&tries| tries > 0)
+        .unwrap_or(3)
+});
+const S3_RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum S3ErrorKind {
+    /// Throttling, timeouts and server errors, which tend to go away on their own.
+    Transient,
+    NotFound,
+    Permanent,
+}
+
+#[derive(Debug, Error)]
+#[error("{error}")]
+pub struct S3RequestError {
+    pub kind: S3ErrorKind,
+    pub error: anyhow::Error,
+}
+
+impl S3RequestError {
+    fn from_sdk<E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static>(
+        key: &str,
+        err: SdkError<E, HttpResponse>,
+    ) -> Self {
+        let kind = match &err {
+            SdkError::TimeoutError(_)
+            | SdkError::DispatchFailure(_)
+            | SdkError::ResponseError(_) => S3ErrorKind::Transient,
+            _ => match (err.raw_response().map(|r| r.status().as_u16()), err.code()) {
+                (Some(404), _) | (_, Some("NoSuchKey")) => S3ErrorKind::NotFound,
+                (Some(408 | 429 | 500..=599), _)
+                | (_, Some("SlowDown" | "Throttling" | "RequestTimeout")) => S3ErrorKind::Transient,
+                _ => S3ErrorKind::Permanent,
+            },
+        };
+        S3RequestError {
+            kind,
+            error: anyhow::Error::new(err).context(format!("S3 request for {key} failed")),
+        }
+    }
+
+    fn is_transient(&self) -> bool {
+        self.kind == S3ErrorKind::Transient
+    }
+}
+
+/// Runs `request` up to `tries` times, doubling the delay after each failure. Errors
+/// `is_retryable` rejects are returned straight away.
+pub async fn retry_with_backoff<T, E: std::fmt::Display>(
+    tries: usize,
+    base_delay: Duration,
+    is_retryable: impl Fn(&E) -> bool,
+    request: impl AsyncFn() -> Result<T, E>,
+) -> Result<T, E> {
+    let mut delay = base_delay;
+    let mut remaining_tries = tries.max(1);
+    loop {
+        remaining_tries -= 1;
+        match request().await {
+            Ok(value) => return Ok(value),
+            Err(err) if remaining_tries == 0 || !is_retryable(&err) => return Err(err),
+            Err(err) => {
+                warn!(%err, %remaining_tries, ?delay, "Retrying failed request");
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+    }
 }
 
 pub async fn upload_object<T: CannonicalS3ObjectLocation>(
@@ -87,9 +249,43 @@ pub async fn upload_object<T: CannonicalS3ObjectLocation>(
 ) -> anyhow::Result<()> {
     let key = get_openscrapers_json_key::<T>(addr);
     let bucket = &**OPENSCRAPERS_S3_OBJECT_BUCKET;
-    S3Addr::new(s3_client, bucket, &key)
-        .upload_json(&object)
-        .await
+    let contents = serde_json::to_vec(object)?;
+    retry_with_backoff(
+        *S3_OBJECT_TRIES,
+        S3_RETRY_BASE_DELAY,
+        S3RequestError::is_transient,
+        async || {
+            limit_s3_request(
+                s3_client
+                    .put_object()
+                    .bucket(bucket)
+                    .key(&key)
+                    .content_type("application/json")
+                    .body(ByteStream::from(contents.clone()))
+                    .send(),
+            )
+            .await
+            .map_err(|err| S3RequestError::from_sdk(&key, err))
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+/// Uploads `object` unless S3 already holds the same one, so resubmitting an unchanged object
+/// leaves its LastModified alone. Returns whether anything was uploaded.
+pub async fn upload_object_if_changed<T: CannonicalS3ObjectLocation>(
+    s3_client: &S3Client,
+    addr: &T::AddressInfo,
+    object: &T,
+) -> anyhow::Result<bool> {
+    if let Ok(existing) = download_openscrapers_object::<T>(s3_client, addr).await
+        && serde_json::to_vec(&existing)? == serde_json::to_vec(object)?
+    {
+        return Ok(false);
+    }
+    upload_object(s3_client, addr, object).await?;
+    Ok(true)
 }
 
 pub async fn delete_openscrapers_s3_object<T: CannonicalS3ObjectLocation>(
@@ -98,11 +294,57 @@ pub async fn delete_openscrapers_s3_object<T: CannonicalS3ObjectLocation>(
 ) -> anyhow::Result<()> {
     let key = get_openscrapers_json_key::<T>(addr);
     let bucket = &**OPENSCRAPERS_S3_OBJECT_BUCKET;
-    S3Addr::new(s3_client, bucket, &key).delete_file().await
+    limit_s3_request(S3Addr::new(s3_client, bucket, &key).delete_file()).await
 }
 
-pub fn generate_s3_object_uri_from_key(key: &str) -> String {
-    let bucket = &**OPENSCRAPERS_S3_OBJECT_BUCKET;
+/// Downloads the processed dockets in `addresses` in the order given, at most `concurrency` at a
+/// time, each paired with the address it came from.
+pub fn stream_processed_dockets(
+    s3_client: S3Client,
+    addresses: Vec<DocketAddress>,
+    concurrency: usize,
+) -> impl Stream<Item = (DocketAddress, anyhow::Result<ProcessedGenericDocket>)> + Send + 'static {
+    stream::iter(addresses)
+        .map(move |addr| {
+            let s3_client = s3_client.clone();
+            async move {
+                let res =
+                    download_openscrapers_object::<ProcessedGenericDocket>(&s3_client, &addr).await;
+                (addr, res)
+            }
+        })
+        .buffered(concurrency.max(1))
+}
+
+/// Downloads every processed docket in `addresses`, at most `concurrency` at a time. Dockets that
+/// fail to download are handed back with their error instead of failing the whole batch.
+pub async fn fetch_processed_dockets(
+    s3_client: &S3Client,
+    addresses: Vec<DocketAddress>,
+    concurrency: usize,
+) -> (
+    Vec<ProcessedGenericDocket>,
+    Vec<(DocketAddress, anyhow::Error)>,
+) {
+    let results = stream_processed_dockets(s3_client.clone(), addresses, concurrency)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut dockets = Vec::with_capacity(results.len());
+    let mut failures = Vec::new();
+    for (addr, res) in results {
+        match res {
+            Ok(docket) => dockets.push(docket),
+            Err(err) => failures.push((addr, err)),
+        }
+    }
+    (dockets, failures)
+}
+
+/// The S3 uri of an attachment file key, in the bucket `jurisdiction`'s attachment files are
+/// stored in.
+pub fn generate_s3_object_uri_from_key(key: &str, jurisdiction: &JurisdictionInfo) -> String {
+    let bucket = get_attachment_bucket(jurisdiction);
     let credentials = &*DIGITALOCEAN_S3;
     let uri = S3LocationWithCredentials::from_key_bucket_and_credentials(key, bucket, credentials)
         .to_string();
@@ -121,37 +363,122 @@ pub async fn fetch_attachment_file_from_s3(
 ) -> anyhow::Result<Vec<u8>> {
     info!(%hash, "Fetching attachment file from S3");
     let key = get_raw_attach_file_key(hash);
-    S3Addr::new(s3_client, &OPENSCRAPERS_S3_OBJECT_BUCKET, &key)
-        .download_bytes()
+    limit_s3_request(S3Addr::new(s3_client, &OPENSCRAPERS_S3_OBJECT_BUCKET, &key).download_bytes())
         .await
 }
 
-pub async fn fetch_attachment_file_from_s3_with_filename(
+/// An attachment file body straight from S3, not yet read into memory.
+pub struct AttachmentFileStream {
+    pub filename: String,
+    /// The object size S3 reported, if it reported one.
+    pub content_length: Option<u64>,
+    pub body: ByteStream,
+}
+
+/// A second bucket attachment files are read from when they're missing from the primary one, for
+/// files that were only ever copied to the new bucket during a migration.
+pub static ATTACHMENT_FALLBACK_S3_BUCKET: LazyLock<Option<String>> = LazyLock::new(|| {
+    std::env::var("ATTACHMENT_FALLBACK_S3_BUCKET")
+        .ok()
+        .filter(|bucket| !bucket.is_empty())
+});
+
+/// Buckets for jurisdictions whose attachment files are kept apart, keyed by jurisdiction name,
+/// ie `ATTACHMENT_S3_BUCKET_OVERRIDES=co_puc=co-attachments,ut_dogm_coal=ut-attachments`. Files
+/// are written to and served from the same bucket.
+pub static ATTACHMENT_S3_BUCKET_OVERRIDES: LazyLock<BTreeMap<String, String>> =
+    LazyLock::new(|| {
+        std::env::var("ATTACHMENT_S3_BUCKET_OVERRIDES")
+            .map(|overrides| parse_jurisdiction_overrides(&overrides))
+            .unwrap_or_default()
+    });
+
+fn attachment_bucket_for<'a>(
+    jurisdiction: &JurisdictionInfo,
+    overrides: &'a BTreeMap<String, String>,
+    default_bucket: &'a str,
+) -> &'a str {
+    overrides
+        .get(&*jurisdiction.jurisdiction)
+        .map(String::as_str)
+        .unwrap_or(default_bucket)
+}
+
+/// The bucket a jurisdiction's attachment files are stored in, its
+/// `ATTACHMENT_S3_BUCKET_OVERRIDES` entry or else the openscrapers bucket.
+pub fn get_attachment_bucket(jurisdiction: &JurisdictionInfo) -> &'static str {
+    attachment_bucket_for(
+        jurisdiction,
+        &ATTACHMENT_S3_BUCKET_OVERRIDES,
+        &OPENSCRAPERS_S3_OBJECT_BUCKET,
+    )
+}
+
+/// Streams an attachment file from its jurisdiction's bucket. Without a jurisdiction it's taken
+/// from the attachment's metadata, which is only fetched first when some jurisdiction has its
+/// own bucket.
+pub async fn stream_attachment_file_from_s3_with_filename(
     s3_client: &S3Client,
     hash: Blake2bHash,
-) -> anyhow::Result<(String, Vec<u8>)> {
-    info!(%hash, "Fetching attachment file from S3");
+    jurisdiction: Option<&JurisdictionInfo>,
+) -> anyhow::Result<AttachmentFileStream> {
+    let primary_bucket = match jurisdiction {
+        Some(jurisdiction) => get_attachment_bucket(jurisdiction),
+        None if ATTACHMENT_S3_BUCKET_OVERRIDES.is_empty() => &**OPENSCRAPERS_S3_OBJECT_BUCKET,
+        None => download_openscrapers_object::<RawAttachment>(s3_client, &hash)
+            .await
+            .map(|metadata| get_attachment_bucket(&metadata.jurisdiction_info))
+            .unwrap_or(&**OPENSCRAPERS_S3_OBJECT_BUCKET),
+    };
+    stream_attachment_file_from_buckets(
+        s3_client,
+        hash,
+        primary_bucket,
+        ATTACHMENT_FALLBACK_S3_BUCKET.as_deref(),
+    )
+    .await
+}
+
+async fn stream_attachment_file_from_buckets(
+    s3_client: &S3Client,
+    hash: Blake2bHash,
+    primary_bucket: &str,
+    fallback_bucket: Option<&str>,
+) -> anyhow::Result<AttachmentFileStream> {
+    info!(%hash, "Streaming attachment file from S3");
     let key = get_raw_attach_file_key(hash);
-    let location = S3Addr::new(s3_client, &OPENSCRAPERS_S3_OBJECT_BUCKET, &key);
-    let bytes_future = location.download_bytes();
+    let get_object =
+        |bucket: &str| limit_s3_request(s3_client.get_object().bucket(bucket).key(&key).send());
     let metadata_future = download_openscrapers_object::<RawAttachment>(s3_client, &hash);
-    let (Ok(bytes), metadata) = join!(bytes_future, metadata_future) else {
-        return Err(anyhow!("fetching bytes failed."));
-    };
+    let (mut object, metadata) = join!(get_object(primary_bucket), metadata_future);
+    if let Some(fallback_bucket) = fallback_bucket {
+        let missed_primary = object.as_ref().is_err_and(|err| {
+            err.raw_response()
+                .is_some_and(|response| response.status().as_u16() == 404)
+        });
+        if missed_primary {
+            info!(%hash, %fallback_bucket, "Attachment file missing from primary bucket, trying fallback");
+            object = get_object(fallback_bucket).await;
+        }
+    }
+    let object = object.map_err(|err| anyhow!("fetching {key} failed: {err}"))?;
 
     let filename = metadata
         .ok()
         .map(|v| v.name + "." + &v.extension.to_string())
         .unwrap_or_else(|| non_empty_string!("unknown_filename.pdf"));
-    Ok((filename.to_string(), bytes))
+    Ok(AttachmentFileStream {
+        filename: filename.to_string(),
+        content_length: object
+            .content_length()
+            .and_then(|length| u64::try_from(length).ok()),
+        body: object.body,
+    })
 }
 
+/// Where the processed dockets of a jurisdiction live, taken from the configured key layout.
 pub fn get_jurisdiction_prefix(jurisdiction: &JurisdictionInfo) -> String {
-    let country = &*jurisdiction.country;
-    let state = &*jurisdiction.state;
-    let jurisdiction_name = &*jurisdiction.jurisdiction;
-    let key = format!("objects/{country}/{state}/{jurisdiction_name}");
-    key
+    S3_KEY_LAYOUT.processed_docket_prefix(jurisdiction)
 }
 
 pub async fn does_openscrapers_attachment_exist(s3_client: &S3Client, hash: Blake2bHash) -> bool {
@@ -164,19 +491,11 @@ pub async fn does_openscrapers_attachment_exist(s3_client: &S3Client, hash: Blak
         obj_key, file_key
     );
 
-    let obj_exists = s3_client
-        .head_object()
-        .bucket(bucket)
-        .key(obj_key)
-        .send()
-        .await;
+    let obj_exists =
+        limit_s3_request(s3_client.head_object().bucket(bucket).key(obj_key).send()).await;
 
-    let file_exists = s3_client
-        .head_object()
-        .bucket(bucket)
-        .key(file_key)
-        .send()
-        .await;
+    let file_exists =
+        limit_s3_request(s3_client.head_object().bucket(bucket).key(file_key).send()).await;
 
     let result = obj_exists.is_ok() && file_exists.is_ok();
     debug!("Attachment exists: {}", result);
@@ -185,21 +504,20 @@ pub async fn does_openscrapers_attachment_exist(s3_client: &S3Client, hash: Blak
 
 pub async fn list_processed_cases_for_jurisdiction(
     s3_client: &S3Client,
-    JurisdictionInfo {
-        jurisdiction,
-        state,
-        country,
-    }: &JurisdictionInfo,
+    jurisdiction_info: &JurisdictionInfo,
 ) -> anyhow::Result<Vec<String>> {
     info!(
-        jurisdiction,
-        state, country, "Listing cases for jurisdiction"
+        jurisdiction = %jurisdiction_info.jurisdiction,
+        state = %jurisdiction_info.state,
+        country = %jurisdiction_info.country,
+        "Listing cases for jurisdiction"
     );
-    let prefix = format!("objects/{country}/{state}/{jurisdiction}/");
+    let prefix = S3_KEY_LAYOUT.processed_docket_prefix(jurisdiction_info);
     info!("Listing cases with prefix: {}", prefix);
-    let mut matches = S3DirectoryAddr::new(s3_client, &OPENSCRAPERS_S3_OBJECT_BUCKET, &prefix)
-        .list_all()
-        .await?;
+    let mut matches = limit_s3_request(
+        S3DirectoryAddr::new(s3_client, &OPENSCRAPERS_S3_OBJECT_BUCKET, &prefix).list_all(),
+    )
+    .await?;
     for val in matches.iter_mut() {
         if let Some(stripped_json) = val.strip_suffix(".json")
             && let Some(stripped) = stripped_json.strip_prefix(&prefix)
@@ -212,21 +530,20 @@ pub async fn list_processed_cases_for_jurisdiction(
 
 pub async fn list_raw_cases_for_jurisdiction(
     s3_client: &S3Client,
-    JurisdictionInfo {
-        jurisdiction,
-        state,
-        country,
-    }: &JurisdictionInfo,
+    jurisdiction_info: &JurisdictionInfo,
 ) -> anyhow::Result<Vec<String>> {
     info!(
-        jurisdiction,
-        state, country, "Listing cases for jurisdiction"
+        jurisdiction = %jurisdiction_info.jurisdiction,
+        state = %jurisdiction_info.state,
+        country = %jurisdiction_info.country,
+        "Listing cases for jurisdiction"
     );
-    let prefix = format!("objects_raw/{country}/{state}/{jurisdiction}/");
+    let prefix = S3_KEY_LAYOUT.raw_docket_prefix(jurisdiction_info);
     info!("Listing cases with prefix: {}", prefix);
-    let mut matches = S3DirectoryAddr::new(s3_client, &OPENSCRAPERS_S3_OBJECT_BUCKET, &prefix)
-        .list_all()
-        .await?;
+    let mut matches = limit_s3_request(
+        S3DirectoryAddr::new(s3_client, &OPENSCRAPERS_S3_OBJECT_BUCKET, &prefix).list_all(),
+    )
+    .await?;
     for val in matches.iter_mut() {
         if let Some(stripped_json) = val.strip_suffix(".json")
             && let Some(stripped) = stripped_json.strip_prefix(&prefix)
@@ -245,10 +562,407 @@ pub async fn push_raw_attach_file_to_s3(
     info!(hash = %raw_att.hash, "Pushing raw attachment file to S3");
     let file_key = get_raw_attach_file_key(raw_att.hash);
 
-    S3Addr::new(s3_client, &OPENSCRAPERS_S3_OBJECT_BUCKET, &file_key)
-        .upload_bytes(file_contents)
-        .await?;
+    upload_s3_bytes(
+        s3_client,
+        get_attachment_bucket(&raw_att.jurisdiction_info),
+        &file_key,
+        file_contents,
+    )
+    .await?;
     debug!("Successfully pushed file to S3");
 
     Ok(())
 }
+
+const DEFAULT_S3_MULTIPART_THRESHOLD_BYTES: usize = 100 * 1024 * 1024;
+static S3_MULTIPART_THRESHOLD_BYTES: LazyLock<usize> = LazyLock::new(|| {
+    std::env::var("S3_MULTIPART_THRESHOLD_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_S3_MULTIPART_THRESHOLD_BYTES)
+});
+// S3 rejects parts smaller than 5MB, except for the last one.
+const S3_MULTIPART_PART_BYTES: usize = 16 * 1024 * 1024;
+const SIMULTANEOUS_PART_UPLOADS: usize = 4;
+
+/// Splits a payload into multipart upload parts, or returns None if it is small enough for a
+/// single put.
+fn plan_multipart_parts(len: usize, threshold: usize) -> Option<Vec<Range<usize>>> {
+    if len <= threshold {
+        return None;
+    }
+    let parts = (0..len)
+        .step_by(S3_MULTIPART_PART_BYTES)
+        .map(|start| start..(start + S3_MULTIPART_PART_BYTES).min(len))
+        .collect();
+    Some(parts)
+}
+
+/// Uploads bytes to S3, switching to a multipart upload once the payload is larger than
+/// `S3_MULTIPART_THRESHOLD_BYTES` (100MB by default).
+pub async fn upload_s3_bytes(
+    s3_client: &S3Client,
+    bucket: &str,
+    key: &str,
+    contents: Vec<u8>,
+) -> anyhow::Result<()> {
+    let Some(parts) = plan_multipart_parts(contents.len(), *S3_MULTIPART_THRESHOLD_BYTES) else {
+        return limit_s3_request(S3Addr::new(s3_client, bucket, key).upload_bytes(contents)).await;
+    };
+    info!(
+        bucket,
+        key,
+        size = contents.len(),
+        parts = parts.len(),
+        "Uploading file to S3 with a multipart upload"
+    );
+    let upload = limit_s3_request(
+        s3_client
+            .create_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .send(),
+    )
+    .await?;
+    let upload_id = upload
+        .upload_id()
+        .ok_or_else(|| anyhow!("S3 did not return an id for the multipart upload"))?;
+
+    let result = upload_multipart_parts(s3_client, bucket, key, upload_id, &contents, parts).await;
+    let completed_parts = match result {
+        Ok(completed_parts) => completed_parts,
+        Err(err) => {
+            // Otherwise the already uploaded parts stick around in the bucket and get billed.
+            if let Err(abort_err) = limit_s3_request(
+                s3_client
+                    .abort_multipart_upload()
+                    .bucket(bucket)
+                    .key(key)
+                    .upload_id(upload_id)
+                    .send(),
+            )
+            .await
+            {
+                warn!(bucket, key, error = %abort_err, "Failed to abort multipart upload");
+            }
+            return Err(err);
+        }
+    };
+    limit_s3_request(
+        s3_client
+            .complete_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(completed_parts))
+                    .build(),
+            )
+            .send(),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn upload_multipart_parts(
+    s3_client: &S3Client,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+    contents: &[u8],
+    parts: Vec<Range<usize>>,
+) -> anyhow::Result<Vec<CompletedPart>> {
+    let simultaneous_part_uploads = Semaphore::new(SIMULTANEOUS_PART_UPLOADS);
+    let part_futures = parts.into_iter().enumerate().map(
+        async |(index, range): (usize, Range<usize>)| -> anyhow::Result<CompletedPart> {
+            let _permit = simultaneous_part_uploads.acquire().await?;
+            // Part numbers start at 1.
+            let part_number = index as i32 + 1;
+            let uploaded = limit_s3_request(
+                s3_client
+                    .upload_part()
+                    .bucket(bucket)
+                    .key(key)
+                    .upload_id(upload_id)
+                    .part_number(part_number)
+                    .body(ByteStream::from(contents[range].to_vec()))
+                    .send(),
+            )
+            .await?;
+            Ok(CompletedPart::builder()
+                .part_number(part_number)
+                .set_e_tag(uploaded.e_tag().map(str::to_string))
+                .build())
+        },
+    );
+    join_all(part_futures).await.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::make_raw_docket;
+    use flate2::{Compression, write::GzEncoder};
+    use std::io::Write;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_fetch_processed_dockets_splits_missing_addresses() {
+        let s3_client = make_s3_client().await;
+        let jurisdiction = JurisdictionInfo::new_usa("ny_puc", "ny");
+        let present_govid = format!("FETCH-PRESENT-{}", Uuid::new_v4());
+        let missing_govid = format!("FETCH-MISSING-{}", Uuid::new_v4());
+        let present_addr = DocketAddress {
+            docket_govid: present_govid.clone(),
+            jurisdiction: jurisdiction.clone(),
+        };
+        let docket = crate::test_fixtures::make_processed_docket(
+            &present_govid,
+            chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            vec![],
+        );
+        upload_object(&s3_client, &present_addr, &docket)
+            .await
+            .unwrap();
+
+        let addresses = vec![
+            DocketAddress {
+                docket_govid: present_govid.clone(),
+                jurisdiction: jurisdiction.clone(),
+            },
+            DocketAddress {
+                docket_govid: missing_govid.clone(),
+                jurisdiction,
+            },
+        ];
+        let (dockets, failures) = fetch_processed_dockets(&s3_client, addresses, 2).await;
+        assert_eq!(dockets.len(), 1);
+        assert_eq!(dockets[0].case_govid.as_str(), present_govid);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0.docket_govid, missing_govid);
+
+        delete_openscrapers_s3_object::<ProcessedGenericDocket>(&s3_client, &present_addr)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_raw_docket_is_not_uploaded_again() {
+        let s3_client = make_s3_client().await;
+        let docket_govid = format!("UNCHANGED-RAW-{}", Uuid::new_v4());
+        let addr = DocketAddress {
+            docket_govid: docket_govid.clone(),
+            jurisdiction: JurisdictionInfo::new_usa("ny_puc", "ny"),
+        };
+        let mut raw_docket = crate::test_fixtures::make_raw_docket(&docket_govid, vec![]);
+
+        let first_upload = upload_object_if_changed(&s3_client, &addr, &raw_docket).await;
+        let unchanged_upload = upload_object_if_changed(&s3_client, &addr, &raw_docket).await;
+        raw_docket.description = "Now with a description".to_string();
+        let changed_upload = upload_object_if_changed(&s3_client, &addr, &raw_docket).await;
+        let stored = download_openscrapers_object::<RawGenericDocket>(&s3_client, &addr).await;
+
+        delete_openscrapers_s3_object::<RawGenericDocket>(&s3_client, &addr)
+            .await
+            .unwrap();
+        assert!(first_upload.unwrap());
+        assert!(!unchanged_upload.unwrap());
+        assert!(changed_upload.unwrap());
+        assert_eq!(stored.unwrap().description, "Now with a description");
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_s3_requests_stay_under_the_global_cap() {
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+        let simulated_request = async || {
+            let now_in_flight = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            max_in_flight.fetch_max(now_in_flight, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+        };
+
+        // Like several tasks each running their own bounded batch of requests.
+        let limiter = S3RequestLimiter::new(3);
+        let tasks = (0..4).map(|_| async {
+            stream::iter(0..5)
+                .for_each_concurrent(5, |_| limiter.run(simulated_request()))
+                .await
+        });
+        join_all(tasks).await;
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+
+        max_in_flight.store(0, Ordering::SeqCst);
+        let request_count = *S3_MAX_CONCURRENT_REQUESTS * 2;
+        join_all((0..request_count).map(|_| limit_s3_request(simulated_request()))).await;
+        let global_max = max_in_flight.load(Ordering::SeqCst);
+        assert!(global_max > 0 && global_max <= *S3_MAX_CONCURRENT_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_transient_s3_error_is_retried_until_success() {
+        let attempts = AtomicUsize::new(0);
+        let result = retry_with_backoff(
+            3,
+            Duration::ZERO,
+            S3RequestError::is_transient,
+            async || match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(S3RequestError {
+                    kind: S3ErrorKind::Transient,
+                    error: anyhow!("SlowDown"),
+                }),
+                _ => Ok("docket"),
+            },
+        )
+        .await;
+        assert_eq!(result.unwrap(), "docket");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_missing_object_is_not_retried() {
+        let attempts = AtomicUsize::new(0);
+        let result = retry_with_backoff(
+            5,
+            Duration::ZERO,
+            S3RequestError::is_transient,
+            async || {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(S3RequestError {
+                    kind: S3ErrorKind::NotFound,
+                    error: anyhow!("NoSuchKey"),
+                })
+            },
+        )
+        .await;
+        assert_eq!(result.unwrap_err().kind, S3ErrorKind::NotFound);
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        let s3_client = make_s3_client().await;
+        let missing_addr = DocketAddress {
+            docket_govid: format!("MISSING-{}", Uuid::new_v4()),
+            jurisdiction: JurisdictionInfo::new_usa("ny_puc", "ny"),
+        };
+        let err = download_openscrapers_object::<RawGenericDocket>(&s3_client, &missing_addr)
+            .await
+            .unwrap_err();
+        let s3_err = err
+            .downcast_ref::<S3RequestError>()
+            .expect("S3 failures should stay downcastable");
+        assert_eq!(s3_err.kind, S3ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_gzipped_raw_docket_deserializes() {
+        let docket = make_raw_docket("GZIP-1", vec![]);
+        let json_bytes = serde_json::to_vec(&docket).unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&json_bytes).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let from_gzip = parse_json_maybe_gzipped::<RawGenericDocket>(&gzipped).unwrap();
+        assert_eq!(from_gzip.case_govid.as_str(), "GZIP-1");
+        let from_plain = parse_json_maybe_gzipped::<RawGenericDocket>(&json_bytes).unwrap();
+        assert_eq!(from_plain.case_govid.as_str(), "GZIP-1");
+    }
+
+    #[test]
+    fn test_small_payload_uses_single_put() {
+        assert!(plan_multipart_parts(1024, DEFAULT_S3_MULTIPART_THRESHOLD_BYTES).is_none());
+        assert!(
+            plan_multipart_parts(
+                DEFAULT_S3_MULTIPART_THRESHOLD_BYTES,
+                DEFAULT_S3_MULTIPART_THRESHOLD_BYTES
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_large_payload_round_trips_through_multipart_parts() {
+        let threshold = 1024 * 1024;
+        let payload = (0..(2 * S3_MULTIPART_PART_BYTES + 12345))
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let parts = plan_multipart_parts(payload.len(), threshold)
+            .expect("payload over the threshold should use a multipart upload");
+        assert_eq!(parts.len(), 3);
+        assert!(
+            parts[..parts.len() - 1]
+                .iter()
+                .all(|range| range.len() == S3_MULTIPART_PART_BYTES)
+        );
+        let reassembled = parts
+            .into_iter()
+            .flat_map(|range| payload[range].to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(reassembled, payload);
+    }
+
+    #[tokio::test]
+    async fn test_attachment_file_falls_back_to_secondary_bucket() {
+        let s3_client = make_s3_client().await;
+        let contents = format!("fallback attachment {}", Uuid::new_v4()).into_bytes();
+        let hash = Blake2bHash::from_bytes(&contents);
+        let key = get_raw_attach_file_key(hash);
+        let secondary_bucket = &**OPENSCRAPERS_S3_OBJECT_BUCKET;
+        upload_s3_bytes(&s3_client, secondary_bucket, &key, contents.clone())
+            .await
+            .unwrap();
+        let primary_bucket = format!("dokito-missing-{}", Uuid::new_v4().simple());
+
+        assert!(
+            stream_attachment_file_from_buckets(&s3_client, hash, &primary_bucket, None)
+                .await
+                .is_err()
+        );
+        let file = stream_attachment_file_from_buckets(
+            &s3_client,
+            hash,
+            &primary_bucket,
+            Some(secondary_bucket),
+        )
+        .await
+        .unwrap();
+        assert_eq!(file.content_length, Some(contents.len() as u64));
+        let served = file.body.collect().await.unwrap().into_bytes();
+        assert_eq!(served.as_ref(), contents.as_slice());
+
+        S3Addr::new(&s3_client, secondary_bucket, &key)
+            .delete_file()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_attachment_file_served_from_jurisdiction_bucket() {
+        let s3_client = make_s3_client().await;
+        let contents = format!("jurisdiction attachment {}", Uuid::new_v4()).into_bytes();
+        let hash = Blake2bHash::from_bytes(&contents);
+        let key = get_raw_attach_file_key(hash);
+        let jurisdiction_bucket = &**OPENSCRAPERS_S3_OBJECT_BUCKET;
+        upload_s3_bytes(&s3_client, jurisdiction_bucket, &key, contents.clone())
+            .await
+            .unwrap();
+        let default_bucket = format!("dokito-missing-{}", Uuid::new_v4().simple());
+        let overrides = parse_jurisdiction_overrides(&format!("co_puc={jurisdiction_bucket}"));
+        let colorado = JurisdictionInfo::new_usa("co_puc", "co");
+        let new_york = JurisdictionInfo::new_usa("ny_puc", "ny");
+
+        let serve = async |jurisdiction| {
+            let bucket = attachment_bucket_for(jurisdiction, &overrides, &default_bucket);
+            stream_attachment_file_from_buckets(&s3_client, hash, bucket, None).await
+        };
+        assert!(serve(&new_york).await.is_err());
+        let file = serve(&colorado).await.unwrap();
+        let served = file.body.collect().await.unwrap().into_bytes();
+        assert_eq!(served.as_ref(), contents.as_slice());
+
+        S3Addr::new(&s3_client, jurisdiction_bucket, &key)
+            .delete_file()
+            .await
+            .unwrap();
+    }
+}
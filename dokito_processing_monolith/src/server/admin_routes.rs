@@ -10,13 +10,19 @@
 
 use aide::axum::{
     ApiRouter,
-    routing::{post, post_with},
+    routing::{delete, get, post, post_with},
 };
 
+use crate::indexes::attachment_url_index;
 use crate::server::direct_file_fetch::{
     handle_directly_process_file_request, handle_directly_process_file_request_docs,
 };
+use crate::server::docket_routes;
+use crate::server::entity_routes;
+use crate::server::llm_routes;
 use crate::server::queue_routes;
+use crate::server::s3_routes;
+use crate::server::schema_routes;
 use crate::server::temporary_routes::define_temporary_routes;
 
 /// Creates the complete admin router with ALL critical administrative endpoints.
@@ -35,6 +41,40 @@ use crate::server::temporary_routes::define_temporary_routes;
 /// - `POST /docket-process/{state}/{jurisdiction_name}/govid/full` - Full process and ingest by government ID
 /// - `POST /docket-process/{state}/{jurisdiction_name}/by-jurisdiction` - Process all dockets by jurisdiction
 /// - `POST /docket-process/{state}/{jurisdiction_name}/by-daterange` - Process dockets within date range
+/// - `POST /docket-process/{state}/{jurisdiction_name}/by-petitioner` - Process the dockets of a petitioner, ie after correcting its name
+/// - `POST /docket-process/{state}/{jurisdiction_name}/{docket_govid}/process` - Process a single raw docket already in S3, without ingesting it
+/// - `POST /docket-process/{state}/{jurisdiction_name}/{docket_govid}/refresh-hashes` - Re-look up a docket's attachment hashes in the url index and persist any that changed
+/// - `POST /docket-process/{state}/{jurisdiction_name}/{docket_govid}/reprocess-diff` - Diff a fresh reprocess of a docket against its stored processed docket, without uploading
+/// - `POST /docket-process/{state}/{jurisdiction_name}/filing/reingest` - Rewrite one corrected filing of an ingested docket in postgres
+///
+/// ### Docket Queries
+/// - `GET /dockets/{state}/{jurisdiction_name}/by-processor-version/{processor_version}` - List dockets processed by a given code version
+/// - `GET /dockets/{state}/{jurisdiction_name}/summaries` - Page through docket level fields without loading fillings
+/// - `GET /dockets/{state}/{jurisdiction_name}/attachment-count-mismatches` - List dockets whose processed attachment count differs from the raw docket
+/// - `GET /dockets/{state}/{jurisdiction_name}/{docket_govid}/verify` - Check that postgres holds exactly the rows of a processed docket in S3
+///
+/// ### Docket Deletion
+/// - `DELETE /docket/{state}/{jurisdiction_name}/by-daterange` - Delete the dockets opened in a date range from postgres, once confirmed with the token the first call returns
+///
+/// ### Jurisdictions
+/// - `POST /jurisdictions/diff` - List the raw docket gov_ids that exist in only one of two jurisdictions
+///
+/// ### Schema Management
+/// - `GET /schema/{state}/{jurisdiction_name}/version` - List the migrations applied to a jurisdiction's postgres schema
+///
+/// ### Attachment Index
+/// - `GET /attachment_index` - Show the size of the in-memory attachment index and a few of its urls
+/// - `POST /attachment_index/clear` - Empty the in-memory attachment index so the next lookup pulls it from S3 again
+///
+/// ### LLM Controls
+/// - `GET /llm/kill-switch` - Whether LLM calls are currently skipped
+/// - `PUT /llm/kill-switch` - Turn every LLM call off (or back on) without a redeploy
+///
+/// ### Disaster Recovery
+/// - `POST /restore/{state}/{jurisdiction_name}` - Ingest an NDJSON backup of processed dockets directly into postgres
+/// - `GET /export/{state}/{jurisdiction_name}` - Stream every processed docket in S3 as an NDJSON backup
+/// - `GET /export/{state}/{jurisdiction_name}/organizations` - Stream the organizations table as NDJSON, for entity resolution
+/// - `GET /export/{state}/{jurisdiction_name}/humans` - Stream the humans table with their merged contacts as NDJSON
 ///
 /// ### Temporary/Development Routes
 /// - Various testing and development endpoints (see temporary_routes module)
@@ -78,6 +118,89 @@ pub fn create_admin_router() -> ApiRouter {
         .api_route(
             "/docket-process/{state}/{jurisdiction_name}/by-daterange",
             post(queue_routes::by_daterange_endpoint),
+        )
+        .api_route(
+            "/docket-process/{state}/{jurisdiction_name}/by-petitioner",
+            post(queue_routes::handle_reprocess_by_petitioner),
+        )
+        .api_route(
+            "/docket-process/{state}/{jurisdiction_name}/{docket_govid}/process",
+            post(docket_routes::handle_process_one),
+        )
+        .api_route(
+            "/docket-process/{state}/{jurisdiction_name}/{docket_govid}/refresh-hashes",
+            post(docket_routes::handle_refresh_docket_hashes),
+        )
+        .api_route(
+            "/docket-process/{state}/{jurisdiction_name}/{docket_govid}/reprocess-diff",
+            post(docket_routes::handle_reprocess_diff),
+        )
+        .api_route(
+            "/docket-process/{state}/{jurisdiction_name}/filing/reingest",
+            post(docket_routes::handle_reingest_filing),
+        )
+        // Docket query endpoints - read directly from postgres
+        .api_route(
+            "/dockets/{state}/{jurisdiction_name}/by-processor-version/{processor_version}",
+            get(docket_routes::handle_list_dockets_by_processor_version),
+        )
+        .api_route(
+            "/dockets/{state}/{jurisdiction_name}/summaries",
+            get(docket_routes::handle_list_docket_summaries),
+        )
+        .api_route(
+            "/dockets/{state}/{jurisdiction_name}/attachment-count-mismatches",
+            get(docket_routes::handle_list_attachment_count_mismatches),
+        )
+        .api_route(
+            "/dockets/{state}/{jurisdiction_name}/{docket_govid}/verify",
+            get(docket_routes::handle_verify_docket),
+        )
+        // Docket deletion - removes rows from postgres
+        .api_route(
+            "/docket/{state}/{jurisdiction_name}/by-daterange",
+            delete(docket_routes::handle_delete_by_daterange),
+        )
+        // Jurisdiction comparison - for migrating data between jurisdictions
+        .api_route(
+            "/jurisdictions/diff",
+            post(s3_routes::handle_jurisdiction_caselist_diff),
+        )
+        // Schema management
+        .api_route(
+            "/schema/{state}/{jurisdiction_name}/version",
+            get(schema_routes::handle_get_schema_version),
+        )
+        // Attachment index - for debugging a stale in-memory index
+        .api_route(
+            "/attachment_index",
+            get(attachment_url_index::handle_attachment_index_summary),
+        )
+        .api_route(
+            "/attachment_index/clear",
+            post(attachment_url_index::handle_clear_attachment_index),
+        )
+        // LLM controls - for outages and cost spikes
+        .api_route(
+            "/llm/kill-switch",
+            get(llm_routes::handle_get_llm_kill_switch).put(llm_routes::handle_set_llm_kill_switch),
+        )
+        // Disaster recovery - bulk load processed dockets without touching S3
+        .api_route(
+            "/restore/{state}/{jurisdiction_name}",
+            post(docket_routes::handle_restore_ndjson),
+        )
+        .api_route(
+            "/export/{state}/{jurisdiction_name}",
+            get(docket_routes::handle_export_ndjson),
+        )
+        .api_route(
+            "/export/{state}/{jurisdiction_name}/organizations",
+            get(entity_routes::handle_export_entities),
+        )
+        .api_route(
+            "/export/{state}/{jurisdiction_name}/humans",
+            get(entity_routes::handle_export_humans),
         );
 
     // Add temporary/development routes to the admin router
@@ -0,0 +1,84 @@
+use std::{convert::Infallible, fmt};
+
+use aide::OperationInput;
+use axum::{extract::FromRequestParts, http::request::Parts};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longer incoming ids are replaced with a generated one rather than copied into every log line.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Groups the logs of a single request, taken from the `X-Request-Id` header when the caller sent
+/// one and generated otherwise.
+#[derive(Clone, Debug)]
+pub struct CorrelationId(pub String);
+
+impl CorrelationId {
+    pub fn generate() -> Self {
+        CorrelationId(Uuid::new_v4().to_string())
+    }
+}
+
+impl fmt::Display for CorrelationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for CorrelationId {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let incoming = parts
+            .headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN);
+        Ok(match incoming {
+            Some(id) => CorrelationId(id.to_string()),
+            None => CorrelationId::generate(),
+        })
+    }
+}
+
+impl OperationInput for CorrelationId {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, http::Request, routing::get};
+    use tower::ServiceExt;
+
+    async fn extracted_id(request_id: Option<&str>) -> String {
+        let router = Router::new().route(
+            "/",
+            get(async |correlation_id: CorrelationId| correlation_id.0),
+        );
+        let mut request = Request::get("/");
+        if let Some(request_id) = request_id {
+            request = request.header(REQUEST_ID_HEADER, request_id);
+        }
+        let response = router
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_incoming_request_id_is_kept() {
+        assert_eq!(extracted_id(Some("batch-42")).await, "batch-42");
+    }
+
+    #[tokio::test]
+    async fn test_missing_request_id_is_generated() {
+        let generated = extracted_id(None).await;
+        assert!(Uuid::parse_str(&generated).is_ok());
+        assert_ne!(extracted_id(Some("   ")).await, "   ");
+    }
+}
@@ -0,0 +1,1567 @@
+use std::pin::pin;
+
+use anyhow::Context;
+use aws_sdk_s3::Client as S3Client;
+use axum::{
+    extract::{Path, Query},
+    response::Json,
+};
+use chrono::NaiveDate;
+use futures::{future::ready, join};
+use futures_util::{Stream, StreamExt, stream};
+use mycorrhiza_common::hash::Blake2bHash;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    indexes::attachment_url_index::lookup_hash_from_url_or_fetch,
+    jurisdiction_schema_mapping::FixedJurisdiction,
+    openscraper_data_traits::process_docket,
+    processing::{
+        attachments::{AttachmentFetchOrder, OpenscrapersExtraData, UrlAttachmentDownloader},
+        docket_processing_error, process_case,
+        reprocess_diff::{DocketDiff, diff_processed_dockets},
+    },
+    s3_stuff::{
+        DocketAddress, download_openscrapers_object, list_processed_cases_for_jurisdiction,
+        make_s3_client, stream_processed_dockets, upload_object,
+    },
+    server::{
+        jurisdiction_extractor::{DocketGovidPath, ValidJurisdiction},
+        ndjson::{NdjsonBody, NdjsonLimits, NdjsonLine, NdjsonResponse},
+        s3_routes::DocketPath,
+    },
+    sql_ingester_tasks::{
+        database_author_association::AuthorAssociationMode,
+        dokito_sql_connection::get_dokito_pool_for,
+        nypuc_ingest::{ingest_sql_fixed_jurisdiction_case, reingest_filing},
+        verify_ingest::{DocketVerification, verify_ingested_docket},
+    },
+    types::{
+        attachments::RawAttachment,
+        jurisdictions::JurisdictionInfo,
+        processed::{ProcessedGenericAttachment, ProcessedGenericDocket, ProcessedGenericFiling},
+        raw::RawGenericDocket,
+    },
+};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ProcessorVersionPath {
+    /// The processor version stamped on the docket, ie `0.1.0+1a2b3c4`.
+    pub processor_version: String,
+}
+
+pub async fn handle_list_dockets_by_processor_version(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    Path(ProcessorVersionPath { processor_version }): Path<ProcessorVersionPath>,
+) -> Result<Json<Vec<String>>, String> {
+    info!(
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
+        %processor_version,
+        "Listing dockets by processor version"
+    );
+    let pg_schema = fixed_jur.get_postgres_schema_name();
+    let pool = get_dokito_pool_for(fixed_jur)
+        .await
+        .map_err(|e| e.to_string())?;
+    let docket_govids = sqlx::query_scalar::<_, String>(&format!(
+        "SELECT docket_govid FROM {pg_schema}.dockets WHERE processor_version = $1 ORDER BY docket_govid"
+    ))
+    .bind(&processor_version)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(Json(docket_govids))
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct NdjsonLineFailure {
+    /// The 1-indexed line in the uploaded NDJSON.
+    pub line: usize,
+    pub error: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct NdjsonRestoreReport {
+    pub ingested: usize,
+    pub failed: Vec<NdjsonLineFailure>,
+}
+
+/// How many restored dockets get ingested at once, the upload is only read as fast as they finish.
+const NDJSON_RESTORE_CONCURRENCY: usize = 4;
+
+/// Bulk loads a backup of processed dockets, one json docket per line, straight into postgres
+/// without going through S3. The upload is streamed, so it can be far larger than memory.
+pub async fn handle_restore_ndjson(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    body: NdjsonBody,
+) -> Result<Json<NdjsonRestoreReport>, String> {
+    info!(
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
+        "Restoring dockets from NDJSON"
+    );
+    let pool = get_dokito_pool_for(fixed_jur)
+        .await
+        .map_err(|e| e.to_string())?;
+    let lines = body.into_lines(NdjsonLimits::from_env());
+    let report = restore_processed_dockets_from_ndjson(lines, fixed_jur, pool).await;
+    info!(
+        ingested = report.ingested,
+        failed = report.failed.len(),
+        "Finished NDJSON restore"
+    );
+    Ok(Json(report))
+}
+
+async fn restore_processed_dockets_from_ndjson(
+    lines: impl Stream<Item = NdjsonLine>,
+    fixed_jur: FixedJurisdiction,
+    pool: &PgPool,
+) -> NdjsonRestoreReport {
+    let results = lines
+        .filter(|(_, line)| ready(!matches!(line, Ok(text) if text.trim().is_empty())))
+        .map(
+            async |(line, text): NdjsonLine| -> Result<(), NdjsonLineFailure> {
+                let to_failure = |error: String| NdjsonLineFailure { line, error };
+                let text = text.map_err(|e| to_failure(e.to_string()))?;
+                let mut docket = serde_json::from_str::<ProcessedGenericDocket>(&text)
+                    .map_err(|e| to_failure(e.to_string()))?;
+                ingest_sql_fixed_jurisdiction_case(&mut docket, fixed_jur, pool, false)
+                    .await
+                    .map_err(|e| to_failure(e.to_string()))
+            },
+        )
+        .buffer_unordered(NDJSON_RESTORE_CONCURRENCY);
+    let mut results = pin!(results);
+    let mut report = NdjsonRestoreReport {
+        ingested: 0,
+        failed: vec![],
+    };
+    while let Some(result) = results.next().await {
+        match result {
+            Ok(()) => report.ingested += 1,
+            Err(failure) => {
+                warn!(line = failure.line, error = %failure.error, "Failed to restore docket from NDJSON line");
+                report.failed.push(failure);
+            }
+        }
+    }
+    report
+}
+
+/// How many processed dockets get downloaded ahead of the one being written out.
+const NDJSON_EXPORT_CONCURRENCY: usize = 8;
+
+/// Streams every processed docket of a jurisdiction out of S3 as NDJSON, in the format the
+/// restore endpoint takes. Each docket is written as soon as it is downloaded, and a docket that
+/// fails to download ends the export with its error.
+pub async fn handle_export_ndjson(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+) -> Result<NdjsonResponse, String> {
+    let jurisdiction = JurisdictionInfo::new_usa(
+        fixed_jur.get_jurisdiction_info_name(),
+        fixed_jur.get_state_code(),
+    );
+    let s3_client = make_s3_client().await;
+    let docket_govids = list_processed_cases_for_jurisdiction(&s3_client, &jurisdiction)
+        .await
+        .map_err(|e| e.to_string())?;
+    info!(
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
+        dockets = docket_govids.len(),
+        "Exporting dockets as NDJSON"
+    );
+    Ok(NdjsonResponse::from_fallible_stream(exported_dockets(
+        s3_client,
+        jurisdiction,
+        docket_govids,
+    )))
+}
+
+/// Downloads the processed dockets a few at a time, in the order they were listed.
+fn exported_dockets(
+    s3_client: S3Client,
+    jurisdiction: JurisdictionInfo,
+    docket_govids: Vec<String>,
+) -> impl Stream<Item = anyhow::Result<ProcessedGenericDocket>> + Send + 'static {
+    let addresses = docket_govids
+        .into_iter()
+        .map(|docket_govid| DocketAddress {
+            docket_govid,
+            jurisdiction: jurisdiction.clone(),
+        })
+        .collect();
+    stream_processed_dockets(s3_client, addresses, NDJSON_EXPORT_CONCURRENCY).map(|(addr, res)| {
+        if let Err(err) = &res {
+            warn!(docket_govid = %addr.docket_govid, error = %err, "Failed to export docket");
+        }
+        res.with_context(|| format!("Failed to export docket {}", addr.docket_govid))
+    })
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct AttachmentCountMismatch {
+    pub docket_govid: String,
+    pub raw_attachment_count: usize,
+    pub processed_attachment_count: usize,
+}
+
+fn compare_attachment_counts(
+    raw: &RawGenericDocket,
+    processed: &ProcessedGenericDocket,
+) -> Option<AttachmentCountMismatch> {
+    let raw_attachment_count = raw.filings.iter().map(|f| f.attachments.len()).sum();
+    let processed_attachment_count = processed.filings.iter().map(|f| f.attachments.len()).sum();
+    if raw_attachment_count == processed_attachment_count {
+        return None;
+    }
+    Some(AttachmentCountMismatch {
+        docket_govid: processed.case_govid.to_string(),
+        raw_attachment_count,
+        processed_attachment_count,
+    })
+}
+
+/// Scans every processed docket in a jurisdiction and reports the ones whose attachment count
+/// differs from their raw counterpart, which usually means processing dropped attachments.
+pub async fn handle_list_attachment_count_mismatches(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+) -> Result<Json<Vec<AttachmentCountMismatch>>, String> {
+    info!(
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
+        "Scanning for attachment count mismatches"
+    );
+    let jurisdiction = JurisdictionInfo::from(fixed_jur);
+    let s3_client = make_s3_client().await;
+    let docket_govids = list_processed_cases_for_jurisdiction(&s3_client, &jurisdiction)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let s3_client_ref = &s3_client;
+    let jurisdiction_ref = &jurisdiction;
+    let mut mismatches = stream::iter(docket_govids)
+        .map(async |docket_govid| {
+            let addr = DocketAddress {
+                docket_govid,
+                jurisdiction: jurisdiction_ref.clone(),
+            };
+            let (raw, processed) = join!(
+                download_openscrapers_object::<RawGenericDocket>(s3_client_ref, &addr),
+                download_openscrapers_object::<ProcessedGenericDocket>(s3_client_ref, &addr)
+            );
+            match (raw, processed) {
+                (Ok(raw), Ok(processed)) => compare_attachment_counts(&raw, &processed),
+                (Err(err), _) | (_, Err(err)) => {
+                    warn!(docket_govid = %addr.docket_govid, %err, "Could not download docket to compare attachment counts");
+                    None
+                }
+            }
+        })
+        .buffer_unordered(10)
+        .filter_map(async |mismatch| mismatch)
+        .collect::<Vec<_>>()
+        .await;
+    mismatches.sort_by(|a, b| a.docket_govid.cmp(&b.docket_govid));
+    info!(
+        mismatch_count = mismatches.len(),
+        "Finished attachment count scan"
+    );
+    Ok(Json(mismatches))
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct PaginationQuery {
+    /// How many rows to skip.
+    #[serde(default)]
+    pub offset: i64,
+    /// The maximum number of rows to return, defaults to 100.
+    #[serde(default = "default_page_limit")]
+    pub limit: i64,
+}
+
+const fn default_page_limit() -> i64 {
+    100
+}
+
+#[derive(Serialize, JsonSchema, FromRow)]
+pub struct DocketAttachmentListing {
+    pub blake2b_hash: String,
+    pub attachment_file_name: String,
+    pub attachment_file_extension: String,
+    pub attachment_url: String,
+    pub filling_govid: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct DocketAttachmentPage {
+    /// The number of attachments on the docket across every page.
+    pub total: i64,
+    pub offset: i64,
+    pub limit: i64,
+    pub attachments: Vec<DocketAttachmentListing>,
+}
+
+pub async fn handle_list_docket_attachments(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    Path(DocketGovidPath { docket_govid }): Path<DocketGovidPath>,
+    Query(pagination): Query<PaginationQuery>,
+) -> Result<Json<DocketAttachmentPage>, String> {
+    info!(
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
+        %docket_govid,
+        "Listing docket attachments"
+    );
+    let pool = get_dokito_pool_for(fixed_jur)
+        .await
+        .map_err(|e| e.to_string())?;
+    let page = list_docket_attachments(&docket_govid, &pagination, fixed_jur, pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(Json(page))
+}
+
+async fn list_docket_attachments(
+    docket_govid: &str,
+    pagination: &PaginationQuery,
+    fixed_jur: FixedJurisdiction,
+    pool: &PgPool,
+) -> Result<DocketAttachmentPage, sqlx::Error> {
+    let pg_schema = fixed_jur.get_postgres_schema_name();
+    let offset = pagination.offset.max(0);
+    let limit = pagination.limit.clamp(1, 1000);
+    let total = sqlx::query_scalar::<_, i64>(&format!(
+        "SELECT COUNT(*) FROM {pg_schema}.attachments a
+         JOIN {pg_schema}.fillings f ON a.parent_filling_uuid = f.uuid
+         WHERE f.docket_govid = $1"
+    ))
+    .bind(docket_govid)
+    .fetch_one(pool)
+    .await?;
+    let attachments = sqlx::query_as::<_, DocketAttachmentListing>(&format!(
+        "SELECT a.blake2b_hash, a.attachment_file_name, a.attachment_file_extension, a.attachment_url, f.filling_govid
+         FROM {pg_schema}.attachments a
+         JOIN {pg_schema}.fillings f ON a.parent_filling_uuid = f.uuid
+         WHERE f.docket_govid = $1
+         ORDER BY f.filed_date, f.uuid, a.attachment_file_name, a.uuid
+         OFFSET $2 LIMIT $3"
+    ))
+    .bind(docket_govid)
+    .bind(offset)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(DocketAttachmentPage {
+        total,
+        offset,
+        limit,
+        attachments,
+    })
+}
+
+/// The docket level fields of an ingested docket, read straight off the dockets table so listing
+/// dockets never loads their fillings or attachments.
+#[derive(Debug, Serialize, JsonSchema, FromRow, PartialEq)]
+pub struct DocketSummary {
+    pub docket_govid: String,
+    pub docket_title: String,
+    pub docket_type: String,
+    pub docket_subtype: String,
+    pub industry: String,
+    pub current_status: String,
+    pub opened_date: NaiveDate,
+    pub closed_date: Option<NaiveDate>,
+    pub petitioner_strings: Vec<String>,
+}
+
+pub async fn handle_list_docket_summaries(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    Query(pagination): Query<PaginationQuery>,
+) -> Result<Json<Vec<DocketSummary>>, String> {
+    info!(
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
+        "Listing docket summaries"
+    );
+    let pool = get_dokito_pool_for(fixed_jur)
+        .await
+        .map_err(|e| e.to_string())?;
+    let summaries = list_docket_summaries(fixed_jur.get_postgres_schema_name(), &pagination, pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(Json(summaries))
+}
+
+/// A page of docket summaries, most recently opened first.
+pub async fn list_docket_summaries(
+    pg_schema: &str,
+    pagination: &PaginationQuery,
+    pool: &PgPool,
+) -> Result<Vec<DocketSummary>, sqlx::Error> {
+    sqlx::query_as::<_, DocketSummary>(&format!(
+        "SELECT docket_govid, docket_title, docket_type, docket_subtype, industry, current_status,
+                opened_date, closed_date, petitioner_strings
+         FROM {pg_schema}.dockets
+         ORDER BY opened_date DESC, docket_govid
+         OFFSET $1 LIMIT $2"
+    ))
+    .bind(pagination.offset.max(0))
+    .bind(pagination.limit.clamp(1, 1000))
+    .fetch_all(pool)
+    .await
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct FilingTypeHistogramQuery {
+    /// Only count fillings filed on or after this date.
+    #[serde(default)]
+    pub start_date: Option<NaiveDate>,
+    /// Only count fillings filed on or before this date.
+    #[serde(default)]
+    pub end_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, JsonSchema, FromRow, PartialEq)]
+pub struct FilingTypeCount {
+    pub filling_type: String,
+    pub count: i64,
+}
+
+/// How many ingested fillings there are of each filling type, most common first.
+pub async fn handle_filing_type_histogram(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    Query(query): Query<FilingTypeHistogramQuery>,
+) -> Result<Json<Vec<FilingTypeCount>>, String> {
+    info!(
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
+        start_date = ?query.start_date,
+        end_date = ?query.end_date,
+        "Counting fillings by filling type"
+    );
+    let pool = get_dokito_pool_for(fixed_jur)
+        .await
+        .map_err(|e| e.to_string())?;
+    let histogram = filing_type_histogram(fixed_jur.get_postgres_schema_name(), &query, pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(Json(histogram))
+}
+
+async fn filing_type_histogram(
+    pg_schema: &str,
+    query: &FilingTypeHistogramQuery,
+    pool: &PgPool,
+) -> Result<Vec<FilingTypeCount>, sqlx::Error> {
+    sqlx::query_as::<_, FilingTypeCount>(&format!(
+        "SELECT filling_type, COUNT(*) AS count
+         FROM {pg_schema}.fillings
+         WHERE ($1::date IS NULL OR filed_date >= $1) AND ($2::date IS NULL OR filed_date <= $2)
+         GROUP BY filling_type
+         ORDER BY count DESC, filling_type"
+    ))
+    .bind(query.start_date)
+    .bind(query.end_date)
+    .fetch_all(pool)
+    .await
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct DeleteByDateRangeRequest {
+    /// The first opened date to delete dockets from.
+    pub start_date: NaiveDate,
+    /// The last opened date to delete dockets from, inclusive.
+    pub end_date: NaiveDate,
+    /// The `confirmation_token` returned by a call with the same range. Without a matching token
+    /// nothing gets deleted, the call only reports what would be.
+    #[serde(default)]
+    pub confirmation_token: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DateRangeDeletion {
+    /// Whether the dockets were deleted, false when this only reported them.
+    pub deleted: bool,
+    pub docket_count: i64,
+    pub filling_count: i64,
+    pub attachment_count: i64,
+    /// Pass back as `confirmation_token` to delete these dockets. It changes when the dockets in
+    /// the range do, so a stale token deletes nothing.
+    pub confirmation_token: String,
+}
+
+/// Deletes every ingested docket opened in a date range, along with their fillings and
+/// attachments, for backing out a bad import. Needs two calls, the first returns what would be
+/// deleted along with the token that confirms it.
+pub async fn handle_delete_by_daterange(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    Json(request): Json<DeleteByDateRangeRequest>,
+) -> Result<Json<DateRangeDeletion>, String> {
+    if request.start_date > request.end_date {
+        return Err("start_date must not be after end_date".to_string());
+    }
+    info!(
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
+        start_date = %request.start_date,
+        end_date = %request.end_date,
+        confirmed = request.confirmation_token.is_some(),
+        "Request received to delete dockets by date range"
+    );
+    let pool = get_dokito_pool_for(fixed_jur)
+        .await
+        .map_err(|e| e.to_string())?;
+    let deletion =
+        delete_dockets_by_daterange(fixed_jur.get_postgres_schema_name(), &request, pool)
+            .await
+            .map_err(|e| e.to_string())?;
+    if deletion.deleted {
+        info!(
+            docket_count = deletion.docket_count,
+            filling_count = deletion.filling_count,
+            attachment_count = deletion.attachment_count,
+            "Deleted dockets by date range"
+        );
+    }
+    Ok(Json(deletion))
+}
+
+fn date_range_confirmation_token(
+    pg_schema: &str,
+    request: &DeleteByDateRangeRequest,
+    counts: (i64, i64, i64),
+) -> String {
+    let (dockets, fillings, attachments) = counts;
+    let deletion = format!(
+        "delete-by-daterange:{pg_schema}:{}:{}:{dockets}:{fillings}:{attachments}",
+        request.start_date, request.end_date
+    );
+    Blake2bHash::from_bytes(deletion.as_bytes()).to_string()
+}
+
+/// Counts the dockets opened in the range and their children, deleting them in the same
+/// transaction when the request carries the matching confirmation token.
+pub async fn delete_dockets_by_daterange(
+    pg_schema: &str,
+    request: &DeleteByDateRangeRequest,
+    pool: &PgPool,
+) -> Result<DateRangeDeletion, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let counts = sqlx::query_as::<_, (i64, i64, i64)>(&format!(
+        "SELECT
+           (SELECT count(*) FROM {pg_schema}.dockets d
+            WHERE d.opened_date BETWEEN $1 AND $2),
+           (SELECT count(*) FROM {pg_schema}.fillings f
+            JOIN {pg_schema}.dockets d ON d.uuid = f.docket_uuid
+            WHERE d.opened_date BETWEEN $1 AND $2),
+           (SELECT count(*) FROM {pg_schema}.attachments a
+            JOIN {pg_schema}.fillings f ON f.uuid = a.parent_filling_uuid
+            JOIN {pg_schema}.dockets d ON d.uuid = f.docket_uuid
+            WHERE d.opened_date BETWEEN $1 AND $2)"
+    ))
+    .bind(request.start_date)
+    .bind(request.end_date)
+    .fetch_one(&mut *tx)
+    .await?;
+    let confirmation_token = date_range_confirmation_token(pg_schema, request, counts);
+    let confirmed = request.confirmation_token.as_deref() == Some(confirmation_token.as_str());
+    if confirmed {
+        // Fillings, attachments and the docket relations cascade.
+        sqlx::query(&format!(
+            "DELETE FROM {pg_schema}.dockets WHERE opened_date BETWEEN $1 AND $2"
+        ))
+        .bind(request.start_date)
+        .bind(request.end_date)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+    }
+    let (docket_count, filling_count, attachment_count) = counts;
+    Ok(DateRangeDeletion {
+        deleted: confirmed,
+        docket_count,
+        filling_count,
+        attachment_count,
+        confirmation_token,
+    })
+}
+
+// Stamped fresh on every processing run even when nothing about the docket changed.
+const VOLATILE_DOCKET_FIELDS: &[&str] = &["processed_at"];
+
+#[derive(Serialize, JsonSchema)]
+pub struct DocketContentHash {
+    pub docket_govid: String,
+    /// Blake2b hash of the processed docket, ignoring when it was processed.
+    pub content_hash: Blake2bHash,
+}
+
+/// Returns a hash of the processed docket in S3 that only changes when its contents do, so
+/// clients can check for changes without downloading the whole docket.
+pub async fn handle_docket_content_hash(
+    Path(DocketPath {
+        state,
+        jurisdiction_name,
+        docket_govid,
+    }): Path<DocketPath>,
+) -> Result<Json<DocketContentHash>, String> {
+    info!(%state, %jurisdiction_name, %docket_govid, "Computing docket content hash");
+    let s3_client = make_s3_client().await;
+    let addr = DocketAddress {
+        jurisdiction: JurisdictionInfo::new_usa(&jurisdiction_name, &state),
+        docket_govid,
+    };
+    let docket = download_openscrapers_object::<ProcessedGenericDocket>(&s3_client, &addr)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(Json(DocketContentHash {
+        docket_govid: addr.docket_govid,
+        content_hash: docket_content_hash(&docket),
+    }))
+}
+
+pub fn docket_content_hash(docket: &ProcessedGenericDocket) -> Blake2bHash {
+    let mut value = serde_json::to_value(docket).unwrap_or_default();
+    if let serde_json::Value::Object(fields) = &mut value {
+        for field in VOLATILE_DOCKET_FIELDS {
+            fields.remove(*field);
+        }
+    }
+    Blake2bHash::from_bytes(value.to_string().as_bytes())
+}
+
+pub async fn handle_process_one(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    Path(DocketGovidPath { docket_govid }): Path<DocketGovidPath>,
+) -> Result<Json<ProcessedGenericDocket>, String> {
+    info!(
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
+        %docket_govid,
+        "Processing single raw docket from S3"
+    );
+    let s3_client = make_s3_client().await;
+    let processed_docket = process_one_raw_docket(s3_client, fixed_jur, &docket_govid)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(Json(processed_docket))
+}
+
+/// Processes a raw docket that is already in S3, `process_case` writes the processed docket back
+/// next to it. Nothing is ingested into postgres.
+async fn process_one_raw_docket(
+    s3_client: S3Client,
+    fixed_jur: FixedJurisdiction,
+    docket_govid: &str,
+) -> anyhow::Result<ProcessedGenericDocket> {
+    let jurisdiction_info = JurisdictionInfo::from(fixed_jur);
+    let addr = DocketAddress {
+        docket_govid: docket_govid.to_string(),
+        jurisdiction: jurisdiction_info.clone(),
+    };
+    let raw_docket = download_openscrapers_object::<RawGenericDocket>(&s3_client, &addr).await?;
+    let extra_data = OpenscrapersExtraData {
+        s3_client,
+        jurisdiction_info,
+        fixed_jurisdiction: fixed_jur,
+        attachment_fetch_order: AttachmentFetchOrder::default(),
+    };
+    process_case(raw_docket, extra_data).await
+}
+
+/// Processes a raw docket from scratch and diffs it against its stored processed docket, without
+/// uploading anything, to preview what a processing change would do to it.
+pub async fn handle_reprocess_diff(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    Path(DocketGovidPath { docket_govid }): Path<DocketGovidPath>,
+) -> Result<Json<DocketDiff>, String> {
+    info!(
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
+        %docket_govid,
+        "Diffing a fresh reprocess against S3"
+    );
+    let s3_client = make_s3_client().await;
+    let diff = reprocess_docket_diff(&s3_client, fixed_jur, &docket_govid)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(Json(diff))
+}
+
+async fn reprocess_docket_diff(
+    s3_client: &S3Client,
+    fixed_jur: FixedJurisdiction,
+    docket_govid: &str,
+) -> anyhow::Result<DocketDiff> {
+    let addr = DocketAddress {
+        docket_govid: docket_govid.to_string(),
+        jurisdiction: JurisdictionInfo::from(fixed_jur),
+    };
+    let (raw_docket, stored_docket) = join!(
+        download_openscrapers_object::<RawGenericDocket>(s3_client, &addr),
+        download_openscrapers_object::<ProcessedGenericDocket>(s3_client, &addr)
+    );
+    let (raw_docket, stored_docket) = (raw_docket?, stored_docket?);
+    // Read only, so previewing a docket doesn't leave its new authors behind in postgres.
+    let reprocessed_docket =
+        process_docket(raw_docket, None, fixed_jur, AuthorAssociationMode::ReadOnly)
+            .await
+            .map_err(|err| docket_processing_error(docket_govid, err))?;
+    Ok(diff_processed_dockets(&stored_docket, &reprocessed_docket))
+}
+
+/// Checks that postgres holds exactly the rows of the processed docket stored in S3.
+pub async fn handle_verify_docket(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    Path(DocketGovidPath { docket_govid }): Path<DocketGovidPath>,
+) -> Result<Json<DocketVerification>, String> {
+    info!(
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
+        %docket_govid,
+        "Verifying ingested docket against S3"
+    );
+    let s3_client = make_s3_client().await;
+    let addr = DocketAddress {
+        docket_govid,
+        jurisdiction: JurisdictionInfo::from(fixed_jur),
+    };
+    let processed_docket =
+        download_openscrapers_object::<ProcessedGenericDocket>(&s3_client, &addr)
+            .await
+            .map_err(|e| e.to_string())?;
+    let pool = get_dokito_pool_for(fixed_jur)
+        .await
+        .map_err(|e| e.to_string())?;
+    let verification = verify_ingested_docket(
+        &processed_docket,
+        fixed_jur.get_postgres_schema_name(),
+        pool,
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(Json(verification))
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RefreshedAttachmentHash {
+    pub attachment_uuid: Uuid,
+    pub url: String,
+    pub old_hash: Option<String>,
+    pub new_hash: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DocketHashRefresh {
+    pub docket_govid: String,
+    pub refreshed: Vec<RefreshedAttachmentHash>,
+}
+
+/// Re-looks up the hash of every attachment of a processed docket in the url index, even the ones
+/// that already have a hash, and writes the docket back to S3 and postgres if any changed. With
+/// `ON_DEMAND_URL_HASHING` set, urls missing from the index are fetched and indexed on the spot.
+pub async fn handle_refresh_docket_hashes(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    Path(DocketGovidPath { docket_govid }): Path<DocketGovidPath>,
+) -> Result<Json<DocketHashRefresh>, String> {
+    info!(
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
+        %docket_govid,
+        "Refreshing docket attachment hashes"
+    );
+    let jurisdiction_info = JurisdictionInfo::from(fixed_jur);
+    let s3_client = make_s3_client().await;
+    let addr = DocketAddress {
+        docket_govid,
+        jurisdiction: jurisdiction_info.clone(),
+    };
+    let mut docket = download_openscrapers_object::<ProcessedGenericDocket>(&s3_client, &addr)
+        .await
+        .map_err(|e| e.to_string())?;
+    let pool = get_dokito_pool_for(fixed_jur)
+        .await
+        .map_err(|e| e.to_string())?;
+    let refresh = refresh_docket_hashes(
+        &mut docket,
+        fixed_jur,
+        pool,
+        async |attachment: &ProcessedGenericAttachment| {
+            let downloader = UrlAttachmentDownloader::for_attachment(
+                s3_client.clone(),
+                jurisdiction_info.clone(),
+                attachment,
+            );
+            lookup_hash_from_url_or_fetch(&attachment.url, &downloader)
+                .await
+                .unwrap_or_else(|err| {
+                    warn!(url = %attachment.url, %err, "Failed to fetch attachment missing from the url index");
+                    None
+                })
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    if !refresh.refreshed.is_empty() {
+        upload_object(&s3_client, &addr, &docket)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    info!(
+        docket_govid = %refresh.docket_govid,
+        refreshed = refresh.refreshed.len(),
+        "Finished refreshing docket attachment hashes"
+    );
+    Ok(Json(refresh))
+}
+
+/// Updates the attachment hashes that `lookup` disagrees with, and reingests the docket when any
+/// changed. Only blake2b hashes are taken, since those are what the documents table is keyed by.
+async fn refresh_docket_hashes(
+    docket: &mut ProcessedGenericDocket,
+    fixed_jur: FixedJurisdiction,
+    pool: &PgPool,
+    lookup: impl AsyncFn(&ProcessedGenericAttachment) -> Option<RawAttachment>,
+) -> anyhow::Result<DocketHashRefresh> {
+    let mut refreshed = vec![];
+    for attachment in docket
+        .filings
+        .iter_mut()
+        .flat_map(|filling| filling.attachments.iter_mut())
+        .filter(|attachment| !attachment.url.is_empty())
+    {
+        let Some(raw_attachment) = lookup(attachment).await else {
+            continue;
+        };
+        let new_hash = raw_attachment.hash;
+        let old_hash = attachment.hash.map(|hash| hash.to_string());
+        if old_hash.as_deref() == Some(new_hash.to_string().as_str()) {
+            continue;
+        }
+        attachment.hash = Some(new_hash);
+        refreshed.push(RefreshedAttachmentHash {
+            attachment_uuid: attachment.object_uuid,
+            url: attachment.url.clone(),
+            old_hash,
+            new_hash: new_hash.to_string(),
+        });
+    }
+    if !refreshed.is_empty() {
+        ingest_sql_fixed_jurisdiction_case(docket, fixed_jur, pool, false).await?;
+    }
+    Ok(DocketHashRefresh {
+        docket_govid: docket.case_govid.to_string(),
+        refreshed,
+    })
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct FilingReingestRequest {
+    /// The govid of the already ingested docket the filing belongs to.
+    pub docket_govid: String,
+    /// The corrected filing, its `object_uuid` picks which filing row gets replaced.
+    pub filing: ProcessedGenericFiling,
+}
+
+/// Rewrites one corrected filing, its attachments and its author relations in postgres, without
+/// reingesting the rest of its docket.
+pub async fn handle_reingest_filing(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    Json(FilingReingestRequest {
+        docket_govid,
+        mut filing,
+    }): Json<FilingReingestRequest>,
+) -> Result<Json<ProcessedGenericFiling>, String> {
+    info!(
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
+        %docket_govid,
+        filling_govid = %filing.filling_govid,
+        "Reingesting single filing"
+    );
+    let pool = get_dokito_pool_for(fixed_jur)
+        .await
+        .map_err(|e| e.to_string())?;
+    reingest_filing(&docket_govid, &mut filing, fixed_jur, pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(Json(filing))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexes::attachment_url_index::{
+        AttachIndex, AttachmentUrlFetcher, HashUrlIndex, InFlightFetches, lookup_or_fetch_in_index,
+    };
+    use crate::s3_stuff::{delete_openscrapers_s3_object, upload_object};
+    use crate::server::ndjson::ndjson_lines;
+    use crate::sql_ingester_tasks::{
+        database_author_association::OrgUuidCache, nypuc_ingest::ingest_sql_case_with_retries,
+        recreate_dokito_table_schema::create_target_schema,
+    };
+    use crate::test_fixtures::{
+        make_attachment_metadata, make_chunked_body, make_processed_attachment,
+        make_processed_docket, make_processed_filing, make_raw_attachment, make_raw_docket,
+        make_raw_filing, setup_test_db,
+    };
+    use async_trait::async_trait;
+    use axum::response::IntoResponse;
+    use tokio::sync::RwLock;
+
+    #[tokio::test]
+    async fn test_export_ends_with_an_error_when_a_docket_fails_to_download() {
+        let s3_client = make_s3_client().await;
+        let jurisdiction = JurisdictionInfo::from(FixedJurisdiction::NewYorkPuc);
+        let exported_govid = format!("EXPORT-{}", Uuid::new_v4());
+        let missing_govid = format!("EXPORT-MISSING-{}", Uuid::new_v4());
+        let addr = DocketAddress {
+            docket_govid: exported_govid.clone(),
+            jurisdiction: jurisdiction.clone(),
+        };
+        let opened_date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        upload_object(
+            &s3_client,
+            &addr,
+            &make_processed_docket(&exported_govid, opened_date, vec![]),
+        )
+        .await
+        .unwrap();
+
+        let docket_govids = vec![exported_govid.clone(), missing_govid.clone()];
+        let dockets = exported_dockets(s3_client.clone(), jurisdiction.clone(), docket_govids)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            dockets[0].as_ref().unwrap().case_govid.as_str(),
+            exported_govid
+        );
+        let err = dockets[1].as_ref().unwrap_err();
+        assert!(format!("{err:#}").contains(&missing_govid));
+
+        let docket_govids = vec![exported_govid.clone(), missing_govid];
+        let response = NdjsonResponse::from_fallible_stream(exported_dockets(
+            s3_client.clone(),
+            jurisdiction,
+            docket_govids,
+        ));
+        let body = axum::body::to_bytes(response.into_response().into_body(), usize::MAX).await;
+        assert!(body.is_err());
+
+        delete_openscrapers_s3_object::<ProcessedGenericDocket>(&s3_client, &addr)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_docket_content_hash_is_stable_until_a_field_changes() {
+        let s3_client = make_s3_client().await;
+        let govid = format!("CONTENT-HASH-{}", Uuid::new_v4());
+        let addr = DocketAddress {
+            docket_govid: govid.clone(),
+            jurisdiction: JurisdictionInfo::from(FixedJurisdiction::NewYorkPuc),
+        };
+        let opened_date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let mut docket = make_processed_docket(&govid, opened_date, vec![]);
+        let fetched_hash = async |docket: &ProcessedGenericDocket| {
+            upload_object(&s3_client, &addr, docket).await.unwrap();
+            let fetched = download_openscrapers_object::<ProcessedGenericDocket>(&s3_client, &addr)
+                .await
+                .unwrap();
+            docket_content_hash(&fetched).to_string()
+        };
+
+        let first_hash = fetched_hash(&docket).await;
+        assert_eq!(fetched_hash(&docket).await, first_hash);
+        docket.processed_at += chrono::TimeDelta::hours(1);
+        assert_eq!(fetched_hash(&docket).await, first_hash);
+        docket.description = "An updated description".to_string();
+        assert_ne!(fetched_hash(&docket).await, first_hash);
+
+        delete_openscrapers_s3_object::<ProcessedGenericDocket>(&s3_client, &addr)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delete_by_daterange_only_deletes_in_range_dockets() {
+        let pool = setup_test_db().await;
+        let pg_schema = format!("daterange_{}", &Uuid::new_v4().simple().to_string()[..8]);
+        create_target_schema(&pg_schema, &pool).await.unwrap();
+        let date = |month| NaiveDate::from_ymd_opt(2024, month, 1).unwrap();
+        for (govid, opened_date) in [("JAN-1", date(1)), ("MAR-1", date(3)), ("MAY-1", date(5))] {
+            let attachment = make_processed_attachment("Comments", "https://example.com/a.pdf");
+            let filing = make_processed_filing("1", opened_date, vec![attachment]);
+            let mut docket = make_processed_docket(govid, opened_date, vec![filing]);
+            ingest_sql_case_with_retries(
+                &mut docket,
+                FixedJurisdiction::NewYorkPuc,
+                Some(&pg_schema),
+                &pool,
+                &OrgUuidCache::default(),
+                false,
+                1,
+            )
+            .await
+            .unwrap();
+        }
+        let remaining_govids = async || {
+            sqlx::query_scalar::<_, String>(&format!(
+                "SELECT docket_govid FROM {pg_schema}.dockets ORDER BY docket_govid"
+            ))
+            .fetch_all(&pool)
+            .await
+            .unwrap()
+        };
+        let mut request = DeleteByDateRangeRequest {
+            start_date: date(2),
+            end_date: date(4),
+            confirmation_token: None,
+        };
+
+        let preview = delete_dockets_by_daterange(&pg_schema, &request, &pool)
+            .await
+            .unwrap();
+        assert!(!preview.deleted);
+        assert_eq!(
+            (
+                preview.docket_count,
+                preview.filling_count,
+                preview.attachment_count
+            ),
+            (1, 1, 1)
+        );
+        request.confirmation_token = Some("not-the-token".to_string());
+        let wrong_token = delete_dockets_by_daterange(&pg_schema, &request, &pool)
+            .await
+            .unwrap();
+        assert!(!wrong_token.deleted);
+        assert_eq!(remaining_govids().await, vec!["JAN-1", "MAR-1", "MAY-1"]);
+
+        request.confirmation_token = Some(preview.confirmation_token);
+        let deletion = delete_dockets_by_daterange(&pg_schema, &request, &pool)
+            .await
+            .unwrap();
+        assert!(deletion.deleted);
+        assert_eq!(deletion.docket_count, 1);
+        assert_eq!(remaining_govids().await, vec!["JAN-1", "MAY-1"]);
+        let orphaned_fillings =
+            sqlx::query_scalar::<_, i64>(&format!("SELECT count(*) FROM {pg_schema}.fillings"))
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(orphaned_fillings, 2);
+    }
+
+    #[tokio::test]
+    async fn test_docket_summary_matches_seeded_docket_without_fillings() {
+        let pool = setup_test_db().await;
+        let fixed_jur = FixedJurisdiction::NewYorkPuc;
+        let pg_schema = format!("summary_{}", &Uuid::new_v4().simple().to_string()[..8]);
+        create_target_schema(&pg_schema, &pool).await.unwrap();
+        let opened_date = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let filing = make_processed_filing("1", opened_date, vec![]);
+        let mut docket = make_processed_docket("SUMMARY-1", opened_date, vec![filing]);
+        docket.case_name = "Summary Test Docket".to_string();
+        docket.case_type = "Complaint".to_string();
+        docket.closed_date = NaiveDate::from_ymd_opt(2024, 5, 6);
+        ingest_sql_case_with_retries(
+            &mut docket,
+            fixed_jur,
+            Some(&pg_schema),
+            &pool,
+            &OrgUuidCache::default(),
+            false,
+            1,
+        )
+        .await
+        .unwrap();
+        // The summary has to come from the dockets table alone.
+        sqlx::query(&format!("DROP TABLE {pg_schema}.fillings CASCADE"))
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let pagination = PaginationQuery {
+            offset: 0,
+            limit: 10,
+        };
+        let summaries = list_docket_summaries(&pg_schema, &pagination, &pool)
+            .await
+            .unwrap();
+        assert_eq!(summaries.len(), 1);
+        let summary = &summaries[0];
+        assert_eq!(summary.docket_govid, "SUMMARY-1");
+        assert_eq!(summary.docket_title, "Summary Test Docket");
+        assert_eq!(summary.docket_type, "Complaint");
+        assert_eq!(summary.opened_date, opened_date);
+        assert_eq!(summary.closed_date, docket.closed_date);
+
+        sqlx::query(&format!("DROP SCHEMA {pg_schema} CASCADE"))
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_filing_type_histogram_counts_each_type() {
+        let pool = setup_test_db().await;
+        let fixed_jur = FixedJurisdiction::NewYorkPuc;
+        let pg_schema = format!("histogram_{}", &Uuid::new_v4().simple().to_string()[..8]);
+        create_target_schema(&pg_schema, &pool).await.unwrap();
+        let date = |month| NaiveDate::from_ymd_opt(2024, month, 10).unwrap();
+        let filings = [
+            ("1", "Comments", date(1)),
+            ("2", "Motion", date(1)),
+            ("3", "Comments", date(2)),
+            ("4", "Order", date(3)),
+            ("5", "Comments", date(3)),
+        ]
+        .map(|(filling_govid, filing_type, filed_date)| {
+            let mut filing = make_processed_filing(filling_govid, filed_date, vec![]);
+            filing.filing_type = filing_type.to_string();
+            filing
+        });
+        let mut docket = make_processed_docket("HISTOGRAM-1", date(1), filings.to_vec());
+        ingest_sql_case_with_retries(
+            &mut docket,
+            fixed_jur,
+            Some(&pg_schema),
+            &pool,
+            &OrgUuidCache::default(),
+            false,
+            1,
+        )
+        .await
+        .unwrap();
+
+        let count = |filling_type: &str, count| FilingTypeCount {
+            filling_type: filling_type.to_string(),
+            count,
+        };
+        let all_time = FilingTypeHistogramQuery {
+            start_date: None,
+            end_date: None,
+        };
+        assert_eq!(
+            filing_type_histogram(&pg_schema, &all_time, &pool)
+                .await
+                .unwrap(),
+            vec![count("Comments", 3), count("Motion", 1), count("Order", 1)]
+        );
+        let first_two_months = FilingTypeHistogramQuery {
+            start_date: Some(date(1)),
+            end_date: Some(date(2)),
+        };
+        assert_eq!(
+            filing_type_histogram(&pg_schema, &first_two_months, &pool)
+                .await
+                .unwrap(),
+            vec![count("Comments", 2), count("Motion", 1)]
+        );
+
+        sqlx::query(&format!("DROP SCHEMA {pg_schema} CASCADE"))
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_process_one_writes_processed_docket_to_s3() {
+        let s3_client = make_s3_client().await;
+        let fixed_jur = FixedJurisdiction::NewYorkPuc;
+        let govid = format!("PROCESS-ONE-{}", Uuid::new_v4());
+        let addr = DocketAddress {
+            docket_govid: govid.clone(),
+            jurisdiction: JurisdictionInfo::from(fixed_jur),
+        };
+        upload_object(&s3_client, &addr, &make_raw_docket(&govid, vec![]))
+            .await
+            .unwrap();
+
+        let returned = process_one_raw_docket(s3_client.clone(), fixed_jur, &govid)
+            .await
+            .unwrap();
+        let stored = download_openscrapers_object::<ProcessedGenericDocket>(&s3_client, &addr)
+            .await
+            .unwrap();
+        assert_eq!(stored.case_govid.as_str(), govid);
+        assert_eq!(stored.object_uuid, returned.object_uuid);
+
+        delete_openscrapers_s3_object::<RawGenericDocket>(&s3_client, &addr)
+            .await
+            .unwrap();
+        delete_openscrapers_s3_object::<ProcessedGenericDocket>(&s3_client, &addr)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reprocess_diff_leaves_new_authors_out_of_postgres() {
+        let s3_client = make_s3_client().await;
+        let pool = setup_test_db().await;
+        let fixed_jur = FixedJurisdiction::NewYorkPuc;
+        let pg_schema = fixed_jur.get_postgres_schema_name();
+        let govid = format!("REPROCESS-DIFF-{}", Uuid::new_v4());
+        let org_name = format!("Preview Only Power {}", Uuid::new_v4().simple());
+        let addr = DocketAddress {
+            docket_govid: govid.clone(),
+            jurisdiction: JurisdictionInfo::from(fixed_jur),
+        };
+        let mut raw_filling = make_raw_filing("1", "2024-01-02", vec![]);
+        raw_filling.organization_authors = vec![org_name.clone()];
+        upload_object(
+            &s3_client,
+            &addr,
+            &make_raw_docket(&govid, vec![raw_filling]),
+        )
+        .await
+        .unwrap();
+        let opened_date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        upload_object(
+            &s3_client,
+            &addr,
+            &make_processed_docket(&govid, opened_date, vec![]),
+        )
+        .await
+        .unwrap();
+
+        let diff = reprocess_docket_diff(&s3_client, fixed_jur, &govid).await;
+        let org_count: i64 = sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM {pg_schema}.organizations WHERE name = $1"
+        ))
+        .bind(&org_name)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        delete_openscrapers_s3_object::<RawGenericDocket>(&s3_client, &addr)
+            .await
+            .unwrap();
+        delete_openscrapers_s3_object::<ProcessedGenericDocket>(&s3_client, &addr)
+            .await
+            .unwrap();
+        let diff = diff.unwrap();
+        assert_eq!(diff.added_fillings, vec!["1"]);
+        assert_eq!(org_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_restore_ndjson_ingests_each_line() {
+        let pool = setup_test_db().await;
+        let fixed_jur = FixedJurisdiction::NewYorkPuc;
+        let pg_schema = fixed_jur.get_postgres_schema_name();
+        let opened_date = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let govids = [
+            format!("RESTORE-{}", Uuid::new_v4()),
+            format!("RESTORE-{}", Uuid::new_v4()),
+        ];
+        let mut ndjson = String::new();
+        for govid in &govids {
+            let docket = make_processed_docket(govid, opened_date, vec![]);
+            ndjson.push_str(&serde_json::to_string(&docket).unwrap());
+            ndjson.push('\n');
+        }
+        ndjson.push_str("{not a docket}\n");
+
+        let limits = NdjsonLimits {
+            max_body_bytes: ndjson.len(),
+            max_line_bytes: ndjson.len(),
+        };
+        let lines = ndjson_lines(make_chunked_body(&ndjson, 64), limits);
+        let report = restore_processed_dockets_from_ndjson(lines, fixed_jur, &pool).await;
+        assert_eq!(report.ingested, 2);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].line, 3);
+
+        for govid in &govids {
+            let count: i64 = sqlx::query_scalar(&format!(
+                "SELECT COUNT(*) FROM {pg_schema}.dockets WHERE docket_govid = $1"
+            ))
+            .bind(govid)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+            assert_eq!(count, 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restore_streams_uploads_larger_than_line_limit() {
+        let pool = setup_test_db().await;
+        let fixed_jur = FixedJurisdiction::NewYorkPuc;
+        let pg_schema = fixed_jur.get_postgres_schema_name();
+        let opened_date = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        let govids = (0..20)
+            .map(|_| format!("RESTORE-STREAM-{}", Uuid::new_v4()))
+            .collect::<Vec<_>>();
+        let lines = govids
+            .iter()
+            .map(|govid| {
+                serde_json::to_string(&make_processed_docket(govid, opened_date, vec![])).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let ndjson = lines.join("\n");
+        // Only about one docket fits in memory at once, far less than the whole upload.
+        let max_line_bytes = lines.iter().map(String::len).max().unwrap();
+        assert!(ndjson.len() > 10 * max_line_bytes);
+        let limits = NdjsonLimits {
+            max_body_bytes: ndjson.len(),
+            max_line_bytes,
+        };
+
+        let lines = ndjson_lines(make_chunked_body(&ndjson, 100), limits);
+        let report = restore_processed_dockets_from_ndjson(lines, fixed_jur, &pool).await;
+        assert_eq!(report.ingested, govids.len());
+        assert!(report.failed.is_empty());
+
+        let restored: i64 = sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM {pg_schema}.dockets WHERE docket_govid = ANY($1)"
+        ))
+        .bind(&govids)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(restored, govids.len() as i64);
+    }
+
+    #[test]
+    fn test_only_mismatched_attachment_counts_are_reported() {
+        let filed_date = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let raw_with_two_attachments = |govid: &str| {
+            make_raw_docket(
+                govid,
+                vec![make_raw_filing(
+                    "1",
+                    "2024-03-04",
+                    vec![make_raw_attachment("A", ""), make_raw_attachment("B", "")],
+                )],
+            )
+        };
+        let processed_with_attachments = |govid: &str, count: usize| {
+            let attachments = (0..count)
+                .map(|i| make_processed_attachment(&i.to_string(), ""))
+                .collect();
+            make_processed_docket(
+                govid,
+                filed_date,
+                vec![make_processed_filing("1", filed_date, attachments)],
+            )
+        };
+
+        let matching = compare_attachment_counts(
+            &raw_with_two_attachments("MATCHING"),
+            &processed_with_attachments("MATCHING", 2),
+        );
+        assert!(matching.is_none());
+
+        let mismatch = compare_attachment_counts(
+            &raw_with_two_attachments("MISMATCHED"),
+            &processed_with_attachments("MISMATCHED", 1),
+        )
+        .expect("dropped attachment should be reported");
+        assert_eq!(mismatch.docket_govid, "MISMATCHED");
+        assert_eq!(mismatch.raw_attachment_count, 2);
+        assert_eq!(mismatch.processed_attachment_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_docket_attachments_paginates() {
+        let pool = setup_test_db().await;
+        let fixed_jur = FixedJurisdiction::NewYorkPuc;
+        let filed_date = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let docket_govid = format!("ATTACH-LIST-{}", Uuid::new_v4());
+        let mut docket = make_processed_docket(
+            &docket_govid,
+            filed_date,
+            vec![
+                make_processed_filing(
+                    "F-1",
+                    filed_date,
+                    vec![
+                        make_processed_attachment("a", "https://example.com/a.pdf"),
+                        make_processed_attachment("b", "https://example.com/b.pdf"),
+                    ],
+                ),
+                make_processed_filing(
+                    "F-2",
+                    filed_date.succ_opt().unwrap(),
+                    vec![make_processed_attachment("c", "https://example.com/c.pdf")],
+                ),
+            ],
+        );
+        ingest_sql_fixed_jurisdiction_case(&mut docket, fixed_jur, &pool, false)
+            .await
+            .unwrap();
+
+        let first_page = list_docket_attachments(
+            &docket_govid,
+            &PaginationQuery {
+                offset: 0,
+                limit: 2,
+            },
+            fixed_jur,
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert_eq!(first_page.total, 3);
+        let first_names = first_page
+            .attachments
+            .iter()
+            .map(|a| (a.attachment_file_name.as_str(), a.filling_govid.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(first_names, vec![("a", "F-1"), ("b", "F-1")]);
+
+        let second_page = list_docket_attachments(
+            &docket_govid,
+            &PaginationQuery {
+                offset: 2,
+                limit: 2,
+            },
+            fixed_jur,
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert_eq!(second_page.attachments.len(), 1);
+        assert_eq!(second_page.attachments[0].attachment_file_name, "c");
+        assert_eq!(
+            second_page.attachments[0].attachment_url,
+            "https://example.com/c.pdf"
+        );
+        assert_eq!(second_page.attachments[0].filling_govid, "F-2");
+    }
+
+    #[tokio::test]
+    async fn test_refresh_docket_hashes_backfills_and_persists() {
+        let pool = setup_test_db().await;
+        let fixed_jur = FixedJurisdiction::NewYorkPuc;
+        let pg_schema = fixed_jur.get_postgres_schema_name();
+        let filed_date = NaiveDate::from_ymd_opt(2024, 8, 9).unwrap();
+        let missing_url = format!("https://example.com/{}.pdf", Uuid::new_v4());
+        let hashed_url = format!("https://example.com/{}.pdf", Uuid::new_v4());
+        let mut hashed_attachment = make_processed_attachment("Exhibit A", &hashed_url);
+        hashed_attachment.hash = Some(make_attachment_metadata(&hashed_url).hash);
+        let mut docket = make_processed_docket(
+            &format!("TEST-{}", Uuid::new_v4()),
+            filed_date,
+            vec![make_processed_filing(
+                "1",
+                filed_date,
+                vec![
+                    make_processed_attachment("Comments", &missing_url),
+                    hashed_attachment,
+                ],
+            )],
+        );
+        ingest_sql_fixed_jurisdiction_case(&mut docket, fixed_jur, &pool, false)
+            .await
+            .unwrap();
+
+        let refresh = refresh_docket_hashes(
+            &mut docket,
+            fixed_jur,
+            &pool,
+            async |attachment: &ProcessedGenericAttachment| {
+                Some(make_attachment_metadata(&attachment.url))
+            },
+        )
+        .await
+        .unwrap();
+        let expected_hash = make_attachment_metadata(&missing_url).hash.to_string();
+        assert_eq!(refresh.refreshed.len(), 1);
+        assert_eq!(refresh.refreshed[0].url, missing_url);
+        assert_eq!(refresh.refreshed[0].old_hash, None);
+        assert_eq!(refresh.refreshed[0].new_hash, expected_hash);
+
+        let stored_hash: Option<String> = sqlx::query_scalar(&format!(
+            "SELECT document_hash FROM {pg_schema}.attachments WHERE uuid = $1"
+        ))
+        .bind(refresh.refreshed[0].attachment_uuid)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(stored_hash, Some(expected_hash));
+    }
+
+    struct IndexingMockFetcher {
+        fetched_urls: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl AttachmentUrlFetcher for IndexingMockFetcher {
+        async fn fetch_and_store(&self, url: &str) -> anyhow::Result<RawAttachment> {
+            self.fetched_urls.lock().unwrap().push(url.to_string());
+            Ok(make_attachment_metadata(url))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_docket_hashes_fetches_urls_missing_from_the_index() {
+        let pool = setup_test_db().await;
+        let fixed_jur = FixedJurisdiction::NewYorkPuc;
+        let filed_date = NaiveDate::from_ymd_opt(2024, 8, 9).unwrap();
+        let never_scraped_url = format!("https://example.com/{}.pdf", Uuid::new_v4());
+        let mut docket = make_processed_docket(
+            &format!("TEST-{}", Uuid::new_v4()),
+            filed_date,
+            vec![make_processed_filing(
+                "1",
+                filed_date,
+                vec![make_processed_attachment("Comments", &never_scraped_url)],
+            )],
+        );
+        ingest_sql_fixed_jurisdiction_case(&mut docket, fixed_jur, &pool, false)
+            .await
+            .unwrap();
+        let index = RwLock::new(AttachIndex::new());
+        let hash_index = RwLock::new(HashUrlIndex::new());
+        let in_flight = InFlightFetches::default();
+        let fetcher = IndexingMockFetcher {
+            fetched_urls: Default::default(),
+        };
+
+        let refresh = refresh_docket_hashes(
+            &mut docket,
+            fixed_jur,
+            &pool,
+            async |attachment: &ProcessedGenericAttachment| {
+                lookup_or_fetch_in_index(
+                    &index,
+                    &hash_index,
+                    &in_flight,
+                    &attachment.url,
+                    &fetcher,
+                    true,
+                )
+                .await
+                .unwrap()
+            },
+        )
+        .await
+        .unwrap();
+
+        let expected_hash = make_attachment_metadata(&never_scraped_url).hash;
+        assert_eq!(
+            *fetcher.fetched_urls.lock().unwrap(),
+            vec![never_scraped_url.clone()]
+        );
+        assert_eq!(refresh.refreshed.len(), 1);
+        assert_eq!(refresh.refreshed[0].new_hash, expected_hash.to_string());
+        assert_eq!(
+            docket.filings[0].attachments[0]
+                .hash
+                .map(|hash| hash.to_string()),
+            Some(expected_hash.to_string())
+        );
+        assert!(index.read().await.contains_key(&never_scraped_url));
+    }
+}
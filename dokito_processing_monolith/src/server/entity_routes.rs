@@ -0,0 +1,249 @@
+use futures_util::{Stream, StreamExt, TryStreamExt, stream};
+use schemars::JsonSchema;
+use serde::Serialize;
+use sqlx::{FromRow, PgPool, postgres::PgRow};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    server::{jurisdiction_extractor::ValidJurisdiction, ndjson::NdjsonResponse},
+    sql_ingester_tasks::dokito_sql_connection::get_dokito_pool_for,
+};
+
+/// How many rows of an entity table get loaded at once while exporting it.
+const ENTITY_EXPORT_PAGE_SIZE: i64 = 1000;
+
+#[derive(Serialize, FromRow, JsonSchema, Debug)]
+pub struct OrganizationExport {
+    pub uuid: Uuid,
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub org_suffix: String,
+    pub artifical_person_type: String,
+    pub description: String,
+}
+
+#[derive(Serialize, FromRow, JsonSchema, Debug)]
+pub struct HumanExport {
+    pub uuid: Uuid,
+    pub name: String,
+    pub western_first_name: String,
+    pub western_last_name: String,
+    /// Every email merged into the human across the dockets it was on, without blanks.
+    pub contact_emails: Vec<String>,
+    pub contact_phone_numbers: Vec<String>,
+}
+
+/// Streams the rows of an entity table a page at a time in uuid order, so the table is never held
+/// in memory. A page that fails to load ends the stream with its error.
+fn stream_entity_rows<T>(
+    select: String,
+    pool: PgPool,
+    page_size: i64,
+    uuid_of: fn(&T) -> Uuid,
+) -> impl Stream<Item = Result<T, sqlx::Error>> + Send + 'static
+where
+    T: for<'r> FromRow<'r, PgRow> + Send + Unpin + 'static,
+{
+    stream::unfold(Some(Uuid::nil()), move |after| {
+        let (select, pool) = (select.clone(), pool.clone());
+        async move {
+            let page = sqlx::query_as::<_, T>(&format!(
+                "{select} WHERE uuid > $1 ORDER BY uuid LIMIT $2"
+            ))
+            .bind(after?)
+            .bind(page_size)
+            .fetch_all(&pool)
+            .await;
+            match page {
+                Ok(page) if page.is_empty() => None,
+                Ok(page) => {
+                    let next_after =
+                        (page.len() as i64 == page_size).then(|| uuid_of(&page[page.len() - 1]));
+                    Some((page.into_iter().map(Ok).collect(), next_after))
+                }
+                Err(err) => {
+                    warn!(error = %err, "Failed to load a page of entities, ending the export early");
+                    Some((vec![Err(err)], None))
+                }
+            }
+        }
+    })
+    .flat_map(stream::iter)
+}
+
+fn organization_rows(
+    pg_schema: &str,
+    pool: PgPool,
+    page_size: i64,
+) -> impl Stream<Item = Result<OrganizationExport, sqlx::Error>> + Send + 'static {
+    stream_entity_rows(
+        format!(
+            "SELECT uuid, name, aliases, org_suffix, artifical_person_type, description FROM {pg_schema}.organizations"
+        ),
+        pool,
+        page_size,
+        |org: &OrganizationExport| org.uuid,
+    )
+}
+
+fn human_rows(
+    pg_schema: &str,
+    pool: PgPool,
+    page_size: i64,
+) -> impl Stream<Item = Result<HumanExport, sqlx::Error>> + Send + 'static {
+    stream_entity_rows(
+        format!(
+            "SELECT uuid, name, western_first_name, western_last_name, contact_emails, contact_phone_numbers FROM {pg_schema}.humans"
+        ),
+        pool,
+        page_size,
+        |human: &HumanExport| human.uuid,
+    )
+    .map_ok(|mut human| {
+        // Scraped parties without contact details still got a blank one stored.
+        human.contact_emails.retain(|email| !email.trim().is_empty());
+        human
+            .contact_phone_numbers
+            .retain(|phone| !phone.trim().is_empty());
+        human
+    })
+}
+
+/// Streams every organization of a jurisdiction's schema as NDJSON, for entity resolution.
+pub async fn handle_export_entities(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+) -> Result<NdjsonResponse, String> {
+    let pool = get_dokito_pool_for(fixed_jur)
+        .await
+        .map_err(|e| e.to_string())?;
+    let pg_schema = fixed_jur.get_postgres_schema_name();
+    info!(%pg_schema, "Exporting organizations as NDJSON");
+    Ok(NdjsonResponse::from_fallible_stream(organization_rows(
+        pg_schema,
+        pool.clone(),
+        ENTITY_EXPORT_PAGE_SIZE,
+    )))
+}
+
+/// Streams every human of a jurisdiction's schema as NDJSON, with their merged contacts.
+pub async fn handle_export_humans(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+) -> Result<NdjsonResponse, String> {
+    let pool = get_dokito_pool_for(fixed_jur)
+        .await
+        .map_err(|e| e.to_string())?;
+    let pg_schema = fixed_jur.get_postgres_schema_name();
+    info!(%pg_schema, "Exporting humans as NDJSON");
+    Ok(NdjsonResponse::from_fallible_stream(human_rows(
+        pg_schema,
+        pool.clone(),
+        ENTITY_EXPORT_PAGE_SIZE,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql_ingester_tasks::recreate_dokito_table_schema::create_target_schema;
+    use crate::test_fixtures::setup_test_db;
+    use axum::response::IntoResponse;
+    use serde_json::Value;
+
+    async fn exported_lines(response: NdjsonResponse) -> Vec<Value> {
+        let body = axum::body::to_bytes(response.into_response().into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_entity_export_pages_through_every_row() {
+        let pool = setup_test_db().await;
+        let pg_schema = format!("entities_{}", &Uuid::new_v4().simple().to_string()[..8]);
+        create_target_schema(&pg_schema, &pool).await.unwrap();
+        for (name, suffix) in [
+            ("Example Power", "corp"),
+            ("Sample Gas", "co"),
+            ("Grid", ""),
+        ] {
+            sqlx::query(&format!(
+                "INSERT INTO {pg_schema}.organizations (name, aliases, artifical_person_type, org_suffix) VALUES ($1, $2, 'organization', $3)"
+            ))
+            .bind(name)
+            .bind(vec![format!("{name} {suffix}")])
+            .bind(suffix)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        sqlx::query(&format!(
+            "INSERT INTO {pg_schema}.humans (name, western_first_name, western_last_name, contact_emails, contact_phone_numbers) VALUES ('Jane Doe', 'Jane', 'Doe', $1, $2)"
+        ))
+        .bind(vec!["jane@example.com", "", "jdoe@example.com"])
+        .bind(vec![""])
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // A page size smaller than the table, so the export has to continue past the first page.
+        let organizations = exported_lines(NdjsonResponse::from_fallible_stream(
+            organization_rows(&pg_schema, pool.clone(), 2),
+        ))
+        .await;
+        assert_eq!(organizations.len(), 3);
+        let example_power = organizations
+            .iter()
+            .find(|org| org["name"] == "Example Power")
+            .unwrap();
+        assert_eq!(example_power["org_suffix"], "corp");
+        assert_eq!(example_power["artifical_person_type"], "organization");
+        assert_eq!(example_power["aliases"][0], "Example Power corp");
+
+        let humans = exported_lines(NdjsonResponse::from_fallible_stream(human_rows(
+            &pg_schema,
+            pool.clone(),
+            2,
+        )))
+        .await;
+        assert_eq!(humans.len(), 1);
+        assert_eq!(humans[0]["western_last_name"], "Doe");
+        assert_eq!(
+            humans[0]["contact_emails"],
+            serde_json::json!(["jane@example.com", "jdoe@example.com"])
+        );
+        assert_eq!(humans[0]["contact_phone_numbers"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_entity_export_ends_with_an_error_when_a_page_fails() {
+        let pool = setup_test_db().await;
+        let pg_schema = format!("entities_{}", &Uuid::new_v4().simple().to_string()[..8]);
+        create_target_schema(&pg_schema, &pool).await.unwrap();
+        for name in ["Example Power", "Sample Gas", "Grid"] {
+            sqlx::query(&format!(
+                "INSERT INTO {pg_schema}.organizations (name, aliases, artifical_person_type, org_suffix) VALUES ($1, '{{}}', 'organization', '')"
+            ))
+            .bind(name)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        let mut organizations = Box::pin(organization_rows(&pg_schema, pool.clone(), 2));
+        assert!(organizations.next().await.unwrap().is_ok());
+        assert!(organizations.next().await.unwrap().is_ok());
+        // The second page can't load anymore.
+        sqlx::query(&format!("DROP SCHEMA {pg_schema} CASCADE"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        let response = NdjsonResponse::from_fallible_stream(organizations).into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await;
+        assert!(body.is_err());
+    }
+}
@@ -1,13 +1,48 @@
+use std::sync::LazyLock;
+
 use aide::axum::{ApiRouter, routing::get};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
 use mycorrhiza_common::llm_deepinfra::test_deepinfra;
 
+use crate::processing::llm_prompts::{
+    LLM_ORG_SPLIT_DURATION_SECONDS, ORG_NAME_CLEANUP_DURATION_SECONDS,
+};
+use crate::throughput::handle_throughput_stats;
+
+const LLM_LATENCY_BUCKETS: &[f64] = &[0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+const ORG_NAME_CLEANUP_BUCKETS: &[f64] = &[0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1];
+
+/// Installs the global prometheus recorder the first time it is used, anything recorded through
+/// the `metrics` macros before that is dropped.
+pub static PROMETHEUS_HANDLE: LazyLock<PrometheusHandle> = LazyLock::new(|| {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(LLM_ORG_SPLIT_DURATION_SECONDS.to_string()),
+            LLM_LATENCY_BUCKETS,
+        )
+        .expect("LLM latency buckets should not be empty")
+        .set_buckets_for_metric(
+            Matcher::Full(ORG_NAME_CLEANUP_DURATION_SECONDS.to_string()),
+            ORG_NAME_CLEANUP_BUCKETS,
+        )
+        .expect("Organization name cleanup buckets should not be empty")
+        .install_recorder()
+        .expect("Failed to install the prometheus metrics recorder")
+});
+
 async fn return_healthy() -> &'static str {
     "service is healthy"
 }
 
+async fn render_metrics() -> String {
+    PROMETHEUS_HANDLE.render()
+}
+
 pub fn create_health_and_test_router() -> ApiRouter {
     ApiRouter::new()
         .api_route("/", get(return_healthy))
         .api_route("/health", get(return_healthy))
+        .api_route("/metrics", get(render_metrics))
+        .api_route("/stats/throughput", get(handle_throughput_stats))
         .api_route("/test", get(test_deepinfra))
 }
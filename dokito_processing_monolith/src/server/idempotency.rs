@@ -0,0 +1,173 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    env,
+    future::Future,
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use aide::OperationInput;
+use axum::{extract::FromRequestParts, http::request::Parts};
+use tokio::sync::OnceCell;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Longer keys are ignored rather than kept around in memory.
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 256;
+
+/// How long a finished request is remembered under its idempotency key.
+pub static IDEMPOTENCY_KEY_TTL_SECS: LazyLock<u64> = LazyLock::new(|| {
+    env::var("IDEMPOTENCY_KEY_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&v| v > 0)
+        .unwrap_or(600)
+});
+
+/// The `Idempotency-Key` header, when the caller sent one.
+#[derive(Clone, Debug)]
+pub struct IdempotencyKey(pub Option<String>);
+
+impl<S: Send + Sync> FromRequestParts<S> for IdempotencyKey {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let key = parts
+            .headers
+            .get(IDEMPOTENCY_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|key| !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LEN);
+        Ok(IdempotencyKey(key.map(str::to_string)))
+    }
+}
+
+impl OperationInput for IdempotencyKey {}
+
+struct IdempotentEntry<T> {
+    created: Instant,
+    result: Arc<OnceCell<T>>,
+}
+
+/// Remembers the successful result of a request under its idempotency key for a while, so a
+/// resubmission gets the earlier result back instead of doing the work again. A resubmission
+/// that arrives while the first one is still running waits for it. Failures aren't remembered,
+/// the next submission with the key runs again. The work outlives the request that started it,
+/// so a client that times out and retries gets the first run's result.
+pub struct IdempotencyCache<T> {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, IdempotentEntry<T>>>,
+}
+
+impl<T: Clone + Send + Sync + 'static> IdempotencyCache<T> {
+    pub fn new(ttl: Duration) -> Self {
+        IdempotencyCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn result_cell(&self, key: &str) -> Arc<OnceCell<T>> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| entry.created.elapsed() < self.ttl);
+        entries
+            .entry(key.to_string())
+            .or_insert_with(|| IdempotentEntry {
+                created: Instant::now(),
+                result: Arc::new(OnceCell::new()),
+            })
+            .result
+            .clone()
+    }
+
+    /// Runs `work` unless a result is already remembered under `key`. Without a key the work
+    /// always runs. With one it runs on its own task, so dropping the returned future, like hyper
+    /// does when the client disconnects, doesn't cancel it halfway through.
+    pub async fn run<E: Send + 'static>(
+        &self,
+        key: Option<&str>,
+        work: impl Future<Output = Result<T, E>> + Send + 'static,
+    ) -> Result<T, E> {
+        let Some(key) = key else {
+            return work.await;
+        };
+        let result = self.result_cell(key);
+        tokio::spawn(async move { result.get_or_try_init(|| work).await.cloned() })
+            .await
+            .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::oneshot;
+
+    #[tokio::test]
+    async fn test_resubmission_with_same_key_runs_once() {
+        let cache = IdempotencyCache::new(Duration::from_secs(60));
+        let runs = Arc::new(AtomicUsize::new(0));
+        let submit = async |key| {
+            let runs = runs.clone();
+            cache
+                .run(key, async move {
+                    let run = runs.fetch_add(1, Ordering::SeqCst) + 1;
+                    Ok::<_, String>(format!("run {run}"))
+                })
+                .await
+                .unwrap()
+        };
+
+        assert_eq!(submit(Some("batch-1")).await, "run 1");
+        assert_eq!(submit(Some("batch-1")).await, "run 1");
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        assert_eq!(submit(Some("batch-2")).await, "run 2");
+        assert_eq!(submit(None).await, "run 3");
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_failed_submission_is_not_remembered() {
+        let cache = IdempotencyCache::new(Duration::from_secs(60));
+        let failed = cache
+            .run(Some("batch-1"), async { Err::<String, _>("ingest failed") })
+            .await;
+        assert!(failed.is_err());
+        let retried = cache
+            .run(Some("batch-1"), async {
+                Ok::<_, &str>("ingested".to_string())
+            })
+            .await;
+        assert_eq!(retried.unwrap(), "ingested");
+    }
+
+    #[tokio::test]
+    async fn test_dropped_request_still_finishes_for_the_resubmission() {
+        let cache = IdempotencyCache::new(Duration::from_secs(60));
+        let runs = Arc::new(AtomicUsize::new(0));
+        let (release, released) = oneshot::channel::<()>();
+        let first_runs = runs.clone();
+        let first = cache.run(Some("batch-1"), async move {
+            released.await.unwrap();
+            let run = first_runs.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok::<_, String>(format!("run {run}"))
+        });
+        // The client gives up while the batch is still running, which drops the request future.
+        let timed_out = tokio::time::timeout(Duration::from_millis(50), first).await;
+        assert!(timed_out.is_err());
+        release.send(()).unwrap();
+
+        let second_runs = runs.clone();
+        let resubmitted = cache
+            .run(Some("batch-1"), async move {
+                let run = second_runs.fetch_add(1, Ordering::SeqCst) + 1;
+                Ok::<_, String>(format!("run {run}"))
+            })
+            .await;
+        assert_eq!(resubmitted.unwrap(), "run 1");
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+}
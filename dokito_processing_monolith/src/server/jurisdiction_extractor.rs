@@ -0,0 +1,119 @@
+use aide::{OperationInput, generate::GenContext, openapi::Operation};
+use axum::{
+    extract::{FromRequestParts, Path},
+    http::{StatusCode, request::Parts},
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::{
+    jurisdiction_schema_mapping::FixedJurisdiction, server::s3_routes::JurisdictionPath,
+    types::jurisdictions::JurisdictionInfo,
+};
+
+/// Extracts the `{state}/{jurisdiction_name}` path params as a [`FixedJurisdiction`], rejecting
+/// jurisdictions we don't store with a 400 before the handler runs.
+pub struct ValidJurisdiction(pub FixedJurisdiction);
+
+impl<S: Send + Sync> FromRequestParts<S> for ValidJurisdiction {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(JurisdictionPath {
+            state: us_state,
+            jurisdiction_name,
+        }) = Path::<JurisdictionPath>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.body_text()))?;
+        let jurisdiction_info = JurisdictionInfo::new_usa(&jurisdiction_name, &us_state);
+        FixedJurisdiction::try_from(&jurisdiction_info)
+            .map(ValidJurisdiction)
+            .map_err(|e| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("{e}: {us_state}/{jurisdiction_name}"),
+                )
+            })
+    }
+}
+
+/// The docket part of a `{state}/{jurisdiction_name}/{docket_govid}` path, for handlers that take
+/// the jurisdiction through [`ValidJurisdiction`].
+#[derive(Deserialize, JsonSchema)]
+pub struct DocketGovidPath {
+    /// The name of the case.
+    pub docket_govid: String,
+}
+
+impl OperationInput for ValidJurisdiction {
+    fn operation_input(ctx: &mut GenContext, operation: &mut Operation) {
+        Path::<JurisdictionPath>::operation_input(ctx, operation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, http::Request, routing::get};
+    use tower::ServiceExt;
+
+    fn schema_name_router() -> Router {
+        Router::new().route(
+            "/{state}/{jurisdiction_name}",
+            get(async |ValidJurisdiction(fixed_jur): ValidJurisdiction| {
+                fixed_jur.get_postgres_schema_name()
+            }),
+        )
+    }
+
+    async fn get_status_and_body(uri: &str) -> (StatusCode, String) {
+        let response = schema_name_router()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_known_jurisdiction_extracts() {
+        let (status, body) = get_status_and_body("/ny/ny_puc").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "ny_puc_data");
+    }
+
+    #[tokio::test]
+    async fn test_docket_path_extracts_next_to_jurisdiction() {
+        let router = Router::new().route(
+            "/{state}/{jurisdiction_name}/{docket_govid}",
+            get(
+                async |ValidJurisdiction(fixed_jur): ValidJurisdiction,
+                       Path(DocketGovidPath { docket_govid }): Path<DocketGovidPath>| {
+                    format!("{}:{docket_govid}", fixed_jur.get_jurisdiction_info_name())
+                },
+            ),
+        );
+        let response = router
+            .oneshot(
+                Request::get("/ny/ny_puc/24-E-0001")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, "ny_puc:24-E-0001");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_jurisdiction_is_bad_request() {
+        let (status, _) = get_status_and_body("/ny/not_a_puc").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
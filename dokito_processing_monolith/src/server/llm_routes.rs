@@ -0,0 +1,30 @@
+use axum::response::Json;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::processing::llm_prompts::LLM_KILL_SWITCH;
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct LlmKillSwitchState {
+    /// While on, every LLM call is skipped in favour of its naive fallback.
+    pub enabled: bool,
+}
+
+pub async fn handle_get_llm_kill_switch() -> Json<LlmKillSwitchState> {
+    Json(LlmKillSwitchState {
+        enabled: LLM_KILL_SWITCH.is_on(),
+    })
+}
+
+/// Flips the LLM kill-switch for this process until the next restart, which goes back to
+/// `DISABLE_LLM_CALLS`.
+pub async fn handle_set_llm_kill_switch(
+    Json(LlmKillSwitchState { enabled }): Json<LlmKillSwitchState>,
+) -> Json<LlmKillSwitchState> {
+    warn!(enabled, "Setting the LLM kill-switch");
+    LLM_KILL_SWITCH.set(enabled);
+    Json(LlmKillSwitchState {
+        enabled: LLM_KILL_SWITCH.is_on(),
+    })
+}
@@ -6,14 +6,22 @@ use tracing::info;
 use crate::sql_ingester_tasks::add_sql_ingest_task_routes;
 
 pub mod direct_file_fetch;
+pub mod docket_routes;
+pub mod entity_routes;
+pub mod jurisdiction_extractor;
+pub mod llm_routes;
+pub mod ndjson;
 pub mod queue_routes;
 pub mod reprocess_all_handlers;
 pub mod s3_routes;
+pub mod schema_routes;
 pub mod scraper_check_completed;
 pub mod temporary_routes;
 
 pub mod admin_routes;
+pub mod correlation_id;
 pub mod health_routes;
+pub mod idempotency;
 pub mod public_routes;
 
 static PUBLIC_SAFE_MODE: LazyLock<bool> = LazyLock::new(|| is_env_var_true("PUBLIC_SAFE_MODE"));
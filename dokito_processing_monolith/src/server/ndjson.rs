@@ -0,0 +1,279 @@
+use std::{convert::Infallible, env, fmt::Display, pin::Pin, sync::LazyLock};
+
+use aide::{OperationInput, OperationOutput};
+use axum::{
+    BoxError,
+    body::{Body, Bytes},
+    extract::{FromRequest, Request},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use futures_util::{Stream, StreamExt, stream};
+use serde::Serialize;
+use thiserror::Error;
+
+/// Largest NDJSON upload accepted, counted as it streams in since it is never held in memory.
+pub static NDJSON_MAX_BODY_BYTES: LazyLock<usize> = LazyLock::new(|| {
+    env::var("NDJSON_MAX_BODY_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&v| v > 0)
+        .unwrap_or(4 * 1024 * 1024 * 1024)
+});
+
+/// Longest single line of an NDJSON upload, the most that gets buffered at once.
+pub static NDJSON_MAX_LINE_BYTES: LazyLock<usize> = LazyLock::new(|| {
+    env::var("NDJSON_MAX_LINE_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&v| v > 0)
+        .unwrap_or(64 * 1024 * 1024)
+});
+
+#[derive(Clone, Copy, Debug)]
+pub struct NdjsonLimits {
+    pub max_body_bytes: usize,
+    pub max_line_bytes: usize,
+}
+
+impl NdjsonLimits {
+    pub fn from_env() -> Self {
+        NdjsonLimits {
+            max_body_bytes: *NDJSON_MAX_BODY_BYTES,
+            max_line_bytes: *NDJSON_MAX_LINE_BYTES,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum NdjsonReadError {
+    #[error("upload is larger than the {limit} byte limit")]
+    BodyTooLarge { limit: usize },
+    #[error("line is longer than the {limit} byte limit")]
+    LineTooLong { limit: usize },
+    #[error("line is not valid utf-8")]
+    InvalidUtf8,
+    #[error("failed reading the upload: {0}")]
+    Body(String),
+}
+
+/// A line of an NDJSON upload along with its 1-indexed line number. Reading stops after any error
+/// other than `InvalidUtf8`.
+pub type NdjsonLine = (usize, Result<String, NdjsonReadError>);
+
+/// The raw request body, left unread so NDJSON handlers can stream it line by line.
+pub struct NdjsonBody(pub Body);
+
+impl<S: Send + Sync> FromRequest<S> for NdjsonBody {
+    type Rejection = Infallible;
+
+    async fn from_request(req: Request, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(NdjsonBody(req.into_body()))
+    }
+}
+
+impl OperationInput for NdjsonBody {}
+
+impl NdjsonBody {
+    pub fn into_lines(self, limits: NdjsonLimits) -> impl Stream<Item = NdjsonLine> {
+        ndjson_lines(self.0.into_data_stream(), limits)
+    }
+}
+
+/// Splits a byte stream into lines, only pulling the next chunk once the lines already read have
+/// been taken, so a slow consumer holds back the upload instead of buffering it.
+pub fn ndjson_lines<S, E>(body: S, limits: NdjsonLimits) -> impl Stream<Item = NdjsonLine>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: Display,
+{
+    let reader = LineReader {
+        body: Box::pin(body),
+        limits,
+        buffered: vec![],
+        scanned: 0,
+        body_bytes: 0,
+        lines_read: 0,
+        finished: false,
+    };
+    stream::unfold(reader, |mut reader| async move {
+        let line = reader.next_line().await?;
+        Some((line, reader))
+    })
+}
+
+struct LineReader<S> {
+    body: Pin<Box<S>>,
+    limits: NdjsonLimits,
+    buffered: Vec<u8>,
+    /// How much of `buffered` is known not to hold a newline.
+    scanned: usize,
+    body_bytes: usize,
+    lines_read: usize,
+    finished: bool,
+}
+
+impl<S, E> LineReader<S>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: Display,
+{
+    async fn next_line(&mut self) -> Option<NdjsonLine> {
+        loop {
+            if self.finished {
+                return None;
+            }
+            if let Some(offset) = self.buffered[self.scanned..]
+                .iter()
+                .position(|&byte| byte == b'\n')
+            {
+                let line_end = self.scanned + offset;
+                if line_end > self.limits.max_line_bytes {
+                    return Some(self.fail(NdjsonReadError::LineTooLong {
+                        limit: self.limits.max_line_bytes,
+                    }));
+                }
+                let mut line = self.buffered.drain(..=line_end).collect::<Vec<_>>();
+                line.pop();
+                self.scanned = 0;
+                return Some(self.decode(line));
+            }
+            self.scanned = self.buffered.len();
+            if self.buffered.len() > self.limits.max_line_bytes {
+                return Some(self.fail(NdjsonReadError::LineTooLong {
+                    limit: self.limits.max_line_bytes,
+                }));
+            }
+            match self.body.next().await {
+                Some(Ok(chunk)) => {
+                    self.body_bytes += chunk.len();
+                    if self.body_bytes > self.limits.max_body_bytes {
+                        return Some(self.fail(NdjsonReadError::BodyTooLarge {
+                            limit: self.limits.max_body_bytes,
+                        }));
+                    }
+                    self.buffered.extend_from_slice(&chunk);
+                }
+                Some(Err(err)) => return Some(self.fail(NdjsonReadError::Body(err.to_string()))),
+                None => {
+                    self.finished = true;
+                    if self.buffered.is_empty() {
+                        return None;
+                    }
+                    let line = std::mem::take(&mut self.buffered);
+                    return Some(self.decode(line));
+                }
+            }
+        }
+    }
+
+    fn decode(&mut self, line: Vec<u8>) -> NdjsonLine {
+        self.lines_read += 1;
+        let line = String::from_utf8(line).map_err(|_| NdjsonReadError::InvalidUtf8);
+        (self.lines_read, line)
+    }
+
+    fn fail(&mut self, err: NdjsonReadError) -> NdjsonLine {
+        self.finished = true;
+        self.buffered = vec![];
+        self.lines_read += 1;
+        (self.lines_read, Err(err))
+    }
+}
+
+/// A response body written one json line at a time, as the items are produced.
+pub struct NdjsonResponse(pub Body);
+
+impl NdjsonResponse {
+    pub fn from_stream<T: Serialize>(items: impl Stream<Item = T> + Send + 'static) -> Self {
+        let lines = items.map(|item| {
+            serde_json::to_vec(&item).map(|mut line| {
+                line.push(b'\n');
+                Bytes::from(line)
+            })
+        });
+        NdjsonResponse(Body::from_stream(lines))
+    }
+
+    /// Same as [`NdjsonResponse::from_stream`], except the first item that failed ends the body
+    /// with its error, so the client gets a broken response instead of one that quietly stopped
+    /// short.
+    pub fn from_fallible_stream<T, E>(
+        items: impl Stream<Item = Result<T, E>> + Send + 'static,
+    ) -> Self
+    where
+        T: Serialize,
+        E: Into<BoxError>,
+    {
+        let lines = items.map(|item| -> Result<Bytes, BoxError> {
+            let item = match item {
+                Ok(item) => item,
+                Err(err) => return Err(err.into()),
+            };
+            let mut line = serde_json::to_vec(&item)?;
+            line.push(b'\n');
+            Ok(Bytes::from(line))
+        });
+        NdjsonResponse(Body::from_stream(lines))
+    }
+}
+
+impl IntoResponse for NdjsonResponse {
+    fn into_response(self) -> Response {
+        ([(header::CONTENT_TYPE, "application/x-ndjson")], self.0).into_response()
+    }
+}
+
+impl OperationOutput for NdjsonResponse {
+    type Inner = ();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::make_chunked_body;
+
+    async fn read_lines(text: &str, limits: NdjsonLimits) -> Vec<(usize, Result<String, String>)> {
+        ndjson_lines(make_chunked_body(text, 3), limits)
+            .map(|(line, text)| (line, text.map_err(|err| err.to_string())))
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_lines_split_across_chunks() {
+        let limits = NdjsonLimits {
+            max_body_bytes: 1024,
+            max_line_bytes: 8,
+        };
+        let lines = read_lines("{\"a\":1}\n\n{\"b\":2}", limits).await;
+        assert_eq!(
+            lines,
+            vec![
+                (1, Ok("{\"a\":1}".to_string())),
+                (2, Ok(String::new())),
+                (3, Ok("{\"b\":2}".to_string())),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_limits_stop_reading() {
+        let long_line = NdjsonLimits {
+            max_body_bytes: 1024,
+            max_line_bytes: 4,
+        };
+        let lines = read_lines("abc\nabcdefgh\nabc\n", long_line).await;
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].1.is_err());
+
+        let large_body = NdjsonLimits {
+            max_body_bytes: 6,
+            max_line_bytes: 4,
+        };
+        let lines = read_lines("abc\nabc\nabc\n", large_body).await;
+        assert_eq!(lines[0], (1, Ok("abc".to_string())));
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].1.is_err());
+    }
+}
@@ -8,7 +8,10 @@ use crate::{
     indexes::attachment_url_index::RegenerateUrlAttachIndex,
     server::scraper_check_completed::get_completed_casedata_differential,
 };
-use crate::{indexes::attachment_url_index::handle_attachment_url_lookup, server::s3_routes};
+use crate::{
+    indexes::attachment_url_index::{handle_attachment_hash_lookup, handle_attachment_url_lookup},
+    server::{docket_routes, s3_routes},
+};
 
 pub fn create_public_router() -> ApiRouter {
     ApiRouter::new()
@@ -16,6 +19,22 @@ pub fn create_public_router() -> ApiRouter {
             "/debug_case/{state}/{jurisdiction_name}/{docket_govid}",
             get(s3_routes::handle_case_debug_info),
         )
+        .api_route(
+            "/debug_case/{state}/{jurisdiction_name}/{docket_govid}/raw",
+            get(s3_routes::handle_raw_case_debug),
+        )
+        .api_route(
+            "/docket/{state}/{jurisdiction_name}/{docket_govid}/attachments",
+            get(docket_routes::handle_list_docket_attachments),
+        )
+        .api_route(
+            "/docket/{state}/{jurisdiction_name}/{docket_govid}/hash",
+            get(docket_routes::handle_docket_content_hash),
+        )
+        .api_route(
+            "/stats/{state}/{jurisdiction_name}/filing_types",
+            get(docket_routes::handle_filing_type_histogram),
+        )
         .api_route(
             "/caselist/{state}/{jurisdiction_name}/casedata_differential",
             post(get_completed_casedata_differential),
@@ -34,10 +53,21 @@ pub fn create_public_router() -> ApiRouter {
                 s3_routes::handle_attachment_file_from_s3_docs,
             ),
         )
+        .api_route(
+            "/raw_attachments/{state}/{jurisdiction_name}/{blake2b_hash}/raw",
+            get_with(
+                s3_routes::handle_jurisdiction_attachment_file_from_s3,
+                s3_routes::handle_attachment_file_from_s3_docs,
+            ),
+        )
         .api_route(
             "/attachment_index/lookup/{url}",
             post(handle_attachment_url_lookup),
         )
+        .api_route(
+            "/attachment_index/lookup_hash/{hash}",
+            post(handle_attachment_hash_lookup),
+        )
         .api_route(
             "/attachment_index/regenerate",
             post(handle_default_task_route::<RegenerateUrlAttachIndex>),
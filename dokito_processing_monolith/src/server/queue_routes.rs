@@ -1,10 +1,11 @@
 use crate::{
-    jurisdiction_schema_mapping::FixedJurisdiction,
-    server::reprocess_all_handlers::download_dokito_cases_with_dates,
+    jurisdiction_schema_mapping::{FixedJurisdiction, validate_schema_name},
+    server::reprocess_all_handlers::{CasesByDate, download_dokito_cases_with_dates},
 };
 
+use anyhow::anyhow;
 use aws_sdk_s3::Client;
-use axum::{extract::Path, response::Json};
+use axum::response::Json;
 use chrono::NaiveDate;
 use dokito_types::{
     env_vars::DIGITALOCEAN_S3, jurisdictions::JurisdictionInfo, processed::ProcessedGenericDocket,
@@ -15,17 +16,36 @@ use non_empty_string::NonEmptyString;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::{
+    env,
+    sync::{LazyLock, Mutex, OnceLock},
+    time::Duration,
+};
 use tokio::sync::Semaphore;
-use tracing::info;
+use tracing::{Instrument, Span, info, info_span};
 
 use crate::{
-    processing::{attachments::OpenscrapersExtraData, process_case},
+    processing::{
+        attachments::{AttachmentFetchOrder, OpenscrapersExtraData},
+        process_case, process_case_for_target_schema,
+    },
     s3_stuff::{
-        DocketAddress, download_openscrapers_object, list_raw_cases_for_jurisdiction, upload_object,
+        DocketAddress, download_openscrapers_object, list_raw_cases_for_jurisdiction,
+        upload_object_if_changed,
+    },
+    server::{
+        correlation_id::CorrelationId,
+        idempotency::{IDEMPOTENCY_KEY_TTL_SECS, IdempotencyCache, IdempotencyKey},
+        jurisdiction_extractor::ValidJurisdiction,
     },
-    server::s3_routes::JurisdictionPath,
     sql_ingester_tasks::{
-        dokito_sql_connection::get_dokito_pool, nypuc_ingest::ingest_sql_case_with_retries,
+        database_author_association::OrgUuidCache,
+        dokito_sql_connection::get_dokito_pool_for,
+        nypuc_ingest::{
+            CASE_INGEST_RETRIES, check_transactional_batch_size, ingest_dockets_in_transaction,
+            ingest_sql_case_with_retries,
+        },
+        verify_ingest::{DocketVerification, verify_ingested_docket},
     },
 };
 
@@ -34,6 +54,7 @@ pub enum ProcessingAction {
     ProcessOnly,
     IngestOnly,
     ProcessAndIngest,
+    ProcessIngestVerify,
     UploadRaw,
 }
 
@@ -42,6 +63,8 @@ pub enum ProcessingAction {
 pub enum ProcessingActionRawData {
     ProcessOnly,
     ProcessAndIngest,
+    /// Process and ingest, then check postgres against the processed docket.
+    ProcessIngestVerify,
     UploadRaw,
 }
 
@@ -50,6 +73,7 @@ impl From<ProcessingActionRawData> for ProcessingAction {
         match value {
             ProcessingActionRawData::ProcessOnly => Self::ProcessOnly,
             ProcessingActionRawData::ProcessAndIngest => Self::ProcessAndIngest,
+            ProcessingActionRawData::ProcessIngestVerify => Self::ProcessIngestVerify,
             ProcessingActionRawData::UploadRaw => Self::UploadRaw,
         }
     }
@@ -61,6 +85,8 @@ pub enum ProcessingActionIdOnly {
     ProcessOnly,
     IngestOnly,
     ProcessAndIngest,
+    /// Process and ingest, then check postgres against the processed docket.
+    ProcessIngestVerify,
 }
 
 impl From<ProcessingActionIdOnly> for ProcessingAction {
@@ -69,24 +95,60 @@ impl From<ProcessingActionIdOnly> for ProcessingAction {
             ProcessingActionIdOnly::ProcessOnly => Self::ProcessOnly,
             ProcessingActionIdOnly::IngestOnly => Self::IngestOnly,
             ProcessingActionIdOnly::ProcessAndIngest => Self::ProcessAndIngest,
+            ProcessingActionIdOnly::ProcessIngestVerify => Self::ProcessIngestVerify,
         }
     }
 }
 
+/// How the dockets of a request are written to postgres.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestMode {
+    /// Each docket is ingested on its own, a failing docket doesn't affect the others.
+    #[default]
+    PerDocket,
+    /// The whole batch is ingested in one transaction and rolled back if any docket fails. Limited
+    /// to `TRANSACTIONAL_INGEST_MAX_DOCKETS` dockets per request.
+    Transactional,
+}
+
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct RawDocketsRequest {
     pub action: ProcessingActionRawData,
     pub dockets: Vec<RawGenericDocket>,
+    /// Postgres schema to ingest into instead of the jurisdiction's own one. It has to exist
+    /// already, and the action has to ingest. Nothing is written to the jurisdiction's schema or
+    /// to the processed dockets in S3.
+    #[serde(default)]
+    pub target_schema: Option<String>,
+    #[serde(default)]
+    pub ingest_mode: IngestMode,
+    /// Stop the batch once `FAIL_FAST_CONSECUTIVE_FAILURES` dockets in a row have failed, instead
+    /// of running every docket.
+    #[serde(default)]
+    pub fail_fast: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ByIdsRequest {
     pub docket_ids: Vec<NonEmptyString>,
+    #[serde(default)]
+    pub target_schema: Option<String>,
+    #[serde(default)]
+    pub ingest_mode: IngestMode,
+    #[serde(default)]
+    pub fail_fast: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ByJurisdictionRequest {
     pub action: ProcessingActionIdOnly,
+    #[serde(default)]
+    pub target_schema: Option<String>,
+    #[serde(default)]
+    pub ingest_mode: IngestMode,
+    #[serde(default)]
+    pub fail_fast: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
@@ -94,6 +156,31 @@ pub struct ByDateRangeRequest {
     pub action: ProcessingActionIdOnly,
     pub start_date: NaiveDate,
     pub end_date: NaiveDate,
+    /// Process the most recently opened dockets first instead of the oldest.
+    #[serde(default)]
+    pub newest_first: bool,
+    #[serde(default)]
+    pub target_schema: Option<String>,
+    #[serde(default)]
+    pub ingest_mode: IngestMode,
+    #[serde(default)]
+    pub fail_fast: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ByPetitionerRequest {
+    pub action: ProcessingActionIdOnly,
+    pub petitioner: String,
+    /// Only match petitioners named exactly this, instead of any petitioner whose name contains
+    /// it, ignoring case.
+    #[serde(default)]
+    pub exact: bool,
+    #[serde(default)]
+    pub target_schema: Option<String>,
+    #[serde(default)]
+    pub ingest_mode: IngestMode,
+    #[serde(default)]
+    pub fail_fast: bool,
 }
 
 // create a standard interface for handling all the possible ingest forms for the dockets. There
@@ -128,6 +215,15 @@ enum RawDocketOrGovid {
     RawInfo(Box<RawGenericDocket>),
 }
 
+impl RawDocketOrGovid {
+    fn gov_id(&self) -> NonEmptyString {
+        match self {
+            RawDocketOrGovid::Govid(govid) => govid.clone(),
+            RawDocketOrGovid::RawInfo(raw) => raw.case_govid.clone(),
+        }
+    }
+}
+
 impl From<NonEmptyString> for RawDocketOrGovid {
     fn from(value: NonEmptyString) -> Self {
         RawDocketOrGovid::Govid(value)
@@ -156,23 +252,115 @@ impl From<RawGenericDocket> for CaseRawOrProcessed {
     }
 }
 
-#[derive(Debug, Serialize, JsonSchema, Default)]
+/// What a single docket action produced, the verification is only there for
+/// `ProcessIngestVerify`.
+struct DocketActionOutcome {
+    docket: CaseRawOrProcessed,
+    verification: Option<DocketVerification>,
+}
+
+impl From<ProcessedGenericDocket> for DocketActionOutcome {
+    fn from(value: ProcessedGenericDocket) -> Self {
+        Self {
+            docket: value.into(),
+            verification: None,
+        }
+    }
+}
+
+impl From<RawGenericDocket> for DocketActionOutcome {
+    fn from(value: RawGenericDocket) -> Self {
+        Self {
+            docket: value.into(),
+            verification: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct DocketProcessingFailure {
+    pub docket_govid: String,
+    pub error: String,
+}
+
+#[derive(Clone, Debug, Serialize, JsonSchema, Default)]
 pub struct ProcessingResponse {
     pub successfully_processed_dockets: Vec<CaseRawOrProcessed>,
+    pub failed_dockets: Vec<DocketProcessingFailure>,
+    /// One entry per successful docket when the action verifies the ingest.
+    pub verifications: Vec<DocketVerification>,
+    /// The docket that made a transactional batch roll back.
+    pub aborted_by_docket: Option<String>,
+    /// Why a fail fast batch stopped before running every docket.
+    pub aborted_reason: Option<String>,
+    /// The dockets that never ran because the batch stopped early.
+    pub skipped_dockets: Vec<String>,
     pub success_count: usize,
     pub error_count: usize,
 }
+
+impl ProcessingResponse {
+    fn from_outcomes(
+        outcomes: impl IntoIterator<Item = (NonEmptyString, anyhow::Result<DocketActionOutcome>)>,
+    ) -> Self {
+        let mut response = ProcessingResponse::default();
+        for (gov_id, outcome) in outcomes {
+            match outcome {
+                Ok(DocketActionOutcome {
+                    docket,
+                    verification,
+                }) => {
+                    response.success_count += 1;
+                    response.successfully_processed_dockets.push(docket);
+                    response.verifications.extend(verification);
+                }
+                Err(err) => {
+                    response.error_count += 1;
+                    info!(?gov_id, ?err, "Processing failed for a docket");
+                    response.failed_dockets.push(DocketProcessingFailure {
+                        docket_govid: gov_id.to_string(),
+                        error: err.to_string(),
+                    });
+                }
+            }
+        }
+        response
+    }
+
+    /// Every docket of a rolled back batch counts as failed, the one that caused the rollback
+    /// keeps its own error.
+    fn from_aborted_batch(
+        gov_ids: Vec<NonEmptyString>,
+        aborted_by: Option<String>,
+        error: String,
+    ) -> Self {
+        let outcomes = gov_ids.into_iter().map(|gov_id| {
+            let err = match (&aborted_by, aborted_by.as_deref() == Some(gov_id.as_str())) {
+                (_, true) => anyhow!("{error}"),
+                (Some(culprit), false) => {
+                    anyhow!("batch rolled back because docket {culprit} failed")
+                }
+                (None, false) => anyhow!("batch rolled back: {error}"),
+            };
+            (gov_id, Err(err))
+        });
+        let mut response = Self::from_outcomes(outcomes);
+        response.aborted_by_docket = aborted_by;
+        response
+    }
+}
+
 async fn execute_processing_single_action(
     info: RawDocketOrGovid,
     action: ProcessingAction,
+    ingest_mode: IngestMode,
     fixed_jurisdiction: FixedJurisdiction,
+    target_schema: Option<&str>,
     s3_client: &Client,
     pool: &PgPool,
-) -> Result<CaseRawOrProcessed, anyhow::Error> {
-    let gov_id = match &info {
-        RawDocketOrGovid::Govid(govid) => govid.clone(),
-        RawDocketOrGovid::RawInfo(raw) => raw.case_govid.clone(),
-    };
+    org_cache: &OrgUuidCache,
+) -> Result<DocketActionOutcome, anyhow::Error> {
+    let gov_id = info.gov_id();
     let jur_info = JurisdictionInfo::from(fixed_jurisdiction);
 
     info!(
@@ -190,12 +378,14 @@ async fn execute_processing_single_action(
 
     if let RawDocketOrGovid::RawInfo(raw) = info {
         info!(?gov_id, "Uploading raw docket to S3");
-        upload_object::<RawGenericDocket>(s3_client, &docket_addr, &raw).await?;
+        upload_object_if_changed::<RawGenericDocket>(s3_client, &docket_addr, &raw).await?;
         info!(?gov_id, "Successfully uploaded raw docket to S3");
     }
 
     let mut processed_docket = match action {
-        ProcessingAction::ProcessOnly | ProcessingAction::ProcessAndIngest => {
+        ProcessingAction::ProcessOnly
+        | ProcessingAction::ProcessAndIngest
+        | ProcessingAction::ProcessIngestVerify => {
             info!(?gov_id, "Downloading raw docket from S3");
             let raw_docket =
                 download_openscrapers_object::<RawGenericDocket>(s3_client, &docket_addr).await?;
@@ -206,9 +396,14 @@ async fn execute_processing_single_action(
                 jurisdiction_info: jur_info.clone(),
                 fixed_jurisdiction,
                 s3_client: DIGITALOCEAN_S3.make_s3_client().await,
+                attachment_fetch_order: AttachmentFetchOrder::default(),
+            };
+            // Handles both fetching the cached s3 processed docket and uploading the result. A
+            // docket headed for a target schema is kept out of the canonical schema and S3.
+            let processed_docket = match target_schema {
+                Some(_) => process_case_for_target_schema(raw_docket, extra_data).await?,
+                None => process_case(raw_docket, extra_data).await?,
             };
-            // Handles both fetching the cached s3 processed docket and uploading the result.
-            let processed_docket = process_case(raw_docket, extra_data).await?;
 
             info!(?gov_id, "Successfully processed docket");
             processed_docket
@@ -235,16 +430,23 @@ async fn execute_processing_single_action(
         }
     };
 
+    // A transactional batch is ingested and verified all at once, after every docket is ready.
+    let ingest_now = ingest_mode == IngestMode::PerDocket;
     match action {
-        ProcessingAction::IngestOnly | ProcessingAction::ProcessAndIngest => {
+        ProcessingAction::IngestOnly
+        | ProcessingAction::ProcessAndIngest
+        | ProcessingAction::ProcessIngestVerify
+            if ingest_now =>
+        {
             info!(?gov_id, "Starting SQL ingestion");
-            const TRIES: usize = 3;
             ingest_sql_case_with_retries(
                 &mut processed_docket,
                 fixed_jurisdiction,
+                target_schema,
                 pool,
+                org_cache,
                 false,
-                TRIES,
+                *CASE_INGEST_RETRIES,
             )
             .await?;
             info!(?gov_id, "Successfully completed SQL ingestion");
@@ -254,192 +456,439 @@ async fn execute_processing_single_action(
         }
     };
 
+    let verification = match action {
+        ProcessingAction::ProcessIngestVerify if ingest_now => {
+            let pg_schema = target_schema.unwrap_or(fixed_jurisdiction.get_postgres_schema_name());
+            info!(?gov_id, %pg_schema, "Verifying ingested docket");
+            let verification = verify_ingested_docket(&processed_docket, pg_schema, pool).await?;
+            info!(
+                ?gov_id,
+                verified = verification.verified,
+                "Finished verifying ingested docket"
+            );
+            Some(verification)
+        }
+        _ => None,
+    };
+
     info!(?gov_id, "Single docket processing completed successfully");
-    Ok(processed_docket.into())
+    Ok(DocketActionOutcome {
+        docket: processed_docket.into(),
+        verification,
+    })
 }
 
+/// How many dockets in a row have to fail before a fail fast batch stops.
+pub static FAIL_FAST_CONSECUTIVE_FAILURES: LazyLock<usize> = LazyLock::new(|| {
+    env::var("FAIL_FAST_CONSECUTIVE_FAILURES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&v| v > 0)
+        .unwrap_or(5)
+});
+
+/// The results of the dockets that ran, and the ones that were skipped because the batch
+/// stopped early.
+#[derive(Default)]
+struct DocketActionsRun {
+    results: Vec<(NonEmptyString, anyhow::Result<DocketActionOutcome>)>,
+    skipped: Vec<NonEmptyString>,
+    aborted_reason: Option<String>,
+}
+
+/// Runs `single_action` on every docket, a couple at a time. With `max_consecutive_failures`
+/// set, the dockets that haven't started yet are skipped once that many in a row have failed.
+async fn run_docket_actions(
+    gov_ids: Vec<RawDocketOrGovid>,
+    max_consecutive_failures: Option<usize>,
+    single_action: impl AsyncFn(RawDocketOrGovid) -> anyhow::Result<DocketActionOutcome>,
+) -> DocketActionsRun {
+    // The semaphore hands out permits first come first served, and join_all polls in order, so
+    // dockets start processing in the order they were given.
+    let max_processes = Semaphore::new(2);
+    let consecutive_failures = Mutex::new(0);
+    let aborted_reason = OnceLock::new();
+    let all_actions = gov_ids.into_iter().map(async |info| {
+        let _permit = max_processes.acquire().await;
+        let gov_id = info.gov_id();
+        if aborted_reason.get().is_some() {
+            return (gov_id, None);
+        }
+        let result = single_action(info).await;
+        info!(?gov_id, success = result.is_ok(), "Finished docket action");
+        if let Some(max_failures) = max_consecutive_failures {
+            let mut failures = consecutive_failures.lock().unwrap();
+            match &result {
+                Ok(_) => *failures = 0,
+                Err(err) => {
+                    *failures += 1;
+                    if *failures >= max_failures {
+                        let _ = aborted_reason.set(format!(
+                            "{max_failures} dockets in a row failed, the last one ({gov_id}) with: {err}"
+                        ));
+                    }
+                }
+            }
+        }
+        (gov_id, Some(result))
+    });
+    let mut run = DocketActionsRun::default();
+    for (gov_id, result) in join_all(all_actions).await {
+        match result {
+            Some(result) => run.results.push((gov_id, result)),
+            None => run.skipped.push(gov_id),
+        }
+    }
+    run.aborted_reason = aborted_reason.into_inner();
+    if let Some(reason) = &run.aborted_reason {
+        info!(skipped_count = run.skipped.len(), %reason, "Stopped the batch early");
+    }
+    run
+}
+
+/// Runs `action` on every docket under a span carrying the request's correlation id, so the logs
+/// of concurrent requests can be told apart.
 async fn execute_processing_action(
     gov_ids: Vec<RawDocketOrGovid>,
     action: ProcessingAction,
-    jurisdiction: JurisdictionInfo,
+    ingest_mode: IngestMode,
+    fail_fast: bool,
+    fixed_jurisdiction: FixedJurisdiction,
+    target_schema: Option<String>,
+    correlation_id: CorrelationId,
+) -> Result<ProcessingResponse, String> {
+    execute_processing_action_inner(
+        gov_ids,
+        action,
+        ingest_mode,
+        fail_fast,
+        fixed_jurisdiction,
+        target_schema,
+    )
+    .instrument(queue_request_span(&correlation_id))
+    .await
+}
+
+fn queue_request_span(correlation_id: &CorrelationId) -> Span {
+    info_span!("queue_request", %correlation_id)
+}
+
+async fn execute_processing_action_inner(
+    gov_ids: Vec<RawDocketOrGovid>,
+    action: ProcessingAction,
+    ingest_mode: IngestMode,
+    fail_fast: bool,
+    fixed_jurisdiction: FixedJurisdiction,
+    target_schema: Option<String>,
 ) -> Result<ProcessingResponse, String> {
     // NOTE:
     // THIS FUNCTIONS REQUIRES THAT THE DATA HAS ALREDY BEEN
     // UPLOADED THROUGH THE RAW DOCKETS ENDPOINT
 
+    let ingests = matches!(
+        action,
+        ProcessingAction::IngestOnly
+            | ProcessingAction::ProcessAndIngest
+            | ProcessingAction::ProcessIngestVerify
+    );
+    // Checked up front so a bad schema name fails the request instead of every docket in it.
+    check_target_schema(target_schema.as_deref(), ingests)?;
+    let transactional = ingests && ingest_mode == IngestMode::Transactional;
+    // Checked before processing anything, a batch this size would be refused at ingest anyway.
+    if transactional {
+        check_transactional_batch_size(gov_ids.len()).map_err(|e| e.to_string())?;
+    }
+
     let s3_client = DIGITALOCEAN_S3.make_s3_client().await;
-    let pool = get_dokito_pool().await.map_err(|e| e.to_string())?;
-    let fixed_jurisdiction =
-        FixedJurisdiction::try_from(&jurisdiction).map_err(|err| err.to_string())?;
+    let pool = get_dokito_pool_for(fixed_jurisdiction)
+        .await
+        .map_err(|e| e.to_string())?;
 
-    let max_processes = Semaphore::new(2);
-    let all_actions = gov_ids.into_iter().map(async |info| {
-        let _permit = max_processes.acquire().await;
-        execute_processing_single_action(info, action, fixed_jurisdiction, &s3_client, pool).await
-    });
+    let org_cache = OrgUuidCache::default();
+    let max_consecutive_failures = fail_fast.then_some(*FAIL_FAST_CONSECUTIVE_FAILURES);
+    let DocketActionsRun {
+        results: action_results,
+        skipped,
+        aborted_reason,
+    } = run_docket_actions(gov_ids, max_consecutive_failures, async |info| {
+        execute_processing_single_action(
+            info,
+            action,
+            ingest_mode,
+            fixed_jurisdiction,
+            target_schema.as_deref(),
+            &s3_client,
+            pool,
+            &org_cache,
+        )
+        .await
+    })
+    .await;
+    let mut response = match transactional {
+        true => {
+            let verify = matches!(action, ProcessingAction::ProcessIngestVerify);
+            ingest_outcomes_in_transaction(
+                action_results,
+                verify,
+                fixed_jurisdiction,
+                target_schema.as_deref(),
+                pool,
+                &org_cache,
+            )
+            .await
+        }
+        false => ProcessingResponse::from_outcomes(action_results),
+    };
+    response.skipped_dockets = skipped.iter().map(ToString::to_string).collect();
+    response.aborted_reason = aborted_reason;
+
+    info!(success_count= %response.success_count, error_count=%response.error_count, "Completed processing batch");
 
-    let action_results = join_all(all_actions).await;
+    Ok(response)
+}
 
-    let mut response = ProcessingResponse {
-        successfully_processed_dockets: vec![],
-        success_count: 0,
-        error_count: 0,
+/// A target schema is only written to by ingesting, so an action that doesn't ingest would
+/// silently ignore it.
+fn check_target_schema(target_schema: Option<&str>, ingests: bool) -> Result<(), String> {
+    let Some(target_schema) = target_schema else {
+        return Ok(());
     };
+    if !ingests {
+        return Err("target_schema only applies to actions that ingest".to_string());
+    }
+    validate_schema_name(target_schema).map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-    for outcome in action_results {
+/// Ingests the dockets a batch produced in a single transaction. A docket that already failed
+/// earlier aborts the batch before anything is written.
+async fn ingest_outcomes_in_transaction(
+    outcomes: Vec<(NonEmptyString, anyhow::Result<DocketActionOutcome>)>,
+    verify: bool,
+    fixed_jur: FixedJurisdiction,
+    target_schema: Option<&str>,
+    pool: &PgPool,
+    org_cache: &OrgUuidCache,
+) -> ProcessingResponse {
+    let mut gov_ids = Vec::with_capacity(outcomes.len());
+    let mut dockets = Vec::with_capacity(outcomes.len());
+    let mut first_failure = None;
+    for (gov_id, outcome) in outcomes {
         match outcome {
-            Ok(data) => {
-                response.success_count += 1;
-                response.successfully_processed_dockets.push(data);
+            Ok(DocketActionOutcome {
+                docket: CaseRawOrProcessed::Processed(docket),
+                ..
+            }) => dockets.push(docket),
+            Ok(_) => {
+                first_failure
+                    .get_or_insert((gov_id.to_string(), "docket was not processed".to_string()));
             }
             Err(err) => {
-                response.error_count += 1;
-                info!(?err, "Processing failed for a docket");
+                first_failure.get_or_insert((gov_id.to_string(), err.to_string()));
             }
         }
+        gov_ids.push(gov_id);
+    }
+    if let Some((aborted_by, error)) = first_failure {
+        info!(%aborted_by, "Docket failed before the transactional ingest, nothing was ingested");
+        return ProcessingResponse::from_aborted_batch(gov_ids, Some(aborted_by), error);
     }
 
-    info!(success_count= %response.success_count, error_count=%response.error_count, "Completed processing batch");
+    if let Err(err) =
+        ingest_dockets_in_transaction(&mut dockets, fixed_jur, target_schema, pool, org_cache).await
+    {
+        let aborted_by = err.failed_docket().map(str::to_string);
+        return ProcessingResponse::from_aborted_batch(gov_ids, aborted_by, err.to_string());
+    }
 
-    Ok(response)
+    let pg_schema = target_schema.unwrap_or(fixed_jur.get_postgres_schema_name());
+    let mut outcomes = Vec::with_capacity(dockets.len());
+    for (gov_id, docket) in gov_ids.into_iter().zip(dockets) {
+        let verification = match verify {
+            true => match verify_ingested_docket(&docket, pg_schema, pool).await {
+                Ok(verification) => Some(verification),
+                Err(err) => {
+                    outcomes.push((gov_id, Err(err)));
+                    continue;
+                }
+            },
+            false => None,
+        };
+        let outcome = DocketActionOutcome {
+            docket: docket.into(),
+            verification,
+        };
+        outcomes.push((gov_id, Ok(outcome)));
+    }
+    ProcessingResponse::from_outcomes(outcomes)
 }
 
+/// Responses of raw dockets requests sent with an `Idempotency-Key`, so a client retrying a
+/// request that timed out doesn't process and ingest the dockets a second time.
+static RAW_DOCKETS_IDEMPOTENCY: LazyLock<IdempotencyCache<ProcessingResponse>> =
+    LazyLock::new(|| IdempotencyCache::new(Duration::from_secs(*IDEMPOTENCY_KEY_TTL_SECS)));
+
 pub async fn raw_dockets_endpoint(
-    Path(JurisdictionPath {
-        state,
-        jurisdiction_name,
-    }): Path<JurisdictionPath>,
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    correlation_id: CorrelationId,
+    IdempotencyKey(idempotency_key): IdempotencyKey,
     Json(request): Json<RawDocketsRequest>,
 ) -> Result<Json<ProcessingResponse>, String> {
     info!(
-        state = %state,
-        jurisdiction_name = %jurisdiction_name,
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
         action = ?request.action,
         docket_count = request.dockets.len(),
         "Processing raw dockets request"
     );
 
-    let jurisdiction = JurisdictionInfo::new_usa(&jurisdiction_name, &state);
-
     let raw_list = request
         .dockets
         .into_iter()
         .map(RawDocketOrGovid::from)
         .collect();
-    let response = execute_processing_action(raw_list, request.action.into(), jurisdiction).await?;
+    // Scoped to the jurisdiction, the same key sent for another one is a different request.
+    let idempotency_key =
+        idempotency_key.map(|key| format!("{}/{key}", fixed_jur.get_jurisdiction_info_name()));
+    let response = RAW_DOCKETS_IDEMPOTENCY
+        .run(
+            idempotency_key.as_deref(),
+            execute_processing_action(
+                raw_list,
+                request.action.into(),
+                request.ingest_mode,
+                request.fail_fast,
+                fixed_jur,
+                request.target_schema,
+                correlation_id,
+            ),
+        )
+        .await?;
     Ok(Json(response))
 }
 
 async fn processing_actions_by_ids(
-    state: String,
-    jurisdiction_name: String,
+    fixed_jur: FixedJurisdiction,
     action: ProcessingActionIdOnly,
     docket_ids: Vec<NonEmptyString>,
+    ingest_mode: IngestMode,
+    fail_fast: bool,
+    target_schema: Option<String>,
+    correlation_id: CorrelationId,
 ) -> Result<Json<ProcessingResponse>, String> {
     info!(
-        state = %state,
-        jurisdiction_name = %jurisdiction_name,
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
         action = ?action,
         id_count = docket_ids.len(),
         "Processing by-ids request"
     );
 
-    let jurisdiction = JurisdictionInfo::new_usa(&jurisdiction_name, &state);
     let docid_info = docket_ids.into_iter().map(RawDocketOrGovid::from).collect();
-    let response = execute_processing_action(docid_info, action.into(), jurisdiction).await?;
+    let response = execute_processing_action(
+        docid_info,
+        action.into(),
+        ingest_mode,
+        fail_fast,
+        fixed_jur,
+        target_schema,
+        correlation_id,
+    )
+    .await?;
     Ok(Json(response))
 }
 
 pub async fn ingest_by_govid(
-    Path(JurisdictionPath {
-        state,
-        jurisdiction_name,
-    }): Path<JurisdictionPath>,
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    correlation_id: CorrelationId,
     Json(request): Json<ByIdsRequest>,
 ) -> Result<Json<ProcessingResponse>, String> {
     info!(
-        state = %state,
-        jurisdiction_name = %jurisdiction_name,
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
         action = ?ProcessingActionIdOnly::IngestOnly,
         id_count = request.docket_ids.len(),
         "Ingest by-ids request"
     );
     let result = processing_actions_by_ids(
-        state,
-        jurisdiction_name,
+        fixed_jur,
         ProcessingActionIdOnly::IngestOnly,
         request.docket_ids,
+        request.ingest_mode,
+        request.fail_fast,
+        request.target_schema,
+        correlation_id,
     )
     .await?;
     Ok(result)
 }
 
 pub async fn process_by_govid(
-    Path(JurisdictionPath {
-        state,
-        jurisdiction_name,
-    }): Path<JurisdictionPath>,
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    correlation_id: CorrelationId,
     Json(request): Json<ByIdsRequest>,
 ) -> Result<Json<ProcessingResponse>, String> {
     info!(
-        state = %state,
-        jurisdiction_name = %jurisdiction_name,
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
         action = ?ProcessingActionIdOnly::ProcessOnly,
         id_count = request.docket_ids.len(),
         "Processing by-ids request"
     );
 
     let result = processing_actions_by_ids(
-        state,
-        jurisdiction_name,
+        fixed_jur,
         // PPROCESS
         ProcessingActionIdOnly::ProcessOnly,
         request.docket_ids,
+        request.ingest_mode,
+        request.fail_fast,
+        request.target_schema,
+        correlation_id,
     )
     .await?;
     Ok(result)
 }
 pub async fn process_and_ingest_by_govid(
-    Path(JurisdictionPath {
-        state,
-        jurisdiction_name,
-    }): Path<JurisdictionPath>,
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    correlation_id: CorrelationId,
     Json(request): Json<ByIdsRequest>,
 ) -> Result<Json<ProcessingResponse>, String> {
     info!(
-        state = %state,
-        jurisdiction_name = %jurisdiction_name,
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
         action = ?ProcessingActionIdOnly::ProcessAndIngest,
         id_count = request.docket_ids.len(),
         "Process and Ingest by-ids request"
     );
 
     let result = processing_actions_by_ids(
-        state,
-        jurisdiction_name,
+        fixed_jur,
         // PROCESS AND INGEST
         ProcessingActionIdOnly::ProcessAndIngest,
         request.docket_ids,
+        request.ingest_mode,
+        request.fail_fast,
+        request.target_schema,
+        correlation_id,
     )
     .await?;
     Ok(result)
 }
 
 pub async fn by_jurisdiction_endpoint(
-    Path(JurisdictionPath {
-        state,
-        jurisdiction_name,
-    }): Path<JurisdictionPath>,
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    correlation_id: CorrelationId,
     Json(request): Json<ByJurisdictionRequest>,
 ) -> Result<Json<ProcessingResponse>, String> {
     info!(
-        state = %state,
-        jurisdiction_name = %jurisdiction_name,
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
         action = ?request.action,
         "Processing by-jurisdiction request"
     );
 
-    let jurisdiction = JurisdictionInfo::new_usa(&jurisdiction_name, &state);
+    let jurisdiction = JurisdictionInfo::from(fixed_jur);
     let s3_client = DIGITALOCEAN_S3.make_s3_client().await;
 
     let gov_ids = list_raw_cases_for_jurisdiction(&s3_client, &jurisdiction)
@@ -456,43 +905,49 @@ pub async fn by_jurisdiction_endpoint(
         .into_iter()
         .map(RawDocketOrGovid::from)
         .collect();
-    let response =
-        execute_processing_action(docid_info, request.action.into(), jurisdiction).await?;
+    let response = execute_processing_action(
+        docid_info,
+        request.action.into(),
+        request.ingest_mode,
+        request.fail_fast,
+        fixed_jur,
+        request.target_schema,
+        correlation_id,
+    )
+    .await?;
     Ok(Json(response))
 }
 
 pub async fn by_daterange_endpoint(
-    Path(JurisdictionPath {
-        state,
-        jurisdiction_name,
-    }): Path<JurisdictionPath>,
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    correlation_id: CorrelationId,
     Json(request): Json<ByDateRangeRequest>,
 ) -> Result<Json<ProcessingResponse>, String> {
     info!(
-        state = %state,
-        jurisdiction_name = %jurisdiction_name,
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
         action = ?request.action,
         start_date = %request.start_date,
         end_date = %request.end_date,
         "Processing by-daterange request"
     );
 
-    let jurisdiction = JurisdictionInfo::new_usa(&jurisdiction_name, &state);
-    let fixed_jur = FixedJurisdiction::try_from(&jurisdiction).map_err(|e| e.to_string())?;
     let caselist_by_dates = download_dokito_cases_with_dates(fixed_jur)
         .await
         .map_err(|e| e.to_string())?;
 
-    // Filter cases by date range
-    let filtered_docket_ids: Vec<String> = caselist_by_dates
-        .range(request.start_date..=request.end_date)
-        .map(|(_, docket_id)| docket_id.clone())
-        .collect();
+    let filtered_docket_ids = docket_ids_in_date_range(
+        &caselist_by_dates,
+        request.start_date,
+        request.end_date,
+        request.newest_first,
+    );
 
     info!(
         filtered_count = filtered_docket_ids.len(),
         start_date = %request.start_date,
         end_date = %request.end_date,
+        newest_first = request.newest_first,
         "Filtered dockets by date range"
     );
 
@@ -502,7 +957,452 @@ pub async fn by_daterange_endpoint(
         .map(RawDocketOrGovid::from)
         .collect();
 
-    let response =
-        execute_processing_action(docid_info, request.action.into(), jurisdiction).await?;
+    let response = execute_processing_action(
+        docid_info,
+        request.action.into(),
+        request.ingest_mode,
+        request.fail_fast,
+        fixed_jur,
+        request.target_schema,
+        correlation_id,
+    )
+    .await?;
     Ok(Json(response))
 }
+
+pub async fn handle_reprocess_by_petitioner(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+    correlation_id: CorrelationId,
+    Json(request): Json<ByPetitionerRequest>,
+) -> Result<Json<ProcessingResponse>, String> {
+    info!(
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
+        action = ?request.action,
+        petitioner = %request.petitioner,
+        exact = request.exact,
+        "Processing by-petitioner request"
+    );
+
+    let pool = get_dokito_pool_for(fixed_jur)
+        .await
+        .map_err(|e| e.to_string())?;
+    let docket_ids = list_docket_govids_by_petitioner(
+        &request.petitioner,
+        request.exact,
+        fixed_jur.get_postgres_schema_name(),
+        pool,
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    info!(
+        matched_count = docket_ids.len(),
+        petitioner = %request.petitioner,
+        "Found dockets for petitioner"
+    );
+
+    let docid_info = filter_out_empty_strings(docket_ids)
+        .into_iter()
+        .map(RawDocketOrGovid::from)
+        .collect();
+    let response = execute_processing_action(
+        docid_info,
+        request.action.into(),
+        request.ingest_mode,
+        request.fail_fast,
+        fixed_jur,
+        request.target_schema,
+        correlation_id,
+    )
+    .await?;
+    Ok(Json(response))
+}
+
+/// The ingested dockets petitioned by `petitioner`, matched either exactly or as a case
+/// insensitive substring of a petitioner name.
+async fn list_docket_govids_by_petitioner(
+    petitioner: &str,
+    exact: bool,
+    pg_schema: &str,
+    pool: &PgPool,
+) -> Result<Vec<String>, sqlx::Error> {
+    let petitioner_clause = match exact {
+        true => "$1 = ANY(petitioner_strings)",
+        false => {
+            "EXISTS (SELECT 1 FROM unnest(petitioner_strings) AS p WHERE p ILIKE '%' || $1 || '%' ESCAPE '\\')"
+        }
+    };
+    let pattern = match exact {
+        true => petitioner.to_string(),
+        false => petitioner
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_"),
+    };
+    sqlx::query_scalar::<_, String>(&format!(
+        "SELECT docket_govid FROM {pg_schema}.dockets WHERE {petitioner_clause} ORDER BY docket_govid"
+    ))
+    .bind(pattern)
+    .fetch_all(pool)
+    .await
+}
+
+/// The dockets opened within `start_date..=end_date`, in chronological order or its reverse.
+fn docket_ids_in_date_range(
+    caselist_by_dates: &CasesByDate,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    newest_first: bool,
+) -> Vec<String> {
+    let in_range = caselist_by_dates
+        .range(start_date..=end_date)
+        .flat_map(|(_, docket_ids)| docket_ids.iter().cloned());
+    match newest_first {
+        true => in_range.rev().collect(),
+        false => in_range.collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::s3_stuff::delete_openscrapers_s3_object;
+    use crate::sql_ingester_tasks::nypuc_ingest::ingest_sql_fixed_jurisdiction_case;
+    use crate::sql_ingester_tasks::recreate_dokito_table_schema::create_target_schema;
+    use crate::test_fixtures::{
+        capture_logs, make_processed_docket, make_processed_petitioner, make_raw_docket,
+        setup_test_db,
+    };
+    use anyhow::anyhow;
+    use dokito_types::processed::PetitionerRole;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_failed_docket_is_reported_separately_from_successes() {
+        let opened_date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let good_govid = NonEmptyString::try_from("GOOD-1".to_string()).unwrap();
+        let bad_govid = NonEmptyString::try_from("BAD-1".to_string()).unwrap();
+        let outcomes: Vec<(NonEmptyString, anyhow::Result<DocketActionOutcome>)> = vec![
+            (
+                good_govid,
+                Ok(make_processed_docket("GOOD-1", opened_date, vec![]).into()),
+            ),
+            (bad_govid, Err(anyhow!("raw docket missing from S3"))),
+        ];
+
+        let response = ProcessingResponse::from_outcomes(outcomes);
+        assert_eq!(response.success_count, 1);
+        assert_eq!(response.error_count, 1);
+        assert_eq!(response.failed_dockets.len(), 1);
+        assert_eq!(response.failed_dockets[0].docket_govid, "BAD-1");
+        assert_eq!(
+            response.failed_dockets[0].error,
+            "raw docket missing from S3"
+        );
+        assert!(
+            response
+                .successfully_processed_dockets
+                .iter()
+                .all(|case| matches!(case, CaseRawOrProcessed::Processed(d) if d.case_govid.as_str() == "GOOD-1"))
+        );
+    }
+
+    #[test]
+    fn test_verifications_are_reported_for_verified_dockets() {
+        let opened_date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let govid = NonEmptyString::try_from("GOOD-1".to_string()).unwrap();
+        let outcomes = vec![(
+            govid,
+            Ok(DocketActionOutcome {
+                docket: make_processed_docket("GOOD-1", opened_date, vec![]).into(),
+                verification: Some(DocketVerification {
+                    docket_govid: "GOOD-1".to_string(),
+                    verified: false,
+                    discrepancies: vec!["filling 1 is missing".to_string()],
+                }),
+            }),
+        )];
+
+        let response = ProcessingResponse::from_outcomes(outcomes);
+        assert_eq!(response.success_count, 1);
+        assert_eq!(response.verifications.len(), 1);
+        assert!(!response.verifications[0].verified);
+        assert_eq!(
+            response.verifications[0].discrepancies,
+            vec!["filling 1 is missing"]
+        );
+    }
+
+    #[test]
+    fn test_aborted_batch_fails_every_docket() {
+        let gov_ids = ["GOOD-1", "BAD-1"]
+            .map(|govid| NonEmptyString::try_from(govid.to_string()).unwrap())
+            .to_vec();
+        let response = ProcessingResponse::from_aborted_batch(
+            gov_ids,
+            Some("BAD-1".to_string()),
+            "filling has no uuid".to_string(),
+        );
+        assert_eq!(response.success_count, 0);
+        assert_eq!(response.error_count, 2);
+        assert_eq!(response.aborted_by_docket.as_deref(), Some("BAD-1"));
+        assert_eq!(
+            response.failed_dockets[0].error,
+            "batch rolled back because docket BAD-1 failed"
+        );
+        assert_eq!(response.failed_dockets[1].error, "filling has no uuid");
+    }
+
+    #[test]
+    fn test_date_range_dockets_are_processed_in_date_order() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let caselist_by_dates = CasesByDate::from([
+            (date(20), vec!["LATEST".to_string()]),
+            (date(1), vec!["TOO-EARLY".to_string()]),
+            (date(5), vec!["OLDEST".to_string()]),
+            (
+                date(12),
+                vec!["MIDDLE-A".to_string(), "MIDDLE-B".to_string()],
+            ),
+        ]);
+
+        let oldest_first = docket_ids_in_date_range(&caselist_by_dates, date(2), date(31), false);
+        assert_eq!(
+            oldest_first,
+            vec!["OLDEST", "MIDDLE-A", "MIDDLE-B", "LATEST"]
+        );
+        let newest_first = docket_ids_in_date_range(&caselist_by_dates, date(2), date(31), true);
+        assert_eq!(
+            newest_first,
+            vec!["LATEST", "MIDDLE-B", "MIDDLE-A", "OLDEST"]
+        );
+
+        let outcomes = newest_first
+            .iter()
+            .map(|govid| {
+                (
+                    NonEmptyString::try_from(govid.clone()).unwrap(),
+                    Ok(make_processed_docket(govid, date(2), vec![]).into()),
+                )
+            })
+            .collect::<Vec<(NonEmptyString, anyhow::Result<DocketActionOutcome>)>>();
+        let response = ProcessingResponse::from_outcomes(outcomes);
+        assert!(matches!(
+            &response.successfully_processed_dockets[0],
+            CaseRawOrProcessed::Processed(d) if d.case_govid.as_str() == "LATEST"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_target_schema_is_rejected_for_actions_that_dont_ingest() {
+        for action in [ProcessingAction::ProcessOnly, ProcessingAction::UploadRaw] {
+            let gov_ids = vec![
+                NonEmptyString::try_from("TARGET-1".to_string())
+                    .unwrap()
+                    .into(),
+            ];
+            let result = execute_processing_action_inner(
+                gov_ids,
+                action,
+                IngestMode::PerDocket,
+                false,
+                FixedJurisdiction::NewYorkPuc,
+                Some("test_target_schema".to_string()),
+            )
+            .await;
+            assert_eq!(
+                result.err().as_deref(),
+                Some("target_schema only applies to actions that ingest"),
+                "{action:?}"
+            );
+        }
+        assert!(check_target_schema(Some("test_target_schema"), true).is_ok());
+        assert!(check_target_schema(Some("bad schema;"), true).is_err());
+        assert!(check_target_schema(None, false).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_correlation_id_is_on_docket_logs() {
+        let (logs, _guard) = capture_logs();
+
+        let opened_date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let gov_ids = ["CORR-1", "CORR-2"]
+            .into_iter()
+            .map(|govid| NonEmptyString::try_from(govid.to_string()).unwrap().into())
+            .collect();
+        let correlation_id = CorrelationId("req-1234".to_string());
+        run_docket_actions(gov_ids, None, async |info| {
+            let gov_id = info.gov_id();
+            info!(%gov_id, "Inside docket action");
+            Ok(make_processed_docket(gov_id.as_str(), opened_date, vec![]).into())
+        })
+        .instrument(queue_request_span(&correlation_id))
+        .await;
+
+        let docket_lines = logs
+            .lines()
+            .into_iter()
+            .filter(|line| line.contains("CORR-"))
+            .collect::<Vec<_>>();
+        assert_eq!(docket_lines.len(), 4);
+        assert!(
+            docket_lines
+                .iter()
+                .all(|line| line.contains("correlation_id=req-1234"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fail_fast_batch_stops_after_consecutive_failures() {
+        let gov_ids = || {
+            (1..=20)
+                .map(|n| {
+                    NonEmptyString::try_from(format!("MISSING-{n}"))
+                        .unwrap()
+                        .into()
+                })
+                .collect::<Vec<RawDocketOrGovid>>()
+        };
+        let attempts = std::sync::atomic::AtomicUsize::new(0);
+        let failing_action =
+            async |_info: RawDocketOrGovid| -> anyhow::Result<DocketActionOutcome> {
+                attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Err(anyhow!("schema dokito_missing does not exist"))
+            };
+
+        let run = run_docket_actions(gov_ids(), Some(3), &failing_action).await;
+        let attempted = attempts.swap(0, std::sync::atomic::Ordering::SeqCst);
+        // The docket that was already running next to the third failure still finishes.
+        assert!((3..=4).contains(&attempted));
+        assert_eq!(run.results.len(), attempted);
+        assert_eq!(run.skipped.len(), 20 - attempted);
+        assert_eq!(run.skipped.last().unwrap().as_str(), "MISSING-20");
+        let reason = run.aborted_reason.unwrap();
+        assert!(reason.starts_with("3 dockets in a row failed"));
+        assert!(reason.contains("schema dokito_missing does not exist"));
+
+        let run = run_docket_actions(gov_ids(), None, &failing_action).await;
+        assert_eq!(run.results.len(), 20);
+        assert!(run.skipped.is_empty());
+        assert!(run.aborted_reason.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_repeated_raw_dockets_request_returns_cached_response() {
+        let pool = setup_test_db().await;
+        let fixed_jur = FixedJurisdiction::NewYorkPuc;
+        let run_id = Uuid::new_v4();
+        let target_schema = format!("test_idempotent_{}", run_id.simple());
+        create_target_schema(&target_schema, &pool)
+            .await
+            .expect("Target schema should be created");
+        let docket_govid = format!("TEST-IDEMPOTENT-{run_id}");
+        let submit = async |key: &str| {
+            let request = RawDocketsRequest {
+                action: ProcessingActionRawData::ProcessAndIngest,
+                dockets: vec![make_raw_docket(&docket_govid, vec![])],
+                target_schema: Some(target_schema.clone()),
+                ingest_mode: IngestMode::PerDocket,
+                fail_fast: false,
+            };
+            raw_dockets_endpoint(
+                ValidJurisdiction(fixed_jur),
+                CorrelationId::generate(),
+                IdempotencyKey(Some(key.to_string())),
+                Json(request),
+            )
+            .await
+            .expect("Request should succeed")
+            .0
+        };
+        let delete_docket_rows = async || {
+            sqlx::query(&format!(
+                "DELETE FROM {target_schema}.dockets WHERE docket_govid = $1"
+            ))
+            .bind(&docket_govid)
+            .execute(&pool)
+            .await
+            .unwrap()
+            .rows_affected()
+        };
+
+        let key = format!("batch-{run_id}");
+        let first = submit(&key).await;
+        let first_deleted = delete_docket_rows().await;
+        // Had the repeat ingested again, the docket would be back in the schema.
+        let repeated = submit(&key).await;
+        let repeat_deleted = delete_docket_rows().await;
+        let other_key = submit(&format!("other-batch-{run_id}")).await;
+        let other_key_deleted = delete_docket_rows().await;
+
+        sqlx::query(&format!("DROP SCHEMA {target_schema} CASCADE"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        let addr = DocketAddress {
+            docket_govid: docket_govid.clone(),
+            jurisdiction: fixed_jur.into(),
+        };
+        let s3_client = DIGITALOCEAN_S3.make_s3_client().await;
+        let _ = delete_openscrapers_s3_object::<RawGenericDocket>(&s3_client, &addr).await;
+
+        assert_eq!(first.success_count, 1);
+        assert_eq!(first_deleted, 1);
+        assert_eq!(
+            serde_json::to_value(&repeated).unwrap(),
+            serde_json::to_value(&first).unwrap()
+        );
+        assert_eq!(repeat_deleted, 0);
+        assert_eq!(other_key.success_count, 1);
+        assert_eq!(other_key_deleted, 1);
+    }
+
+    #[tokio::test]
+    async fn test_petitioner_lookup_only_matches_that_petitioner() {
+        let pool = setup_test_db().await;
+        let fixed_jur = FixedJurisdiction::NewYorkPuc;
+        let run_id = Uuid::new_v4();
+        let opened_date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        let target_petitioner = format!("Example Power {run_id}");
+        let other_petitioner = format!("Sample Gas {run_id}");
+        let ingest_petitioned_docket = async |govid: String, petitioner: &str| {
+            let mut docket = make_processed_docket(&govid, opened_date, vec![]);
+            docket.petitioner_list = vec![make_processed_petitioner(
+                petitioner,
+                PetitionerRole::Petitioner,
+            )];
+            ingest_sql_fixed_jurisdiction_case(&mut docket, fixed_jur, &pool, false)
+                .await
+                .expect("Ingest should succeed");
+            govid
+        };
+        let target_govid =
+            ingest_petitioned_docket(format!("TEST-PET-A-{run_id}"), &target_petitioner).await;
+        ingest_petitioned_docket(format!("TEST-PET-B-{run_id}"), &other_petitioner).await;
+
+        let pg_schema = fixed_jur.get_postgres_schema_name();
+        let fuzzy = list_docket_govids_by_petitioner(
+            &format!("example power {run_id}"),
+            false,
+            pg_schema,
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert_eq!(fuzzy, vec![target_govid.clone()]);
+        let exact = list_docket_govids_by_petitioner(&target_petitioner, true, pg_schema, &pool)
+            .await
+            .unwrap();
+        assert_eq!(exact, vec![target_govid]);
+        let exact_miss = list_docket_govids_by_petitioner(
+            &format!("example power {run_id}"),
+            true,
+            pg_schema,
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert!(exact_miss.is_empty());
+    }
+}
@@ -11,18 +11,21 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, query_as};
 use tokio::sync::Semaphore;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::{
     data_processing_traits::DownloadIncomplete,
     jurisdiction_schema_mapping::FixedJurisdiction,
-    processing::{ReprocessDocketInfo, attachments::OpenscrapersExtraData},
+    processing::{
+        ReprocessDocketInfo,
+        attachments::{AttachmentFetchOrder, OpenscrapersExtraData},
+    },
     s3_stuff::{
-        DocketAddress, download_openscrapers_object, list_processed_cases_for_jurisdiction,
+        DocketAddress, fetch_processed_dockets, list_processed_cases_for_jurisdiction,
         list_raw_cases_for_jurisdiction, make_s3_client, upload_object,
     },
-    sql_ingester_tasks::dokito_sql_connection::get_dokito_pool,
-    types::{jurisdictions::JurisdictionInfo, processed::ProcessedGenericDocket},
+    sql_ingester_tasks::dokito_sql_connection::get_dokito_pool_for,
+    types::jurisdictions::JurisdictionInfo,
 };
 
 #[derive(FromRow)]
@@ -41,6 +44,13 @@ pub struct ReprocessJurisdictionInfo {
     pub ignore_cached_older_than: Option<DateTime<Utc>>,
     #[serde(default = "default_true")]
     pub only_process_missing: bool,
+    /// Don't reprocess dockets that were already closed when they were last processed.
+    #[serde(default)]
+    pub skip_closed: bool,
+    /// With `skip_closed`, only skip dockets closed at least this many days ago, so late
+    /// filings on recently closed dockets still get picked up.
+    #[serde(default)]
+    pub skip_closed_after_days: Option<u32>,
 }
 pub async fn reprocess_dockets(
     Json(payload): Json<ReprocessJurisdictionInfo>,
@@ -64,6 +74,8 @@ pub async fn reprocess_dockets(
             jurisdiction: payload.jurisdiction.clone(),
             only_process_missing: payload.only_process_missing,
             ignore_cachced_if_older_than: payload.ignore_cached_older_than,
+            skip_closed: payload.skip_closed,
+            skip_closed_after_days: payload.skip_closed_after_days,
         };
         Box::new(task_info)
     });
@@ -93,43 +105,116 @@ async fn get_initial_govid_list_to_process(
     Ok(raw_govid_map.into_iter().collect())
 }
 
+/// Docket govids by opened date, several dockets can be opened on the same day.
+pub type CasesByDate = BTreeMap<NaiveDate, Vec<String>>;
+
 pub async fn download_dokito_cases_with_dates(
     fixed_jur: FixedJurisdiction,
-) -> anyhow::Result<BTreeMap<NaiveDate, String>> {
-    let pool = get_dokito_pool().await.unwrap();
-    let pg_schema = fixed_jur.get_jurisdiction_info_name();
+) -> anyhow::Result<CasesByDate> {
+    let pool = get_dokito_pool_for(fixed_jur).await?;
+    let pg_schema = fixed_jur.get_postgres_schema_name();
     let results = query_as::<_, DocketResult>(&format!(
-        "SELECT docket_govid, opened_date FROM {pg_schema}.dockets"
+        "SELECT docket_govid, opened_date FROM {pg_schema}.dockets ORDER BY docket_govid"
     ))
     .fetch_all(pool)
     .await?;
-    let bmap = results
-        .into_iter()
-        .map(|val| (val.opened_date, val.docket_govid))
-        .collect();
+    Ok(group_cases_by_date(
+        results
+            .into_iter()
+            .map(|val| (val.opened_date, val.docket_govid)),
+    ))
+}
+
+fn group_cases_by_date(cases: impl IntoIterator<Item = (NaiveDate, String)>) -> CasesByDate {
+    let mut cases_by_date = CasesByDate::new();
+    for (opened_date, docket_govid) in cases {
+        cases_by_date
+            .entry(opened_date)
+            .or_default()
+            .push(docket_govid);
+    }
+    cases_by_date
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct DownloadNewestHashesRequest {
+    #[serde(flatten)]
+    pub jurisdiction: JurisdictionInfo,
+    /// The most dockets to go through in this call, all of them when unset.
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Only go through dockets opened before this date, pass the `next_cursor` of the previous
+    /// call to pick up where it stopped.
+    #[serde(default)]
+    pub before: Option<NaiveDate>,
+    /// The order each docket's missing attachments get downloaded in.
+    #[serde(default)]
+    pub fetch_order: AttachmentFetchOrder,
+}
 
-    Ok(bmap)
+#[derive(Serialize, JsonSchema)]
+pub struct DownloadNewestHashesProgress {
+    pub dockets_attempted: usize,
+    /// The opened date of the oldest docket gone through, none once the backlog is done.
+    pub next_cursor: Option<NaiveDate>,
 }
 
 pub async fn handle_download_all_missing_hashes_newest(
-    Json(payload): Json<JurisdictionInfo>,
-) -> Result<String, String> {
-    info!("Downloading all hashes starting from newest.");
-    let fixed_jur = FixedJurisdiction::try_from(&payload).map_err(|e| e.to_string())?;
+    Json(payload): Json<DownloadNewestHashesRequest>,
+) -> Result<Json<DownloadNewestHashesProgress>, String> {
+    info!(limit = ?payload.limit, before = ?payload.before, "Downloading all hashes starting from newest.");
+    let fixed_jur =
+        FixedJurisdiction::try_from(&payload.jurisdiction).map_err(|e| e.to_string())?;
     let s3_client = make_s3_client().await;
     let cases_with_dates = download_dokito_cases_with_dates(fixed_jur)
         .await
         .map_err(|e| e.to_string())?;
-    let caselist: Vec<String> = cases_with_dates
-        .into_iter()
-        .rev() // reverse iteration (newest → oldest)
-        .map(|(_, docketid)| docketid)
-        .collect();
-    info!(length = %caselist.len(),"Successfully got caselist, beginning to download.");
-
-    let _res = download_attachments_from_docids(caselist, s3_client, payload).await;
-    Ok("Completed Successfully".into())
+    let (caselist, next_cursor) =
+        newest_cases_before(&cases_with_dates, payload.before, payload.limit);
+    info!(length = %caselist.len(), ?next_cursor, "Successfully got caselist, beginning to download.");
+
+    let dockets_attempted = caselist.len();
+    download_attachments_from_docids(
+        caselist,
+        s3_client,
+        payload.jurisdiction,
+        payload.fetch_order,
+    )
+    .await;
+    Ok(Json(DownloadNewestHashesProgress {
+        dockets_attempted,
+        next_cursor,
+    }))
+}
+
+/// Dockets opened before `before`, newest first, along with the cursor to pass as `before` next
+/// time. The cursor is none when there is nothing older left. Dockets opened on the same day are
+/// never split across calls, so a page can go over `limit` by the rest of its last day.
+fn newest_cases_before(
+    cases_with_dates: &CasesByDate,
+    before: Option<NaiveDate>,
+    limit: Option<usize>,
+) -> (Vec<String>, Option<NaiveDate>) {
+    let older = match before {
+        Some(before) => cases_with_dates.range(..before),
+        None => cases_with_dates.range(..),
+    };
+    let limit = limit.unwrap_or(usize::MAX);
+    let mut caselist = vec![];
+    let mut oldest_date = None;
+    // reverse iteration (newest → oldest)
+    for (date, docket_govids) in older.rev() {
+        if caselist.len() >= limit {
+            break;
+        }
+        caselist.extend(docket_govids.iter().cloned());
+        oldest_date = Some(*date);
+    }
+    let next_cursor =
+        oldest_date.filter(|&oldest| cases_with_dates.range(..oldest).next().is_some());
+    (caselist, next_cursor)
 }
+
 pub async fn handle_download_all_missing_hashes_random(
     Json(payload): Json<JurisdictionInfo>,
 ) -> Result<String, String> {
@@ -140,46 +225,102 @@ pub async fn handle_download_all_missing_hashes_random(
     // Randomizing the list just to insure that the processing difficulty is uniform.
     let mut rng = SmallRng::from_os_rng();
     processed_caselist.shuffle(&mut rng);
-    let _res = download_attachments_from_docids(processed_caselist, s3_client, payload).await;
+    let _res = download_attachments_from_docids(
+        processed_caselist,
+        s3_client,
+        payload,
+        AttachmentFetchOrder::default(),
+    )
+    .await;
     Ok("Completed Successfully".into())
 }
 
+const DOCKET_FETCH_CHUNK_SIZE: usize = 200;
+
 pub async fn download_attachments_from_docids(
     docid_list: Vec<String>,
     s3_client: Client,
     jur_info: JurisdictionInfo,
+    fetch_order: AttachmentFetchOrder,
 ) {
     let fixed_jurisdiction = FixedJurisdiction::try_from(&jur_info).unwrap();
     let extra_info = OpenscrapersExtraData {
         s3_client: s3_client.clone(),
         jurisdiction_info: jur_info.clone(),
         fixed_jurisdiction,
+        attachment_fetch_order: fetch_order,
     };
     let max_simultaneous_attachment_process = Semaphore::new(20);
-    let task_futures = docid_list
-        .into_iter()
-        .map(async |docket_govid| {
-            let extra_info_clone = extra_info.clone();
-            let s3_client_clone = s3_client.clone();
-            let docket_address = DocketAddress {
+    let mut dockets_downloaded = 0;
+    // Fetched in chunks so a whole jurisdiction of processed dockets is never held in memory.
+    for docid_chunk in docid_list.chunks(DOCKET_FETCH_CHUNK_SIZE) {
+        let addresses = docid_chunk
+            .iter()
+            .map(|docket_govid| DocketAddress {
                 jurisdiction: jur_info.clone(),
-                docket_govid,
-            };
+                docket_govid: docket_govid.clone(),
+            })
+            .collect();
+        let (proc_dockets, failures) = fetch_processed_dockets(&s3_client, addresses, 20).await;
+        for (addr, err) in failures {
+            warn!(docket_govid = %addr.docket_govid, %err, "Could not download processed docket");
+        }
+        let task_futures = proc_dockets.into_iter().map(async |mut proc_docket| {
             let permit = max_simultaneous_attachment_process.acquire().await;
-            if let Ok(mut proc_docket) = download_openscrapers_object::<ProcessedGenericDocket>(
-                &s3_client_clone,
-                &docket_address,
-            )
-            .await
-            {
-                let res = proc_docket.download_incomplete(extra_info_clone).await;
-                if res.is_ok() {
-                    let _ = upload_object(&s3_client_clone, &docket_address, &proc_docket).await;
-                }
+            let docket_address = DocketAddress {
+                jurisdiction: jur_info.clone(),
+                docket_govid: proc_docket.case_govid.to_string(),
             };
+            let res = proc_docket.download_incomplete(extra_info.clone()).await;
+            if res.is_ok() {
+                let _ = upload_object(&s3_client, &docket_address, &proc_docket).await;
+            }
             drop(permit);
-        })
-        .collect::<Vec<_>>();
-    let results = join_all(task_futures).await;
-    info!(dockets_downloaded = %results.len(),"Finished downloading attachments");
+        });
+        dockets_downloaded += join_all(task_futures).await.len();
+    }
+    info!(%dockets_downloaded,"Finished downloading attachments");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consecutive_newest_pages_advance_the_cursor() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let cases_with_dates =
+            group_cases_by_date((1..=5).map(|day| (date(day), format!("CASE-{day}"))));
+
+        let (first_page, cursor) = newest_cases_before(&cases_with_dates, None, Some(2));
+        assert_eq!(first_page, vec!["CASE-5", "CASE-4"]);
+        assert_eq!(cursor, Some(date(4)));
+        let (second_page, cursor) = newest_cases_before(&cases_with_dates, cursor, Some(2));
+        assert_eq!(second_page, vec!["CASE-3", "CASE-2"]);
+        assert_eq!(cursor, Some(date(2)));
+        let (last_page, cursor) = newest_cases_before(&cases_with_dates, cursor, Some(2));
+        assert_eq!(last_page, vec!["CASE-1"]);
+        assert_eq!(cursor, None);
+
+        let (everything, cursor) = newest_cases_before(&cases_with_dates, None, None);
+        assert_eq!(everything.len(), 5);
+        assert_eq!(cursor, None);
+    }
+
+    #[test]
+    fn test_dockets_opened_on_the_same_day_are_all_kept() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let cases_with_dates = group_cases_by_date([
+            (date(1), "CASE-A".to_string()),
+            (date(2), "CASE-B".to_string()),
+            (date(2), "CASE-C".to_string()),
+        ]);
+        assert_eq!(cases_with_dates[&date(2)], vec!["CASE-B", "CASE-C"]);
+
+        let (first_page, cursor) = newest_cases_before(&cases_with_dates, None, Some(1));
+        assert_eq!(first_page, vec!["CASE-B", "CASE-C"]);
+        let (second_page, cursor) = newest_cases_before(&cases_with_dates, cursor, Some(1));
+        assert_eq!(second_page, vec!["CASE-A"]);
+        assert_eq!(cursor, None);
+    }
 }
@@ -1,12 +1,14 @@
 use aide::{self, axum::IntoApiResponse, transform::TransformOperation};
+use aws_sdk_s3::primitives::ByteStream;
 use axum::{
+    body::Body,
     debug_handler,
     extract::Path,
     http::HeaderValue,
-    response::{IntoResponse, Json},
+    response::{IntoResponse, Json, Response},
 };
 use dokito_types::{env_vars::DIGITALOCEAN_S3, raw::RawGenericDocket};
-use futures::join;
+use futures::{join, stream};
 use hyper::{StatusCode, body::Bytes, header};
 use mycorrhiza_common::{
     hash::Blake2bHash,
@@ -15,18 +17,25 @@ use mycorrhiza_common::{
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::str::FromStr;
+use std::{collections::BTreeSet, str::FromStr};
+use tokio::sync::Semaphore;
 use tracing::{error, info};
 use uuid::Uuid;
 
 use crate::{
     data_processing_traits::Revalidate,
     jurisdiction_schema_mapping::FixedJurisdiction,
+    s3_key_layout::S3_KEY_LAYOUT,
     s3_stuff::{
-        DocketAddress, delete_openscrapers_s3_object, download_openscrapers_object,
-        get_jurisdiction_prefix, list_processed_cases_for_jurisdiction, upload_object,
+        AttachmentFileStream, DocketAddress, delete_openscrapers_s3_object,
+        download_openscrapers_object, get_jurisdiction_prefix, get_openscrapers_json_key,
+        limit_s3_request, list_processed_cases_for_jurisdiction, upload_object,
+    },
+    sql_ingester_tasks::{
+        cleanup_orphaned_dockets::docket_keys_by_govid,
+        dokito_sql_connection::get_dokito_pool_for,
+        rename_jurisdiction::{JurisdictionObjectStore, S3JurisdictionObjectStore},
     },
-    sql_ingester_tasks::dokito_sql_connection::get_dokito_pool,
     types::{
         attachments::RawAttachment, env_vars::OPENSCRAPERS_S3_OBJECT_BUCKET,
         jurisdictions::JurisdictionInfo, processed::ProcessedGenericDocket,
@@ -42,9 +51,7 @@ pub async fn read_openscrapers_s3_file(
 ) -> impl IntoApiResponse {
     let bucket = &**OPENSCRAPERS_S3_OBJECT_BUCKET;
     let s3_client = crate::s3_stuff::make_s3_client().await;
-    let result = S3Addr::new(&s3_client, bucket, &path)
-        .download_bytes()
-        .await;
+    let result = limit_s3_request(S3Addr::new(&s3_client, bucket, &path).download_bytes()).await;
     match result {
         Ok(contents) => (axum::http::StatusCode::OK, Bytes::from(contents)).into_response(),
         Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
@@ -69,9 +76,7 @@ pub async fn write_s3_file_string(Json(payload): Json<S3UploadString>) -> impl I
     let bucket = (payload.bucket)
         .as_deref()
         .unwrap_or(&**OPENSCRAPERS_S3_OBJECT_BUCKET);
-    let result = S3Addr::new(&s3_client, bucket, &payload.key)
-        .upload_bytes(contents)
-        .await;
+    let result = crate::s3_stuff::upload_s3_bytes(&s3_client, bucket, &payload.key, contents).await;
     match result {
         Ok(_) => (axum::http::StatusCode::OK).into_response(),
         Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
@@ -90,9 +95,10 @@ pub async fn write_s3_file_json(Json(payload): Json<S3UploadJson>) -> impl IntoA
     let bucket = (payload.bucket)
         .as_deref()
         .unwrap_or(&**OPENSCRAPERS_S3_OBJECT_BUCKET);
-    let result = S3Addr::new(&s3_client, bucket, &payload.key)
-        .upload_json(&payload.contents)
-        .await;
+    let result = limit_s3_request(
+        S3Addr::new(&s3_client, bucket, &payload.key).upload_json(&payload.contents),
+    )
+    .await;
     match result {
         Ok(_) => (axum::http::StatusCode::OK).into_response(),
         Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
@@ -108,11 +114,11 @@ pub fn write_s3_file_docs(op: TransformOperation) -> TransformOperation {
 #[derive(Clone, Deserialize, JsonSchema)]
 pub struct DocketPath {
     /// The state of the jurisdiction.
-    state: String,
+    pub state: String,
     /// The name of the jurisdiction.
-    jurisdiction_name: String,
+    pub jurisdiction_name: String,
     /// The name of the case.
-    docket_govid: String,
+    pub docket_govid: String,
 }
 
 #[derive(Clone, Serialize, Deserialize, JsonSchema)]
@@ -141,7 +147,9 @@ pub async fn handle_case_debug_info(
         docket_govid: docket_govid.clone(),
     };
     let pg_schema = fixed_jur.get_postgres_schema_name();
-    let pool = get_dokito_pool().await.map_err(|e| e.to_string())?;
+    let pool = get_dokito_pool_for(fixed_jur)
+        .await
+        .map_err(|e| e.to_string())?;
     let processed_docket_future =
         download_openscrapers_object::<ProcessedGenericDocket>(&s3_client, &addr_info);
     let raw_docket_option =
@@ -164,6 +172,52 @@ pub async fn handle_case_debug_info(
     Ok(Json(response))
 }
 
+/// Returns the raw docket exactly as the scraper uploaded it, for comparing against the processed
+/// one from the debug info.
+pub async fn handle_raw_case_debug(
+    Path(DocketPath {
+        state,
+        jurisdiction_name,
+        docket_govid,
+    }): Path<DocketPath>,
+) -> Result<Json<RawGenericDocket>, (StatusCode, String)> {
+    info!(%state, %jurisdiction_name, %docket_govid, "Request received for raw case debug");
+    let s3_client = crate::s3_stuff::make_s3_client().await;
+    let addr_info = DocketAddress {
+        jurisdiction: JurisdictionInfo::new_usa(&jurisdiction_name, &state),
+        docket_govid,
+    };
+    let raw_docket = download_raw_docket_or_not_found(&s3_client, &addr_info).await?;
+    Ok(Json(raw_docket))
+}
+
+async fn download_raw_docket_or_not_found(
+    s3_client: &aws_sdk_s3::Client,
+    addr_info: &DocketAddress,
+) -> Result<RawGenericDocket, (StatusCode, String)> {
+    let key = get_openscrapers_json_key::<RawGenericDocket>(addr_info);
+    let head_result = limit_s3_request(
+        s3_client
+            .head_object()
+            .bucket(&**OPENSCRAPERS_S3_OBJECT_BUCKET)
+            .key(&key)
+            .send(),
+    )
+    .await;
+    if let Err(err) = head_result {
+        if err.as_service_error().is_some_and(|e| e.is_not_found()) {
+            return Err((
+                StatusCode::NOT_FOUND,
+                format!("No raw docket found at {key}"),
+            ));
+        }
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string()));
+    }
+    download_openscrapers_object::<RawGenericDocket>(s3_client, addr_info)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
 pub async fn handle_processed_case_filing_from_s3(
     Path(DocketPath {
         state,
@@ -236,9 +290,8 @@ pub async fn recursive_delete_all_jurisdiction_data(
     let jurisdiction_info = JurisdictionInfo::new_usa(&jurisdiction_name, &state);
     let prefix = get_jurisdiction_prefix(&jurisdiction_info);
     let bucket = &**OPENSCRAPERS_S3_OBJECT_BUCKET;
-    let result = S3DirectoryAddr::new(&s3_client, bucket, &prefix)
-        .delete_all()
-        .await;
+    let result =
+        limit_s3_request(S3DirectoryAddr::new(&s3_client, bucket, &prefix).delete_all()).await;
     match result {
         Ok(_) => {
             info!(state = %state, jurisdiction = %jurisdiction_name, "Successfully deleted all jurisdiction data");
@@ -289,6 +342,76 @@ pub async fn handle_caselist_jurisdiction_fetch_all(
     }
 }
 
+/// Listings of large jurisdictions page through S3 for a while, so only a few run at once no
+/// matter how many diffs are requested.
+static SIMULTANEOUS_CASELIST_LISTINGS: Semaphore = Semaphore::const_new(2);
+
+#[derive(Deserialize, JsonSchema)]
+pub struct JurisdictionCaselistDiffRequest {
+    pub left: JurisdictionInfo,
+    pub right: JurisdictionInfo,
+}
+
+/// The gov_ids of the raw dockets in only one of the two jurisdictions, sorted.
+#[derive(Serialize, JsonSchema, Debug)]
+pub struct JurisdictionCaselistDiff {
+    pub only_in_left: Vec<String>,
+    pub only_in_right: Vec<String>,
+    pub in_both_count: usize,
+}
+
+async fn list_raw_govids_bounded(
+    store: &impl JurisdictionObjectStore,
+    jurisdiction: &JurisdictionInfo,
+) -> anyhow::Result<BTreeSet<String>> {
+    let _permit = SIMULTANEOUS_CASELIST_LISTINGS.acquire().await?;
+    let keys = docket_keys_by_govid(store, &S3_KEY_LAYOUT.raw_docket_prefix(jurisdiction)).await?;
+    Ok(keys.into_keys().collect())
+}
+
+pub async fn diff_jurisdiction_caselists(
+    store: &impl JurisdictionObjectStore,
+    left: &JurisdictionInfo,
+    right: &JurisdictionInfo,
+) -> anyhow::Result<JurisdictionCaselistDiff> {
+    let (left_govids, right_govids) = join!(
+        list_raw_govids_bounded(store, left),
+        list_raw_govids_bounded(store, right)
+    );
+    let (left_govids, right_govids) = (left_govids?, right_govids?);
+    Ok(JurisdictionCaselistDiff {
+        only_in_left: left_govids.difference(&right_govids).cloned().collect(),
+        only_in_right: right_govids.difference(&left_govids).cloned().collect(),
+        in_both_count: left_govids.intersection(&right_govids).count(),
+    })
+}
+
+pub async fn handle_jurisdiction_caselist_diff(
+    Json(JurisdictionCaselistDiffRequest { left, right }): Json<JurisdictionCaselistDiffRequest>,
+) -> Result<Json<JurisdictionCaselistDiff>, String> {
+    info!(
+        ?left,
+        ?right,
+        "Request received to diff jurisdiction caselists"
+    );
+    let store = S3JurisdictionObjectStore {
+        s3_client: crate::s3_stuff::make_s3_client().await,
+    };
+    let diff = diff_jurisdiction_caselists(&store, &left, &right)
+        .await
+        .map_err(|e| {
+            error!(?left, ?right, error = %e, "Error diffing jurisdiction caselists");
+            e.to_string()
+        })?;
+    info!(
+        only_in_left = diff.only_in_left.len(),
+        only_in_right = diff.only_in_right.len(),
+        in_both = diff.in_both_count,
+        "Diffed jurisdiction caselists"
+    );
+    Ok(Json(diff))
+}
+
 pub fn handle_caselist_jurisdiction_fetch_all_docs(op: TransformOperation) -> TransformOperation {
     op.description("List all cases for a jurisdiction.")
         .response::<200, Json<Vec<String>>>()
@@ -333,57 +456,61 @@ pub fn handle_attachment_data_from_s3_docs(op: TransformOperation) -> TransformO
         .response_with::<500, String, _>(|res| res.description("Error fetching attachment data."))
 }
 
+#[derive(Deserialize, JsonSchema)]
+pub struct JurisdictionAttachmentPath {
+    /// The state of the jurisdiction.
+    state: String,
+    /// The name of the jurisdiction.
+    jurisdiction_name: String,
+    /// The blake2b hash of the attachment.
+    blake2b_hash: String,
+}
+
 pub async fn handle_attachment_file_from_s3(
     Path(AttachmentPath { blake2b_hash }): Path<AttachmentPath>,
 ) -> impl IntoApiResponse {
     info!(hash = %blake2b_hash, "Request received for attachment file");
+    serve_attachment_file(&blake2b_hash, None).await
+}
+
+/// Serves the file straight from the jurisdiction's attachment bucket, without looking up the
+/// attachment's metadata to find it.
+pub async fn handle_jurisdiction_attachment_file_from_s3(
+    Path(JurisdictionAttachmentPath {
+        state,
+        jurisdiction_name,
+        blake2b_hash,
+    }): Path<JurisdictionAttachmentPath>,
+) -> impl IntoApiResponse {
+    info!(hash = %blake2b_hash, %state, jurisdiction = %jurisdiction_name, "Request received for attachment file");
+    let jurisdiction = JurisdictionInfo::new_usa(&jurisdiction_name, &state);
+    serve_attachment_file(&blake2b_hash, Some(&jurisdiction)).await
+}
+
+async fn serve_attachment_file(
+    blake2b_hash: &str,
+    jurisdiction: Option<&JurisdictionInfo>,
+) -> Response {
     let s3_client = crate::s3_stuff::make_s3_client().await;
-    let hash = match Blake2bHash::from_str(&blake2b_hash) {
+    let hash = match Blake2bHash::from_str(blake2b_hash) {
         Ok(hash) => hash,
         Err(e) => {
             error!(hash = %blake2b_hash, error = %e, "Invalid hash format for attachment file request");
             return (axum::http::StatusCode::BAD_REQUEST, e.to_string()).into_response();
         }
     };
-    let result =
-        crate::s3_stuff::fetch_attachment_file_from_s3_with_filename(&s3_client, hash).await;
+    let result = crate::s3_stuff::stream_attachment_file_from_s3_with_filename(
+        &s3_client,
+        hash,
+        jurisdiction,
+    )
+    .await;
     match result {
-        Ok((filename, contents)) => {
-            // Content‑Type – generic binary stream
-            let ct = HeaderValue::from_static("application/octet-stream");
-
-            // Content‑Disposition – attachment; filename="<sanitized>"
-            let escaped_name = urlencoding::encode(&filename);
-            let cd = format!(
-                "attachment; filename=\"{}\"; filename*=UTF-8''{}",
-                // Legacy (ASCII‑only) fallback – we keep the original name but
-                // escape any double‑quotes or backslashes.
-                filename.replace('\\', "\\\\").replace('\"', "\\\""),
-                escaped_name
-            );
-            let cd = HeaderValue::from_str(&cd).expect("valid header value");
-
-            // Optional: Content‑Length – helps the client know the exact size
-            let cl = HeaderValue::from_str(&contents.len().to_string())
-                .expect("content length is a valid integer");
-
-            // ---------------------------------------------------------------
-            //   3b️⃣ Return the tuple that Axum knows how to turn into a response
-            // ---------------------------------------------------------------
-            (
-                StatusCode::OK,
-                // An array of header‑name / header‑value pairs.
-                // You can add more if you need (Cache‑Control, ETag, …)
-                [
-                    (header::CONTENT_TYPE, ct),
-                    (header::CONTENT_DISPOSITION, cd),
-                    (header::CONTENT_LENGTH, cl),
-                ],
-                // The body – we turn the Vec<u8> (or whatever `contents` is) into Bytes.
-                Bytes::from(contents),
-            )
-                .into_response()
-        }
+        Ok(AttachmentFileStream {
+            filename,
+            content_length,
+            body,
+        }) => attachment_file_response(&filename, content_length, byte_stream_body(body)),
         Err(e) => {
             error!(hash = %blake2b_hash,error = %e, "Error reading attachment file from disk");
             (axum::http::StatusCode::NOT_FOUND, e.to_string()).into_response()
@@ -391,9 +518,201 @@ pub async fn handle_attachment_file_from_s3(
     }
 }
 
+/// Streams an S3 body through to the client chunk by chunk instead of buffering it.
+fn byte_stream_body(byte_stream: ByteStream) -> Body {
+    Body::from_stream(stream::unfold(byte_stream, async |mut byte_stream| {
+        let chunk = byte_stream.try_next().await.transpose()?;
+        Some((chunk, byte_stream))
+    }))
+}
+
+fn attachment_file_response(filename: &str, content_length: Option<u64>, body: Body) -> Response {
+    // Content‑Type – generic binary stream
+    let ct = HeaderValue::from_static("application/octet-stream");
+
+    // Content‑Disposition – attachment; filename="<sanitized>"
+    let escaped_name = urlencoding::encode(filename);
+    let cd = format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        // Legacy (ASCII‑only) fallback – we keep the original name but
+        // escape any double‑quotes or backslashes.
+        filename.replace('\\', "\\\\").replace('\"', "\\\""),
+        escaped_name
+    );
+    let cd = HeaderValue::from_str(&cd).expect("valid header value");
+
+    let mut response = (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, ct),
+            (header::CONTENT_DISPOSITION, cd),
+        ],
+        body,
+    )
+        .into_response();
+    // Content‑Length – lets clients show progress and keeps proxies from buffering. When S3
+    // didn't report a size the header is left off and the body goes out chunked.
+    if let Some(content_length) = content_length {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(content_length));
+    }
+    response
+}
+
 pub fn handle_attachment_file_from_s3_docs(op: TransformOperation) -> TransformOperation {
     op.description("Fetch an attachment file from S3.")
         .response::<200, Bytes>()
         .response_with::<400, String, _>(|res| res.description("Invalid hash format."))
         .response_with::<500, String, _>(|res| res.description("Error fetching attachment file."))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::s3_stuff::{
+        get_attachment_bucket, get_raw_attach_file_key, make_s3_client,
+        stream_attachment_file_from_s3_with_filename, upload_s3_bytes,
+    };
+    use crate::test_fixtures::{MemoryObjectStore, make_raw_docket};
+
+    #[tokio::test]
+    async fn test_caselist_diff_of_overlapping_jurisdictions() {
+        let left = JurisdictionInfo::new_usa("diff_left", "zz");
+        let right = JurisdictionInfo::new_usa("diff_right", "zz");
+        let store = MemoryObjectStore::default();
+        for (jurisdiction, govids) in [
+            (&left, ["A-1", "B-2", "C-3"]),
+            (&right, ["B-2", "C-3", "D-4"]),
+        ] {
+            let prefix = S3_KEY_LAYOUT.raw_docket_prefix(jurisdiction);
+            for govid in govids {
+                store.insert(&format!("{prefix}{govid}.json"), b"{}");
+            }
+        }
+        // Processed dockets aren't part of the caselist.
+        store.insert(
+            &format!("{}E-5.json", S3_KEY_LAYOUT.processed_docket_prefix(&left)),
+            b"{}",
+        );
+
+        let diff = diff_jurisdiction_caselists(&store, &left, &right)
+            .await
+            .unwrap();
+        assert_eq!(diff.only_in_left, vec!["A-1"]);
+        assert_eq!(diff.only_in_right, vec!["D-4"]);
+        assert_eq!(diff.in_both_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_raw_case_debug_returns_raw_docket_or_404() {
+        let s3_client = make_s3_client().await;
+        let govid = format!("RAW-DEBUG-{}", Uuid::new_v4());
+        let addr_info = DocketAddress {
+            jurisdiction: JurisdictionInfo::new_usa("ny_puc", "ny"),
+            docket_govid: govid.clone(),
+        };
+
+        let (status, _) = download_raw_docket_or_not_found(&s3_client, &addr_info)
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        upload_object(&s3_client, &addr_info, &make_raw_docket(&govid, vec![]))
+            .await
+            .unwrap();
+        let raw_docket = download_raw_docket_or_not_found(&s3_client, &addr_info)
+            .await
+            .unwrap();
+        assert_eq!(raw_docket.case_govid.as_str(), govid);
+
+        delete_openscrapers_s3_object::<RawGenericDocket>(&s3_client, &addr_info)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_attachment_file_response_sets_content_length_from_s3() {
+        let s3_client = make_s3_client().await;
+        let contents = format!("attachment file {}", Uuid::new_v4()).into_bytes();
+        let hash = Blake2bHash::from_bytes(&contents);
+        let key = get_raw_attach_file_key(hash);
+        upload_s3_bytes(
+            &s3_client,
+            &OPENSCRAPERS_S3_OBJECT_BUCKET,
+            &key,
+            contents.clone(),
+        )
+        .await
+        .unwrap();
+
+        let file = stream_attachment_file_from_s3_with_filename(&s3_client, hash, None)
+            .await
+            .unwrap();
+        let response = attachment_file_response(
+            &file.filename,
+            file.content_length,
+            byte_stream_body(file.body),
+        );
+        assert_eq!(
+            response.headers()[header::CONTENT_LENGTH],
+            contents.len().to_string()
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.as_ref(), contents.as_slice());
+
+        S3Addr::new(&s3_client, &OPENSCRAPERS_S3_OBJECT_BUCKET, &key)
+            .delete_file()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_jurisdiction_attachment_file_is_served_from_its_bucket() {
+        let s3_client = make_s3_client().await;
+        let contents = format!("jurisdiction attachment {}", Uuid::new_v4()).into_bytes();
+        let hash = Blake2bHash::from_bytes(&contents);
+        let key = get_raw_attach_file_key(hash);
+        let jurisdiction = JurisdictionInfo::new_usa("co_puc", "co");
+        let bucket = get_attachment_bucket(&jurisdiction);
+        upload_s3_bytes(&s3_client, bucket, &key, contents.clone())
+            .await
+            .unwrap();
+
+        let serve = async |blake2b_hash: String| {
+            handle_jurisdiction_attachment_file_from_s3(Path(JurisdictionAttachmentPath {
+                state: "co".to_string(),
+                jurisdiction_name: "co_puc".to_string(),
+                blake2b_hash,
+            }))
+            .await
+            .into_response()
+        };
+        let response = serve(hash.to_string()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.as_ref(), contents.as_slice());
+
+        let missing_contents = format!("missing attachment {}", Uuid::new_v4()).into_bytes();
+        let missing_hash = Blake2bHash::from_bytes(&missing_contents);
+        let response = serve(missing_hash.to_string()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = serve("not-a-hash".to_string()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        S3Addr::new(&s3_client, bucket, &key)
+            .delete_file()
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_attachment_file_response_omits_unknown_content_length() {
+        let response = attachment_file_response("filing.pdf", None, Body::empty());
+        assert!(!response.headers().contains_key(header::CONTENT_LENGTH));
+    }
+}
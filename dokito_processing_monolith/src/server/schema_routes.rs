@@ -0,0 +1,28 @@
+use axum::response::Json;
+use tracing::info;
+
+use crate::{
+    server::jurisdiction_extractor::ValidJurisdiction,
+    sql_ingester_tasks::{
+        dokito_sql_connection::get_dokito_pool_for,
+        recreate_dokito_table_schema::{SchemaVersion, get_schema_version},
+    },
+};
+
+/// Lists the migrations applied to a jurisdiction's postgres schema, and the ones still pending.
+pub async fn handle_get_schema_version(
+    ValidJurisdiction(fixed_jur): ValidJurisdiction,
+) -> Result<Json<SchemaVersion>, String> {
+    info!(
+        state = fixed_jur.get_state_code(),
+        jurisdiction_name = fixed_jur.get_jurisdiction_info_name(),
+        "Getting schema version"
+    );
+    let pool = get_dokito_pool_for(fixed_jur)
+        .await
+        .map_err(|e| e.to_string())?;
+    let version = get_schema_version(fixed_jur, pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(Json(version))
+}
@@ -7,6 +7,7 @@ use mycorrhiza_common::s3_generic::fetchers_and_getters::S3DirectoryAddr;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::s3_stuff::limit_s3_request;
 use crate::types::env_vars::OPENSCRAPERS_S3_OBJECT_BUCKET;
 
 pub fn define_temporary_routes(app: ApiRouter) -> ApiRouter {
@@ -51,10 +52,10 @@ pub async fn move_s3_objects(Json(payload): Json<CopyPrefixRequest>) -> Result<(
         &payload.destination.prefix,
     );
 
-    let result = source.copy_into(&destination).await;
+    let result = limit_s3_request(source.copy_into(&destination)).await;
 
     if result.is_ok() && payload.delete_after_copy {
-        let _ = source.delete_all().await;
+        let _ = limit_s3_request(source.delete_all()).await;
     }
     result.map_err(|e| e.to_string())
 }
@@ -68,6 +69,6 @@ pub async fn delete_s3_objects(Json(payload): Json<S3DirectoryLoc>) -> Result<()
         payload.bucket.as_deref().unwrap_or(default_bucket),
         &payload.prefix,
     );
-    let result = source.delete_all().await;
+    let result = limit_s3_request(source.delete_all()).await;
     result.map_err(|e| e.to_string())
 }
@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use mycorrhiza_common::tasks::ExecuteUserTask;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::info;
+
+use crate::{
+    health_gate::WORKER_HEALTH_GATE,
+    s3_key_layout::S3_KEY_LAYOUT,
+    sql_ingester_tasks::rename_jurisdiction::{JurisdictionObjectStore, S3JurisdictionObjectStore},
+    types::{env_vars::DIGITALOCEAN_S3, jurisdictions::JurisdictionInfo},
+};
+
+const fn default_true() -> bool {
+    true
+}
+
+/// Finds the docket objects of a jurisdiction that only exist processed or only exist raw, which
+/// failed or partial processing leaves behind.
+#[derive(Clone, Deserialize, JsonSchema)]
+pub struct CleanupOrphanedDockets {
+    pub jurisdiction: JurisdictionInfo,
+    /// Only report the orphaned objects, without deleting anything.
+    #[serde(default = "default_true")]
+    pub dry_run: bool,
+    /// Also delete raw dockets that were never processed. Those are usually just waiting on
+    /// processing, so they are only reported unless this is set.
+    #[serde(default)]
+    pub delete_unprocessed_raw: bool,
+}
+
+#[async_trait]
+impl ExecuteUserTask for CleanupOrphanedDockets {
+    async fn execute_task(self: Box<Self>) -> Result<Value, Value> {
+        WORKER_HEALTH_GATE.wait_until_ready().await;
+        let store = S3JurisdictionObjectStore {
+            s3_client: DIGITALOCEAN_S3.make_s3_client().await,
+        };
+        match cleanup_orphaned_dockets(&self, &store).await {
+            Ok(report) => serde_json::to_value(report).map_err(|err| err.to_string().into()),
+            Err(err) => {
+                tracing::error!(error = %err, error_debug = ?err, "Encountered error in cleanup_orphaned_dockets");
+                Err(err.to_string().into())
+            }
+        }
+    }
+    fn get_task_label(&self) -> &'static str {
+        "cleanup_orphaned_dockets"
+    }
+    fn get_task_label_static() -> &'static str
+    where
+        Self: Sized,
+    {
+        "cleanup_orphaned_dockets"
+    }
+}
+
+/// What a `CleanupOrphanedDockets` found, and deleted unless it was a dry run.
+#[derive(Serialize, JsonSchema, Debug)]
+pub struct OrphanedDocketsReport {
+    pub dry_run: bool,
+    pub processed_without_raw_count: usize,
+    pub raw_without_processed_count: usize,
+    pub deleted_count: usize,
+    pub processed_without_raw: Vec<String>,
+    pub raw_without_processed: Vec<String>,
+}
+
+/// The docket objects under `prefix`, keyed by docket govid.
+pub async fn docket_keys_by_govid(
+    store: &impl JurisdictionObjectStore,
+    prefix: &str,
+) -> anyhow::Result<BTreeMap<String, String>> {
+    let keys = store.list_keys(prefix).await?;
+    Ok(keys
+        .into_iter()
+        .filter_map(|key| {
+            let govid = key.strip_prefix(prefix)?.strip_suffix(".json")?.to_string();
+            Some((govid, key))
+        })
+        .collect())
+}
+
+pub async fn cleanup_orphaned_dockets(
+    request: &CleanupOrphanedDockets,
+    store: &impl JurisdictionObjectStore,
+) -> anyhow::Result<OrphanedDocketsReport> {
+    let jurisdiction = &request.jurisdiction;
+    let raw_keys =
+        docket_keys_by_govid(store, &S3_KEY_LAYOUT.raw_docket_prefix(jurisdiction)).await?;
+    let processed_keys =
+        docket_keys_by_govid(store, &S3_KEY_LAYOUT.processed_docket_prefix(jurisdiction)).await?;
+
+    let processed_without_raw = processed_keys
+        .iter()
+        .filter(|(govid, _)| !raw_keys.contains_key(*govid))
+        .collect::<Vec<_>>();
+    let raw_without_processed = raw_keys
+        .iter()
+        .filter(|(govid, _)| !processed_keys.contains_key(*govid))
+        .collect::<Vec<_>>();
+    info!(
+        ?jurisdiction,
+        dry_run = request.dry_run,
+        processed_without_raw = processed_without_raw.len(),
+        raw_without_processed = raw_without_processed.len(),
+        "Found orphaned docket objects"
+    );
+
+    let mut deleted_count = 0;
+    if !request.dry_run {
+        let mut to_delete = processed_without_raw.clone();
+        if request.delete_unprocessed_raw {
+            to_delete.extend(raw_without_processed.iter().copied());
+        }
+        for (_, key) in to_delete {
+            store.delete_object(key).await?;
+            deleted_count += 1;
+        }
+        info!(
+            ?jurisdiction,
+            deleted_count, "Deleted orphaned docket objects"
+        );
+    }
+
+    let govids = |orphans: Vec<(&String, &String)>| {
+        orphans
+            .into_iter()
+            .map(|(govid, _)| govid.clone())
+            .collect::<Vec<_>>()
+    };
+    Ok(OrphanedDocketsReport {
+        dry_run: request.dry_run,
+        processed_without_raw_count: processed_without_raw.len(),
+        raw_without_processed_count: raw_without_processed.len(),
+        deleted_count,
+        processed_without_raw: govids(processed_without_raw),
+        raw_without_processed: govids(raw_without_processed),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::MemoryObjectStore;
+
+    #[tokio::test]
+    async fn test_orphaned_processed_docket_only_deleted_when_enabled() {
+        let jurisdiction = JurisdictionInfo::new_usa("cleanup_test", "zz");
+        let raw_key = |govid: &str| {
+            format!(
+                "{}{govid}.json",
+                S3_KEY_LAYOUT.raw_docket_prefix(&jurisdiction)
+            )
+        };
+        let processed_key = |govid: &str| {
+            format!(
+                "{}{govid}.json",
+                S3_KEY_LAYOUT.processed_docket_prefix(&jurisdiction)
+            )
+        };
+        let store = MemoryObjectStore::default();
+        store.insert(&raw_key("COMPLETE-1"), b"{}");
+        store.insert(&processed_key("COMPLETE-1"), b"{}");
+        store.insert(&processed_key("ORPHAN-1"), b"{}");
+        store.insert(&raw_key("UNPROCESSED-1"), b"{}");
+        let keys_before = store.keys();
+
+        let mut request = CleanupOrphanedDockets {
+            jurisdiction: jurisdiction.clone(),
+            dry_run: true,
+            delete_unprocessed_raw: false,
+        };
+        let dry_report = cleanup_orphaned_dockets(&request, &store).await.unwrap();
+        assert_eq!(dry_report.processed_without_raw, vec!["ORPHAN-1"]);
+        assert_eq!(dry_report.raw_without_processed, vec!["UNPROCESSED-1"]);
+        assert_eq!(dry_report.processed_without_raw_count, 1);
+        assert_eq!(dry_report.deleted_count, 0);
+        assert_eq!(store.keys(), keys_before);
+
+        request.dry_run = false;
+        let report = cleanup_orphaned_dockets(&request, &store).await.unwrap();
+        assert_eq!(report.deleted_count, 1);
+        let mut keys_after = vec![
+            raw_key("COMPLETE-1"),
+            processed_key("COMPLETE-1"),
+            raw_key("UNPROCESSED-1"),
+        ];
+        keys_after.sort();
+        assert_eq!(store.keys(), keys_after);
+    }
+}
@@ -1,11 +1,14 @@
 use anyhow::bail;
-use dokito_types::processed::{ProcessedGenericHuman, ProcessedGenericOrganization};
-use sqlx::{FromRow, PgPool, query_as};
-use std::collections::BTreeSet;
+use dokito_types::processed::{
+    ProcessedGenericDocket, ProcessedGenericFiling, ProcessedGenericHuman,
+    ProcessedGenericOrganization, ProcessedGenericPetitioner,
+};
+use sqlx::{FromRow, PgConnection, PgPool, query_as, query_scalar};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
 use uuid::Uuid;
 
-use crate::jurisdiction_schema_mapping::FixedJurisdiction;
-
 #[derive(FromRow)]
 struct HumanRecord {
     uuid: Uuid,
@@ -23,10 +26,9 @@ struct OrganizationRecord {
 
 pub async fn associate_individual_author_with_name(
     individual: &mut ProcessedGenericHuman,
-    fixed_jur: FixedJurisdiction,
+    pg_schema: &str,
     pool: &PgPool,
 ) -> Result<(), anyhow::Error> {
-    let pg_schema = fixed_jur.get_postgres_schema_name();
     if !individual.object_uuid.is_nil() {
         let author_id = individual.object_uuid;
         let result = query_as::<_, HumanRecord>(&format!("SELECT uuid, western_first_name, western_last_name, contact_emails, contact_phone_numbers FROM {pg_schema}.humans WHERE uuid=$1"))
@@ -120,10 +122,9 @@ pub async fn associate_individual_author_with_name(
 
 pub async fn associate_organization_with_name(
     org: &mut ProcessedGenericOrganization,
-    fixed_jur: FixedJurisdiction,
+    pg_schema: &str,
     pool: &PgPool,
 ) -> Result<(), anyhow::Error> {
-    let pg_schema = fixed_jur.get_postgres_schema_name();
     if !org.object_uuid.is_nil() {
         let org_id = org.object_uuid;
         let match_on_uuid = query_as::<_, OrganizationRecord>(&format!(
@@ -139,18 +140,6 @@ pub async fn associate_organization_with_name(
             return Ok(());
         }
     };
-    let org_name = org.truncated_org_name.as_str();
-
-    let match_on_org_name = query_as::<_, OrganizationRecord>(&format!(
-        "SELECT uuid, name FROM {pg_schema}.organizations WHERE name=$1"
-    ))
-    .bind(org_name)
-    .fetch_optional(pool)
-    .await?;
-    if let Some(matched_record) = match_on_org_name {
-        org.object_uuid = matched_record.uuid;
-        return Ok(());
-    }
     let mut provisional_uuid = org.object_uuid;
 
     if provisional_uuid.is_nil() {
@@ -158,8 +147,14 @@ pub async fn associate_organization_with_name(
     }
     let org_type = org.org_type.to_string();
 
-    sqlx::query(&format!(
-        "INSERT INTO {pg_schema}.organizations (uuid, name, aliases, description, artifical_person_type, org_suffix) VALUES ($1, $2, $3, $4, $5, $6)"
+    // Matching and inserting in one statement, so concurrent ingests of the same org can't both
+    // miss the lookup and insert duplicates. An existing org keeps its uuid and only picks up the
+    // suffix if it didn't have one yet.
+    let org_uuid = query_scalar::<_, Uuid>(&format!(
+        "INSERT INTO {pg_schema}.organizations AS orgs (uuid, name, aliases, description, artifical_person_type, org_suffix) VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT (name, artifical_person_type) DO UPDATE SET
+         org_suffix = CASE WHEN orgs.org_suffix = '' THEN EXCLUDED.org_suffix ELSE orgs.org_suffix END
+         RETURNING uuid"
     ))
     .bind(provisional_uuid)
     .bind(org.truncated_org_name.as_str())
@@ -167,24 +162,121 @@ pub async fn associate_organization_with_name(
     .bind("")
     .bind(&org_type)
     .bind(&org.org_suffix)
-    .execute(pool)
+    .fetch_one(pool)
     .await?;
-    org.object_uuid = provisional_uuid;
+    org.object_uuid = org_uuid;
 
     Ok(())
 }
 
+/// Whether associating an author may write to postgres.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AuthorAssociationMode {
+    /// Authors missing from postgres get inserted, and known humans pick up new contact info.
+    #[default]
+    Upsert,
+    /// Only looks authors up, for dry runs. Authors missing from postgres keep the uuid they had.
+    ReadOnly,
+}
+
+impl AuthorAssociationMode {
+    pub async fn associate_individual(
+        self,
+        individual: &mut ProcessedGenericHuman,
+        pg_schema: &str,
+        pool: &PgPool,
+    ) -> Result<(), anyhow::Error> {
+        match self {
+            AuthorAssociationMode::Upsert => {
+                associate_individual_author_with_name(individual, pg_schema, pool).await
+            }
+            AuthorAssociationMode::ReadOnly => {
+                let existing_uuid = query_scalar::<_, Uuid>(&format!(
+                    "SELECT uuid FROM {pg_schema}.humans WHERE western_first_name=$1 AND western_last_name = $2"
+                ))
+                .bind(&individual.western_first_name)
+                .bind(&individual.western_last_name)
+                .fetch_optional(pool)
+                .await?;
+                if let Some(existing_uuid) = existing_uuid {
+                    individual.object_uuid = existing_uuid;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub async fn associate_organization(
+        self,
+        org: &mut ProcessedGenericOrganization,
+        pg_schema: &str,
+        pool: &PgPool,
+    ) -> Result<(), anyhow::Error> {
+        match self {
+            AuthorAssociationMode::Upsert => {
+                associate_organization_with_name(org, pg_schema, pool).await
+            }
+            AuthorAssociationMode::ReadOnly => {
+                let existing_uuid = query_scalar::<_, Uuid>(&format!(
+                    "SELECT uuid FROM {pg_schema}.organizations WHERE name=$1 AND artifical_person_type=$2"
+                ))
+                .bind(org.truncated_org_name.as_str())
+                .bind(org.org_type.to_string())
+                .fetch_optional(pool)
+                .await?;
+                if let Some(existing_uuid) = existing_uuid {
+                    org.object_uuid = existing_uuid;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Org uuids resolved during one batch of ingests, keyed like the organizations upsert. An org
+/// shared by many dockets in the batch only gets upserted once, and dockets asking for it at the
+/// same time wait on that one upsert instead of racing it.
+#[derive(Default)]
+pub struct OrgUuidCache {
+    uuids: Mutex<HashMap<(String, String, String), Arc<OnceCell<Uuid>>>>,
+}
+
+impl OrgUuidCache {
+    pub async fn associate_organization_with_name(
+        &self,
+        org: &mut ProcessedGenericOrganization,
+        pg_schema: &str,
+        pool: &PgPool,
+    ) -> Result<(), anyhow::Error> {
+        let key = (
+            pg_schema.to_string(),
+            org.truncated_org_name.to_string(),
+            org.org_type.to_string(),
+        );
+        let cell = self.uuids.lock().unwrap().entry(key).or_default().clone();
+        let org_uuid = cell
+            .get_or_try_init(async || {
+                let mut uncached_org = org.clone();
+                associate_organization_with_name(&mut uncached_org, pg_schema, pool).await?;
+                Ok::<_, anyhow::Error>(uncached_org.object_uuid)
+            })
+            .await?;
+        org.object_uuid = *org_uuid;
+        Ok(())
+    }
+}
+
 pub async fn upload_docket_party_human_connection(
     upload_party: &mut ProcessedGenericHuman,
     parent_docket_uuid: Uuid,
-    fixed_jur: FixedJurisdiction,
+    pg_schema: &str,
     pool: &PgPool,
 ) -> Result<(), anyhow::Error> {
     if parent_docket_uuid.is_nil() {
         bail!("Uploading docket must have a non nil uuid.")
     }
 
-    associate_individual_author_with_name(upload_party, fixed_jur, pool).await?;
+    associate_individual_author_with_name(upload_party, pg_schema, pool).await?;
 
     if upload_party.object_uuid.is_nil() {
         unreachable!(
@@ -192,18 +284,8 @@ pub async fn upload_docket_party_human_connection(
         )
     }
 
-    let party_email = upload_party
-        .contact_emails
-        .first()
-        .map(|s| s.as_str())
-        .unwrap_or("");
-    let party_phone = upload_party
-        .contact_phone_numbers
-        .first()
-        .map(|s| s.as_str())
-        .unwrap_or("");
+    let (party_email, party_phone) = party_contacts(upload_party);
 
-    let pg_schema = fixed_jur.get_postgres_schema_name();
     sqlx::query(&format!(
         "INSERT INTO {pg_schema}.individual_offical_party_to_docket (docket_uuid, individual_uuid, party_email_contact, party_phone_contact) VALUES ($1, $2, $3, $4)"
     ))
@@ -217,16 +299,59 @@ pub async fn upload_docket_party_human_connection(
     Ok(())
 }
 
+/// Links a hearing officer to the docket, creating their human record if this is the first docket
+/// they show up on.
+pub async fn upload_docket_hearing_officer_connection(
+    hearing_officer: &mut ProcessedGenericHuman,
+    parent_docket_uuid: Uuid,
+    pg_schema: &str,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    if parent_docket_uuid.is_nil() {
+        bail!("Uploading docket must have a non nil uuid.")
+    }
+    associate_individual_author_with_name(hearing_officer, pg_schema, pool).await?;
+    sqlx::query(&format!(
+        "INSERT INTO {pg_schema}.docket_hearing_officers (docket_uuid, human_uuid) VALUES ($1, $2)
+         ON CONFLICT (docket_uuid, human_uuid) DO NOTHING"
+    ))
+    .bind(parent_docket_uuid)
+    .bind(hearing_officer.object_uuid)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The email and phone number stored on a docket party row, the first of each or empty.
+fn party_contacts(party: &ProcessedGenericHuman) -> (&str, &str) {
+    let party_email = party
+        .contact_emails
+        .first()
+        .map(|s| s.as_str())
+        .unwrap_or("");
+    let party_phone = party
+        .contact_phone_numbers
+        .first()
+        .map(|s| s.as_str())
+        .unwrap_or("");
+    (party_email, party_phone)
+}
+
 pub async fn upload_docket_petitioner_org_connection(
-    upload_petitioner: &mut ProcessedGenericOrganization,
+    upload_petitioner: &mut ProcessedGenericPetitioner,
     parent_docket_uuid: Uuid,
-    fixed_jur: FixedJurisdiction,
+    pg_schema: &str,
     pool: &PgPool,
+    org_cache: &OrgUuidCache,
 ) -> Result<(), anyhow::Error> {
     if parent_docket_uuid.is_nil() {
         bail!("Uploading filling must have a non nil uuid.")
     }
-    associate_organization_with_name(upload_petitioner, fixed_jur, pool).await?;
+    let role = upload_petitioner.role;
+    let upload_petitioner = &mut upload_petitioner.organization;
+    org_cache
+        .associate_organization_with_name(upload_petitioner, pg_schema, pool)
+        .await?;
     if upload_petitioner.object_uuid.is_nil() {
         unreachable!(
             "Uploading filling author must have a non nil uuid. This should be impossible because it just happened in the previous step"
@@ -234,12 +359,12 @@ pub async fn upload_docket_petitioner_org_connection(
     };
     let petitioner_uuid = upload_petitioner.object_uuid;
 
-    let pg_schema = fixed_jur.get_postgres_schema_name();
     sqlx::query(&format!(
-        "INSERT INTO {pg_schema}.docket_petitioned_by_org (docket_uuid, petitioner_uuid) VALUES ($1,$2)"
+        "INSERT INTO {pg_schema}.docket_petitioned_by_org (docket_uuid, petitioner_uuid, role) VALUES ($1,$2,$3)"
     ))
     .bind(parent_docket_uuid)
     .bind(petitioner_uuid)
+    .bind(role.as_str())
     .execute(pool)
     .await?;
     Ok(())
@@ -247,13 +372,16 @@ pub async fn upload_docket_petitioner_org_connection(
 pub async fn upload_filling_organization_author(
     upload_org_author: &mut ProcessedGenericOrganization,
     parent_filling_uuid: Uuid,
-    fixed_jur: FixedJurisdiction,
+    pg_schema: &str,
     pool: &PgPool,
+    org_cache: &OrgUuidCache,
 ) -> Result<(), anyhow::Error> {
     if parent_filling_uuid.is_nil() {
         bail!("Uploading filling must have a non nil uuid.")
     }
-    associate_organization_with_name(upload_org_author, fixed_jur, pool).await?;
+    org_cache
+        .associate_organization_with_name(upload_org_author, pg_schema, pool)
+        .await?;
     if upload_org_author.object_uuid.is_nil() {
         unreachable!(
             "Uploading filling author must have a non nil uuid. This should be impossible because it just happened in the previous step"
@@ -261,7 +389,6 @@ pub async fn upload_filling_organization_author(
     }
     let org_uuid = upload_org_author.object_uuid;
 
-    let pg_schema = fixed_jur.get_postgres_schema_name();
     sqlx::query(&format!(
             "INSERT INTO {pg_schema}.fillings_on_behalf_of_org_relation (author_organization_uuid, filling_uuid) VALUES ($1, $2)"
         ))
@@ -275,20 +402,19 @@ pub async fn upload_filling_organization_author(
 pub async fn upload_filling_human_author(
     upload_author: &mut ProcessedGenericHuman,
     parent_filling_uuid: Uuid,
-    fixed_jur: FixedJurisdiction,
+    pg_schema: &str,
     pool: &PgPool,
 ) -> Result<(), anyhow::Error> {
     if parent_filling_uuid.is_nil() {
         bail!("Uploading filling must have a non nil uuid.")
     }
-    associate_individual_author_with_name(upload_author, fixed_jur, pool).await?;
+    associate_individual_author_with_name(upload_author, pg_schema, pool).await?;
     if upload_author.object_uuid.is_nil() {
         unreachable!(
             "Uploading filling author must have a non nil uuid, this should be impossible dispite it being validated on the previous line."
         )
     }
 
-    let pg_schema = fixed_jur.get_postgres_schema_name();
     sqlx::query(&format!(
         "INSERT INTO {pg_schema}.fillings_filed_by_individual (human_uuid, filling_uuid) VALUES ($1, $2)"
     ))
@@ -299,9 +425,138 @@ pub async fn upload_filling_human_author(
     Ok(())
 }
 
+/// Replaces every author relation of a filling with its current authors. The authors must already
+/// be associated, so that each of them has a uuid.
+pub async fn replace_filling_author_relations(
+    filling: &ProcessedGenericFiling,
+    pg_schema: &str,
+    conn: &mut PgConnection,
+) -> Result<(), anyhow::Error> {
+    if filling.object_uuid.is_nil() {
+        bail!("Uploading filling must have a non nil uuid.")
+    }
+    sqlx::query(&format!(
+        "DELETE FROM {pg_schema}.fillings_on_behalf_of_org_relation WHERE filling_uuid = $1"
+    ))
+    .bind(filling.object_uuid)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query(&format!(
+        "DELETE FROM {pg_schema}.fillings_filed_by_individual WHERE filling_uuid = $1"
+    ))
+    .bind(filling.object_uuid)
+    .execute(&mut *conn)
+    .await?;
+
+    for org_author in filling.organization_authors.iter() {
+        if org_author.object_uuid.is_nil() {
+            bail!(
+                "Filling organization authors must be associated before their relations are replaced."
+            )
+        }
+        sqlx::query(&format!(
+            "INSERT INTO {pg_schema}.fillings_on_behalf_of_org_relation (author_organization_uuid, filling_uuid) VALUES ($1, $2)"
+        ))
+        .bind(org_author.object_uuid)
+        .bind(filling.object_uuid)
+        .execute(&mut *conn)
+        .await?;
+    }
+    for individual_author in filling.individual_authors.iter() {
+        if individual_author.object_uuid.is_nil() {
+            bail!(
+                "Filling individual authors must be associated before their relations are replaced."
+            )
+        }
+        sqlx::query(&format!(
+            "INSERT INTO {pg_schema}.fillings_filed_by_individual (human_uuid, filling_uuid) VALUES ($1, $2)"
+        ))
+        .bind(individual_author.object_uuid)
+        .bind(filling.object_uuid)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Rewrites the petitioner, party and hearing officer relations of a docket on `conn`, so they can be part of a
+/// transaction. The orgs and humans have to be associated beforehand.
+pub async fn replace_docket_relations(
+    case: &ProcessedGenericDocket,
+    docket_uuid: Uuid,
+    pg_schema: &str,
+    conn: &mut PgConnection,
+) -> Result<(), anyhow::Error> {
+    if docket_uuid.is_nil() {
+        bail!("Uploading docket must have a non nil uuid.")
+    }
+    sqlx::query(&format!(
+        "DELETE FROM {pg_schema}.docket_petitioned_by_org WHERE docket_uuid = $1"
+    ))
+    .bind(docket_uuid)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query(&format!(
+        "DELETE FROM {pg_schema}.individual_offical_party_to_docket WHERE docket_uuid = $1"
+    ))
+    .bind(docket_uuid)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query(&format!(
+        "DELETE FROM {pg_schema}.docket_hearing_officers WHERE docket_uuid = $1"
+    ))
+    .bind(docket_uuid)
+    .execute(&mut *conn)
+    .await?;
+
+    for petitioner in case.petitioner_list.iter() {
+        if petitioner.organization.object_uuid.is_nil() {
+            bail!("Docket petitioners must be associated before their relations are replaced.")
+        }
+        sqlx::query(&format!(
+            "INSERT INTO {pg_schema}.docket_petitioned_by_org (docket_uuid, petitioner_uuid, role) VALUES ($1,$2,$3)"
+        ))
+        .bind(docket_uuid)
+        .bind(petitioner.organization.object_uuid)
+        .bind(petitioner.role.as_str())
+        .execute(&mut *conn)
+        .await?;
+    }
+    for party in case.case_parties.iter() {
+        if party.object_uuid.is_nil() {
+            bail!("Docket parties must be associated before their relations are replaced.")
+        }
+        let (party_email, party_phone) = party_contacts(party);
+        sqlx::query(&format!(
+            "INSERT INTO {pg_schema}.individual_offical_party_to_docket (docket_uuid, individual_uuid, party_email_contact, party_phone_contact) VALUES ($1, $2, $3, $4)"
+        ))
+        .bind(docket_uuid)
+        .bind(party.object_uuid)
+        .bind(party_email)
+        .bind(party_phone)
+        .execute(&mut *conn)
+        .await?;
+    }
+    for hearing_officer in case.hearing_officers.iter() {
+        if hearing_officer.object_uuid.is_nil() {
+            bail!("Docket hearing officers must be associated before their relations are replaced.")
+        }
+        sqlx::query(&format!(
+            "INSERT INTO {pg_schema}.docket_hearing_officers (docket_uuid, human_uuid) VALUES ($1, $2)
+             ON CONFLICT (docket_uuid, human_uuid) DO NOTHING"
+        ))
+        .bind(docket_uuid)
+        .bind(hearing_officer.object_uuid)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::jurisdiction_schema_mapping::FixedJurisdiction;
     use sqlx::PgPool;
     use std::env;
     use uuid::Uuid;
@@ -332,8 +587,8 @@ mod tests {
             title: "Senior Manager".into(),
         };
 
-        let fixed_jur = FixedJurisdiction::NewYorkPuc; // Use a test jurisdiction
-        let result = associate_individual_author_with_name(&mut individual, fixed_jur, &pool).await;
+        let pg_schema = FixedJurisdiction::NewYorkPuc.get_postgres_schema_name(); // Use a test jurisdiction
+        let result = associate_individual_author_with_name(&mut individual, pg_schema, &pool).await;
 
         assert!(
             result.is_ok(),
@@ -343,7 +598,6 @@ mod tests {
         assert!(!individual.object_uuid.is_nil(), "UUID should be assigned");
 
         // Verify the person was actually inserted
-        let pg_schema = fixed_jur.get_postgres_schema_name();
         let db_record = query_as::<_, HumanRecord>(&format!(
             "SELECT uuid, western_first_name, western_last_name, contact_emails, contact_phone_numbers FROM {pg_schema}.humans WHERE uuid = $1"
         ))
@@ -358,6 +612,41 @@ mod tests {
         assert_eq!(record.contact_emails, vec!["test@example.com"]);
     }
 
+    #[tokio::test]
+    async fn test_concurrent_org_association_creates_single_row() {
+        let pool = setup_test_db().await;
+        let pg_schema = FixedJurisdiction::NewYorkPuc.get_postgres_schema_name();
+        let org_name = format!("Concurrent Test Org {}", Uuid::new_v4());
+
+        let mut orgs = (0..8)
+            .map(|_| ProcessedGenericOrganization {
+                truncated_org_name: org_name.clone().try_into().unwrap(),
+                org_suffix: "LLC".to_string(),
+                object_uuid: Uuid::nil(),
+                org_type: Default::default(),
+            })
+            .collect::<Vec<_>>();
+        let results = futures::future::join_all(
+            orgs.iter_mut()
+                .map(|org| associate_organization_with_name(org, pg_schema, &pool)),
+        )
+        .await;
+        assert!(results.iter().all(|res| res.is_ok()));
+
+        let org_count: i64 = sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM {pg_schema}.organizations WHERE name = $1"
+        ))
+        .bind(&org_name)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(org_count, 1);
+        assert!(
+            orgs.iter()
+                .all(|org| org.object_uuid == orgs[0].object_uuid)
+        );
+    }
+
     #[tokio::test]
     async fn test_query_structures_compile() {
         // This test verifies that our query structures compile correctly
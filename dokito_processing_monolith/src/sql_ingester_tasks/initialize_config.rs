@@ -5,11 +5,14 @@ use mycorrhiza_common::tasks::{ExecuteUserTask, display_error_as_json};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::health_gate::WORKER_HEALTH_GATE;
+
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct InitializeConfig {}
 #[async_trait]
 impl ExecuteUserTask for InitializeConfig {
     async fn execute_task(self: Box<Self>) -> Result<serde_json::Value, serde_json::Value> {
+        WORKER_HEALTH_GATE.wait_until_ready().await;
         test_s3_client_permissions().await;
         make_openscrapers_public().await?;
         Ok("Successfully Configured Everything".into())
@@ -0,0 +1 @@
+ALTER TABLE public.dockets DROP COLUMN IF EXISTS processor_version;
@@ -0,0 +1 @@
+ALTER TABLE public.dockets ADD COLUMN IF NOT EXISTS processor_version text NOT NULL DEFAULT ''::text;
@@ -0,0 +1,2 @@
+ALTER TABLE public.attachments DROP COLUMN IF EXISTS document_hash;
+DROP TABLE IF EXISTS public.documents CASCADE;
@@ -0,0 +1,14 @@
+-- Documents, one row per distinct attachment file shared by every attachment with that hash
+CREATE TABLE IF NOT EXISTS public.documents (
+  blake2b_hash text NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT now(),
+  file_extension text NOT NULL DEFAULT ''::text,
+  file_size_bytes bigint,
+  page_count integer,
+  CONSTRAINT documents_pkey PRIMARY KEY (blake2b_hash)
+);
+ALTER TABLE public.documents ENABLE ROW LEVEL SECURITY;
+
+ALTER TABLE public.attachments ADD COLUMN IF NOT EXISTS document_hash text;
+ALTER TABLE public.attachments DROP CONSTRAINT IF EXISTS attachments_document_hash_fkey;
+ALTER TABLE public.attachments ADD CONSTRAINT attachments_document_hash_fkey FOREIGN KEY (document_hash) REFERENCES public.documents(blake2b_hash) ON UPDATE CASCADE ON DELETE SET NULL;
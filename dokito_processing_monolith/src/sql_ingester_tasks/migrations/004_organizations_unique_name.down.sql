@@ -0,0 +1 @@
+ALTER TABLE public.organizations DROP CONSTRAINT IF EXISTS organizations_name_type_key;
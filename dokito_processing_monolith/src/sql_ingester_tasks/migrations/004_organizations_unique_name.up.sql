@@ -0,0 +1,56 @@
+-- Organizations are matched on name and type, so ingest can upsert them in a single statement.
+-- Duplicates ingested before the constraint existed are merged into the oldest row first.
+UPDATE public.fillings_on_behalf_of_org_relation AS relation
+SET author_organization_uuid = merged.survivor_uuid
+FROM (
+  SELECT uuid, first_value(uuid) OVER (PARTITION BY name, artifical_person_type ORDER BY created_at, uuid) AS survivor_uuid
+  FROM public.organizations
+) AS merged
+WHERE relation.author_organization_uuid = merged.uuid AND merged.uuid <> merged.survivor_uuid;
+
+UPDATE public.docket_petitioned_by_org AS relation
+SET petitioner_uuid = merged.survivor_uuid
+FROM (
+  SELECT uuid, first_value(uuid) OVER (PARTITION BY name, artifical_person_type ORDER BY created_at, uuid) AS survivor_uuid
+  FROM public.organizations
+) AS merged
+WHERE relation.petitioner_uuid = merged.uuid AND merged.uuid <> merged.survivor_uuid;
+
+UPDATE public.individual_offical_party_to_docket AS relation
+SET representing_org_uuid = merged.survivor_uuid
+FROM (
+  SELECT uuid, first_value(uuid) OVER (PARTITION BY name, artifical_person_type ORDER BY created_at, uuid) AS survivor_uuid
+  FROM public.organizations
+) AS merged
+WHERE relation.representing_org_uuid = merged.uuid AND merged.uuid <> merged.survivor_uuid;
+
+UPDATE public.individual_offical_party_to_docket AS relation
+SET employed_by_org = merged.survivor_uuid
+FROM (
+  SELECT uuid, first_value(uuid) OVER (PARTITION BY name, artifical_person_type ORDER BY created_at, uuid) AS survivor_uuid
+  FROM public.organizations
+) AS merged
+WHERE relation.employed_by_org = merged.uuid AND merged.uuid <> merged.survivor_uuid;
+
+-- A filling authored by two of the duplicates now has the same author twice
+DELETE FROM public.fillings_on_behalf_of_org_relation AS relation
+USING public.fillings_on_behalf_of_org_relation AS kept
+WHERE relation.filling_uuid = kept.filling_uuid
+  AND relation.author_organization_uuid = kept.author_organization_uuid
+  AND relation.relation_uuid > kept.relation_uuid;
+
+-- Same for a docket petitioned by two of the duplicates
+DELETE FROM public.docket_petitioned_by_org AS relation
+USING public.docket_petitioned_by_org AS kept
+WHERE relation.docket_uuid = kept.docket_uuid
+  AND relation.petitioner_uuid = kept.petitioner_uuid
+  AND relation.uuid > kept.uuid;
+
+DELETE FROM public.organizations AS duplicate
+USING public.organizations AS survivor
+WHERE duplicate.name = survivor.name
+  AND duplicate.artifical_person_type = survivor.artifical_person_type
+  AND (duplicate.created_at, duplicate.uuid) > (survivor.created_at, survivor.uuid);
+
+ALTER TABLE public.organizations DROP CONSTRAINT IF EXISTS organizations_name_type_key;
+ALTER TABLE public.organizations ADD CONSTRAINT organizations_name_type_key UNIQUE (name, artifical_person_type);
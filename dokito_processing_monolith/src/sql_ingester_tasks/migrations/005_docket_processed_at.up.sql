@@ -0,0 +1,2 @@
+-- When the docket's content last changed, reingesting an unchanged docket keeps the original value
+ALTER TABLE public.dockets ADD COLUMN IF NOT EXISTS processed_at timestamptz;
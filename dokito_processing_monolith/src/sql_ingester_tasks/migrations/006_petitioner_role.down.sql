@@ -0,0 +1 @@
+ALTER TABLE public.docket_petitioned_by_org DROP COLUMN IF EXISTS role;
@@ -0,0 +1,2 @@
+-- Whether the org petitioned the docket or joined it some other way, ie as an intervenor
+ALTER TABLE public.docket_petitioned_by_org ADD COLUMN IF NOT EXISTS role text NOT NULL DEFAULT 'petitioner';
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS public.docket_hearing_officers CASCADE;
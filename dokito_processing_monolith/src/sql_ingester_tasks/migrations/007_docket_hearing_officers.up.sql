@@ -0,0 +1,12 @@
+-- Hearing officers of a docket, parsed out of dockets.hearing_officer so they can be linked across dockets
+CREATE TABLE IF NOT EXISTS public.docket_hearing_officers (
+  uuid uuid NOT NULL DEFAULT gen_random_uuid(),
+  created_at timestamp with time zone NOT NULL DEFAULT now(),
+  docket_uuid uuid NOT NULL,
+  human_uuid uuid NOT NULL,
+  CONSTRAINT docket_hearing_officers_pkey PRIMARY KEY (uuid),
+  CONSTRAINT docket_hearing_officers_docket_uuid_human_uuid_key UNIQUE (docket_uuid, human_uuid),
+  CONSTRAINT docket_hearing_officers_docket_uuid_fkey FOREIGN KEY (docket_uuid) REFERENCES public.dockets(uuid) ON UPDATE CASCADE ON DELETE CASCADE,
+  CONSTRAINT docket_hearing_officers_human_uuid_fkey FOREIGN KEY (human_uuid) REFERENCES public.humans(uuid) ON UPDATE CASCADE ON DELETE CASCADE
+);
+ALTER TABLE public.docket_hearing_officers ENABLE ROW LEVEL SECURITY;
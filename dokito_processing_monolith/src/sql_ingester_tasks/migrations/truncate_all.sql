@@ -5,9 +5,11 @@ TRUNCATE public.fillings_filed_by_individual CASCADE;
 TRUNCATE public.fillings_filed_by_org_relation CASCADE;
 TRUNCATE public.fillings_on_behalf_of_org_relation CASCADE;
 TRUNCATE public.individual_offical_party_to_docket CASCADE;
+TRUNCATE public.docket_hearing_officers CASCADE;
 
 -- Child tables
 TRUNCATE public.attachments CASCADE;
+TRUNCATE public.documents CASCADE;
 
 -- Parent tables
 TRUNCATE public.fillings CASCADE;
@@ -3,16 +3,32 @@ use std::convert::identity;
 use aide::axum::ApiRouter;
 use mycorrhiza_common::tasks::routing::declare_task_route;
 
-use crate::sql_ingester_tasks::recreate_dokito_table_schema::RecreateDokitoTableSchema;
+use crate::sql_ingester_tasks::cleanup_orphaned_dockets::CleanupOrphanedDockets;
+use crate::sql_ingester_tasks::recreate_dokito_table_schema::{
+    MigrateJurisdictionSchemas, RecreateDokitoTableSchema,
+};
+use crate::sql_ingester_tasks::rename_jurisdiction::RenameJurisdiction;
+use crate::sql_ingester_tasks::split_docket_types::SplitDocketTypes;
+use crate::sql_ingester_tasks::verify_processed_against_raw::VerifyProcessedAgainstRaw;
 
+pub mod cleanup_orphaned_dockets;
 pub mod database_author_association;
 pub mod dokito_sql_connection;
 pub mod initialize_config;
 pub mod nypuc_ingest;
 pub mod recreate_dokito_table_schema;
+pub mod rename_jurisdiction;
+pub mod split_docket_types;
+pub mod verify_ingest;
+pub mod verify_processed_against_raw;
 
 pub fn add_sql_ingest_task_routes(router: ApiRouter) -> ApiRouter {
     let router = declare_task_route::<RecreateDokitoTableSchema>(router);
+    let router = declare_task_route::<MigrateJurisdictionSchemas>(router);
+    let router = declare_task_route::<RenameJurisdiction>(router);
+    let router = declare_task_route::<SplitDocketTypes>(router);
+    let router = declare_task_route::<CleanupOrphanedDockets>(router);
+    let router = declare_task_route::<VerifyProcessedAgainstRaw>(router);
 
     identity(router)
 }
@@ -1,47 +1,76 @@
 use std::{
     collections::HashSet,
+    env,
     hash::{DefaultHasher, Hash, Hasher},
     mem::take,
+    sync::LazyLock,
 };
 
+use anyhow::Context;
 use async_trait::async_trait;
+use aws_sdk_s3::Client as S3Client;
+use chrono::{DateTime, TimeDelta, Utc};
 use dokito_types::{
     env_vars::DIGITALOCEAN_S3,
     jurisdictions::JurisdictionInfo,
-    processed::{ProcessedGenericDocket, ProcessedGenericFiling, ProcessedGenericOrganization},
+    processed::{
+        PetitionerRole, ProcessedGenericAttachment, ProcessedGenericDocket, ProcessedGenericFiling,
+        ProcessedGenericPetitioner,
+    },
     raw::RawGenericDocket,
-    s3_stuff::{DocketAddress, list_raw_cases_for_jurisdiction},
 };
 use futures::future::join_all;
+use futures_util::{StreamExt, stream};
 use rand::{SeedableRng, rngs::SmallRng, seq::SliceRandom};
 use schemars::JsonSchema;
 use serde::Deserialize;
 use serde_json::Value;
-use sqlx::{PgPool, Pool, Postgres, query_scalar, types::Uuid};
+use sqlx::{PgConnection, PgPool, Pool, Postgres, query_scalar, types::Uuid};
+use thiserror::Error;
 
-use mycorrhiza_common::{
-    s3_generic::cannonical_location::{download_openscrapers_object, upload_object},
-    tasks::ExecuteUserTask,
-};
-use tokio::sync::Semaphore;
+use mycorrhiza_common::{hash::Blake2bHash, misc::is_env_var_true, tasks::ExecuteUserTask};
+use tokio::sync::{Semaphore, mpsc};
 use tracing::{info, warn};
 
 use crate::{
     data_processing_traits::Revalidate,
-    jurisdiction_schema_mapping::FixedJurisdiction,
-    processing::{attachments::OpenscrapersExtraData, process_case},
+    health_gate::WORKER_HEALTH_GATE,
+    indexes::attachment_url_index::lookup_hash_from_url,
+    jurisdiction_schema_mapping::{FixedJurisdiction, InvalidSchemaName, validate_schema_name},
+    processing::{
+        attachments::{AttachmentFetchOrder, OpenscrapersExtraData},
+        process_case,
+    },
+    s3_stuff::{
+        DocketAddress, download_openscrapers_object, list_raw_cases_for_jurisdiction, upload_object,
+    },
     sql_ingester_tasks::{
-        database_author_association::*, dokito_sql_connection::get_dokito_pool,
+        database_author_association::*, dokito_sql_connection::get_dokito_pool_for,
         recreate_dokito_table_schema::delete_all_data,
     },
+    throughput::{PROCESSING_THROUGHPUT, ThroughputStage},
 };
 
+/// The first `max_bytes` bytes of `s` at most, cut back to the nearest char boundary. Used to keep
+/// debug printed errors and audited LLM calls short in the logs.
+pub fn truncate_safe(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
 #[derive(Clone, Copy, Deserialize, JsonSchema)]
 #[repr(transparent)]
 pub struct FixedJurisdictionPurgePrevious(pub FixedJurisdiction);
 #[async_trait]
 impl ExecuteUserTask for FixedJurisdictionPurgePrevious {
     async fn execute_task(self: Box<Self>) -> Result<Value, Value> {
+        WORKER_HEALTH_GATE.wait_until_ready().await;
         let res = ingest_all_fixed_jurisdiction_data(self.0, true).await;
         match res {
             Ok(()) => {
@@ -50,7 +79,7 @@ impl ExecuteUserTask for FixedJurisdictionPurgePrevious {
             }
             Err(err) => {
                 let err_debug = format!("{:?}", err);
-                tracing::error!(error= % err, error_debug= &err_debug[..500],"Encountered error in ny_ingest");
+                tracing::error!(error= % err, error_debug= truncate_safe(&err_debug, 500),"Encountered error in ny_ingest");
                 Err(err.to_string().into())
             }
         }
@@ -72,6 +101,7 @@ pub struct GetMissingDocketsForFixedJurisdiction(pub FixedJurisdiction);
 #[async_trait]
 impl ExecuteUserTask for GetMissingDocketsForFixedJurisdiction {
     async fn execute_task(self: Box<Self>) -> Result<Value, Value> {
+        WORKER_HEALTH_GATE.wait_until_ready().await;
         let res = ingest_all_fixed_jurisdiction_data(self.0, false).await;
         match res {
             Ok(()) => {
@@ -80,7 +110,7 @@ impl ExecuteUserTask for GetMissingDocketsForFixedJurisdiction {
             }
             Err(err) => {
                 let err_debug = format!("{:?}", err);
-                tracing::error!(error= % err, error_debug= &err_debug[..500],"Encountered error in ny_ingest");
+                tracing::error!(error= % err, error_debug= truncate_safe(&err_debug, 500),"Encountered error in ny_ingest");
                 Err(err.to_string().into())
             }
         }
@@ -102,7 +132,7 @@ pub async fn ingest_all_fixed_jurisdiction_data(
 ) -> anyhow::Result<()> {
     info!("Got request to ingest all nypuc data.");
 
-    let pool = get_dokito_pool().await?;
+    let pool = get_dokito_pool_for(fixed_jur).await?;
     info!("Created pg pool");
 
     // Drop all existing tables first
@@ -130,19 +160,47 @@ pub async fn ingest_all_fixed_jurisdiction_data(
     let cases_to_process_len = case_govids.len();
     info!(total_cases = %original_caselist_length, cases_to_process= %cases_to_process_len,"Filtered down original raw cases to a subset that is not present in the database.");
 
-    let max_simultaneous_cases = Semaphore::new(20);
-    let execute_case_wraped = async |case_id: String| {
-        let _perm = max_simultaneous_cases.acquire().await;
-        ingest_wrapped_fixed_jurisdiction_data(fixed_jur, &case_id, pool, ignore_existing).await
+    let org_cache = OrgUuidCache::default();
+    let process_case = async |case_id: String| {
+        let case_address = DocketAddress {
+            jurisdiction: jurisdiction_info.clone(),
+            docket_govid: case_id,
+        };
+        get_processed_case_or_process_if_not_existing(&case_address, *INGEST_PROCESSED_MAX_AGE)
+            .await
+            .inspect_err(|e| {
+                let err_debug = format!("{:?}", e);
+                tracing::error!(case_id = %case_address.docket_govid, error = %e, error_debug = truncate_safe(&err_debug, 500), "Failed to parse case")
+            })
     };
-    let future_cases = case_govids.into_iter().map(execute_case_wraped);
-    let futures_count = join_all(future_cases).await.len();
+    let ingest_case = async |mut case: ProcessedGenericDocket| {
+        ingest_sql_case_with_retries(
+            &mut case,
+            fixed_jur,
+            None,
+            pool,
+            &org_cache,
+            ignore_existing,
+            *CASE_INGEST_RETRIES,
+        )
+        .await
+        .inspect_err(|e| {
+            let err_debug = format!("{:?}", e);
+            tracing::error!(case_id = %case.case_govid, error = %e, error_debug = truncate_safe(&err_debug, 500), "Failed to ingest case, dispite retries.");
+        })
+    };
+    let counts = process_then_ingest_pipeline(
+        case_govids,
+        process_case,
+        ingest_case,
+        BULK_PROCESS_CONCURRENCY,
+        BULK_INGEST_WORKERS,
+        *INGEST_PIPELINE_CAPACITY,
+    )
+    .await;
 
-    info!(
-        futures_count,
-        "Successfully completed all sql ingest futures."
-    );
-    info!(total_dockets = %original_caselist_length, missing_cases = % cases_to_process_len, attempted_cases = % futures_count,"Out of all the cases, we wanted to proccess the missing cases, and tried to process:");
+    info!(?counts, "Successfully completed all sql ingest futures.");
+    info!(total_dockets = %original_caselist_length, missing_cases = % cases_to_process_len, attempted_cases = % counts.attempted,"Out of all the cases, we wanted to proccess the missing cases, and tried to process:");
     Ok(())
 }
 
@@ -166,15 +224,44 @@ async fn filter_out_existing_dokito_cases(
     Ok(())
 }
 
+/// Cached processed dockets older than this many hours get reprocessed by the bulk ingest instead
+/// of being ingested as is. Unset means cached dockets are used no matter their age.
+pub static INGEST_PROCESSED_MAX_AGE: LazyLock<Option<TimeDelta>> = LazyLock::new(|| {
+    env::var("INGEST_PROCESSED_MAX_AGE_HOURS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .and_then(TimeDelta::try_hours)
+});
+
+/// Ages the cached docket on `processed_at`, which every processing run moves forward, so a
+/// docket reprocessed for being too old is fresh again even when its content didn't change.
+fn is_processed_docket_stale(
+    docket: &ProcessedGenericDocket,
+    max_age: Option<TimeDelta>,
+    now: DateTime<Utc>,
+) -> bool {
+    max_age.is_some_and(|max_age| now - docket.processed_at > max_age)
+}
+
 async fn get_processed_case_or_process_if_not_existing(
     case_address: &DocketAddress,
+    max_age: Option<TimeDelta>,
 ) -> anyhow::Result<ProcessedGenericDocket> {
     let s3_client = DIGITALOCEAN_S3.make_s3_client().await;
-    let case_res =
-        download_openscrapers_object::<ProcessedGenericDocket>(&s3_client, case_address).await;
-    let docket = match case_res {
-        Ok(docket) => Ok(docket),
-        Err(_) => {
+    let cached_docket =
+        download_openscrapers_object::<ProcessedGenericDocket>(&s3_client, case_address)
+            .await
+            .ok()
+            .filter(|docket| {
+                let is_stale = is_processed_docket_stale(docket, max_age, Utc::now());
+                if is_stale {
+                    info!(docket_govid = %docket.case_govid, processed_at = %docket.processed_at, "Cached processed docket is too old, reprocessing it.");
+                }
+                !is_stale
+            });
+    let docket = match cached_docket {
+        Some(docket) => Ok(docket),
+        None => {
             let jurisdiction = case_address.jurisdiction.clone();
             let raw_case =
                 download_openscrapers_object::<RawGenericDocket>(&s3_client, case_address).await?;
@@ -183,6 +270,7 @@ async fn get_processed_case_or_process_if_not_existing(
                 s3_client,
                 jurisdiction_info: jurisdiction,
                 fixed_jurisdiction,
+                attachment_fetch_order: AttachmentFetchOrder::default(),
             };
 
             process_case(raw_case, extra_info).await
@@ -197,38 +285,87 @@ async fn get_processed_case_or_process_if_not_existing(
     }
 }
 
-async fn ingest_wrapped_fixed_jurisdiction_data(
-    fixed_jur: FixedJurisdiction,
-    case_id: &str,
-    pool: &PgPool,
-    ignore_existing: bool,
-) {
-    let case_address = DocketAddress {
-        jurisdiction: JurisdictionInfo::from(fixed_jur),
-        docket_govid: case_id.to_string(),
+const BULK_PROCESS_CONCURRENCY: usize = 20;
+const BULK_INGEST_WORKERS: usize = 20;
+
+/// How many processed dockets a bulk ingest lets pile up waiting for postgres. Processing pauses
+/// once this many are waiting, so memory stays bounded no matter how many dockets are ingested.
+pub static INGEST_PIPELINE_CAPACITY: LazyLock<usize> = LazyLock::new(|| {
+    env::var("INGEST_PIPELINE_CAPACITY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&v| v > 0)
+        .unwrap_or(20)
+});
+
+#[derive(Debug, Default, PartialEq)]
+pub struct PipelineCounts {
+    pub attempted: usize,
+    pub processed: usize,
+    pub process_failures: usize,
+    pub ingested: usize,
+    pub ingest_failures: usize,
+}
+
+/// Processes every item and ingests the results, handing processed dockets from one stage to the
+/// other over a channel holding at most `capacity` of them. Failures are counted and left for
+/// the callbacks to report.
+pub async fn process_then_ingest_pipeline<T>(
+    items: Vec<T>,
+    process: impl AsyncFn(T) -> anyhow::Result<ProcessedGenericDocket>,
+    ingest: impl AsyncFn(ProcessedGenericDocket) -> anyhow::Result<()>,
+    process_concurrency: usize,
+    ingest_workers: usize,
+    capacity: usize,
+) -> PipelineCounts {
+    let mut counts = PipelineCounts {
+        attempted: items.len(),
+        ..Default::default()
     };
-    let case_res = get_processed_case_or_process_if_not_existing(&case_address).await;
-    match case_res {
-        Ok(mut case) => {
-            const CASE_RETRIES: usize = 3;
-            if let Err(e) = ingest_sql_case_with_retries(
-                &mut case,
-                fixed_jur,
-                pool,
-                ignore_existing,
-                CASE_RETRIES,
-            )
-            .await
-            {
-                let err_debug = format!("{:?}", e);
-                tracing::error!(case_id = %case_id, error = %e, error_debug = &err_debug[..500], "Failed to ingest case, dispite retries.");
+    let (sender, receiver) = mpsc::channel(capacity.max(1));
+
+    let produce = async {
+        let (mut processed, mut failures) = (0, 0);
+        let mut results = stream::iter(items)
+            .map(async |item| process(item).await)
+            .buffer_unordered(process_concurrency.max(1));
+        while let Some(res) = results.next().await {
+            match res {
+                Ok(docket) => {
+                    processed += 1;
+                    // Waits here while the channel is full, which keeps new dockets from being
+                    // processed until ingest catches up.
+                    if sender.send(docket).await.is_err() {
+                        break;
+                    }
+                }
+                Err(_) => failures += 1,
             }
         }
-        Err(e) => {
-            let err_debug = format!("{:?}", e);
-            tracing::error!(case_id = %case_id, error = %e, error_debug = &err_debug[..500], "Failed to parse case")
+        drop(sender);
+        (processed, failures)
+    };
+    let consume = async {
+        let (mut ingested, mut failures) = (0, 0);
+        let mut results = stream::unfold(receiver, async |mut receiver| {
+            receiver.recv().await.map(|docket| (docket, receiver))
+        })
+        .map(async |docket| ingest(docket).await)
+        .buffer_unordered(ingest_workers.max(1));
+        while let Some(res) = results.next().await {
+            match res {
+                Ok(()) => ingested += 1,
+                Err(_) => failures += 1,
+            }
         }
-    }
+        (ingested, failures)
+    };
+
+    (
+        (counts.processed, counts.process_failures),
+        (counts.ingested, counts.ingest_failures),
+    ) = tokio::join!(produce, consume);
+    counts
 }
 
 fn generate_hash(x: &impl Hash) -> u64 {
@@ -237,34 +374,75 @@ fn generate_hash(x: &impl Hash) -> u64 {
     hasher.finish()
 }
 
+/// Uploads a docket that picked up uuids while being ingested, so the processed copy in S3
+/// carries them too.
+async fn upload_ingested_docket(
+    s3_client: &S3Client,
+    case: &ProcessedGenericDocket,
+    fixed_jur: FixedJurisdiction,
+) {
+    let addr = DocketAddress {
+        docket_govid: case.case_govid.to_string(),
+        jurisdiction: fixed_jur.into(),
+    };
+    // If this doesnt work everything should still be okay
+    let _ = upload_object(s3_client, &addr, case).await;
+}
+
+const DEFAULT_CASE_INGEST_RETRIES: usize = 3;
+/// How many times a docket ingest is attempted before giving up on it.
+pub static CASE_INGEST_RETRIES: LazyLock<usize> = LazyLock::new(|| {
+    env::var("CASE_INGEST_RETRIES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&tries| tries > 0)
+        .unwrap_or(DEFAULT_CASE_INGEST_RETRIES)
+});
+
+/// Ingests a docket, deleting whatever a failed attempt left behind before retrying. The rows go
+/// to `target_schema` when one is given instead of the jurisdiction's own schema, which is meant
+/// for trial ingests that shouldn't touch production data.
 pub async fn ingest_sql_case_with_retries(
     case: &mut ProcessedGenericDocket,
     fixed_jur: FixedJurisdiction,
+    target_schema: Option<&str>,
     pool: &Pool<Postgres>,
-    ignore_existing: bool,
+    org_cache: &OrgUuidCache,
+    _ignore_existing: bool,
     tries: usize,
 ) -> anyhow::Result<()> {
+    // Zero tries would otherwise report success without ingesting anything.
+    let tries = tries.max(1);
     let initial_hash = generate_hash(&*case);
     let mut return_res = Ok(());
-    let pg_schema = fixed_jur.get_postgres_schema_name();
+    let pg_schema = match target_schema {
+        Some(target_schema) => validate_schema_name(target_schema)?,
+        None => fixed_jur.get_postgres_schema_name(),
+    };
     for remaining_tries in (0..tries).rev() {
-        match ingest_sql_fixed_jurisdiction_case(case, fixed_jur, pool, ignore_existing).await {
+        match ingest_docket_rows(
+            case,
+            pg_schema,
+            pool,
+            org_cache,
+            *INGEST_SKIP_UNCHANGED_ROWS,
+        )
+        .await
+        {
             Ok(val) => {
+                PROCESSING_THROUGHPUT.record_docket(ThroughputStage::Ingested, case);
                 let hash_post_upload = generate_hash(&*case);
-                if hash_post_upload != initial_hash {
+                // The uuids of an ingest into another schema don't belong in the canonical
+                // processed docket.
+                if hash_post_upload != initial_hash && target_schema.is_none() {
                     let s3_client = DIGITALOCEAN_S3.make_s3_client().await;
-                    let addr = DocketAddress {
-                        docket_govid: case.case_govid.to_string(),
-                        jurisdiction: fixed_jur.into(),
-                    };
-                    // If this doesnt work everything should still be okay
-                    let _ = upload_object(&s3_client, &addr, &*case).await;
+                    upload_ingested_docket(&s3_client, case, fixed_jur).await;
                 }
                 return Ok(val);
             }
             Err(err) => {
-                let mut error_debug = format!("{:?}", &err);
-                error_debug.truncate(200);
+                let error_debug = format!("{:?}", &err);
+                let error_debug = truncate_safe(&error_debug, 200);
                 warn!(docket_govid=%case.case_govid, %remaining_tries, %err, err_debug=%error_debug,"Encountered error while processing docket, retrying.");
                 return_res = Err(err);
                 let existing_docket: Option<Uuid> = query_scalar(&format!(
@@ -286,7 +464,38 @@ pub async fn ingest_sql_case_with_retries(
             }
         }
     }
-    return_res
+    return_res.with_context(|| format!("Docket ingest failed after {tries} attempts"))
+}
+
+/// Makes sure the deduplicated documents row for an attachment's file exists, returning the hash
+/// the attachment row should reference. Existing rows are left untouched, so the first attachment
+/// seen with a given hash decides its metadata.
+async fn upsert_document(
+    attachment: &ProcessedGenericAttachment,
+    hash: Blake2bHash,
+    pg_schema: &str,
+    conn: &mut PgConnection,
+) -> anyhow::Result<String> {
+    let hashstr = hash.to_string();
+    // The url index already holds the metadata from when the file was downloaded, so use it for
+    // the size when it agrees on the hash.
+    let file_size_bytes = match attachment.url.is_empty() {
+        true => None,
+        false => lookup_hash_from_url(&attachment.url)
+            .await
+            .filter(|raw_attach| raw_attach.hash.to_string() == hashstr)
+            .map(|raw_attach| raw_attach.file_size_bytes as i64),
+    };
+    sqlx::query(&format!(
+        "INSERT INTO {pg_schema}.documents (blake2b_hash, file_extension, file_size_bytes) VALUES ($1, $2, $3)
+         ON CONFLICT (blake2b_hash) DO NOTHING"
+    ))
+    .bind(&hashstr)
+    .bind(attachment.document_extension.to_string())
+    .bind(file_size_bytes)
+    .execute(conn)
+    .await?;
+    Ok(hashstr)
 }
 
 pub fn bubble_error<T, E, I>(results: I) -> Result<(), E>
@@ -298,6 +507,50 @@ where
     }
     Ok(())
 }
+/// When set, reingesting a docket only rewrites the docket, filling and attachment rows whose
+/// values actually changed, instead of updating every row and bumping updated_at.
+pub static INGEST_SKIP_UNCHANGED_ROWS: LazyLock<bool> =
+    LazyLock::new(|| is_env_var_true("INGEST_SKIP_UNCHANGED_ROWS"));
+
+/// The `ON CONFLICT DO UPDATE` condition that skips the update when none of `columns` changed.
+/// Skipped rows return nothing from `RETURNING`, so callers fall back to the uuid they inserted.
+fn changed_rows_only_clause(skip_unchanged: bool, columns: &[&str]) -> String {
+    if !skip_unchanged {
+        return String::new();
+    }
+    format!("WHERE {}", rows_differ_condition(columns))
+}
+
+/// True in an upsert when any of `columns` differs between the existing and the incoming row.
+fn rows_differ_condition(columns: &[&str]) -> String {
+    let existing = columns
+        .iter()
+        .map(|col| format!("existing.{col}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let excluded = columns
+        .iter()
+        .map(|col| format!("EXCLUDED.{col}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("({existing}) IS DISTINCT FROM ({excluded})")
+}
+
+/// The docket columns holding its content, `processed_at` only moves when one of them changes.
+const DOCKET_CONTENT_COLUMNS: &[&str] = &[
+    "docket_govid",
+    "docket_description",
+    "docket_title",
+    "industry",
+    "hearing_officer",
+    "opened_date",
+    "closed_date",
+    "petitioner_strings",
+    "docket_type",
+    "docket_subtype",
+    "processor_version",
+];
+
 pub async fn ingest_sql_fixed_jurisdiction_case(
     case: &mut ProcessedGenericDocket,
     fixed_jur: FixedJurisdiction,
@@ -305,26 +558,127 @@ pub async fn ingest_sql_fixed_jurisdiction_case(
     _ignore_existing: bool,
 ) -> anyhow::Result<()> {
     let pg_schema = fixed_jur.get_postgres_schema_name();
-    let petitioner_list: &mut [ProcessedGenericOrganization] = &mut case.petitioner_list;
-    let petitioner_strings = petitioner_list
+    let org_cache = OrgUuidCache::default();
+    ingest_docket_rows(
+        case,
+        pg_schema,
+        pool,
+        &org_cache,
+        *INGEST_SKIP_UNCHANGED_ROWS,
+    )
+    .await
+}
+
+async fn ingest_docket_rows(
+    case: &mut ProcessedGenericDocket,
+    pg_schema: &str,
+    pool: &Pool<Postgres>,
+    org_cache: &OrgUuidCache,
+    skip_unchanged: bool,
+) -> anyhow::Result<()> {
+    let docket_uuid = {
+        let mut conn = pool.acquire().await?;
+        upsert_docket_row(case, pg_schema, &mut conn, skip_unchanged).await?
+    };
+    let petitioner_list: &mut [ProcessedGenericPetitioner] = &mut case.petitioner_list;
+
+    let simultaneous_party_and_individuals = Semaphore::new(4);
+    let petitioner_futures = petitioner_list.iter_mut().map(async |petitioner| {
+        let _permit = simultaneous_party_and_individuals.acquire().await;
+        upload_docket_petitioner_org_connection(petitioner, docket_uuid, pg_schema, pool, org_cache)
+            .await
+    });
+    let petitioner_results = join_all(petitioner_futures).await;
+    bubble_error(petitioner_results.into_iter())?;
+
+    let party_futures = case.case_parties.iter_mut().map(async |party| {
+        let _permit = simultaneous_party_and_individuals.acquire().await;
+        upload_docket_party_human_connection(party, docket_uuid, pg_schema, pool).await
+    });
+    let party_results = join_all(party_futures).await;
+    bubble_error(party_results)?;
+
+    for hearing_officer in case.hearing_officers.iter_mut() {
+        upload_docket_hearing_officer_connection(hearing_officer, docket_uuid, pg_schema, pool)
+            .await?;
+    }
+
+    let process_filling_closure =
+        async |filling: &mut ProcessedGenericFiling| -> Result<(), anyhow::Error> {
+            let filling_uuid = {
+                let mut conn = pool.acquire().await?;
+                upsert_filling_rows(
+                    filling,
+                    docket_uuid,
+                    case.case_govid.as_str(),
+                    pg_schema,
+                    &mut conn,
+                    skip_unchanged,
+                )
+                .await?
+            };
+
+            // Associate individual authors using the proper association functions
+            for individual_author in filling.individual_authors.iter_mut() {
+                upload_filling_human_author(individual_author, filling_uuid, pg_schema, pool)
+                    .await?;
+            }
+
+            // Associate organization authors using the proper association functions
+            for org_author in filling.organization_authors.iter_mut() {
+                upload_filling_organization_author(
+                    org_author,
+                    filling_uuid,
+                    pg_schema,
+                    pool,
+                    org_cache,
+                )
+                .await?;
+            }
+            Ok(())
+        };
+    let simultaneous_file_uploads = Semaphore::new(3);
+    let filling_futures = case.filings.iter_mut().map(async |filling| {
+        let _permit = simultaneous_file_uploads.acquire().await?;
+        process_filling_closure(filling).await
+    });
+    let filling_results = join_all(filling_futures).await;
+    bubble_error(filling_results.into_iter())?;
+
+    tracing::info!(govid=%case.case_govid, uuid=%docket_uuid,"Successfully processed case with no errors");
+    Ok(())
+}
+
+/// The names stored in a docket's `petitioner_strings`, sorted and without duplicates so the
+/// order petitioners were extracted in doesn't show up as a change on reingest. Intervenors and
+/// respondents only show up in docket_petitioned_by_org, with their role.
+fn docket_petitioner_strings(case: &ProcessedGenericDocket) -> Vec<String> {
+    let mut petitioner_strings = case
+        .petitioner_list
         .iter()
-        .map(|n| n.truncated_org_name.to_string())
+        .filter(|n| n.role == PetitionerRole::Petitioner)
+        .map(|n| n.organization.truncated_org_name.to_string())
         .collect::<Vec<_>>();
-    let mut case_type = case.case_type.clone();
-    let mut case_subtype = case.case_subtype.clone();
-    if let Some(actual_subtype_value) = case.extra_metadata.get("matter_subtype")
-        && let Some(actual_subtype) = actual_subtype_value.as_str()
-        && let Some(actual_type_value) = case.extra_metadata.get("matter_type")
-        && let Some(actual_type) = actual_type_value.as_str()
-    {
-        case_type = actual_type.to_string();
-        case_subtype = actual_subtype.to_string();
-    }
+    petitioner_strings.sort();
+    petitioner_strings.dedup();
+    petitioner_strings
+}
+
+/// Upserts the docket row itself, returning its uuid. Everything hanging off the docket is left
+/// to the caller.
+async fn upsert_docket_row(
+    case: &ProcessedGenericDocket,
+    pg_schema: &str,
+    conn: &mut PgConnection,
+    skip_unchanged: bool,
+) -> anyhow::Result<Uuid> {
+    let petitioner_strings = docket_petitioner_strings(case);
 
-    // Upsert docket
+    let docket_only_if_changed = changed_rows_only_clause(skip_unchanged, DOCKET_CONTENT_COLUMNS);
+    let docket_content_changed = rows_differ_condition(DOCKET_CONTENT_COLUMNS);
     let docket_uuid: Uuid = query_scalar(
-        &format!("INSERT INTO {pg_schema}.dockets (uuid, docket_govid, docket_description, docket_title, industry, hearing_officer, opened_date, closed_date, petitioner_strings, docket_type, docket_subtype )
-         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        &format!("INSERT INTO {pg_schema}.dockets AS existing (uuid, docket_govid, docket_description, docket_title, industry, hearing_officer, opened_date, closed_date, petitioner_strings, docket_type, docket_subtype, processor_version, processed_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
          ON CONFLICT (uuid) DO UPDATE SET
          docket_govid = EXCLUDED.docket_govid,
          docket_description = EXCLUDED.docket_description,
@@ -335,7 +689,11 @@ pub async fn ingest_sql_fixed_jurisdiction_case(
          closed_date = EXCLUDED.closed_date,
          petitioner_strings = EXCLUDED.petitioner_strings,
          docket_type = EXCLUDED.docket_type,
-         docket_subtype = EXCLUDED.docket_subtype
+         docket_subtype = EXCLUDED.docket_subtype,
+         processor_version = EXCLUDED.processor_version,
+         processed_at = CASE WHEN existing.processed_at IS NULL OR {docket_content_changed}
+             THEN EXCLUDED.processed_at ELSE existing.processed_at END
+         {docket_only_if_changed}
          RETURNING uuid")
     )
     .bind(case.object_uuid)
@@ -347,128 +705,1089 @@ pub async fn ingest_sql_fixed_jurisdiction_case(
     .bind(case.opened_date)
     .bind(case.closed_date)
     .bind(&petitioner_strings)
-    .bind(case_type)
-    .bind(case_subtype)
-    .fetch_one(pool)
-    .await?;
+    .bind(&case.case_type)
+    .bind(&case.case_subtype)
+    .bind(&case.processor_version)
+    .bind(case.processed_at)
+    .fetch_optional(conn)
+    .await?
+    .unwrap_or(case.object_uuid);
     if docket_uuid != case.object_uuid {
         info!("Created new uuid for docket.")
     }
+    Ok(docket_uuid)
+}
 
-    let simultaneous_party_and_individuals = Semaphore::new(4);
-    let petitioner_futures = petitioner_list.iter_mut().map(async |petitioner| {
-        let _permit = simultaneous_party_and_individuals.acquire().await;
-        upload_docket_petitioner_org_connection(petitioner, docket_uuid, fixed_jur, pool).await
-    });
-    let petitioner_results = join_all(petitioner_futures).await;
-    bubble_error(petitioner_results.into_iter())?;
-
-    let party_futures = case.case_parties.iter_mut().map(async |party| {
-        let _permit = simultaneous_party_and_individuals.acquire().await;
-        upload_docket_party_human_connection(party, docket_uuid, fixed_jur, pool).await
-    });
-    let party_results = join_all(party_futures).await;
-    bubble_error(party_results)?;
+/// Upserts a filling row and its attachment rows, returning the filling's uuid. Author relations
+/// are left to the caller.
+async fn upsert_filling_rows(
+    filling: &mut ProcessedGenericFiling,
+    docket_uuid: Uuid,
+    docket_govid: &str,
+    pg_schema: &str,
+    conn: &mut PgConnection,
+    skip_unchanged: bool,
+) -> anyhow::Result<Uuid> {
+    let filling_only_if_changed = changed_rows_only_clause(
+        skip_unchanged,
+        &[
+            "docket_uuid",
+            "docket_govid",
+            "individual_author_strings",
+            "organization_author_strings",
+            "filed_date",
+            "filling_type",
+            "filling_name",
+            "filling_description",
+            "openscrapers_id",
+            "filling_govid",
+        ],
+    );
+    let attachment_only_if_changed = changed_rows_only_clause(
+        skip_unchanged,
+        &[
+            "parent_filling_uuid",
+            "blake2b_hash",
+            "attachment_file_extension",
+            "attachment_file_name",
+            "attachment_title",
+            "attachment_url",
+            "openscrapers_id",
+            "document_hash",
+            "attachment_type",
+            "attachment_subtype",
+        ],
+    );
+    let individual_author_strings = filling
+        .individual_authors
+        .iter()
+        .map(|s| s.human_name.to_string())
+        .collect::<Vec<_>>();
+    let organization_author_strings = filling
+        .organization_authors
+        .iter()
+        .map(|s| s.truncated_org_name.to_string())
+        .collect::<Vec<_>>();
+    let filling_uuid: Uuid = query_scalar(
+        &format!("INSERT INTO {pg_schema}.fillings AS existing (uuid, docket_uuid, docket_govid, individual_author_strings, organization_author_strings, filed_date, filling_type, filling_name, filling_description, openscrapers_id, filling_govid)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+         ON CONFLICT (uuid) DO UPDATE SET
+         docket_uuid = EXCLUDED.docket_uuid,
+         docket_govid = EXCLUDED.docket_govid,
+         individual_author_strings = EXCLUDED.individual_author_strings,
+         organization_author_strings = EXCLUDED.organization_author_strings,
+         filed_date = EXCLUDED.filed_date,
+         filling_type = EXCLUDED.filling_type,
+         filling_name = EXCLUDED.filling_name,
+         filling_description = EXCLUDED.filling_description,
+         openscrapers_id = EXCLUDED.openscrapers_id,
+         filling_govid = EXCLUDED.filling_govid
+         {filling_only_if_changed}
+         RETURNING uuid")
+    )
+    .bind(filling.object_uuid)
+    .bind(docket_uuid)
+    .bind(docket_govid)
+    .bind(&individual_author_strings)
+    .bind(&organization_author_strings)
+    .bind(filling.filed_date)
+    .bind(&filling.filing_type)
+    .bind(&filling.name)
+    .bind(&filling.description)
+    .bind(filling.object_uuid.to_string())
+    .bind(&filling.filling_govid)
+    .fetch_optional(&mut *conn)
+    .await?
+    .unwrap_or(filling.object_uuid);
+    if filling_uuid != filling.object_uuid {
+        info!(%filling_uuid, "Set filling to have new uuid");
+        filling.object_uuid = filling_uuid;
+    }
 
-    let process_filling_closure =
-        async |filling: &mut ProcessedGenericFiling| -> Result<(), anyhow::Error> {
-            let individual_author_strings = filling
-                .individual_authors
-                .iter()
-                .map(|s| s.human_name.to_string())
-                .collect::<Vec<_>>();
-            let organization_author_strings = filling
-                .organization_authors
-                .iter()
-                .map(|s| s.truncated_org_name.to_string())
-                .collect::<Vec<_>>();
-            let filling_uuid: Uuid = query_scalar(
-            &format!("INSERT INTO {pg_schema}.fillings (uuid, docket_uuid, docket_govid, individual_author_strings, organization_author_strings, filed_date, filling_type, filling_name, filling_description, openscrapers_id)
-             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+    for attachment in filling.attachments.iter_mut() {
+        let document_hash = match attachment.hash {
+            Some(hash) => Some(upsert_document(attachment, hash, pg_schema, &mut *conn).await?),
+            None => None,
+        };
+        let hashstr = document_hash.clone().unwrap_or_default();
+        let attachment_uuid: Uuid = query_scalar(
+            &format!("INSERT INTO {pg_schema}.attachments AS existing (uuid, parent_filling_uuid, blake2b_hash, attachment_file_extension, attachment_file_name, attachment_title, attachment_url, openscrapers_id, document_hash, attachment_type, attachment_subtype)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
              ON CONFLICT (uuid) DO UPDATE SET
-             docket_uuid = EXCLUDED.docket_uuid,
-             docket_govid = EXCLUDED.docket_govid,
-             individual_author_strings = EXCLUDED.individual_author_strings,
-             organization_author_strings = EXCLUDED.organization_author_strings,
-             filed_date = EXCLUDED.filed_date,
-             filling_type = EXCLUDED.filling_type,
-             filling_name = EXCLUDED.filling_name,
-             filling_description = EXCLUDED.filling_description,
-             openscrapers_id = EXCLUDED.openscrapers_id
+             parent_filling_uuid = EXCLUDED.parent_filling_uuid,
+             blake2b_hash = EXCLUDED.blake2b_hash,
+             attachment_file_extension = EXCLUDED.attachment_file_extension,
+             attachment_file_name = EXCLUDED.attachment_file_name,
+             attachment_title = EXCLUDED.attachment_title,
+             attachment_url = EXCLUDED.attachment_url,
+             openscrapers_id = EXCLUDED.openscrapers_id,
+             document_hash = EXCLUDED.document_hash,
+             attachment_type = EXCLUDED.attachment_type,
+             attachment_subtype = EXCLUDED.attachment_subtype
+             {attachment_only_if_changed}
              RETURNING uuid")
         )
-        .bind(filling.object_uuid)
-        .bind(docket_uuid)
-        .bind(case.case_govid.as_str())
-        .bind(&individual_author_strings)
-        .bind(&organization_author_strings)
-        .bind(filling.filed_date)
-        .bind(&filling.filing_type)
-        .bind(&filling.name)
-        .bind(&filling.description)
-        .bind(filling.object_uuid.to_string())
-        .fetch_one(pool)
+        .bind(attachment.object_uuid)
+        .bind(filling_uuid)
+        .bind(hashstr)
+        .bind(&*attachment.document_extension.to_string())
+        .bind(&attachment.name)
+        .bind(&attachment.name)
+        .bind(&attachment.url)
+        .bind(&*attachment.object_uuid.to_string())
+        .bind(document_hash)
+        .bind(&attachment.attachment_type)
+        .bind(&attachment.attachment_subtype)
+        .fetch_optional(&mut *conn)
+        .await?
+        .unwrap_or(attachment.object_uuid);
+        if attachment_uuid != attachment.object_uuid {
+            info!(%attachment_uuid, "Set attachment to have new uuid");
+            attachment.object_uuid = attachment_uuid;
+        }
+    }
+    Ok(filling_uuid)
+}
+
+/// Reingests a single corrected filling of an already ingested docket. The filling, its
+/// attachments and its author relations are rewritten in one transaction, and attachments that
+/// are no longer part of the filling are removed. The rest of the docket is left untouched.
+pub async fn reingest_filing(
+    docket_govid: &str,
+    filling: &mut ProcessedGenericFiling,
+    fixed_jur: FixedJurisdiction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let pg_schema = fixed_jur.get_postgres_schema_name();
+    let docket_uuid: Uuid = query_scalar(&format!(
+        "SELECT uuid FROM {pg_schema}.dockets WHERE docket_govid = $1"
+    ))
+    .bind(docket_govid)
+    .fetch_optional(pool)
+    .await?
+    .with_context(|| format!("No docket with govid {docket_govid} has been ingested"))?;
+
+    // Author rows are shared between dockets, so they are associated outside the transaction.
+    for individual_author in filling.individual_authors.iter_mut() {
+        associate_individual_author_with_name(individual_author, pg_schema, pool).await?;
+    }
+    for org_author in filling.organization_authors.iter_mut() {
+        associate_organization_with_name(org_author, pg_schema, pool).await?;
+    }
+
+    let mut tx = pool.begin().await?;
+    let filling_uuid = upsert_filling_rows(
+        filling,
+        docket_uuid,
+        docket_govid,
+        pg_schema,
+        &mut tx,
+        false,
+    )
+    .await?;
+    let kept_attachment_uuids = filling
+        .attachments
+        .iter()
+        .map(|attachment| attachment.object_uuid)
+        .collect::<Vec<_>>();
+    sqlx::query(&format!(
+        "DELETE FROM {pg_schema}.attachments WHERE parent_filling_uuid = $1 AND uuid <> ALL($2)"
+    ))
+    .bind(filling_uuid)
+    .bind(&kept_attachment_uuids)
+    .execute(&mut *tx)
+    .await?;
+    replace_filling_author_relations(filling, pg_schema, &mut tx).await?;
+    tx.commit().await?;
+
+    info!(%docket_govid, %filling_uuid, "Reingested filling");
+    Ok(())
+}
+
+const DEFAULT_TRANSACTIONAL_INGEST_MAX_DOCKETS: usize = 50;
+/// Most dockets a transactional batch ingest takes, the whole batch holds one connection and all
+/// of its row locks until it commits.
+pub static TRANSACTIONAL_INGEST_MAX_DOCKETS: LazyLock<usize> = LazyLock::new(|| {
+    env::var("TRANSACTIONAL_INGEST_MAX_DOCKETS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&max| max > 0)
+        .unwrap_or(DEFAULT_TRANSACTIONAL_INGEST_MAX_DOCKETS)
+});
+
+#[derive(Debug, Error)]
+pub enum BatchIngestError {
+    #[error("batch of {count} dockets is over the limit of {limit} for a single transaction")]
+    TooLarge { count: usize, limit: usize },
+    #[error("docket {docket_govid} failed to ingest, rolled back the whole batch: {error:#}")]
+    DocketFailed {
+        docket_govid: String,
+        error: anyhow::Error,
+    },
+    #[error("batch transaction failed: {0}")]
+    Transaction(#[from] sqlx::Error),
+    #[error(transparent)]
+    InvalidSchema(#[from] InvalidSchemaName),
+}
+
+impl BatchIngestError {
+    /// The docket that caused the batch to be rolled back, if it was down to a single docket.
+    pub fn failed_docket(&self) -> Option<&str> {
+        match self {
+            BatchIngestError::DocketFailed { docket_govid, .. } => Some(docket_govid),
+            _ => None,
+        }
+    }
+}
+
+pub fn check_transactional_batch_size(count: usize) -> Result<(), BatchIngestError> {
+    let limit = *TRANSACTIONAL_INGEST_MAX_DOCKETS;
+    match count > limit {
+        true => Err(BatchIngestError::TooLarge { count, limit }),
+        false => Ok(()),
+    }
+}
+
+/// Ingests every docket in a single transaction, so either all of them land in postgres or, if
+/// any one fails, none of them do. Unlike the per docket ingest nothing is retried, but the
+/// dockets that picked up uuids are uploaded again once the transaction commits, the same as
+/// [`ingest_sql_case_with_retries`] does.
+pub async fn ingest_dockets_in_transaction(
+    cases: &mut [ProcessedGenericDocket],
+    fixed_jur: FixedJurisdiction,
+    target_schema: Option<&str>,
+    pool: &PgPool,
+    org_cache: &OrgUuidCache,
+) -> Result<(), BatchIngestError> {
+    check_transactional_batch_size(cases.len())?;
+    let pg_schema = match target_schema {
+        Some(target_schema) => validate_schema_name(target_schema)?,
+        None => fixed_jur.get_postgres_schema_name(),
+    };
+    let initial_hashes = cases.iter().map(generate_hash).collect::<Vec<_>>();
+    let docket_failed = |case: &ProcessedGenericDocket, error| BatchIngestError::DocketFailed {
+        docket_govid: case.case_govid.to_string(),
+        error,
+    };
+
+    // Author rows are shared between dockets, so they are associated outside the transaction.
+    for case in cases.iter_mut() {
+        if let Err(error) = associate_docket_authors(case, pg_schema, pool, org_cache).await {
+            return Err(docket_failed(case, error));
+        }
+    }
+
+    let mut tx = pool.begin().await?;
+    for case in cases.iter_mut() {
+        if let Err(error) = upsert_docket_with_relations(case, pg_schema, &mut tx).await {
+            // Dropping the transaction rolls back every docket written so far.
+            warn!(docket_govid = %case.case_govid, %error, "Rolling back transactional batch ingest");
+            return Err(docket_failed(case, error));
+        }
+    }
+    tx.commit().await?;
+    for case in cases.iter() {
+        PROCESSING_THROUGHPUT.record_docket(ThroughputStage::Ingested, case);
+    }
+    // The uuids of an ingest into another schema don't belong in the canonical processed docket.
+    if target_schema.is_none() {
+        let updated_cases = cases
+            .iter()
+            .zip(initial_hashes)
+            .filter(|(case, initial_hash)| generate_hash(*case) != *initial_hash)
+            .map(|(case, _)| case)
+            .collect::<Vec<_>>();
+        if !updated_cases.is_empty() {
+            let s3_client = DIGITALOCEAN_S3.make_s3_client().await;
+            for case in updated_cases {
+                upload_ingested_docket(&s3_client, case, fixed_jur).await;
+            }
+        }
+    }
+
+    info!(%pg_schema, docket_count = cases.len(), "Ingested batch in a single transaction");
+    Ok(())
+}
+
+async fn associate_docket_authors(
+    case: &mut ProcessedGenericDocket,
+    pg_schema: &str,
+    pool: &PgPool,
+    org_cache: &OrgUuidCache,
+) -> anyhow::Result<()> {
+    for petitioner in case.petitioner_list.iter_mut() {
+        org_cache
+            .associate_organization_with_name(&mut petitioner.organization, pg_schema, pool)
+            .await?;
+    }
+    for human in case
+        .case_parties
+        .iter_mut()
+        .chain(case.hearing_officers.iter_mut())
+    {
+        associate_individual_author_with_name(human, pg_schema, pool).await?;
+    }
+    for filling in case.filings.iter_mut() {
+        for individual_author in filling.individual_authors.iter_mut() {
+            associate_individual_author_with_name(individual_author, pg_schema, pool).await?;
+        }
+        for org_author in filling.organization_authors.iter_mut() {
+            org_cache
+                .associate_organization_with_name(org_author, pg_schema, pool)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes a docket whose authors are already associated, along with its fillings and every
+/// relation, all on `conn`.
+async fn upsert_docket_with_relations(
+    case: &mut ProcessedGenericDocket,
+    pg_schema: &str,
+    conn: &mut PgConnection,
+) -> anyhow::Result<()> {
+    let docket_uuid =
+        upsert_docket_row(case, pg_schema, &mut *conn, *INGEST_SKIP_UNCHANGED_ROWS).await?;
+    replace_docket_relations(case, docket_uuid, pg_schema, &mut *conn).await?;
+    for filling in case.filings.iter_mut() {
+        upsert_filling_rows(
+            filling,
+            docket_uuid,
+            case.case_govid.as_str(),
+            pg_schema,
+            &mut *conn,
+            *INGEST_SKIP_UNCHANGED_ROWS,
+        )
         .await?;
-            if filling_uuid != filling.object_uuid {
-                info!(%filling_uuid, "Set filling to have new uuid");
-                filling.object_uuid = filling_uuid;
+        replace_filling_author_relations(filling, pg_schema, &mut *conn).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_processing_traits::ProcessFrom;
+    use crate::sql_ingester_tasks::recreate_dokito_table_schema::create_target_schema;
+    use crate::test_fixtures::{
+        make_processed_attachment, make_processed_docket, make_processed_filing,
+        make_processed_petitioner, make_raw_docket, make_raw_filing, setup_test_db,
+    };
+    use chrono::NaiveDate;
+    use sqlx::query_as;
+
+    #[test]
+    fn test_truncate_safe_handles_short_and_multibyte_strings() {
+        assert_eq!(
+            truncate_safe("connection refused", 500),
+            "connection refused"
+        );
+        assert_eq!(truncate_safe("", 500), "");
+
+        let exact = "a".repeat(500);
+        assert_eq!(truncate_safe(&exact, 500), exact);
+        assert_eq!(truncate_safe(&format!("{exact}b"), 500), exact);
+
+        // 'é' takes bytes 499 and 500, so cutting at 500 would split it.
+        let multibyte = format!("{}é tail", "a".repeat(499));
+        assert_eq!(truncate_safe(&multibyte, 500), "a".repeat(499));
+        assert_eq!(
+            truncate_safe(&multibyte, 501),
+            format!("{}é", "a".repeat(499))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_counts_match_with_small_channel() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Duration;
+
+        let (capacity, ingest_workers) = (2, 2);
+        let waiting = AtomicUsize::new(0);
+        let max_waiting = AtomicUsize::new(0);
+        let process = async |i: usize| {
+            if i % 10 == 0 {
+                anyhow::bail!("could not process docket {i}");
+            }
+            let now_waiting = waiting.fetch_add(1, Ordering::SeqCst) + 1;
+            max_waiting.fetch_max(now_waiting, Ordering::SeqCst);
+            let opened_date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+            Ok(make_processed_docket(
+                &format!("PIPE-{i}"),
+                opened_date,
+                vec![],
+            ))
+        };
+        let ingest = async |docket: ProcessedGenericDocket| {
+            tokio::time::sleep(Duration::from_millis(2)).await;
+            waiting.fetch_sub(1, Ordering::SeqCst);
+            match docket.case_govid.ends_with('7') {
+                true => anyhow::bail!("could not ingest {}", docket.case_govid),
+                false => Ok(()),
             }
+        };
 
-            // Associate individual authors using the proper association functions
-            for individual_author in filling.individual_authors.iter_mut() {
-                upload_filling_human_author(individual_author, filling_uuid, fixed_jur, pool)
-                    .await?;
+        let counts = process_then_ingest_pipeline(
+            (0..50).collect(),
+            process,
+            ingest,
+            4,
+            ingest_workers,
+            capacity,
+        )
+        .await;
+        assert_eq!(
+            counts,
+            PipelineCounts {
+                attempted: 50,
+                processed: 45,
+                process_failures: 5,
+                ingested: 40,
+                ingest_failures: 5,
             }
+        );
+        assert_eq!(waiting.load(Ordering::SeqCst), 0);
+        // The channel, the docket waiting to be sent, and the ones being ingested.
+        assert!(max_waiting.load(Ordering::SeqCst) <= capacity + 1 + ingest_workers);
+    }
 
-            // Associate organization authors using the proper association functions
-            for org_author in filling.organization_authors.iter_mut() {
-                upload_filling_organization_author(org_author, filling_uuid, fixed_jur, pool)
-                    .await?;
+    #[tokio::test]
+    async fn test_shared_attachment_hash_creates_single_document() {
+        let pool = setup_test_db().await;
+        let fixed_jur = FixedJurisdiction::NewYorkPuc;
+        let pg_schema = fixed_jur.get_postgres_schema_name();
+        let filed_date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let hash = Blake2bHash::from_bytes(format!("shared-{}", Uuid::new_v4()).as_bytes());
+
+        let mut first_attachment = make_processed_attachment("Comments", "");
+        first_attachment.hash = Some(hash);
+        let mut second_attachment = make_processed_attachment("Comments (refiled)", "");
+        second_attachment.hash = Some(hash);
+        let mut case = make_processed_docket(
+            &format!("TEST-{}", Uuid::new_v4()),
+            filed_date,
+            vec![
+                make_processed_filing("1", filed_date, vec![first_attachment]),
+                make_processed_filing("2", filed_date, vec![second_attachment]),
+            ],
+        );
+
+        ingest_sql_fixed_jurisdiction_case(&mut case, fixed_jur, &pool, false)
+            .await
+            .expect("Ingest should succeed");
+
+        let document_count: i64 = query_scalar(&format!(
+            "SELECT COUNT(*) FROM {pg_schema}.documents WHERE blake2b_hash = $1"
+        ))
+        .bind(hash.to_string())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(document_count, 1);
+
+        let attachment_count: i64 = query_scalar(&format!(
+            "SELECT COUNT(*) FROM {pg_schema}.attachments WHERE document_hash = $1"
+        ))
+        .bind(hash.to_string())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(attachment_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_reingest_unchanged_docket_skips_updates() {
+        let pool = setup_test_db().await;
+        let fixed_jur = FixedJurisdiction::NewYorkPuc;
+        let pg_schema = fixed_jur.get_postgres_schema_name();
+        let filed_date = NaiveDate::from_ymd_opt(2024, 5, 6).unwrap();
+        let mut case = make_processed_docket(
+            &format!("TEST-{}", Uuid::new_v4()),
+            filed_date,
+            vec![make_processed_filing(
+                "1",
+                filed_date,
+                vec![make_processed_attachment("Comments", "")],
+            )],
+        );
+        ingest_docket_rows(&mut case, pg_schema, &pool, &OrgUuidCache::default(), true)
+            .await
+            .expect("Ingest should succeed");
+
+        // Every UPDATE writes a new row version, which changes the row's xmin.
+        let row_versions = async |case: &ProcessedGenericDocket| -> Vec<String> {
+            let filling = &case.filings[0];
+            let mut versions = vec![];
+            for (table, uuid) in [
+                ("dockets", case.object_uuid),
+                ("fillings", filling.object_uuid),
+                ("attachments", filling.attachments[0].object_uuid),
+            ] {
+                let xmin: String = query_scalar(&format!(
+                    "SELECT xmin::text FROM {pg_schema}.{table} WHERE uuid = $1"
+                ))
+                .bind(uuid)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+                versions.push(xmin);
             }
+            versions
+        };
+        let versions_before = row_versions(&case).await;
 
-            for attachment in filling.attachments.iter_mut() {
-                let hashstr = attachment
-                    .hash
-                    .map(|h| h.to_string())
-                    .unwrap_or_else(|| "".to_string());
-                let attachment_uuid: Uuid = query_scalar(
-                &format!("INSERT INTO {pg_schema}.attachments (uuid, parent_filling_uuid, blake2b_hash, attachment_file_extension, attachment_file_name, attachment_title, attachment_url, openscrapers_id)
-                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-                    ON CONFLICT (uuid) DO UPDATE SET
-                    parent_filling_uuid = EXCLUDED.parent_filling_uuid,
-                    blake2b_hash = EXCLUDED.blake2b_hash,
-                    attachment_file_extension = EXCLUDED.attachment_file_extension,
-                    attachment_file_name = EXCLUDED.attachment_file_name,
-                    attachment_title = EXCLUDED.attachment_title,
-                    attachment_url = EXCLUDED.attachment_url,
-                    openscrapers_id = EXCLUDED.openscrapers_id
-                    RETURNING uuid")
-            )
-            .bind(attachment.object_uuid)
-            .bind(filling_uuid)
-            .bind(hashstr)
-            .bind(&*attachment.document_extension.to_string())
-            .bind(&attachment.name)
-            .bind(&attachment.name)
-            .bind(&attachment.url)
-            .bind(&*attachment.object_uuid.to_string())
-            .fetch_one(pool)
-            .await?;
-                if attachment_uuid != attachment.object_uuid {
-                    info!(%attachment_uuid, "Set attachment to have new uuid");
-                    attachment.object_uuid = attachment_uuid;
-                }
+        ingest_docket_rows(&mut case, pg_schema, &pool, &OrgUuidCache::default(), true)
+            .await
+            .expect("Reingest should succeed");
+        assert_eq!(row_versions(&case).await, versions_before);
+
+        case.description = "Now with a description".to_string();
+        ingest_docket_rows(&mut case, pg_schema, &pool, &OrgUuidCache::default(), true)
+            .await
+            .expect("Reingest should succeed");
+        let versions_after_change = row_versions(&case).await;
+        assert_ne!(versions_after_change[0], versions_before[0]);
+        assert_eq!(versions_after_change[1..], versions_before[1..]);
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_reingest_keeps_original_processed_at() {
+        let pool = setup_test_db().await;
+        let pg_schema = FixedJurisdiction::NewYorkPuc.get_postgres_schema_name();
+        let opened_date = NaiveDate::from_ymd_opt(2024, 7, 8).unwrap();
+        let first_processed_at = DateTime::from_timestamp(1_720_000_000, 0).unwrap();
+        let mut case =
+            make_processed_docket(&format!("TEST-{}", Uuid::new_v4()), opened_date, vec![]);
+        case.processed_at = first_processed_at;
+        ingest_docket_rows(&mut case, pg_schema, &pool, &OrgUuidCache::default(), false)
+            .await
+            .expect("Ingest should succeed");
+
+        let stored_processed_at = async |case: &ProcessedGenericDocket| -> DateTime<Utc> {
+            query_scalar(&format!(
+                "SELECT processed_at FROM {pg_schema}.dockets WHERE uuid = $1"
+            ))
+            .bind(case.object_uuid)
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+        };
+        assert_eq!(stored_processed_at(&case).await, first_processed_at);
+
+        case.processed_at = first_processed_at + TimeDelta::hours(1);
+        ingest_docket_rows(&mut case, pg_schema, &pool, &OrgUuidCache::default(), false)
+            .await
+            .expect("Reingest should succeed");
+        assert_eq!(stored_processed_at(&case).await, first_processed_at);
+
+        let changed_processed_at = first_processed_at + TimeDelta::hours(2);
+        case.processed_at = changed_processed_at;
+        case.description = "Now with a description".to_string();
+        ingest_docket_rows(&mut case, pg_schema, &pool, &OrgUuidCache::default(), false)
+            .await
+            .expect("Reingest should succeed");
+        assert_eq!(stored_processed_at(&case).await, changed_processed_at);
+    }
+
+    #[tokio::test]
+    async fn test_attachment_type_and_subtype_are_ingested() {
+        let pool = setup_test_db().await;
+        let pg_schema = FixedJurisdiction::NewYorkPuc.get_postgres_schema_name();
+        let filed_date = NaiveDate::from_ymd_opt(2024, 2, 3).unwrap();
+        let mut attachment = make_processed_attachment("Rebuttal Testimony", "");
+        attachment.attachment_type = "Testimony - Rebuttal".to_string();
+        let mut case = make_processed_docket(
+            &format!("TEST-{}", Uuid::new_v4()),
+            filed_date,
+            vec![make_processed_filing("1", filed_date, vec![attachment])],
+        );
+        case.revalidate().await;
+
+        ingest_docket_rows(&mut case, pg_schema, &pool, &OrgUuidCache::default(), false)
+            .await
+            .expect("Ingest should succeed");
+        let (attachment_type, attachment_subtype): (String, String) = sqlx::query_as(&format!(
+            "SELECT attachment_type, attachment_subtype FROM {pg_schema}.attachments WHERE uuid = $1"
+        ))
+        .bind(case.filings[0].attachments[0].object_uuid)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(attachment_type, "Testimony");
+        assert_eq!(attachment_subtype, "Rebuttal");
+    }
+
+    #[test]
+    fn test_only_cached_dockets_older_than_max_age_are_reprocessed() {
+        let now = Utc::now();
+        let opened_date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let mut old_docket = make_processed_docket("TEST-OLD", opened_date, vec![]);
+        old_docket.processed_at = now - TimeDelta::days(10);
+        let mut fresh_docket = make_processed_docket("TEST-FRESH", opened_date, vec![]);
+        fresh_docket.processed_at = now - TimeDelta::hours(1);
+        let max_age = Some(TimeDelta::days(7));
+
+        assert!(is_processed_docket_stale(&old_docket, max_age, now));
+        assert!(!is_processed_docket_stale(&fresh_docket, max_age, now));
+        // Without a max age every cached docket is reused.
+        assert!(!is_processed_docket_stale(&old_docket, None, now));
+    }
+
+    #[tokio::test]
+    async fn test_reprocessed_unchanged_docket_is_no_longer_stale() {
+        let indexed_at = Utc::now();
+        let raw_docket = || {
+            let mut raw_docket = make_raw_docket(
+                "TEST-PROCESSED-AT-1",
+                vec![make_raw_filing("1", "2024-01-02", vec![])],
+            );
+            raw_docket.indexed_at = indexed_at;
+            raw_docket
+        };
+        let max_age = Some(TimeDelta::days(7));
+        let mut cached =
+            ProcessedGenericDocket::process_from(raw_docket(), None, FixedJurisdiction::NewYorkPuc)
+                .await
+                .expect("Processing should succeed");
+        cached.processed_at -= TimeDelta::days(10);
+        assert!(is_processed_docket_stale(&cached, max_age, Utc::now()));
+
+        let reprocessed = ProcessedGenericDocket::process_from(
+            raw_docket(),
+            Some(cached.clone()),
+            FixedJurisdiction::NewYorkPuc,
+        )
+        .await
+        .expect("Reprocessing should succeed");
+        // Nothing about the docket changed, but it was just processed.
+        assert!(reprocessed.processed_at > cached.processed_at);
+        assert!(!is_processed_docket_stale(
+            &reprocessed,
+            max_age,
+            Utc::now()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_ingest_retries_configured_number_of_times() {
+        let pool = setup_test_db().await;
+        let opened_date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let mut case =
+            make_processed_docket(&format!("TEST-{}", Uuid::new_v4()), opened_date, vec![]);
+        // Postgres rejects NUL bytes in text columns, so every attempt fails.
+        case.case_name = "Broken\0Case".to_string();
+
+        let err = ingest_sql_case_with_retries(
+            &mut case,
+            FixedJurisdiction::NewYorkPuc,
+            None,
+            &pool,
+            &OrgUuidCache::default(),
+            false,
+            2,
+        )
+        .await
+        .expect_err("Ingest should fail on every attempt");
+        assert_eq!(err.to_string(), "Docket ingest failed after 2 attempts");
+    }
+
+    #[tokio::test]
+    async fn test_ingest_retries_until_a_transient_failure_clears() {
+        let pool = setup_test_db().await;
+        let target_schema = format!("test_retries_{}", Uuid::new_v4().simple());
+        create_target_schema(&target_schema, &pool)
+            .await
+            .expect("Target schema should be created");
+        // Sequences aren't rolled back with the failed insert, so the trigger sees every attempt
+        // and lets the third one through.
+        sqlx::raw_sql(&format!(
+            "CREATE SEQUENCE {target_schema}.docket_insert_attempts;
+             CREATE FUNCTION {target_schema}.fail_first_docket_inserts() RETURNS trigger AS $$
+             BEGIN
+               IF nextval('{target_schema}.docket_insert_attempts') < 3 THEN
+                 RAISE EXCEPTION 'transient docket insert failure';
+               END IF;
+               RETURN NEW;
+             END $$ LANGUAGE plpgsql;
+             CREATE TRIGGER fail_first_docket_inserts BEFORE INSERT ON {target_schema}.dockets
+               FOR EACH ROW EXECUTE FUNCTION {target_schema}.fail_first_docket_inserts();"
+        ))
+        .execute(&pool)
+        .await
+        .unwrap();
+        let opened_date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let mut case =
+            make_processed_docket(&format!("TEST-{}", Uuid::new_v4()), opened_date, vec![]);
+
+        let ingest_res = ingest_sql_case_with_retries(
+            &mut case,
+            FixedJurisdiction::NewYorkPuc,
+            Some(&target_schema),
+            &pool,
+            &OrgUuidCache::default(),
+            false,
+            4,
+        )
+        .await;
+        let attempts: i64 = query_scalar(&format!(
+            "SELECT last_value FROM {target_schema}.docket_insert_attempts"
+        ))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let docket_count: i64 = query_scalar(&format!(
+            "SELECT COUNT(*) FROM {target_schema}.dockets WHERE docket_govid = $1"
+        ))
+        .bind(case.case_govid.as_str())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        sqlx::query(&format!("DROP SCHEMA {target_schema} CASCADE"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        ingest_res.expect("Ingest should succeed once the failure clears");
+        assert_eq!(attempts, 3);
+        assert_eq!(docket_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_into_target_schema_leaves_production_untouched() {
+        let pool = setup_test_db().await;
+        let fixed_jur = FixedJurisdiction::NewYorkPuc;
+        let target_schema = format!("test_target_{}", Uuid::new_v4().simple());
+        create_target_schema(&target_schema, &pool)
+            .await
+            .expect("Target schema should be created");
+        let filed_date = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let mut case = make_processed_docket(
+            &format!("TEST-{}", Uuid::new_v4()),
+            filed_date,
+            vec![make_processed_filing(
+                "1",
+                filed_date,
+                vec![make_processed_attachment("Comments", "")],
+            )],
+        );
+
+        let ingest_res = ingest_sql_case_with_retries(
+            &mut case,
+            fixed_jur,
+            Some(&target_schema),
+            &pool,
+            &OrgUuidCache::default(),
+            false,
+            1,
+        )
+        .await;
+        let count_dockets = async |pg_schema: &str| -> i64 {
+            query_scalar(&format!(
+                "SELECT COUNT(*) FROM {pg_schema}.dockets WHERE docket_govid = $1"
+            ))
+            .bind(case.case_govid.as_str())
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+        };
+        let target_count = count_dockets(&target_schema).await;
+        let production_count = count_dockets(fixed_jur.get_postgres_schema_name()).await;
+
+        sqlx::query(&format!("DROP SCHEMA {target_schema} CASCADE"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        ingest_res.expect("Ingest into the target schema should succeed");
+        assert_eq!(target_count, 1);
+        assert_eq!(production_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_target_schema_is_rejected() {
+        let pool = setup_test_db().await;
+        let opened_date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let mut case =
+            make_processed_docket(&format!("TEST-{}", Uuid::new_v4()), opened_date, vec![]);
+
+        let err = ingest_sql_case_with_retries(
+            &mut case,
+            FixedJurisdiction::NewYorkPuc,
+            Some("ny_puc_data; DROP TABLE x"),
+            &pool,
+            &OrgUuidCache::default(),
+            false,
+            1,
+        )
+        .await
+        .expect_err("Invalid schema names must be rejected");
+        assert!(err.downcast_ref::<InvalidSchemaName>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_reingest_filing_only_touches_that_filing() {
+        let pool = setup_test_db().await;
+        let fixed_jur = FixedJurisdiction::NewYorkPuc;
+        let pg_schema = fixed_jur.get_postgres_schema_name();
+        let filed_date = NaiveDate::from_ymd_opt(2024, 7, 8).unwrap();
+        let mut case = make_processed_docket(
+            &format!("TEST-{}", Uuid::new_v4()),
+            filed_date,
+            vec![
+                make_processed_filing(
+                    "1",
+                    filed_date,
+                    vec![make_processed_attachment("Comments", "")],
+                ),
+                make_processed_filing(
+                    "2",
+                    filed_date,
+                    vec![make_processed_attachment("Reply", "")],
+                ),
+            ],
+        );
+        ingest_docket_rows(&mut case, pg_schema, &pool, &OrgUuidCache::default(), false)
+            .await
+            .expect("Ingest should succeed");
+
+        let untouched = &case.filings[1];
+        let untouched_xmins = async || -> Vec<String> {
+            let mut versions = vec![];
+            for (table, uuid) in [
+                ("fillings", untouched.object_uuid),
+                ("attachments", untouched.attachments[0].object_uuid),
+            ] {
+                let xmin: String = query_scalar(&format!(
+                    "SELECT xmin::text FROM {pg_schema}.{table} WHERE uuid = $1"
+                ))
+                .bind(uuid)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+                versions.push(xmin);
             }
-            Ok(())
+            versions
         };
-    let simultaneous_file_uploads = Semaphore::new(3);
-    let filling_futures = case.filings.iter_mut().map(async |filling| {
-        let _permit = simultaneous_file_uploads.acquire().await?;
-        process_filling_closure(filling).await
-    });
-    let filling_results = join_all(filling_futures).await;
-    bubble_error(filling_results.into_iter())?;
+        let versions_before = untouched_xmins().await;
 
-    tracing::info!(govid=%case.case_govid, uuid=%docket_uuid,"Successfully processed case with no errors");
-    Ok(())
+        let mut corrected = case.filings[0].clone();
+        corrected.attachments = vec![
+            make_processed_attachment("Comments (corrected)", ""),
+            make_processed_attachment("Exhibit A", ""),
+        ];
+        reingest_filing(case.case_govid.as_str(), &mut corrected, fixed_jur, &pool)
+            .await
+            .expect("Filing reingest should succeed");
+
+        let mut attachment_names: Vec<String> = query_scalar(&format!(
+            "SELECT attachment_file_name FROM {pg_schema}.attachments WHERE parent_filling_uuid = $1"
+        ))
+        .bind(corrected.object_uuid)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        attachment_names.sort();
+        assert_eq!(attachment_names, vec!["Comments (corrected)", "Exhibit A"]);
+        assert_eq!(untouched_xmins().await, versions_before);
+    }
+
+    #[tokio::test]
+    async fn test_shared_petitioner_upserted_once_per_batch() {
+        let pool = setup_test_db().await;
+        let pg_schema = FixedJurisdiction::NewYorkPuc.get_postgres_schema_name();
+        let opened_date = NaiveDate::from_ymd_opt(2024, 7, 8).unwrap();
+        let org_name = format!("Shared Petitioner {}", Uuid::new_v4());
+        let petitioner = make_processed_petitioner(&org_name, PetitionerRole::Petitioner);
+        let mut dockets = [(); 2].map(|_| {
+            let mut docket =
+                make_processed_docket(&format!("TEST-{}", Uuid::new_v4()), opened_date, vec![]);
+            docket.petitioner_list = vec![petitioner.clone()];
+            docket
+        });
+        // Every upsert of the row gives it a new xmin, so an unchanged xmin means no second upsert.
+        let org_row_version = async || -> (i64, String) {
+            query_as(&format!(
+                "SELECT COUNT(*) OVER (), xmin::text FROM {pg_schema}.organizations WHERE name = $1"
+            ))
+            .bind(&org_name)
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+        };
+
+        let org_cache = OrgUuidCache::default();
+        let [first, second] = &mut dockets;
+        ingest_docket_rows(first, pg_schema, &pool, &org_cache, false)
+            .await
+            .expect("Ingest should succeed");
+        let after_first = org_row_version().await;
+        ingest_docket_rows(second, pg_schema, &pool, &org_cache, false)
+            .await
+            .expect("Ingest should succeed");
+        let after_second = org_row_version().await;
+
+        assert_eq!(after_first.0, 1);
+        assert_eq!(after_first, after_second);
+        assert_eq!(
+            first.petitioner_list[0].organization.object_uuid,
+            second.petitioner_list[0].organization.object_uuid
+        );
+    }
+
+    #[tokio::test]
+    async fn test_petitioner_roles_are_ingested() {
+        let pool = setup_test_db().await;
+        let pg_schema = FixedJurisdiction::NewYorkPuc.get_postgres_schema_name();
+        let opened_date = NaiveDate::from_ymd_opt(2024, 9, 3).unwrap();
+        let run_id = Uuid::new_v4();
+        let petitioner_name = format!("Example Power {run_id}");
+        let intervenor_name = format!("Sample Consumers {run_id}");
+        let mut docket =
+            make_processed_docket(&format!("TEST-ROLES-{run_id}"), opened_date, vec![]);
+        docket.petitioner_list = vec![
+            make_processed_petitioner(&petitioner_name, PetitionerRole::Petitioner),
+            make_processed_petitioner(&intervenor_name, PetitionerRole::Intervenor),
+        ];
+
+        ingest_docket_rows(
+            &mut docket,
+            pg_schema,
+            &pool,
+            &OrgUuidCache::default(),
+            false,
+        )
+        .await
+        .expect("Ingest should succeed");
+
+        let mut roles: Vec<(String, String)> = query_as(&format!(
+            "SELECT o.name, p.role FROM {pg_schema}.docket_petitioned_by_org p
+             JOIN {pg_schema}.organizations o ON o.uuid = p.petitioner_uuid
+             WHERE p.docket_uuid = $1"
+        ))
+        .bind(docket.object_uuid)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        roles.sort();
+        assert_eq!(
+            roles,
+            vec![
+                (petitioner_name.clone(), "petitioner".to_string()),
+                (intervenor_name, "intervenor".to_string()),
+            ]
+        );
+        let petitioner_strings: Vec<String> = query_scalar(&format!(
+            "SELECT petitioner_strings FROM {pg_schema}.dockets WHERE uuid = $1"
+        ))
+        .bind(docket.object_uuid)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(petitioner_strings, vec![petitioner_name]);
+    }
+
+    #[tokio::test]
+    async fn test_reordered_petitioners_store_the_same_petitioner_strings() {
+        let pool = setup_test_db().await;
+        let pg_schema = FixedJurisdiction::NewYorkPuc.get_postgres_schema_name();
+        let opened_date = NaiveDate::from_ymd_opt(2024, 9, 3).unwrap();
+        let run_id = Uuid::new_v4();
+        let names =
+            ["Sample Gas", "Example Power", "Grid Co"].map(|name| format!("{name} {run_id}"));
+        let mut docket =
+            make_processed_docket(&format!("TEST-ORDER-{run_id}"), opened_date, vec![]);
+
+        let mut stored = vec![];
+        for order in [[0, 1, 2, 0], [2, 0, 1, 1]] {
+            docket.petitioner_list = order
+                .iter()
+                .map(|&i| make_processed_petitioner(&names[i], PetitionerRole::Petitioner))
+                .collect();
+            ingest_docket_rows(
+                &mut docket,
+                pg_schema,
+                &pool,
+                &OrgUuidCache::default(),
+                false,
+            )
+            .await
+            .expect("Ingest should succeed");
+            let petitioner_strings: Vec<String> = query_scalar(&format!(
+                "SELECT petitioner_strings FROM {pg_schema}.dockets WHERE uuid = $1"
+            ))
+            .bind(docket.object_uuid)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+            stored.push(petitioner_strings);
+        }
+        assert_eq!(stored[0], stored[1]);
+        assert_eq!(
+            stored[0],
+            vec![names[1].clone(), names[2].clone(), names[0].clone()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_docket_rolls_back_whole_batch() {
+        let pool = setup_test_db().await;
+        let pg_schema = format!("test_batch_{}", Uuid::new_v4().simple());
+        create_target_schema(&pg_schema, &pool)
+            .await
+            .expect("Target schema should be created");
+        let filed_date = NaiveDate::from_ymd_opt(2024, 10, 1).unwrap();
+        let make_docket = |govid: &str| {
+            let mut docket = make_processed_docket(
+                govid,
+                filed_date,
+                vec![make_processed_filing(
+                    "1",
+                    filed_date,
+                    vec![make_processed_attachment("Comments", "")],
+                )],
+            );
+            docket.petitioner_list = vec![make_processed_petitioner(
+                "Example Power",
+                PetitionerRole::Petitioner,
+            )];
+            docket
+        };
+        let mut failing = make_docket("BATCH-FAILING");
+        // A filling can't have its relations written without a uuid, so this docket fails after
+        // the ones before it were already written.
+        failing.filings[0].object_uuid = Uuid::nil();
+        let mut batch = vec![
+            make_docket("BATCH-GOOD-1"),
+            failing,
+            make_docket("BATCH-GOOD-2"),
+        ];
+
+        let ingest_res = ingest_dockets_in_transaction(
+            &mut batch,
+            FixedJurisdiction::NewYorkPuc,
+            Some(&pg_schema),
+            &pool,
+            &OrgUuidCache::default(),
+        )
+        .await;
+        let count_rows = async |table: &str| -> i64 {
+            query_scalar(&format!("SELECT COUNT(*) FROM {pg_schema}.{table}"))
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+        };
+        let docket_count = count_rows("dockets").await;
+        let filling_count = count_rows("fillings").await;
+        let petitioner_relation_count = count_rows("docket_petitioned_by_org").await;
+
+        sqlx::query(&format!("DROP SCHEMA {pg_schema} CASCADE"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        let err = ingest_res.expect_err("The batch should be aborted");
+        assert_eq!(err.failed_docket(), Some("BATCH-FAILING"));
+        assert_eq!(docket_count, 0);
+        assert_eq!(filling_count, 0);
+        assert_eq!(petitioner_relation_count, 0);
+    }
 }
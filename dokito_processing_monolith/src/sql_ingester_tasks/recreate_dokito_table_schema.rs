@@ -1,15 +1,21 @@
+use anyhow::Context;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use schemars::JsonSchema;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use sqlx::PgPool;
+use sqlx::{Connection, FromRow, PgConnection, PgPool};
+use std::sync::LazyLock;
 use tracing::{info, warn};
 
-use mycorrhiza_common::tasks::ExecuteUserTask;
+use mycorrhiza_common::{misc::is_env_var_true, tasks::ExecuteUserTask};
 
 use crate::{
-    jurisdiction_schema_mapping::FixedJurisdiction,
-    sql_ingester_tasks::dokito_sql_connection::get_dokito_pool,
+    health_gate::WORKER_HEALTH_GATE,
+    jurisdiction_schema_mapping::{
+        ALL_FIXED_JURISDICTIONS, FixedJurisdiction, validate_schema_name,
+    },
+    sql_ingester_tasks::dokito_sql_connection::get_dokito_pool_for,
 };
 
 #[derive(Clone, Copy, Deserialize, JsonSchema)]
@@ -18,14 +24,15 @@ pub struct RecreateDokitoTableSchema(pub FixedJurisdiction);
 #[async_trait]
 impl ExecuteUserTask for RecreateDokitoTableSchema {
     async fn execute_task(self: Box<Self>) -> Result<Value, Value> {
+        WORKER_HEALTH_GATE.wait_until_ready().await;
         // You'll need to specify which jurisdiction to recreate schema for
         // This is a placeholder - you may need to modify this based on your use case
         let fixed_jur = self.0; // or get from config/params
         let res = recreate_schema(fixed_jur).await;
         match res {
-            Ok(()) => {
+            Ok(report) => {
                 info!("Recreated schema.");
-                Ok("Task Completed Successfully".into())
+                serde_json::to_value(report).map_err(|err| err.to_string().into())
             }
             Err(err) => {
                 tracing::error!(error= % err, error_debug= ?err,"Encountered error in recreate_schema");
@@ -44,67 +51,356 @@ impl ExecuteUserTask for RecreateDokitoTableSchema {
     }
 }
 
-pub async fn recreate_schema(fixed_jur: FixedJurisdiction) -> anyhow::Result<()> {
-    let pg_schema = fixed_jur.get_postgres_schema_name();
+/// Applies the migrations every jurisdiction's schema is missing, leaving the data in place.
+#[derive(Clone, Copy, Deserialize, JsonSchema)]
+pub struct MigrateJurisdictionSchemas {}
+
+#[async_trait]
+impl ExecuteUserTask for MigrateJurisdictionSchemas {
+    async fn execute_task(self: Box<Self>) -> Result<Value, Value> {
+        WORKER_HEALTH_GATE.wait_until_ready().await;
+        let reports = migrate_all_jurisdiction_schemas().await;
+        let failed = reports.iter().any(|report| report.error.is_some());
+        let reports = serde_json::to_value(reports).map_err(|err| err.to_string())?;
+        match failed {
+            true => Err(reports),
+            false => Ok(reports),
+        }
+    }
+    fn get_task_label(&self) -> &'static str {
+        "migrate_jurisdiction_schemas"
+    }
+    fn get_task_label_static() -> &'static str
+    where
+        Self: Sized,
+    {
+        "migrate_jurisdiction_schemas"
+    }
+}
+
+/// Migrates every jurisdiction's schema before the server starts taking requests.
+pub static POSTGRES_MIGRATE_ON_STARTUP: LazyLock<bool> =
+    LazyLock::new(|| is_env_var_true("POSTGRES_MIGRATE_ON_STARTUP"));
+
+#[derive(Serialize, JsonSchema, Debug)]
+pub struct SchemaMigrationReport {
+    pub pg_schema: String,
+    pub applied_migrations: Vec<String>,
+    /// Why the schema could not be migrated, it was left as it was.
+    pub error: Option<String>,
+}
+
+/// Brings the schema of every jurisdiction up to date, one failing schema doesn't stop the rest.
+pub async fn migrate_all_jurisdiction_schemas() -> Vec<SchemaMigrationReport> {
+    let mut reports = vec![];
+    for &fixed_jur in ALL_FIXED_JURISDICTIONS {
+        let pg_schema = fixed_jur.get_postgres_schema_name();
+        let migrated = async {
+            let pool = get_dokito_pool_for(fixed_jur).await?;
+            migrate_schema_in_place(pg_schema, pool).await
+        }
+        .await;
+        let report = match migrated {
+            Ok(applied_migrations) => {
+                info!(%pg_schema, ?applied_migrations, "Migrated schema in place");
+                SchemaMigrationReport {
+                    pg_schema: pg_schema.to_string(),
+                    applied_migrations,
+                    error: None,
+                }
+            }
+            Err(err) => {
+                warn!(%pg_schema, error = %err, "Failed to migrate schema, it was left as it was");
+                SchemaMigrationReport {
+                    pg_schema: pg_schema.to_string(),
+                    applied_migrations: vec![],
+                    error: Some(format!("{err:#}")),
+                }
+            }
+        };
+        reports.push(report);
+    }
+    reports
+}
+
+/// Applies the migrations a schema is missing in a single transaction, so a failing migration
+/// leaves the schema as it was.
+pub async fn migrate_schema_in_place(
+    pg_schema: &str,
+    pool: &PgPool,
+) -> anyhow::Result<Vec<String>> {
+    let mut conn = pool.acquire().await?;
+    let mut tx = conn.begin().await?;
+    let applied_migrations =
+        create_named_schema(pg_schema, DOKITO_SCHEMA_MIGRATIONS, &mut tx).await?;
+    tx.commit().await?;
+    Ok(applied_migrations)
+}
+
+/// What a successful `recreate_schema` did.
+#[derive(Serialize, JsonSchema, Debug)]
+pub struct RecreateSchemaReport {
+    pub pg_schema: String,
+    /// Whether there was an existing schema to drop.
+    pub dropped_existing: bool,
+    pub applied_migrations: Vec<String>,
+}
+
+/// Drops and recreates a jurisdiction's schema in a single transaction, so a failing migration
+/// rolls back to the schema as it was before instead of leaving the jurisdiction without tables.
+pub async fn recreate_schema(fixed_jur: FixedJurisdiction) -> anyhow::Result<RecreateSchemaReport> {
     info!("Got request to recreate schema");
-    let pool = get_dokito_pool().await?;
+    let pool = get_dokito_pool_for(fixed_jur).await?;
     info!("Created pg pool");
+    let mut conn = pool.acquire().await?;
+    recreate_named_schema(
+        fixed_jur.get_postgres_schema_name(),
+        DOKITO_SCHEMA_MIGRATIONS,
+        &mut conn,
+    )
+    .await
+}
 
-    info!("Dropping existing tables");
-    let drop_result = drop_existing_schema(fixed_jur, pool).await;
-    if let Err(err) = drop_result {
-        warn!(%err,%pg_schema,"Encountered error in dropping schema.  Continuing on and creating new tables.")
-    } else {
-        info!(%pg_schema,"Tables deleted successfully.")
-    }
+async fn recreate_named_schema(
+    pg_schema: &str,
+    migrations: &[(&str, &str)],
+    conn: &mut PgConnection,
+) -> anyhow::Result<RecreateSchemaReport> {
+    let pg_schema = validate_schema_name(pg_schema)?;
+    let mut tx = conn.begin().await?;
 
-    info!(%pg_schema,"Creating tables");
-    // create_schema(&pool).await?;
-    create_schema(fixed_jur, pool).await?;
+    info!(%pg_schema, "Dropping existing tables");
+    let dropped_existing = drop_named_schema(pg_schema, &mut tx)
+        .await
+        .with_context(|| format!("Failed to drop schema {pg_schema}, it was left untouched"))?;
 
-    info!(%pg_schema,"Successfully recreated schema");
+    info!(%pg_schema, "Creating tables");
+    let applied_migrations = create_named_schema(pg_schema, migrations, &mut tx)
+        .await
+        .with_context(|| {
+            format!("Failed to create schema {pg_schema}, rolled back to the previous schema")
+        })?;
 
-    Ok(())
+    tx.commit().await?;
+    info!(%pg_schema, ?applied_migrations, "Successfully recreated schema");
+    Ok(RecreateSchemaReport {
+        pg_schema: pg_schema.to_string(),
+        dropped_existing,
+        applied_migrations,
+    })
 }
 
 pub async fn drop_existing_schema(
     fixed_jur: FixedJurisdiction,
     pool: &PgPool,
 ) -> anyhow::Result<()> {
-    let pg_schema = fixed_jur.get_postgres_schema_name();
+    let mut conn = pool.acquire().await?;
+    drop_named_schema(fixed_jur.get_postgres_schema_name(), &mut conn).await?;
+    Ok(())
+}
+
+/// Drops the schema and everything in it, returning whether there was one to drop.
+async fn drop_named_schema(pg_schema: &str, conn: &mut PgConnection) -> anyhow::Result<bool> {
+    let pg_schema = validate_schema_name(pg_schema)?;
     // migrator.set_ignore_missing(true).undo(pool, 0).await?;
+    let schema_exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM information_schema.schemata WHERE schema_name = $1)",
+    )
+    .bind(pg_schema)
+    .fetch_one(&mut *conn)
+    .await?;
 
     // Drop schema-specific tables
     sqlx::query(&format!("DROP SCHEMA IF EXISTS {pg_schema} CASCADE"))
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
-    Ok(())
+    Ok(schema_exists)
+}
+
+/// The migration that builds the original tables. Schemas created before migrations were tracked
+/// already have them, so it's recorded as applied instead of run again.
+const BASELINE_MIGRATION: &str = "001_dokito_complete";
+
+/// Every migration needed to build a jurisdiction schema, applied in order by `create_schema`.
+const DOKITO_SCHEMA_MIGRATIONS: &[(&str, &str)] = &[
+    (
+        "001_dokito_complete",
+        include_str!("./migrations/001_dokito_complete.up.sql"),
+    ),
+    (
+        "002_processor_version",
+        include_str!("./migrations/002_processor_version.up.sql"),
+    ),
+    (
+        "003_documents",
+        include_str!("./migrations/003_documents.up.sql"),
+    ),
+    (
+        "004_organizations_unique_name",
+        include_str!("./migrations/004_organizations_unique_name.up.sql"),
+    ),
+    (
+        "005_docket_processed_at",
+        include_str!("./migrations/005_docket_processed_at.up.sql"),
+    ),
+    (
+        "006_petitioner_role",
+        include_str!("./migrations/006_petitioner_role.up.sql"),
+    ),
+    (
+        "007_docket_hearing_officers",
+        include_str!("./migrations/007_docket_hearing_officers.up.sql"),
+    ),
+];
+
+/// Applies every migration the schema is missing, returning the ones that were applied.
+pub async fn create_schema(
+    fixed_jur: FixedJurisdiction,
+    pool: &PgPool,
+) -> anyhow::Result<Vec<String>> {
+    create_target_schema(fixed_jur.get_postgres_schema_name(), pool).await
+}
+
+/// Same as `create_schema`, for a schema no jurisdiction maps to, like the `target_schema` of an
+/// ingest request.
+pub async fn create_target_schema(pg_schema: &str, pool: &PgPool) -> anyhow::Result<Vec<String>> {
+    let mut conn = pool.acquire().await?;
+    create_named_schema(pg_schema, DOKITO_SCHEMA_MIGRATIONS, &mut conn).await
 }
 
-pub async fn create_schema(fixed_jur: FixedJurisdiction, pool: &PgPool) -> anyhow::Result<()> {
-    let pg_schema = fixed_jur.get_postgres_schema_name();
+async fn create_named_schema(
+    pg_schema: &str,
+    migrations: &[(&str, &str)],
+    conn: &mut PgConnection,
+) -> anyhow::Result<Vec<String>> {
+    let pg_schema = validate_schema_name(pg_schema)?;
     // migrator.set_ignore_missing(true).run(pool).await?;
 
     // Create schema first
     sqlx::query(&format!("CREATE SCHEMA IF NOT EXISTS {pg_schema}"))
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
+    // The migrations are string-substituted into each schema, so sqlx's own migration table
+    // can't track them, every schema keeps its own record instead.
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {pg_schema}._dokito_migrations (
+           version text NOT NULL PRIMARY KEY,
+           applied_at timestamp with time zone NOT NULL DEFAULT now()
+         )"
+    ))
+    .execute(&mut *conn)
+    .await?;
+    let mut already_applied: Vec<String> = sqlx::query_scalar(&format!(
+        "SELECT version FROM {pg_schema}._dokito_migrations"
+    ))
+    .fetch_all(&mut *conn)
+    .await?;
+    if !already_applied
+        .iter()
+        .any(|version| version == BASELINE_MIGRATION)
+    {
+        let has_baseline_tables: bool = sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+            .bind(format!("{pg_schema}.dockets"))
+            .fetch_one(&mut *conn)
+            .await?;
+        if has_baseline_tables {
+            info!(%pg_schema, "Schema predates migration tracking, recording the baseline as applied");
+            sqlx::query(&format!(
+                "INSERT INTO {pg_schema}._dokito_migrations (version) VALUES ($1) ON CONFLICT (version) DO NOTHING"
+            ))
+            .bind(BASELINE_MIGRATION)
+            .execute(&mut *conn)
+            .await?;
+            already_applied.push(BASELINE_MIGRATION.to_string());
+        }
+    }
+
+    let mut applied_migrations = vec![];
+    for (migration_name, migration_sql) in migrations {
+        if already_applied
+            .iter()
+            .any(|version| version == migration_name)
+        {
+            info!(%pg_schema, %migration_name, "Schema migration already applied, skipping");
+            continue;
+        }
+        // Replace default schema references with dynamic schema
+        let schema_specific_sql = migration_sql.replace("public.", &format!("{pg_schema}."));
 
-    // Read the migration file content and replace default schema references with dynamic schema
-    let migration_sql = include_str!("./migrations/001_dokito_complete.up.sql");
-    let schema_specific_sql = migration_sql.replace("public.", &format!("{pg_schema}."));
+        info!(%pg_schema, %migration_name, "Executing schema migration SQL");
 
-    info!(%pg_schema, "Executing complete schema creation SQL");
+        // Execute the entire SQL as a single raw query
+        sqlx::raw_sql(&schema_specific_sql)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(&format!(
+            "INSERT INTO {pg_schema}._dokito_migrations (version) VALUES ($1) ON CONFLICT (version) DO NOTHING"
+        ))
+        .bind(migration_name)
+        .execute(&mut *conn)
+        .await?;
+        applied_migrations.push(migration_name.to_string());
+    }
 
-    // Execute the entire SQL as a single raw query
-    sqlx::raw_sql(&schema_specific_sql).execute(pool).await?;
+    Ok(applied_migrations)
+}
 
-    Ok(())
+#[derive(Serialize, JsonSchema, FromRow)]
+pub struct AppliedMigration {
+    pub version: String,
+    pub applied_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct SchemaVersion {
+    /// The latest migration applied to the schema, `None` for schemas created before migrations
+    /// were tracked.
+    pub current_version: Option<String>,
+    pub applied: Vec<AppliedMigration>,
+    /// Known migrations that have not been applied to the schema yet.
+    pub pending: Vec<String>,
+}
+
+pub async fn get_schema_version(
+    fixed_jur: FixedJurisdiction,
+    pool: &PgPool,
+) -> anyhow::Result<SchemaVersion> {
+    get_named_schema_version(fixed_jur.get_postgres_schema_name(), pool).await
+}
+
+async fn get_named_schema_version(pg_schema: &str, pool: &PgPool) -> anyhow::Result<SchemaVersion> {
+    let pg_schema = validate_schema_name(pg_schema)?;
+    let migrations_table = format!("{pg_schema}._dokito_migrations");
+    let is_tracked: bool = sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+        .bind(&migrations_table)
+        .fetch_one(pool)
+        .await?;
+    let applied: Vec<AppliedMigration> = match is_tracked {
+        true => {
+            sqlx::query_as(&format!(
+                "SELECT version, applied_at FROM {migrations_table} ORDER BY version"
+            ))
+            .fetch_all(pool)
+            .await?
+        }
+        false => vec![],
+    };
+    let pending = DOKITO_SCHEMA_MIGRATIONS
+        .iter()
+        .map(|(migration_name, _)| *migration_name)
+        .filter(|migration_name| !applied.iter().any(|m| m.version == *migration_name))
+        .map(String::from)
+        .collect();
+    Ok(SchemaVersion {
+        current_version: applied.last().map(|m| m.version.clone()),
+        applied,
+        pending,
+    })
 }
 
 pub async fn delete_all_data(fixed_jur: FixedJurisdiction, pool: &PgPool) -> anyhow::Result<()> {
-    let pg_schema = fixed_jur.get_postgres_schema_name();
+    let pg_schema = validate_schema_name(fixed_jur.get_postgres_schema_name())?;
     info!("Starting full data deletion...");
 
     // Start a transaction
@@ -122,3 +418,248 @@ pub async fn delete_all_data(fixed_jur: FixedJurisdiction, pool: &PgPool) -> any
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jurisdiction_schema_mapping::InvalidSchemaName;
+    use crate::sql_ingester_tasks::{
+        database_author_association::OrgUuidCache, nypuc_ingest::ingest_sql_case_with_retries,
+    };
+    use crate::test_fixtures::{make_processed_docket, setup_test_db};
+    use chrono::NaiveDate;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_create_schema_records_applied_migrations() {
+        let pool = setup_test_db().await;
+        let pg_schema = format!("test_migrations_{}", Uuid::new_v4().simple());
+
+        let untracked = get_named_schema_version(&pg_schema, &pool).await.unwrap();
+        assert_eq!(untracked.current_version, None);
+        assert_eq!(untracked.pending.len(), DOKITO_SCHEMA_MIGRATIONS.len());
+
+        let mut conn = pool.acquire().await.unwrap();
+        create_named_schema(&pg_schema, DOKITO_SCHEMA_MIGRATIONS, &mut conn)
+            .await
+            .unwrap();
+        let version = get_named_schema_version(&pg_schema, &pool).await.unwrap();
+        // Rerunning has to skip every recorded migration, or the CREATE TABLEs would fail.
+        let rerun = create_named_schema(&pg_schema, DOKITO_SCHEMA_MIGRATIONS, &mut conn).await;
+
+        sqlx::query(&format!("DROP SCHEMA {pg_schema} CASCADE"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        rerun.unwrap();
+        let applied_versions = version
+            .applied
+            .iter()
+            .map(|m| m.version.as_str())
+            .collect::<Vec<_>>();
+        let all_versions = DOKITO_SCHEMA_MIGRATIONS
+            .iter()
+            .map(|(migration_name, _)| *migration_name)
+            .collect::<Vec<_>>();
+        assert_eq!(applied_versions, all_versions);
+        assert_eq!(
+            version.current_version.as_deref(),
+            all_versions.last().copied()
+        );
+        assert!(version.pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_untracked_schema_is_migrated_in_place() {
+        let pool = setup_test_db().await;
+        let pg_schema = format!("test_untracked_{}", Uuid::new_v4().simple());
+        // A schema as it was built before migrations were tracked, with only the baseline tables.
+        let (_, baseline_sql) = DOKITO_SCHEMA_MIGRATIONS[0];
+        sqlx::raw_sql(&format!(
+            "CREATE SCHEMA {pg_schema}; {}",
+            baseline_sql.replace("public.", &format!("{pg_schema}."))
+        ))
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(&format!(
+            "INSERT INTO {pg_schema}.dockets (docket_govid, opened_date) VALUES ('KEEP-ME', '2024-01-01')"
+        ))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let applied = migrate_schema_in_place(&pg_schema, &pool).await;
+        let version = get_named_schema_version(&pg_schema, &pool).await;
+        let surviving_govids: Result<Vec<String>, _> =
+            sqlx::query_scalar(&format!("SELECT docket_govid FROM {pg_schema}.dockets"))
+                .fetch_all(&pool)
+                .await;
+
+        sqlx::query(&format!("DROP SCHEMA {pg_schema} CASCADE"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        let later_migrations = DOKITO_SCHEMA_MIGRATIONS[1..]
+            .iter()
+            .map(|(migration_name, _)| migration_name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(applied.unwrap(), later_migrations);
+        assert!(version.unwrap().pending.is_empty());
+        assert_eq!(surviving_govids.unwrap(), vec!["KEEP-ME"]);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_organizations_are_merged_before_the_unique_constraint() {
+        let pool = setup_test_db().await;
+        let pg_schema = format!("test_org_merge_{}", Uuid::new_v4().simple());
+        let (survivor, duplicate) = (Uuid::new_v4(), Uuid::new_v4());
+        let (docket, filling) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut conn = pool.acquire().await.unwrap();
+        let before_unique_name = DOKITO_SCHEMA_MIGRATIONS
+            .iter()
+            .position(|(name, _)| *name == "004_organizations_unique_name")
+            .unwrap();
+        create_named_schema(
+            &pg_schema,
+            &DOKITO_SCHEMA_MIGRATIONS[..before_unique_name],
+            &mut conn,
+        )
+        .await
+        .unwrap();
+        sqlx::raw_sql(&format!(
+            "INSERT INTO {pg_schema}.organizations (uuid, created_at, name) VALUES
+               ('{survivor}', '2024-01-01', 'Example Power'),
+               ('{duplicate}', '2024-06-01', 'Example Power');
+             INSERT INTO {pg_schema}.dockets (uuid, docket_govid, opened_date) VALUES ('{docket}', 'MERGE-ME', '2024-01-01');
+             INSERT INTO {pg_schema}.fillings (uuid, docket_uuid, filed_date, openscrapers_id) VALUES ('{filling}', '{docket}', '2024-01-01', 'merge-filling');
+             INSERT INTO {pg_schema}.fillings_on_behalf_of_org_relation (filling_uuid, author_organization_uuid) VALUES
+               ('{filling}', '{survivor}'),
+               ('{filling}', '{duplicate}');
+             INSERT INTO {pg_schema}.docket_petitioned_by_org (docket_uuid, petitioner_uuid) VALUES
+               ('{docket}', '{survivor}'),
+               ('{docket}', '{duplicate}');"
+        ))
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let applied = create_named_schema(&pg_schema, DOKITO_SCHEMA_MIGRATIONS, &mut conn).await;
+        let org_uuids: Result<Vec<Uuid>, _> =
+            sqlx::query_scalar(&format!("SELECT uuid FROM {pg_schema}.organizations"))
+                .fetch_all(&mut *conn)
+                .await;
+        let author_uuids: Result<Vec<Uuid>, _> = sqlx::query_scalar(&format!(
+            "SELECT author_organization_uuid FROM {pg_schema}.fillings_on_behalf_of_org_relation"
+        ))
+        .fetch_all(&mut *conn)
+        .await;
+        let petitioner_uuids: Result<Vec<Uuid>, _> = sqlx::query_scalar(&format!(
+            "SELECT petitioner_uuid FROM {pg_schema}.docket_petitioned_by_org"
+        ))
+        .fetch_all(&mut *conn)
+        .await;
+
+        sqlx::query(&format!("DROP SCHEMA {pg_schema} CASCADE"))
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        assert!(applied.is_ok(), "{:#}", applied.unwrap_err());
+        assert_eq!(org_uuids.unwrap(), vec![survivor]);
+        assert_eq!(author_uuids.unwrap(), vec![survivor]);
+        assert_eq!(petitioner_uuids.unwrap(), vec![survivor]);
+    }
+
+    #[tokio::test]
+    async fn test_malicious_schema_name_is_rejected_before_any_ddl() {
+        let pool = setup_test_db().await;
+        let pg_schema = format!("test_malicious_{}", Uuid::new_v4().simple());
+        let mut conn = pool.acquire().await.unwrap();
+        create_named_schema(&pg_schema, DOKITO_SCHEMA_MIGRATIONS, &mut conn)
+            .await
+            .unwrap();
+        let opened_date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let mut docket =
+            make_processed_docket(&format!("TEST-{}", Uuid::new_v4()), opened_date, vec![]);
+        ingest_sql_case_with_retries(
+            &mut docket,
+            FixedJurisdiction::NewYorkPuc,
+            Some(&pg_schema),
+            &pool,
+            &OrgUuidCache::default(),
+            false,
+            1,
+        )
+        .await
+        .unwrap();
+
+        let malicious = format!("{pg_schema}; DROP SCHEMA {pg_schema} CASCADE; --");
+        let err = create_named_schema(&malicious, DOKITO_SCHEMA_MIGRATIONS, &mut conn)
+            .await
+            .expect_err("A malicious schema name should be rejected");
+        let version_res = get_named_schema_version(&malicious, &pool).await;
+        let recreate_res =
+            recreate_named_schema(&malicious, DOKITO_SCHEMA_MIGRATIONS, &mut conn).await;
+        let docket_count: i64 = sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM {pg_schema}.dockets WHERE docket_govid = $1"
+        ))
+        .bind(docket.case_govid.as_str())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        sqlx::query(&format!("DROP SCHEMA {pg_schema} CASCADE"))
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        assert!(err.is::<InvalidSchemaName>(), "{err:#}");
+        assert!(version_res.is_err());
+        assert!(recreate_res.is_err());
+        assert_eq!(docket_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_recreate_leaves_old_schema_intact() {
+        let pool = setup_test_db().await;
+        let pg_schema = format!("test_recreate_{}", Uuid::new_v4().simple());
+        let mut conn = pool.acquire().await.unwrap();
+        create_named_schema(&pg_schema, DOKITO_SCHEMA_MIGRATIONS, &mut conn)
+            .await
+            .unwrap();
+        sqlx::query(&format!(
+            "INSERT INTO {pg_schema}.dockets (docket_govid, opened_date) VALUES ('KEEP-ME', '2024-01-01')"
+        ))
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let mut broken_migrations = DOKITO_SCHEMA_MIGRATIONS.to_vec();
+        broken_migrations.push((
+            "999_broken",
+            "CREATE TABLE public.broken (uuid not_a_type);",
+        ));
+        let recreate_result =
+            recreate_named_schema(&pg_schema, &broken_migrations, &mut conn).await;
+        let surviving_govids: Result<Vec<String>, _> =
+            sqlx::query_scalar(&format!("SELECT docket_govid FROM {pg_schema}.dockets"))
+                .fetch_all(&mut *conn)
+                .await;
+        let recreated =
+            recreate_named_schema(&pg_schema, DOKITO_SCHEMA_MIGRATIONS, &mut conn).await;
+
+        sqlx::query(&format!("DROP SCHEMA {pg_schema} CASCADE"))
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        let err = recreate_result.expect_err("The broken migration should fail the recreate");
+        assert!(format!("{err:#}").contains("rolled back"), "{err:#}");
+        assert_eq!(surviving_govids.unwrap(), vec!["KEEP-ME"]);
+
+        let report = recreated.unwrap();
+        assert!(report.dropped_existing);
+        assert_eq!(
+            report.applied_migrations.len(),
+            DOKITO_SCHEMA_MIGRATIONS.len()
+        );
+    }
+}
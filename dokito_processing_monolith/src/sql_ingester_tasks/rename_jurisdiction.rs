@@ -0,0 +1,303 @@
+use async_trait::async_trait;
+use aws_sdk_s3::Client;
+use chrono::{DateTime, Utc};
+use mycorrhiza_common::{
+    s3_generic::fetchers_and_getters::{S3Addr, S3DirectoryAddr},
+    tasks::ExecuteUserTask,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::{
+    health_gate::WORKER_HEALTH_GATE,
+    jurisdiction_schema_mapping::{FixedJurisdiction, JurisdictionInfoExt, validate_schema_name},
+    s3_key_layout::S3_KEY_LAYOUT,
+    s3_stuff::{limit_s3_request, upload_s3_bytes},
+    sql_ingester_tasks::dokito_sql_connection::{get_dokito_pool, get_dokito_pool_for},
+    types::{
+        env_vars::{DIGITALOCEAN_S3, OPENSCRAPERS_S3_OBJECT_BUCKET},
+        jurisdictions::JurisdictionInfo,
+    },
+};
+
+/// Moves a jurisdiction's dockets in S3 and its postgres schema over to a new name.
+#[derive(Clone, Deserialize, JsonSchema)]
+pub struct RenameJurisdiction {
+    pub from: JurisdictionInfo,
+    pub to: JurisdictionInfo,
+    /// Only report what would be moved, without touching S3 or postgres.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[async_trait]
+impl ExecuteUserTask for RenameJurisdiction {
+    async fn execute_task(self: Box<Self>) -> Result<Value, Value> {
+        WORKER_HEALTH_GATE.wait_until_ready().await;
+        let store = S3JurisdictionObjectStore {
+            s3_client: DIGITALOCEAN_S3.make_s3_client().await,
+        };
+        let pool = match FixedJurisdiction::try_from(&self.from) {
+            Ok(fixed_jur) => get_dokito_pool_for(fixed_jur).await,
+            Err(_) => get_dokito_pool().await,
+        }
+        .map_err(|err| Value::from(err.to_string()))?;
+        match rename_jurisdiction(&self, &store, pool).await {
+            Ok(report) => serde_json::to_value(report).map_err(|err| err.to_string().into()),
+            Err(err) => {
+                tracing::error!(error = %err, error_debug = ?err, "Encountered error in rename_jurisdiction");
+                Err(err.to_string().into())
+            }
+        }
+    }
+    fn get_task_label(&self) -> &'static str {
+        "rename_jurisdiction"
+    }
+    fn get_task_label_static() -> &'static str
+    where
+        Self: Sized,
+    {
+        "rename_jurisdiction"
+    }
+}
+
+/// Where a jurisdiction's docket objects live.
+#[async_trait]
+pub trait JurisdictionObjectStore: Sync {
+    async fn list_keys(&self, prefix: &str) -> anyhow::Result<Vec<String>>;
+    async fn copy_object(&self, from_key: &str, to_key: &str) -> anyhow::Result<()>;
+    async fn delete_object(&self, key: &str) -> anyhow::Result<()>;
+    async fn read_object(&self, key: &str) -> anyhow::Result<Vec<u8>>;
+    async fn last_modified(&self, key: &str) -> anyhow::Result<Option<DateTime<Utc>>>;
+}
+
+pub struct S3JurisdictionObjectStore {
+    pub s3_client: Client,
+}
+
+#[async_trait]
+impl JurisdictionObjectStore for S3JurisdictionObjectStore {
+    async fn list_keys(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        limit_s3_request(
+            S3DirectoryAddr::new(&self.s3_client, &OPENSCRAPERS_S3_OBJECT_BUCKET, prefix)
+                .list_all(),
+        )
+        .await
+    }
+    async fn copy_object(&self, from_key: &str, to_key: &str) -> anyhow::Result<()> {
+        let bucket = &**OPENSCRAPERS_S3_OBJECT_BUCKET;
+        let contents =
+            limit_s3_request(S3Addr::new(&self.s3_client, bucket, from_key).download_bytes())
+                .await?;
+        upload_s3_bytes(&self.s3_client, bucket, to_key, contents).await
+    }
+    async fn delete_object(&self, key: &str) -> anyhow::Result<()> {
+        limit_s3_request(
+            S3Addr::new(&self.s3_client, &OPENSCRAPERS_S3_OBJECT_BUCKET, key).delete_file(),
+        )
+        .await
+    }
+    async fn read_object(&self, key: &str) -> anyhow::Result<Vec<u8>> {
+        limit_s3_request(
+            S3Addr::new(&self.s3_client, &OPENSCRAPERS_S3_OBJECT_BUCKET, key).download_bytes(),
+        )
+        .await
+    }
+    async fn last_modified(&self, key: &str) -> anyhow::Result<Option<DateTime<Utc>>> {
+        let head = limit_s3_request(
+            self.s3_client
+                .head_object()
+                .bucket(&**OPENSCRAPERS_S3_OBJECT_BUCKET)
+                .key(key)
+                .send(),
+        )
+        .await?;
+        Ok(head.last_modified().and_then(|modified| {
+            DateTime::from_timestamp(modified.secs(), modified.subsec_nanos())
+        }))
+    }
+}
+
+#[derive(Serialize, JsonSchema, Debug, PartialEq)]
+pub struct MovedObject {
+    pub from_key: String,
+    pub to_key: String,
+}
+
+/// What a `RenameJurisdiction` moved, or would move on a dry run.
+#[derive(Serialize, JsonSchema, Debug)]
+pub struct RenameJurisdictionReport {
+    pub dry_run: bool,
+    pub moved_objects: Vec<MovedObject>,
+    pub from_schema: String,
+    pub to_schema: String,
+    /// Whether there was a schema under the old name to rename.
+    pub schema_renamed: bool,
+}
+
+/// The schema a jurisdiction's dockets are ingested into. Jurisdictions we store go through
+/// [`FixedJurisdiction::get_postgres_schema_name`], so their `POSTGRES_SCHEMA_OVERRIDES` entry
+/// applies.
+fn jurisdiction_schema_name(jurisdiction: &JurisdictionInfo) -> String {
+    match FixedJurisdiction::try_from(jurisdiction) {
+        Ok(fixed_jur) => fixed_jur.get_postgres_schema_name().to_string(),
+        Err(_) => jurisdiction.default_postgres_schema_name(),
+    }
+}
+
+/// Copies every docket object over to the new prefixes, renames the schema, and only then deletes
+/// the old objects, so a failure partway leaves everything readable under the old name.
+pub async fn rename_jurisdiction(
+    request: &RenameJurisdiction,
+    store: &impl JurisdictionObjectStore,
+    pool: &PgPool,
+) -> anyhow::Result<RenameJurisdictionReport> {
+    let (from, to) = (&request.from, &request.to);
+    let from_schema = jurisdiction_schema_name(from);
+    let to_schema = jurisdiction_schema_name(to);
+    if from_schema == to_schema {
+        anyhow::bail!(
+            "{from:?} and {to:?} share the schema {from_schema}, there is nothing to rename"
+        );
+    }
+    validate_schema_name(&from_schema)?;
+    validate_schema_name(&to_schema)?;
+
+    let schema_exists = async |pg_schema: &str| -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pg_namespace WHERE nspname = $1)")
+            .bind(pg_schema)
+            .fetch_one(pool)
+            .await
+    };
+    if schema_exists(&to_schema).await? {
+        anyhow::bail!(
+            "Schema {to_schema} already exists, refusing to rename {from_schema} over it"
+        );
+    }
+    let schema_renamed = schema_exists(&from_schema).await?;
+
+    let mut moved_objects = vec![];
+    for (from_prefix, to_prefix) in [
+        (
+            S3_KEY_LAYOUT.raw_docket_prefix(from),
+            S3_KEY_LAYOUT.raw_docket_prefix(to),
+        ),
+        (
+            S3_KEY_LAYOUT.processed_docket_prefix(from),
+            S3_KEY_LAYOUT.processed_docket_prefix(to),
+        ),
+    ] {
+        for from_key in store.list_keys(&from_prefix).await? {
+            if let Some(rest) = from_key.strip_prefix(&from_prefix) {
+                let to_key = format!("{to_prefix}{rest}");
+                moved_objects.push(MovedObject { from_key, to_key });
+            }
+        }
+    }
+    info!(
+        ?from,
+        ?to,
+        dry_run = request.dry_run,
+        object_count = moved_objects.len(),
+        %from_schema,
+        %to_schema,
+        schema_renamed,
+        "Planned jurisdiction rename"
+    );
+
+    if !request.dry_run {
+        for moved in &moved_objects {
+            store.copy_object(&moved.from_key, &moved.to_key).await?;
+        }
+        if schema_renamed {
+            sqlx::query(&format!("ALTER SCHEMA {from_schema} RENAME TO {to_schema}"))
+                .execute(pool)
+                .await?;
+        }
+        for moved in &moved_objects {
+            store.delete_object(&moved.from_key).await?;
+        }
+        info!(?from, ?to, "Finished jurisdiction rename");
+    }
+
+    Ok(RenameJurisdictionReport {
+        dry_run: request.dry_run,
+        moved_objects,
+        from_schema,
+        to_schema,
+        schema_renamed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql_ingester_tasks::recreate_dokito_table_schema::create_target_schema;
+    use crate::test_fixtures::{MemoryObjectStore, setup_test_db};
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_rename_moves_objects_and_schema_unless_dry_run() {
+        let pool = setup_test_db().await;
+        let run_id = &Uuid::new_v4().simple().to_string()[..8];
+        let from = JurisdictionInfo::new_usa(&format!("rename_from_{run_id}"), "zz");
+        let to = JurisdictionInfo::new_usa(&format!("rename_to_{run_id}"), "zz");
+        create_target_schema(&jurisdiction_schema_name(&from), &pool)
+            .await
+            .unwrap();
+
+        let raw_key = format!("{}CASE-1.json", S3_KEY_LAYOUT.raw_docket_prefix(&from));
+        let processed_key = format!(
+            "{}CASE-1.json",
+            S3_KEY_LAYOUT.processed_docket_prefix(&from)
+        );
+        let store = MemoryObjectStore::default();
+        for key in [&raw_key, &processed_key] {
+            store.insert(key, b"{}");
+        }
+        let schema_exists = async |pg_schema: String| -> bool {
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pg_namespace WHERE nspname = $1)")
+                .bind(pg_schema)
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+        };
+
+        let mut request = RenameJurisdiction {
+            from: from.clone(),
+            to: to.clone(),
+            dry_run: true,
+        };
+        let dry_report = rename_jurisdiction(&request, &store, &pool).await.unwrap();
+        assert_eq!(dry_report.moved_objects.len(), 2);
+        assert!(dry_report.schema_renamed);
+        let mut keys_before = vec![raw_key.clone(), processed_key.clone()];
+        keys_before.sort();
+        assert_eq!(store.keys(), keys_before);
+        assert!(schema_exists(jurisdiction_schema_name(&from)).await);
+        assert!(!schema_exists(jurisdiction_schema_name(&to)).await);
+
+        request.dry_run = false;
+        let report = rename_jurisdiction(&request, &store, &pool).await.unwrap();
+        let mut keys_after = vec![
+            format!("{}CASE-1.json", S3_KEY_LAYOUT.raw_docket_prefix(&to)),
+            format!("{}CASE-1.json", S3_KEY_LAYOUT.processed_docket_prefix(&to)),
+        ];
+        keys_after.sort();
+        assert_eq!(store.keys(), keys_after);
+        assert_eq!(report.moved_objects, dry_report.moved_objects);
+        assert!(!schema_exists(jurisdiction_schema_name(&from)).await);
+        assert!(schema_exists(jurisdiction_schema_name(&to)).await);
+
+        sqlx::query(&format!(
+            "DROP SCHEMA {} CASCADE",
+            jurisdiction_schema_name(&to)
+        ))
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,151 @@
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use tracing::info;
+use uuid::Uuid;
+
+use mycorrhiza_common::tasks::ExecuteUserTask;
+
+use crate::{
+    health_gate::WORKER_HEALTH_GATE, jurisdiction_schema_mapping::FixedJurisdiction,
+    openscraper_data_traits::split_combined_type,
+    sql_ingester_tasks::dokito_sql_connection::get_dokito_pool_for,
+};
+
+/// Splits "Type - Subtype" docket types into `docket_type` and `docket_subtype` in postgres, the
+/// same way revalidating a processed docket does, without reprocessing anything.
+#[derive(Clone, Copy, Deserialize, JsonSchema)]
+pub struct SplitDocketTypes(pub FixedJurisdiction);
+
+#[async_trait]
+impl ExecuteUserTask for SplitDocketTypes {
+    async fn execute_task(self: Box<Self>) -> Result<Value, Value> {
+        WORKER_HEALTH_GATE.wait_until_ready().await;
+        let fixed_jur = self.0;
+        let pool = get_dokito_pool_for(fixed_jur)
+            .await
+            .map_err(|err| Value::from(err.to_string()))?;
+        match split_docket_types(fixed_jur.get_postgres_schema_name(), pool).await {
+            Ok(report) => serde_json::to_value(report).map_err(|err| err.to_string().into()),
+            Err(err) => {
+                tracing::error!(error = %err, error_debug = ?err, "Encountered error in split_docket_types");
+                Err(err.to_string().into())
+            }
+        }
+    }
+    fn get_task_label(&self) -> &'static str {
+        "split_docket_types"
+    }
+    fn get_task_label_static() -> &'static str
+    where
+        Self: Sized,
+    {
+        "split_docket_types"
+    }
+}
+
+#[derive(Serialize, JsonSchema, Debug)]
+pub struct SplitDocketTypesReport {
+    pub dockets_updated: usize,
+}
+
+#[derive(FromRow)]
+struct CombinedTypeRow {
+    uuid: Uuid,
+    docket_type: String,
+}
+
+pub async fn split_docket_types(
+    pg_schema: &str,
+    pool: &PgPool,
+) -> anyhow::Result<SplitDocketTypesReport> {
+    let rows = sqlx::query_as::<_, CombinedTypeRow>(&format!(
+        "SELECT uuid, docket_type FROM {pg_schema}.dockets
+         WHERE docket_subtype = '' AND docket_type LIKE '% - %'"
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    let mut tx = pool.begin().await?;
+    let mut dockets_updated = 0;
+    for row in rows {
+        let Some((docket_type, docket_subtype)) = split_combined_type(&row.docket_type) else {
+            continue;
+        };
+        sqlx::query(&format!(
+            "UPDATE {pg_schema}.dockets SET docket_type = $1, docket_subtype = $2 WHERE uuid = $3"
+        ))
+        .bind(docket_type)
+        .bind(docket_subtype)
+        .bind(row.uuid)
+        .execute(&mut *tx)
+        .await?;
+        dockets_updated += 1;
+    }
+    tx.commit().await?;
+    info!(%pg_schema, dockets_updated, "Split combined docket types");
+
+    Ok(SplitDocketTypesReport { dockets_updated })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_processing_traits::Revalidate;
+    use crate::sql_ingester_tasks::{
+        database_author_association::OrgUuidCache, nypuc_ingest::ingest_sql_case_with_retries,
+        recreate_dokito_table_schema::create_target_schema,
+    };
+    use crate::test_fixtures::{make_processed_docket, setup_test_db};
+    use chrono::NaiveDate;
+
+    #[tokio::test]
+    async fn test_db_task_splits_like_revalidate() {
+        let pool = setup_test_db().await;
+        let pg_schema = format!("split_types_{}", &Uuid::new_v4().simple().to_string()[..8]);
+        create_target_schema(&pg_schema, &pool).await.unwrap();
+        let opened_date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let mut docket = make_processed_docket("SPLIT-TYPE-1", opened_date, vec![]);
+        docket.case_type = "A - B - C".to_string();
+        docket.case_subtype = String::new();
+        let mut revalidated = docket.clone();
+        ingest_sql_case_with_retries(
+            &mut docket,
+            FixedJurisdiction::NewYorkPuc,
+            Some(&pg_schema),
+            &pool,
+            &OrgUuidCache::default(),
+            false,
+            1,
+        )
+        .await
+        .unwrap();
+
+        revalidated.revalidate().await;
+        let report = split_docket_types(&pg_schema, &pool).await.unwrap();
+        assert_eq!(report.dockets_updated, 1);
+        let (docket_type, docket_subtype) = sqlx::query_as::<_, (String, String)>(&format!(
+            "SELECT docket_type, docket_subtype FROM {pg_schema}.dockets WHERE docket_govid = $1"
+        ))
+        .bind("SPLIT-TYPE-1")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            (docket_type.as_str(), docket_subtype.as_str()),
+            ("A", "B - C")
+        );
+        assert_eq!(revalidated.case_type, docket_type);
+        assert_eq!(revalidated.case_subtype, docket_subtype);
+
+        let rerun = split_docket_types(&pg_schema, &pool).await.unwrap();
+        assert_eq!(rerun.dockets_updated, 0);
+
+        sqlx::query(&format!("DROP SCHEMA {pg_schema} CASCADE"))
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
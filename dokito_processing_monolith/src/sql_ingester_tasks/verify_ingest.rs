@@ -0,0 +1,166 @@
+use std::collections::BTreeSet;
+
+use dokito_types::processed::ProcessedGenericDocket;
+use schemars::JsonSchema;
+use serde::Serialize;
+use sqlx::{PgPool, query_as, query_scalar};
+use uuid::Uuid;
+
+use crate::jurisdiction_schema_mapping::validate_schema_name;
+
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct DocketVerification {
+    pub docket_govid: String,
+    /// Whether postgres holds exactly the docket, fillings and attachments of the processed docket.
+    pub verified: bool,
+    pub discrepancies: Vec<String>,
+}
+
+/// Compares an ingested docket against the processed docket it was ingested from, by the uuids
+/// of its rows. Rows only one side has are reported as discrepancies.
+pub async fn verify_ingested_docket(
+    docket: &ProcessedGenericDocket,
+    pg_schema: &str,
+    pool: &PgPool,
+) -> anyhow::Result<DocketVerification> {
+    let pg_schema = validate_schema_name(pg_schema)?;
+    let mut discrepancies = vec![];
+
+    let docket_row: Option<(String, String)> = query_as(&format!(
+        "SELECT docket_govid, docket_title FROM {pg_schema}.dockets WHERE uuid = $1"
+    ))
+    .bind(docket.object_uuid)
+    .fetch_optional(pool)
+    .await?;
+    match docket_row {
+        None => discrepancies.push(format!("docket {} is missing", docket.object_uuid)),
+        Some((docket_govid, docket_title)) => {
+            if docket_govid != docket.case_govid.as_str() {
+                discrepancies.push(format!(
+                    "docket govid is {docket_govid:?} instead of {:?}",
+                    docket.case_govid.as_str()
+                ));
+            }
+            if docket_title != docket.case_name {
+                discrepancies.push(format!(
+                    "docket title is {docket_title:?} instead of {:?}",
+                    docket.case_name
+                ));
+            }
+        }
+    }
+
+    let db_filling_uuids: Vec<Uuid> = query_scalar(&format!(
+        "SELECT uuid FROM {pg_schema}.fillings WHERE docket_uuid = $1"
+    ))
+    .bind(docket.object_uuid)
+    .fetch_all(pool)
+    .await?;
+    let processed_filling_uuids = docket.filings.iter().map(|filling| filling.object_uuid);
+    compare_row_uuids(
+        "filling",
+        processed_filling_uuids,
+        db_filling_uuids,
+        &mut discrepancies,
+    );
+
+    let db_attachment_uuids: Vec<Uuid> = query_scalar(&format!(
+        "SELECT a.uuid FROM {pg_schema}.attachments a
+         JOIN {pg_schema}.fillings f ON a.parent_filling_uuid = f.uuid
+         WHERE f.docket_uuid = $1"
+    ))
+    .bind(docket.object_uuid)
+    .fetch_all(pool)
+    .await?;
+    let processed_attachment_uuids = docket
+        .filings
+        .iter()
+        .flat_map(|filling| filling.attachments.iter())
+        .map(|attachment| attachment.object_uuid);
+    compare_row_uuids(
+        "attachment",
+        processed_attachment_uuids,
+        db_attachment_uuids,
+        &mut discrepancies,
+    );
+
+    Ok(DocketVerification {
+        docket_govid: docket.case_govid.to_string(),
+        verified: discrepancies.is_empty(),
+        discrepancies,
+    })
+}
+
+fn compare_row_uuids(
+    row_kind: &str,
+    processed_uuids: impl IntoIterator<Item = Uuid>,
+    db_uuids: impl IntoIterator<Item = Uuid>,
+    discrepancies: &mut Vec<String>,
+) {
+    let processed_uuids = processed_uuids.into_iter().collect::<BTreeSet<_>>();
+    let db_uuids = db_uuids.into_iter().collect::<BTreeSet<_>>();
+    for missing in processed_uuids.difference(&db_uuids) {
+        discrepancies.push(format!("{row_kind} {missing} is missing"));
+    }
+    for unexpected in db_uuids.difference(&processed_uuids) {
+        discrepancies.push(format!(
+            "{row_kind} {unexpected} is not in the processed docket"
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jurisdiction_schema_mapping::FixedJurisdiction;
+    use crate::sql_ingester_tasks::nypuc_ingest::ingest_sql_fixed_jurisdiction_case;
+    use crate::test_fixtures::{
+        make_processed_attachment, make_processed_docket, make_processed_filing, setup_test_db,
+    };
+    use chrono::NaiveDate;
+
+    #[tokio::test]
+    async fn test_verification_reports_seeded_mismatch() {
+        let pool = setup_test_db().await;
+        let fixed_jur = FixedJurisdiction::NewYorkPuc;
+        let pg_schema = fixed_jur.get_postgres_schema_name();
+        let filed_date = NaiveDate::from_ymd_opt(2024, 4, 5).unwrap();
+        let mut case = make_processed_docket(
+            &format!("TEST-{}", Uuid::new_v4()),
+            filed_date,
+            vec![make_processed_filing(
+                "1",
+                filed_date,
+                vec![
+                    make_processed_attachment("Comments", ""),
+                    make_processed_attachment("Exhibit A", ""),
+                ],
+            )],
+        );
+        ingest_sql_fixed_jurisdiction_case(&mut case, fixed_jur, &pool, false)
+            .await
+            .expect("Ingest should succeed");
+
+        let verification = verify_ingested_docket(&case, pg_schema, &pool)
+            .await
+            .unwrap();
+        assert!(verification.verified, "{:?}", verification.discrepancies);
+
+        let dropped_attachment = case.filings[0].attachments[1].object_uuid;
+        sqlx::query(&format!(
+            "DELETE FROM {pg_schema}.attachments WHERE uuid = $1"
+        ))
+        .bind(dropped_attachment)
+        .execute(&pool)
+        .await
+        .unwrap();
+        let verification = verify_ingested_docket(&case, pg_schema, &pool)
+            .await
+            .unwrap();
+        assert!(!verification.verified);
+        assert_eq!(
+            verification.discrepancies,
+            vec![format!("attachment {dropped_attachment} is missing")]
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mycorrhiza_common::tasks::ExecuteUserTask;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::{
+    health_gate::WORKER_HEALTH_GATE,
+    processing::ReprocessDocketInfo,
+    s3_key_layout::S3_KEY_LAYOUT,
+    sql_ingester_tasks::{
+        cleanup_orphaned_dockets::docket_keys_by_govid,
+        rename_jurisdiction::{JurisdictionObjectStore, S3JurisdictionObjectStore},
+    },
+    types::{env_vars::DIGITALOCEAN_S3, jurisdictions::JurisdictionInfo},
+};
+
+/// Finds the processed dockets of a jurisdiction whose raw docket was uploaded again after they
+/// were processed, so they no longer reflect their source.
+#[derive(Clone, Deserialize, JsonSchema)]
+pub struct VerifyProcessedAgainstRaw {
+    pub jurisdiction: JurisdictionInfo,
+    /// Reprocess the stale dockets from their current raw docket, instead of only reporting them.
+    #[serde(default)]
+    pub reprocess: bool,
+}
+
+#[async_trait]
+impl ExecuteUserTask for VerifyProcessedAgainstRaw {
+    async fn execute_task(self: Box<Self>) -> Result<Value, Value> {
+        WORKER_HEALTH_GATE.wait_until_ready().await;
+        let store = S3JurisdictionObjectStore {
+            s3_client: DIGITALOCEAN_S3.make_s3_client().await,
+        };
+        let mut report = match find_stale_processed_dockets(&self.jurisdiction, &store).await {
+            Ok(report) => report,
+            Err(err) => {
+                tracing::error!(error = %err, error_debug = ?err, "Encountered error in verify_processed_against_raw");
+                return Err(err.to_string().into());
+            }
+        };
+        if self.reprocess {
+            for stale in &report.stale_dockets {
+                let reprocess = ReprocessDocketInfo {
+                    docket_govid: stale.docket_govid.clone(),
+                    jurisdiction: self.jurisdiction.clone(),
+                    only_process_missing: false,
+                    ignore_cachced_if_older_than: None,
+                    skip_closed: false,
+                    skip_closed_after_days: None,
+                };
+                match Box::new(reprocess).execute_task().await {
+                    Ok(_) => report.reprocessed_count += 1,
+                    Err(err) => {
+                        warn!(docket_govid = %stale.docket_govid, error = %err, "Failed to reprocess stale docket");
+                        report.failed_dockets.push(VerifyDocketFailure {
+                            docket_govid: stale.docket_govid.clone(),
+                            error: err.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+        report.failed_count = report.failed_dockets.len();
+        serde_json::to_value(report).map_err(|err| err.to_string().into())
+    }
+    fn get_task_label(&self) -> &'static str {
+        "verify_processed_against_raw"
+    }
+    fn get_task_label_static() -> &'static str
+    where
+        Self: Sized,
+    {
+        "verify_processed_against_raw"
+    }
+}
+
+#[derive(Serialize, JsonSchema, Debug)]
+pub struct StaleProcessedDocket {
+    pub docket_govid: String,
+    pub processed_at: DateTime<Utc>,
+    pub raw_last_modified: DateTime<Utc>,
+}
+
+/// A docket the task couldn't check or reprocess.
+#[derive(Serialize, JsonSchema, Debug)]
+pub struct VerifyDocketFailure {
+    pub docket_govid: String,
+    pub error: String,
+}
+
+/// What a `VerifyProcessedAgainstRaw` found, and reprocessed when asked to.
+#[derive(Serialize, JsonSchema, Debug)]
+pub struct StaleProcessedDocketsReport {
+    pub checked_count: usize,
+    pub stale_count: usize,
+    pub reprocessed_count: usize,
+    pub failed_count: usize,
+    pub stale_dockets: Vec<StaleProcessedDocket>,
+    /// One failing docket doesn't stop the rest from being checked, it's reported here instead.
+    pub failed_dockets: Vec<VerifyDocketFailure>,
+}
+
+/// Only the one field of a processed docket this task looks at, so the rest isn't parsed.
+#[derive(Deserialize)]
+struct ProcessedAt {
+    processed_at: DateTime<Utc>,
+}
+
+/// Compares every processed docket's `processed_at` against when its raw docket was last
+/// written. Processed dockets without a raw docket are left to `CleanupOrphanedDockets`.
+pub async fn find_stale_processed_dockets(
+    jurisdiction: &JurisdictionInfo,
+    store: &impl JurisdictionObjectStore,
+) -> anyhow::Result<StaleProcessedDocketsReport> {
+    let raw_keys =
+        docket_keys_by_govid(store, &S3_KEY_LAYOUT.raw_docket_prefix(jurisdiction)).await?;
+    let processed_keys =
+        docket_keys_by_govid(store, &S3_KEY_LAYOUT.processed_docket_prefix(jurisdiction)).await?;
+
+    let mut checked_count = 0;
+    let mut stale_dockets = vec![];
+    let mut failed_dockets = vec![];
+    for (docket_govid, processed_key) in &processed_keys {
+        let Some(raw_key) = raw_keys.get(docket_govid) else {
+            continue;
+        };
+        checked_count += 1;
+        match check_processed_docket(docket_govid, raw_key, processed_key, store).await {
+            Ok(Some(stale)) => stale_dockets.push(stale),
+            Ok(None) => {}
+            Err(err) => {
+                warn!(%docket_govid, error = %err, "Failed to compare processed docket against its raw docket");
+                failed_dockets.push(VerifyDocketFailure {
+                    docket_govid: docket_govid.clone(),
+                    error: err.to_string(),
+                });
+            }
+        }
+    }
+    info!(
+        ?jurisdiction,
+        checked_count,
+        stale_count = stale_dockets.len(),
+        failed_count = failed_dockets.len(),
+        "Compared processed dockets against their raw dockets"
+    );
+    Ok(StaleProcessedDocketsReport {
+        checked_count,
+        stale_count: stale_dockets.len(),
+        reprocessed_count: 0,
+        failed_count: failed_dockets.len(),
+        stale_dockets,
+        failed_dockets,
+    })
+}
+
+/// The docket when its raw docket was written after it was processed.
+async fn check_processed_docket(
+    docket_govid: &str,
+    raw_key: &str,
+    processed_key: &str,
+    store: &impl JurisdictionObjectStore,
+) -> anyhow::Result<Option<StaleProcessedDocket>> {
+    let Some(raw_last_modified) = store.last_modified(raw_key).await? else {
+        return Ok(None);
+    };
+    let processed_bytes = store.read_object(processed_key).await?;
+    let processed_at = match serde_json::from_slice::<ProcessedAt>(&processed_bytes) {
+        Ok(ProcessedAt { processed_at }) => processed_at,
+        Err(err) => {
+            warn!(%docket_govid, error = %err, "Processed docket has no readable processed_at, skipping");
+            return Ok(None);
+        }
+    };
+    Ok(
+        (raw_last_modified > processed_at).then(|| StaleProcessedDocket {
+            docket_govid: docket_govid.to_string(),
+            processed_at,
+            raw_last_modified,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_processing_traits::ProcessFrom;
+    use crate::jurisdiction_schema_mapping::FixedJurisdiction;
+    use crate::test_fixtures::{MemoryObjectStore, make_processed_docket, make_raw_docket};
+    use crate::types::processed::ProcessedGenericDocket;
+    use chrono::{Duration, NaiveDate};
+
+    /// A store whose reads of one key fail, like an S3 read that timed out.
+    struct FailingReadStore {
+        inner: MemoryObjectStore,
+        failing_key: String,
+    }
+
+    #[async_trait]
+    impl JurisdictionObjectStore for FailingReadStore {
+        async fn list_keys(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+            self.inner.list_keys(prefix).await
+        }
+        async fn copy_object(&self, from_key: &str, to_key: &str) -> anyhow::Result<()> {
+            self.inner.copy_object(from_key, to_key).await
+        }
+        async fn delete_object(&self, key: &str) -> anyhow::Result<()> {
+            self.inner.delete_object(key).await
+        }
+        async fn read_object(&self, key: &str) -> anyhow::Result<Vec<u8>> {
+            if key == self.failing_key {
+                anyhow::bail!("read of {key} timed out");
+            }
+            self.inner.read_object(key).await
+        }
+        async fn last_modified(&self, key: &str) -> anyhow::Result<Option<DateTime<Utc>>> {
+            self.inner.last_modified(key).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_raw_docket_newer_than_processed_is_flagged() {
+        let jurisdiction = JurisdictionInfo::new_usa("drift_test", "zz");
+        let key = |prefix: String, govid: &str| format!("{prefix}{govid}.json");
+        let raw_key = |govid: &str| key(S3_KEY_LAYOUT.raw_docket_prefix(&jurisdiction), govid);
+        let processed_key =
+            |govid: &str| key(S3_KEY_LAYOUT.processed_docket_prefix(&jurisdiction), govid);
+        let processed_at = Utc::now() - Duration::hours(2);
+        let store = MemoryObjectStore::default();
+        for govid in ["CURRENT-1", "DRIFTED-1"] {
+            let opened_date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+            let mut docket = make_processed_docket(govid, opened_date, vec![]);
+            docket.processed_at = processed_at;
+            store.insert(&processed_key(govid), &serde_json::to_vec(&docket).unwrap());
+        }
+        store.insert_modified_at(
+            &raw_key("CURRENT-1"),
+            b"{}",
+            processed_at - Duration::minutes(5),
+        );
+        store.insert_modified_at(
+            &raw_key("DRIFTED-1"),
+            b"{}",
+            processed_at + Duration::minutes(5),
+        );
+
+        let report = find_stale_processed_dockets(&jurisdiction, &store)
+            .await
+            .unwrap();
+        assert_eq!(report.checked_count, 2);
+        assert_eq!(report.stale_count, 1);
+        assert_eq!(report.stale_dockets[0].docket_govid, "DRIFTED-1");
+        assert_eq!(report.stale_dockets[0].processed_at, processed_at);
+    }
+
+    #[tokio::test]
+    async fn test_failing_docket_is_reported_without_stopping_the_rest() {
+        let jurisdiction = JurisdictionInfo::new_usa("drift_failure_test", "zz");
+        let key = |prefix: String, govid: &str| format!("{prefix}{govid}.json");
+        let raw_key = |govid: &str| key(S3_KEY_LAYOUT.raw_docket_prefix(&jurisdiction), govid);
+        let processed_key =
+            |govid: &str| key(S3_KEY_LAYOUT.processed_docket_prefix(&jurisdiction), govid);
+        let processed_at = Utc::now() - Duration::hours(2);
+        let store = FailingReadStore {
+            inner: MemoryObjectStore::default(),
+            failing_key: processed_key("BROKEN-1"),
+        };
+        for govid in ["BROKEN-1", "DRIFTED-1"] {
+            let opened_date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+            let mut docket = make_processed_docket(govid, opened_date, vec![]);
+            docket.processed_at = processed_at;
+            store
+                .inner
+                .insert(&processed_key(govid), &serde_json::to_vec(&docket).unwrap());
+            store.inner.insert_modified_at(
+                &raw_key(govid),
+                b"{}",
+                processed_at + Duration::minutes(5),
+            );
+        }
+
+        let report = find_stale_processed_dockets(&jurisdiction, &store)
+            .await
+            .expect("A failing docket shouldn't fail the whole check");
+        assert_eq!(report.checked_count, 2);
+        assert_eq!(report.stale_count, 1);
+        assert_eq!(report.stale_dockets[0].docket_govid, "DRIFTED-1");
+        assert_eq!(report.failed_count, 1);
+        assert_eq!(report.failed_dockets[0].docket_govid, "BROKEN-1");
+        assert!(
+            report.failed_dockets[0].error.contains("timed out"),
+            "{}",
+            report.failed_dockets[0].error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reprocessed_docket_is_no_longer_flagged() {
+        let jurisdiction = JurisdictionInfo::new_usa("drift_reprocess_test", "zz");
+        let raw_key = format!(
+            "{}REPROCESSED-1.json",
+            S3_KEY_LAYOUT.raw_docket_prefix(&jurisdiction)
+        );
+        let processed_key = format!(
+            "{}REPROCESSED-1.json",
+            S3_KEY_LAYOUT.processed_docket_prefix(&jurisdiction)
+        );
+        let store = MemoryObjectStore::default();
+        store.insert_modified_at(&raw_key, b"{}", Utc::now() - Duration::hours(1));
+        let mut cached = ProcessedGenericDocket::process_from(
+            make_raw_docket("REPROCESSED-1", vec![]),
+            None,
+            FixedJurisdiction::NewYorkPuc,
+        )
+        .await
+        .expect("Processing should succeed");
+        cached.processed_at = Utc::now() - Duration::hours(2);
+        store.insert(&processed_key, &serde_json::to_vec(&cached).unwrap());
+        let before = find_stale_processed_dockets(&jurisdiction, &store)
+            .await
+            .unwrap();
+
+        // The raw docket didn't change, so reprocessing gives the same docket, processed just now.
+        let reprocessed = ProcessedGenericDocket::process_from(
+            make_raw_docket("REPROCESSED-1", vec![]),
+            Some(cached),
+            FixedJurisdiction::NewYorkPuc,
+        )
+        .await
+        .expect("Reprocessing should succeed");
+        store.insert(&processed_key, &serde_json::to_vec(&reprocessed).unwrap());
+        let after = find_stale_processed_dockets(&jurisdiction, &store)
+            .await
+            .unwrap();
+
+        assert_eq!(before.stale_count, 1);
+        assert_eq!(after.stale_count, 0);
+    }
+}
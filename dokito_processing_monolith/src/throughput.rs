@@ -0,0 +1,173 @@
+use std::{
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::Json;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::types::processed::ProcessedGenericDocket;
+
+const THROUGHPUT_BUCKET_WIDTH: Duration = Duration::from_secs(15);
+/// 15 minutes of 15 second buckets.
+const THROUGHPUT_BUCKET_COUNT: usize = 60;
+
+/// How many dockets got processed and ingested recently, kept for as long as the process runs.
+pub static PROCESSING_THROUGHPUT: LazyLock<ThroughputWindow> = LazyLock::new(|| {
+    ThroughputWindow::new(
+        Instant::now(),
+        THROUGHPUT_BUCKET_WIDTH,
+        THROUGHPUT_BUCKET_COUNT,
+    )
+});
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThroughputStage {
+    Processed,
+    Ingested,
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+struct StageCounts {
+    dockets: u64,
+    attachments: u64,
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+struct ThroughputBucket {
+    /// Which bucket width since the window started this slot holds, so a slot left over from a
+    /// previous lap around the ring is recognized as stale.
+    bucket_index: u64,
+    processed: StageCounts,
+    ingested: StageCounts,
+}
+
+/// Completion counts over a rolling window, kept in a ring of fixed width time buckets that get
+/// reused as the window moves on.
+pub struct ThroughputWindow {
+    started: Instant,
+    bucket_width: Duration,
+    buckets: Mutex<Vec<ThroughputBucket>>,
+}
+
+#[derive(Serialize, JsonSchema, Debug, PartialEq)]
+pub struct ThroughputStats {
+    pub window_seconds: u64,
+    pub dockets_processed_per_minute: f64,
+    pub attachments_processed_per_minute: f64,
+    pub dockets_ingested_per_minute: f64,
+    pub attachments_ingested_per_minute: f64,
+}
+
+impl ThroughputWindow {
+    pub fn new(started: Instant, bucket_width: Duration, bucket_count: usize) -> Self {
+        ThroughputWindow {
+            started,
+            bucket_width,
+            buckets: Mutex::new(vec![ThroughputBucket::default(); bucket_count.max(1)]),
+        }
+    }
+
+    fn bucket_index_at(&self, at: Instant) -> u64 {
+        let elapsed = at.saturating_duration_since(self.started);
+        (elapsed.as_nanos() / self.bucket_width.as_nanos().max(1)) as u64
+    }
+
+    pub fn record_docket(&self, stage: ThroughputStage, docket: &ProcessedGenericDocket) {
+        let attachment_count = docket
+            .filings
+            .iter()
+            .map(|filling| filling.attachments.len() as u64)
+            .sum();
+        self.record_at(stage, 1, attachment_count, Instant::now());
+    }
+
+    pub fn record_at(&self, stage: ThroughputStage, dockets: u64, attachments: u64, at: Instant) {
+        let bucket_index = self.bucket_index_at(at);
+        let mut buckets = self.buckets.lock().unwrap();
+        let slot = (bucket_index % buckets.len() as u64) as usize;
+        let bucket = &mut buckets[slot];
+        if bucket.bucket_index != bucket_index {
+            *bucket = ThroughputBucket {
+                bucket_index,
+                ..Default::default()
+            };
+        }
+        let counts = match stage {
+            ThroughputStage::Processed => &mut bucket.processed,
+            ThroughputStage::Ingested => &mut bucket.ingested,
+        };
+        counts.dockets += dockets;
+        counts.attachments += attachments;
+    }
+
+    /// The rates over the whole window, which includes the bucket still being filled.
+    pub fn stats_at(&self, at: Instant) -> ThroughputStats {
+        let current_index = self.bucket_index_at(at);
+        let buckets = self.buckets.lock().unwrap();
+        let bucket_count = buckets.len() as u64;
+        let mut processed = StageCounts::default();
+        let mut ingested = StageCounts::default();
+        for bucket in buckets.iter() {
+            if bucket.bucket_index > current_index
+                || bucket.bucket_index + bucket_count <= current_index
+            {
+                continue;
+            }
+            processed.dockets += bucket.processed.dockets;
+            processed.attachments += bucket.processed.attachments;
+            ingested.dockets += bucket.ingested.dockets;
+            ingested.attachments += bucket.ingested.attachments;
+        }
+        let window = self.bucket_width * bucket_count as u32;
+        let window_minutes = window.as_secs_f64() / 60.0;
+        ThroughputStats {
+            window_seconds: window.as_secs(),
+            dockets_processed_per_minute: processed.dockets as f64 / window_minutes,
+            attachments_processed_per_minute: processed.attachments as f64 / window_minutes,
+            dockets_ingested_per_minute: ingested.dockets as f64 / window_minutes,
+            attachments_ingested_per_minute: ingested.attachments as f64 / window_minutes,
+        }
+    }
+}
+
+/// Recent processing and ingest rates, for estimating when a backlog will be done.
+pub async fn handle_throughput_stats() -> Json<ThroughputStats> {
+    Json(PROCESSING_THROUGHPUT.stats_at(Instant::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rates_count_only_events_inside_the_window() {
+        let started = Instant::now();
+        let seconds = |secs| started + Duration::from_secs(secs);
+        // A 60 second window of 10 second buckets.
+        let window = ThroughputWindow::new(started, Duration::from_secs(10), 6);
+
+        for second in 0..60 {
+            window.record_at(ThroughputStage::Processed, 1, 3, seconds(second));
+        }
+        window.record_at(ThroughputStage::Ingested, 30, 90, seconds(59));
+        let stats = window.stats_at(seconds(59));
+        assert_eq!(stats.window_seconds, 60);
+        assert_eq!(stats.dockets_processed_per_minute, 60.0);
+        assert_eq!(stats.attachments_processed_per_minute, 180.0);
+        assert_eq!(stats.dockets_ingested_per_minute, 30.0);
+        assert_eq!(stats.attachments_ingested_per_minute, 90.0);
+
+        // 20 seconds on, the first two buckets have fallen out of the window and one of their
+        // slots got reused.
+        window.record_at(ThroughputStage::Processed, 5, 0, seconds(65));
+        let stats = window.stats_at(seconds(79));
+        assert_eq!(stats.dockets_processed_per_minute, 45.0);
+        assert_eq!(stats.dockets_ingested_per_minute, 30.0);
+
+        let idle = window.stats_at(seconds(600));
+        assert_eq!(idle.dockets_processed_per_minute, 0.0);
+        assert_eq!(idle.attachments_ingested_per_minute, 0.0);
+    }
+}
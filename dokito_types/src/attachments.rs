@@ -3,7 +3,7 @@ use mycorrhiza_common::{file_extension::FileExtension, hash::Blake2bHash};
 use non_empty_string::NonEmptyString;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt::Display, str::FromStr};
 
 use crate::jurisdictions::JurisdictionInfo;
 
@@ -22,9 +22,96 @@ pub struct RawAttachmentText {
     pub timestamp: DateTime<Utc>,
 }
 
+/// The algorithm an attachment digest was computed with. Files dokito downloads itself are
+/// always hashed with blake2b, other algorithms come from systems we exchange attachments with.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    JsonSchema,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum HashAlgorithm {
+    #[default]
+    Blake2b,
+    Sha256,
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashAlgorithm::Blake2b => write!(f, "blake2b"),
+            HashAlgorithm::Sha256 => write!(f, "sha256"),
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blake2b" => Ok(HashAlgorithm::Blake2b),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            other => Err(format!("unknown hash algorithm {other:?}")),
+        }
+    }
+}
+
+/// A SHA-256 digest, hex encoded in its string form.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct Sha256Hash([u8; 32]);
+
+impl Sha256Hash {
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Sha256Hash(bytes)
+    }
+}
+
+impl Display for Sha256Hash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl FromStr for Sha256Hash {
+    type Err = hex::FromHexError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0u8; 32];
+        hex::decode_to_slice(s, &mut bytes)?;
+        Ok(Sha256Hash(bytes))
+    }
+}
+
+impl TryFrom<String> for Sha256Hash {
+    type Error = hex::FromHexError;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Sha256Hash> for String {
+    fn from(value: Sha256Hash) -> Self {
+        value.to_string()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, JsonSchema, Clone)]
 pub struct RawAttachment {
+    /// The blake2b digest of the file, which its S3 keys and the documents table go by.
     pub hash: Blake2bHash,
+    /// The SHA-256 digest of the file, for systems we exchange attachments with that key on it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub sha256_hash: Option<Sha256Hash>,
     pub jurisdiction_info: JurisdictionInfo,
     pub name: NonEmptyString,
     pub extension: FileExtension,
@@ -1,6 +1,7 @@
 use std::{env, sync::LazyLock};
 
 use mycorrhiza_common::s3_generic::{S3Credentials, S3EnvNames, make_s3_lazylock};
+use thiserror::Error;
 
 pub static OPENSCRAPERS_S3_OBJECT_BUCKET: LazyLock<String> = LazyLock::new(|| {
     env::var("OPENSCRAPERS_S3_OBJECT_BUCKET").unwrap_or_else(|_| "openscrapers".to_string())
@@ -16,6 +17,39 @@ impl S3EnvNames for DigitalOceanS3Envs {
 
 pub static DIGITALOCEAN_S3: LazyLock<S3Credentials> = make_s3_lazylock::<DigitalOceanS3Envs>();
 
+/// Env vars with no default, the statics reading them panic on first use if they are missing.
+pub const REQUIRED_ENV_VARS: &[&str] = &[
+    DigitalOceanS3Envs::REGION_ENV,
+    DigitalOceanS3Envs::ENDPOINT_ENV,
+    DigitalOceanS3Envs::ACCESS_ENV,
+    DigitalOceanS3Envs::SECRET_ENV,
+];
+
+#[derive(Error, Debug)]
+#[error("Missing required environment variables: {}", .0.join(", "))]
+pub struct MissingEnvVars(pub Vec<&'static str>);
+
+/// Checks every required env var up front, so a misconfigured deployment fails at startup with
+/// the full list instead of panicking partway through a run.
+pub fn validate_required_env_vars() -> Result<(), MissingEnvVars> {
+    find_missing_env_vars(REQUIRED_ENV_VARS, |name| env::var(name).ok())
+}
+
+fn find_missing_env_vars(
+    names: &[&'static str],
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<(), MissingEnvVars> {
+    let missing = names
+        .iter()
+        .copied()
+        .filter(|name| lookup(name).is_none_or(|val| val.is_empty()))
+        .collect::<Vec<_>>();
+    match missing.is_empty() {
+        true => Ok(()),
+        false => Err(MissingEnvVars(missing)),
+    }
+}
+
 // pub static OPENSCRAPERS_REDIS_DOMAIN: LazyLock<String> = LazyLock::new(|| {
 //     env::var("OPENSCRAPERS_REDIS_DOMAIN").unwrap_or_else(|_| "localhost:6379".to_string())
 // });
@@ -26,3 +60,36 @@ pub static DIGITALOCEAN_S3: LazyLock<S3Credentials> = make_s3_lazylock::<Digital
 pub static CRIMSON_URL: LazyLock<String> = LazyLock::new(|| {
     env::var("CRIMSON_URL").unwrap_or_else(|_| "http://localhost:14423".to_string())
 });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation_reports_all_missing_vars() {
+        let lookup = |name: &str| match name {
+            "DIGITALOCEAN_S3_ENDPOINT" => Some("https://example.com".to_string()),
+            "DIGITALOCEAN_S3_SECRET_KEY" => Some(String::new()),
+            _ => None,
+        };
+        let err = find_missing_env_vars(REQUIRED_ENV_VARS, lookup).unwrap_err();
+        assert_eq!(
+            err.0,
+            vec![
+                "DIGITALOCEAN_S3_CLOUD_REGION",
+                "DIGITALOCEAN_S3_ACCESS_KEY",
+                "DIGITALOCEAN_S3_SECRET_KEY",
+            ]
+        );
+        assert_eq!(
+            err.to_string(),
+            "Missing required environment variables: DIGITALOCEAN_S3_CLOUD_REGION, DIGITALOCEAN_S3_ACCESS_KEY, DIGITALOCEAN_S3_SECRET_KEY"
+        );
+    }
+
+    #[test]
+    fn test_validation_passes_when_all_vars_set() {
+        let lookup = |_: &str| Some("set".to_string());
+        assert!(find_missing_env_vars(REQUIRED_ENV_VARS, lookup).is_ok());
+    }
+}
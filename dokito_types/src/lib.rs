@@ -5,6 +5,7 @@ pub mod env_vars;
 pub mod processed;
 pub mod raw;
 pub mod s3_stuff;
+pub mod sentinel_dates;
 
 pub mod jurisdictions {
     pub use openscraper_types::jurisdictions::*;
@@ -10,6 +10,8 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::sentinel_dates::{optional_sentinel_date, sentinel_date};
+
 #[derive(Serialize, Deserialize, Debug, JsonSchema, Clone, Hash)]
 pub struct ProcessedGenericAttachment {
     #[serde(default)]
@@ -91,6 +93,35 @@ pub struct ProcessedGenericOrganization {
     pub org_type: OrganizationType,
 }
 
+/// How an organization takes part in a docket it is listed on.
+#[derive(Serialize, Deserialize, Debug, JsonSchema, Clone, Copy, Default, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PetitionerRole {
+    #[default]
+    Petitioner,
+    Intervenor,
+    Respondent,
+}
+
+impl PetitionerRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PetitionerRole::Petitioner => "petitioner",
+            PetitionerRole::Intervenor => "intervenor",
+            PetitionerRole::Respondent => "respondent",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema, Clone, Hash)]
+pub struct ProcessedGenericPetitioner {
+    #[serde(flatten)]
+    pub organization: ProcessedGenericOrganization,
+    /// Dockets processed before roles were recorded only listed petitioners.
+    #[serde(default)]
+    pub role: PetitionerRole,
+}
+
 #[derive(Serialize, Deserialize, Debug, JsonSchema, Clone, Copy, Default, Hash, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum OrganizationType {
@@ -109,6 +140,7 @@ impl Display for OrganizationType {
 
 #[derive(Serialize, Deserialize, Debug, JsonSchema, Clone, Hash)]
 pub struct ProcessedGenericFiling {
+    #[serde(default, with = "optional_sentinel_date")]
     pub filed_date: Option<NaiveDate>,
     pub index_in_docket: u64,
     #[serde(default)]
@@ -136,7 +168,8 @@ pub struct ProcessedGenericFiling {
 #[derive(Serialize, Deserialize, Debug, JsonSchema, Clone, Hash)]
 pub struct ProcessedGenericDocket {
     pub case_govid: NonEmptyString,
-    #[serde(default)]
+    #[serde(default, with = "sentinel_date")]
+    #[schemars(with = "Option<NaiveDate>")]
     pub opened_date: NaiveDate,
     #[serde(default)]
     pub object_uuid: Uuid,
@@ -153,10 +186,13 @@ pub struct ProcessedGenericDocket {
     #[serde(default)]
     pub industry: String,
     #[serde(default)]
-    pub petitioner_list: Vec<ProcessedGenericOrganization>,
+    pub petitioner_list: Vec<ProcessedGenericPetitioner>,
     #[serde(default)]
     pub hearing_officer: String,
+    /// The officers parsed out of `hearing_officer`, which is kept as the scraper gave it.
     #[serde(default)]
+    pub hearing_officers: Vec<ProcessedGenericHuman>,
+    #[serde(default, with = "optional_sentinel_date")]
     pub closed_date: Option<NaiveDate>,
     #[serde(default, deserialize_with = "deserialize_vec_or_map")]
     pub filings: Vec<ProcessedGenericFiling>, // 👈 same trick here
@@ -168,4 +204,8 @@ pub struct ProcessedGenericDocket {
     pub indexed_at: DateTime<Utc>,
     #[serde(default = "Utc::now")]
     pub processed_at: DateTime<Utc>,
+    /// Version of the processing code that produced this docket, empty for dockets processed
+    /// before versions were recorded.
+    #[serde(default)]
+    pub processor_version: String,
 }
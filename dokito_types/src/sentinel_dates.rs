@@ -0,0 +1,88 @@
+//! Serde helpers for dates that can hold the `NaiveDate::MAX`/`NaiveDate::MIN` sentinels, which
+//! processing uses when no real date could be found. The sentinels are written out as `null` so
+//! clients never see a year 262143 date.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+fn without_sentinel(date: NaiveDate) -> Option<NaiveDate> {
+    match date {
+        NaiveDate::MAX | NaiveDate::MIN => None,
+        real_date => Some(real_date),
+    }
+}
+
+/// For `NaiveDate` fields, a `null` reads back in as `NaiveDate::MAX`.
+pub mod sentinel_date {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+        without_sentinel(*date).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+        Ok(Option::<NaiveDate>::deserialize(deserializer)?.unwrap_or(NaiveDate::MAX))
+    }
+}
+
+/// For `Option<NaiveDate>` fields, a sentinel is written out the same as `None`.
+pub mod optional_sentinel_date {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        date: &Option<NaiveDate>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        date.and_then(without_sentinel).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<NaiveDate>, D::Error> {
+        Option::<NaiveDate>::deserialize(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Serialize, Deserialize)]
+    struct Dated {
+        #[serde(with = "sentinel_date")]
+        opened_date: NaiveDate,
+        #[serde(with = "optional_sentinel_date")]
+        closed_date: Option<NaiveDate>,
+    }
+
+    #[test]
+    fn test_sentinel_dates_serialize_as_null() {
+        let dated = Dated {
+            opened_date: NaiveDate::MAX,
+            closed_date: Some(NaiveDate::MIN),
+        };
+        assert_eq!(
+            serde_json::to_value(&dated).unwrap(),
+            json!({"opened_date": null, "closed_date": null})
+        );
+
+        let read_back: Dated =
+            serde_json::from_value(json!({"opened_date": null, "closed_date": null})).unwrap();
+        assert_eq!(read_back.opened_date, NaiveDate::MAX);
+        assert_eq!(read_back.closed_date, None);
+    }
+
+    #[test]
+    fn test_real_dates_serialize_as_iso_8601() {
+        let date = NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+        let dated = Dated {
+            opened_date: date,
+            closed_date: Some(date),
+        };
+        assert_eq!(
+            serde_json::to_value(&dated).unwrap(),
+            json!({"opened_date": "2024-02-29", "closed_date": "2024-02-29"})
+        );
+    }
+}